@@ -0,0 +1,166 @@
+//! Clipboard change notifications from the OS, so the clipboard watcher reads the clipboard
+//! when something is copied instead of polling it.
+
+/// Start a background thread that calls `changed` whenever the clipboard changes, until it
+/// returns `false`. Windows uses `AddClipboardFormatListener`; Linux uses XFixes selection
+/// events, which also covers Wayland sessions through XWayland. Fails where neither is
+/// available (macOS, Wayland without XWayland), and the caller polls instead.
+pub fn listen(changed: impl Fn() -> bool + Send + 'static) -> Result<(), String> {
+  platform::listen(Box::new(changed))
+}
+
+/// Called on every clipboard change; returns whether to keep listening.
+type OnChange = Box<dyn Fn() -> bool + Send>;
+
+#[cfg(windows)]
+mod platform {
+  use std::cell::RefCell;
+  use std::sync::mpsc;
+  use std::thread;
+
+  use windows_sys::w;
+  use windows_sys::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+  use windows_sys::Win32::System::DataExchange::AddClipboardFormatListener;
+  use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+  use windows_sys::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW, HWND_MESSAGE, MSG,
+    WM_CLIPBOARDUPDATE, WNDCLASSW,
+  };
+
+  use super::OnChange;
+
+  thread_local! {
+    /// Set on the listener thread, which is also where the window procedure runs.
+    static CHANGED: RefCell<Option<OnChange>> = const { RefCell::new(None) };
+  }
+
+  unsafe extern "system" fn window_proc(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if message == WM_CLIPBOARDUPDATE {
+      CHANGED.with(|changed| {
+        if let Some(changed) = changed.borrow().as_ref() {
+          // The window stays registered; there is nothing to stop.
+          let _ = changed();
+        }
+      });
+      return 0;
+    }
+    DefWindowProcW(window, message, wparam, lparam)
+  }
+
+  pub fn listen(changed: OnChange) -> Result<(), String> {
+    // The window has to be created on the thread that pumps its messages; wait until it
+    // is registered as a listener.
+    let (ready_tx, ready_rx) = mpsc::channel();
+    thread::spawn(move || unsafe {
+      let instance = GetModuleHandleW(std::ptr::null());
+      let class_name = w!("PromptLabClipboardListener");
+      let class = WNDCLASSW {
+        lpfnWndProc: Some(window_proc),
+        hInstance: instance,
+        lpszClassName: class_name,
+        ..std::mem::zeroed()
+      };
+      RegisterClassW(&class);
+      // A message-only window: never shown, only receives messages.
+      let window = CreateWindowExW(
+        0,
+        class_name,
+        std::ptr::null(),
+        0,
+        0,
+        0,
+        0,
+        0,
+        HWND_MESSAGE,
+        std::ptr::null_mut(),
+        instance,
+        std::ptr::null(),
+      );
+      if window.is_null() {
+        let _ = ready_tx.send(Err(format!("CreateWindowExW failed: {}", std::io::Error::last_os_error())));
+        return;
+      }
+      if AddClipboardFormatListener(window) == 0 {
+        let error = std::io::Error::last_os_error();
+        let _ = ready_tx.send(Err(format!("AddClipboardFormatListener failed: {error}")));
+        return;
+      }
+      CHANGED.with(|slot| *slot.borrow_mut() = Some(changed));
+      let _ = ready_tx.send(Ok(()));
+      let mut message: MSG = std::mem::zeroed();
+      while GetMessageW(&mut message, std::ptr::null_mut(), 0, 0) > 0 {
+        DispatchMessageW(&message);
+      }
+    });
+    ready_rx
+      .recv()
+      .unwrap_or_else(|_| Err("clipboard listener thread exited".into()))
+  }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+  use std::thread;
+
+  use x11rb::connection::Connection;
+  use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+  use x11rb::protocol::xproto::{ConnectionExt as _, CreateWindowAux, WindowClass};
+  use x11rb::protocol::Event;
+  use x11rb::COPY_DEPTH_FROM_PARENT;
+
+  use super::OnChange;
+
+  fn describe(error: impl std::fmt::Display) -> String {
+    format!("X11 clipboard events unavailable: {error}")
+  }
+
+  pub fn listen(changed: OnChange) -> Result<(), String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(describe)?;
+    conn.xfixes_query_version(5, 0).map_err(describe)?.reply().map_err(describe)?;
+    let screen = &conn.setup().roots[screen_num];
+    // XFixes reports selection changes to a window; an unmapped one is enough.
+    let window = conn.generate_id().map_err(describe)?;
+    conn
+      .create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        screen.root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        screen.root_visual,
+        &CreateWindowAux::new(),
+      )
+      .map_err(describe)?;
+    let clipboard = conn
+      .intern_atom(false, b"CLIPBOARD")
+      .map_err(describe)?
+      .reply()
+      .map_err(describe)?
+      .atom;
+    conn
+      .xfixes_select_selection_input(window, clipboard, SelectionEventMask::SET_SELECTION_OWNER)
+      .map_err(describe)?;
+    conn.flush().map_err(describe)?;
+    thread::spawn(move || {
+      while let Ok(event) = conn.wait_for_event() {
+        if matches!(event, Event::XfixesSelectionNotify(_)) && !changed() {
+          break;
+        }
+      }
+    });
+    Ok(())
+  }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod platform {
+  use super::OnChange;
+
+  pub fn listen(_changed: OnChange) -> Result<(), String> {
+    Err("no clipboard change notifications on this platform".into())
+  }
+}
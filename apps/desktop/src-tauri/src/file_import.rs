@@ -0,0 +1,244 @@
+//! Prompts read from files dropped on the window or picked for import: Markdown with an
+//! optional frontmatter block, plain text, CSV, JSON and JSON Lines.
+
+use std::path::Path;
+
+use serde_json::Value;
+
+/// Column names (CSV) and keys (JSON, frontmatter) accepted for each field.
+const BODY_KEYS: [&str; 7] = ["body", "prompt", "content", "text", "正文", "提示词", "内容"];
+const TITLE_KEYS: [&str; 4] = ["title", "name", "标题", "名称"];
+/// `latest_tags` is the column written by `export_prompts_csv`.
+const TAG_KEYS: [&str; 3] = ["tags", "latest_tags", "标签"];
+const LANGUAGE_KEYS: [&str; 2] = ["language", "语言"];
+const MODEL_KEYS: [&str; 3] = ["model_hint", "model", "模型"];
+
+/// One prompt read from an import file; the caller derives a missing title from the body.
+#[derive(Debug, Clone, Default)]
+pub struct ImportedPrompt {
+  pub title: Option<String>,
+  pub body: String,
+  pub tags: Vec<String>,
+  pub language: Option<String>,
+  pub model_hint: Option<String>,
+}
+
+/// Read the prompts in `path`, by extension. `.md`/`.markdown` and `.txt` files hold one prompt
+/// each, titled by the frontmatter `title`, else the first `#` heading (Markdown), else the
+/// file name. `.csv` files hold one prompt per row under a header naming the columns; `.json`
+/// files an array of prompts (or `{ "prompts": [...] }`) and `.jsonl` files one per line, each
+/// an object or a bare string. Fields go by [`BODY_KEYS`] and the other key lists.
+pub fn read_prompts(path: &Path) -> Result<Vec<ImportedPrompt>, String> {
+  let extension = path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .map(str::to_lowercase)
+    .unwrap_or_default();
+  let data = std::fs::read_to_string(path).map_err(|error| format!("无法读取文件: {error}"))?;
+  let data = data.trim_start_matches('\u{feff}');
+  let stem = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string);
+  match extension.as_str() {
+    "md" | "markdown" => Ok(vec![read_markdown(data, stem)]),
+    "txt" => Ok(vec![ImportedPrompt {
+      title: stem,
+      body: data.trim().to_string(),
+      ..ImportedPrompt::default()
+    }]),
+    "csv" => read_csv(data),
+    "json" => read_json(data),
+    "jsonl" => data
+      .lines()
+      .enumerate()
+      .filter(|(_, line)| !line.trim().is_empty())
+      .map(|(index, line)| {
+        let value: Value = serde_json::from_str(line).map_err(|error| format!("第 {} 行: {error}", index + 1))?;
+        prompt_from_json(&value).ok_or_else(|| format!("第 {} 行: 不是提示词对象或字符串", index + 1))
+      })
+      .collect(),
+    "" => Err("无法识别的文件类型（缺少扩展名）".into()),
+    other => Err(format!("不支持的文件类型: .{other}")),
+  }
+}
+
+/// A Markdown prompt; the frontmatter, if any, is not part of the body.
+fn read_markdown(data: &str, stem: Option<String>) -> ImportedPrompt {
+  let (fields, body) = split_frontmatter(data);
+  let field = |keys: &[&str]| {
+    fields
+      .iter()
+      .find(|(key, _)| keys.contains(&key.as_str()))
+      .map(|(_, values)| values.clone())
+  };
+  let body = body.trim();
+  let heading = body
+    .lines()
+    .find(|line| !line.trim().is_empty())
+    .and_then(|line| line.trim().strip_prefix("# "))
+    .map(|heading| heading.trim().to_string());
+  ImportedPrompt {
+    title: field(&TITLE_KEYS)
+      .and_then(|values| values.into_iter().next())
+      .or(heading)
+      .or(stem),
+    body: body.to_string(),
+    tags: field(&TAG_KEYS).map(|values| split_tags(&values)).unwrap_or_default(),
+    language: field(&LANGUAGE_KEYS).and_then(|values| values.into_iter().next()),
+    model_hint: field(&MODEL_KEYS).and_then(|values| values.into_iter().next()),
+  }
+}
+
+/// The `key: value` pairs of a leading `---` block and the text after it. Values may be
+/// scalars, inline lists (`[a, b]`) or block lists (`- a` lines under the key); quotes around
+/// values are dropped. Text without a closed block is all body.
+fn split_frontmatter(data: &str) -> (Vec<(String, Vec<String>)>, &str) {
+  let Some(rest) = data.strip_prefix("---").filter(|rest| rest.starts_with(['\n', '\r'])) else {
+    return (Vec::new(), data);
+  };
+  let Some(end) = rest.find("\n---") else {
+    return (Vec::new(), data);
+  };
+  let body = rest[end + 4..].trim_start_matches(['-', '\r']);
+  let mut fields: Vec<(String, Vec<String>)> = Vec::new();
+  for line in rest[..end].lines() {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+      continue;
+    }
+    if let Some(item) = trimmed.strip_prefix("- ") {
+      if let Some((_, values)) = fields.last_mut() {
+        values.push(unquote(item));
+      }
+      continue;
+    }
+    let Some((key, value)) = trimmed.split_once(':') else {
+      continue;
+    };
+    let value = value.trim();
+    let values = match value.strip_prefix('[').and_then(|list| list.strip_suffix(']')) {
+      Some(list) => list.split(',').map(unquote).filter(|item| !item.is_empty()).collect(),
+      None if value.is_empty() => Vec::new(),
+      None => vec![unquote(value)],
+    };
+    fields.push((key.trim().to_lowercase(), values));
+  }
+  (fields, body)
+}
+
+fn unquote(value: &str) -> String {
+  value.trim().trim_matches(['"', '\'']).trim().to_string()
+}
+
+/// Tags from list items or delimited strings (`a, b`, `a|b`, `a、b`), without duplicates.
+fn split_tags(values: &[String]) -> Vec<String> {
+  let mut tags: Vec<String> = Vec::new();
+  for tag in values.iter().flat_map(|value| value.split([',', '，', '|', ';', '、'])) {
+    let tag = unquote(tag);
+    if !tag.is_empty() && !tags.contains(&tag) {
+      tags.push(tag);
+    }
+  }
+  tags
+}
+
+fn read_csv(data: &str) -> Result<Vec<ImportedPrompt>, String> {
+  let mut reader = csv::ReaderBuilder::new()
+    .flexible(true)
+    .trim(csv::Trim::Headers)
+    .from_reader(data.as_bytes());
+  let headers: Vec<String> = reader
+    .headers()
+    .map_err(|error| error.to_string())?
+    .iter()
+    .map(str::to_lowercase)
+    .collect();
+  let column = |keys: &[&str]| headers.iter().position(|header| keys.contains(&header.as_str()));
+  let body = column(&BODY_KEYS).ok_or_else(|| "CSV 缺少正文列（body / prompt / content / text）".to_string())?;
+  let (title, tags, language, model) = (
+    column(&TITLE_KEYS),
+    column(&TAG_KEYS),
+    column(&LANGUAGE_KEYS),
+    column(&MODEL_KEYS),
+  );
+
+  let mut prompts = Vec::new();
+  for record in reader.records() {
+    let record = record.map_err(|error| error.to_string())?;
+    let cell = |index: Option<usize>| {
+      index
+        .and_then(|index| record.get(index))
+        .map(str::trim)
+        .filter(|cell| !cell.is_empty())
+        .map(str::to_string)
+    };
+    prompts.push(ImportedPrompt {
+      title: cell(title),
+      body: cell(Some(body)).unwrap_or_default(),
+      tags: cell(tags).map(|tags| split_tags(&[tags])).unwrap_or_default(),
+      language: cell(language),
+      model_hint: cell(model),
+    });
+  }
+  Ok(prompts)
+}
+
+fn read_json(data: &str) -> Result<Vec<ImportedPrompt>, String> {
+  let value: Value = serde_json::from_str(data).map_err(|error| error.to_string())?;
+  let items = match &value {
+    Value::Array(items) => items.as_slice(),
+    Value::Object(object) => match object.get("prompts") {
+      Some(Value::Array(items)) => items.as_slice(),
+      _ => std::slice::from_ref(&value),
+    },
+    _ => std::slice::from_ref(&value),
+  };
+  items
+    .iter()
+    .enumerate()
+    .map(|(index, item)| prompt_from_json(item).ok_or_else(|| format!("第 {} 项: 不是提示词对象或字符串", index + 1)))
+    .collect()
+}
+
+/// A prompt from a JSON string or an object with a body field.
+fn prompt_from_json(value: &Value) -> Option<ImportedPrompt> {
+  let object = match value {
+    Value::String(body) => {
+      return Some(ImportedPrompt {
+        body: body.trim().to_string(),
+        ..ImportedPrompt::default()
+      })
+    }
+    Value::Object(object) => object,
+    _ => return None,
+  };
+  let field = |keys: &[&str]| {
+    object
+      .iter()
+      .find(|(key, _)| keys.contains(&key.to_lowercase().as_str()))
+      .map(|(_, value)| value)
+  };
+  let text = |keys: &[&str]| {
+    field(keys)
+      .and_then(Value::as_str)
+      .map(str::trim)
+      .filter(|text| !text.is_empty())
+      .map(str::to_string)
+  };
+  let tags = match field(&TAG_KEYS) {
+    Some(Value::Array(items)) => split_tags(
+      &items
+        .iter()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect::<Vec<_>>(),
+    ),
+    Some(Value::String(tags)) => split_tags(std::slice::from_ref(tags)),
+    _ => Vec::new(),
+  };
+  Some(ImportedPrompt {
+    title: text(&TITLE_KEYS),
+    body: text(&BODY_KEYS)?,
+    tags,
+    language: text(&LANGUAGE_KEYS),
+    model_hint: text(&MODEL_KEYS),
+  })
+}
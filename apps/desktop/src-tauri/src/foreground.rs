@@ -0,0 +1,145 @@
+//! The app in the foreground when something is copied, recorded with captured prompts.
+
+use serde::{Deserialize, Serialize};
+
+/// Process name and window title of the foreground window; either may be missing, e.g. for
+/// windows of other users' processes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceApp {
+  /// Executable file name, such as `chrome.exe` on Windows or `code` on Linux.
+  pub process: Option<String>,
+  pub title: Option<String>,
+}
+
+/// The app whose window has focus. Windows asks `GetForegroundWindow`; Linux reads the
+/// window manager's `_NET_ACTIVE_WINDOW` over X11, which misses native Wayland windows. Fails
+/// elsewhere (macOS) or when no window has focus.
+pub fn source_app() -> Result<SourceApp, String> {
+  let app = platform::source_app()?;
+  if app.process.is_none() && app.title.is_none() {
+    return Err("no foreground window".into());
+  }
+  Ok(app)
+}
+
+/// Non-empty trimmed text, or `None`.
+#[cfg_attr(not(any(windows, target_os = "linux")), allow(dead_code))]
+fn non_empty(text: String) -> Option<String> {
+  let text = text.trim();
+  (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(windows)]
+mod platform {
+  use windows_sys::Win32::Foundation::CloseHandle;
+  use windows_sys::Win32::System::Threading::{
+    OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+  };
+  use windows_sys::Win32::UI::WindowsAndMessaging::{
+    GetForegroundWindow, GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId,
+  };
+
+  use super::{non_empty, SourceApp};
+
+  pub fn source_app() -> Result<SourceApp, String> {
+    unsafe {
+      let window = GetForegroundWindow();
+      if window.is_null() {
+        return Err("no foreground window".into());
+      }
+      let mut title = vec![0u16; GetWindowTextLengthW(window).max(0) as usize + 1];
+      let title_len = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32).max(0) as usize;
+      let title = non_empty(String::from_utf16_lossy(&title[..title_len]));
+
+      let mut pid = 0;
+      GetWindowThreadProcessId(window, &mut pid);
+      Ok(SourceApp {
+        process: process_name(pid),
+        title,
+      })
+    }
+  }
+
+  /// File name of the process's executable; `None` when the process cannot be opened.
+  unsafe fn process_name(pid: u32) -> Option<String> {
+    let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+    if process.is_null() {
+      return None;
+    }
+    let mut path = [0u16; 1024];
+    let mut len = path.len() as u32;
+    let ok = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut len);
+    CloseHandle(process);
+    if ok == 0 {
+      return None;
+    }
+    let path = String::from_utf16_lossy(&path[..len as usize]);
+    non_empty(path.rsplit(['\\', '/']).next().unwrap_or_default().to_string())
+  }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+  use x11rb::connection::Connection;
+  use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, Window};
+  use x11rb::rust_connection::RustConnection;
+
+  use super::{non_empty, SourceApp};
+
+  fn describe(error: impl std::fmt::Display) -> String {
+    format!("X11 active window unavailable: {error}")
+  }
+
+  fn atom(conn: &RustConnection, name: &[u8]) -> Result<Atom, String> {
+    Ok(conn.intern_atom(false, name).map_err(describe)?.reply().map_err(describe)?.atom)
+  }
+
+  /// The raw value of `property` on `window`, if set.
+  fn property(conn: &RustConnection, window: Window, property: Atom, kind: impl Into<Atom>) -> Result<Vec<u8>, String> {
+    Ok(
+      conn
+        .get_property(false, window, property, kind, 0, 1024)
+        .map_err(describe)?
+        .reply()
+        .map_err(describe)?
+        .value,
+    )
+  }
+
+  pub fn source_app() -> Result<SourceApp, String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(describe)?;
+    let root = conn.setup().roots[screen_num].root;
+    let active = property(&conn, root, atom(&conn, b"_NET_ACTIVE_WINDOW")?, AtomEnum::WINDOW)?;
+    let window = active
+      .get(..4)
+      .and_then(|bytes| bytes.try_into().ok())
+      .map(Window::from_ne_bytes)
+      .filter(|window| *window != 0)
+      .ok_or_else(|| describe("no active window"))?;
+
+    let mut title = property(&conn, window, atom(&conn, b"_NET_WM_NAME")?, atom(&conn, b"UTF8_STRING")?)?;
+    if title.is_empty() {
+      title = property(&conn, window, AtomEnum::WM_NAME.into(), AtomEnum::STRING)?;
+    }
+    let title = non_empty(String::from_utf8_lossy(&title).into_owned());
+
+    // The process name comes from `/proc`, so only local windows have one.
+    let pid = property(&conn, window, atom(&conn, b"_NET_WM_PID")?, AtomEnum::CARDINAL)?;
+    let process = pid
+      .get(..4)
+      .and_then(|bytes| bytes.try_into().ok())
+      .map(u32::from_ne_bytes)
+      .and_then(|pid| std::fs::read_to_string(format!("/proc/{pid}/comm")).ok())
+      .and_then(non_empty);
+    Ok(SourceApp { process, title })
+  }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod platform {
+  use super::SourceApp;
+
+  pub fn source_app() -> Result<SourceApp, String> {
+    Err("the foreground app is not available on this platform".into())
+  }
+}
@@ -0,0 +1,294 @@
+//! `promptlab.log`: the `tracing` subscriber's file output, one `[timestamp] LEVEL module:
+//! message fields` entry per event, rotated by size and by day, with the rotated files
+//! (`promptlab.log.1` is the newest) kept for a limited time.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::{self, format::Writer, time::FormatTime, MakeWriter};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, Registry};
+
+/// The log is rotated once it grows past this size, or on the first write of a new day...
+const MAX_BYTES: u64 = 2 * 1024 * 1024;
+/// ...keeping at most this many rotated files...
+const MAX_FILES: usize = 7;
+/// ...none older than this.
+const RETENTION: Duration = Duration::from_secs(14 * 24 * 3600);
+
+/// Serializes writes, so rotation never races another write.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// When set, events also go to stderr, as they always do in debug builds.
+const CONSOLE_ENV: &str = "PROMPTLAB_LOG_CONSOLE";
+
+/// How serious an entry is, least first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+  Debug,
+  Info,
+  Warn,
+  Error,
+}
+
+impl LogLevel {
+  /// The level named by `word` (`ERROR`, `WARN`, `INFO`, `DEBUG`, any case).
+  fn parse(word: &str) -> Option<Self> {
+    match word.trim_end_matches(':').to_ascii_uppercase().as_str() {
+      "ERROR" => Some(LogLevel::Error),
+      "WARN" | "WARNING" => Some(LogLevel::Warn),
+      "INFO" => Some(LogLevel::Info),
+      "DEBUG" | "TRACE" => Some(LogLevel::Debug),
+      _ => None,
+    }
+  }
+
+  fn filter(self) -> LevelFilter {
+    match self {
+      LogLevel::Debug => LevelFilter::DEBUG,
+      LogLevel::Info => LevelFilter::INFO,
+      LogLevel::Warn => LevelFilter::WARN,
+      LogLevel::Error => LevelFilter::ERROR,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+  /// As written, in local time (`%Y-%m-%d %H:%M:%S`).
+  pub timestamp: String,
+  pub level: LogLevel,
+  /// The event's target, e.g. `llm`, `jobs` or `clipboard`; `None` for entries written
+  /// before logging went through `tracing`.
+  pub module: Option<String>,
+  /// May span several lines; structured fields follow as `key=value`.
+  pub message: String,
+}
+
+/// Changes the level of the subscriber installed by [`init`].
+#[derive(Clone)]
+pub struct LevelHandle(reload::Handle<LevelFilter, Registry>);
+
+impl LevelHandle {
+  pub fn set(&self, level: LogLevel) -> Result<(), String> {
+    self.0.reload(level.filter()).map_err(|error| error.to_string())
+  }
+}
+
+/// Install the global `tracing` subscriber: events at `level` or above are written to
+/// `path` and, in debug builds or with `PROMPTLAB_LOG_CONSOLE` set, to stderr. Records of
+/// the `log` crate (used by some dependencies) go the same way.
+pub fn init(path: &Path, level: LogLevel) -> LevelHandle {
+  let (filter, handle) = reload::Layer::new(level.filter());
+  let file = fmt::layer()
+    .with_ansi(false)
+    .with_timer(Timestamp)
+    .with_writer(FileWriter {
+      path: path.to_path_buf(),
+    });
+  let console = (cfg!(debug_assertions) || std::env::var_os(CONSOLE_ENV).is_some())
+    .then(|| fmt::layer().with_timer(Timestamp).with_writer(std::io::stderr));
+  if let Err(error) = tracing_subscriber::registry()
+    .with(filter)
+    .with(file)
+    .with(console)
+    .try_init()
+  {
+    eprintln!("failed to set up logging: {error}");
+  }
+  LevelHandle(handle)
+}
+
+/// `[%Y-%m-%d %H:%M:%S]` in local time, the header [`parse`] looks for.
+struct Timestamp;
+
+impl FormatTime for Timestamp {
+  fn format_time(&self, writer: &mut Writer<'_>) -> std::fmt::Result {
+    write!(writer, "[{}]", Local::now().format("%Y-%m-%d %H:%M:%S"))
+  }
+}
+
+/// Collects each formatted event and hands it to [`append`] in one piece.
+struct FileWriter {
+  path: PathBuf,
+}
+
+impl<'a> MakeWriter<'a> for FileWriter {
+  type Writer = EventWriter<'a>;
+
+  fn make_writer(&'a self) -> Self::Writer {
+    EventWriter {
+      path: &self.path,
+      buffer: Vec::new(),
+    }
+  }
+}
+
+struct EventWriter<'a> {
+  path: &'a Path,
+  buffer: Vec<u8>,
+}
+
+impl Write for EventWriter<'_> {
+  fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+    self.buffer.extend_from_slice(bytes);
+    Ok(bytes.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+impl Drop for EventWriter<'_> {
+  fn drop(&mut self) {
+    if self.buffer.is_empty() {
+      return;
+    }
+    if let Err(error) = append(self.path, &self.buffer) {
+      eprintln!("failed to write log: {error}");
+    }
+  }
+}
+
+/// Append one formatted entry, rotating the file first when it is too large or was last
+/// written on an earlier day.
+fn append(path: &Path, entry: &[u8]) -> std::io::Result<()> {
+  let _guard = WRITE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  if let Ok(meta) = std::fs::metadata(path) {
+    let written_today = meta
+      .modified()
+      .is_ok_and(|modified| DateTime::<Local>::from(modified).date_naive() == Local::now().date_naive());
+    if meta.len() >= MAX_BYTES || (meta.len() > 0 && !written_today) {
+      rotate(path)?;
+    }
+  }
+  let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+  file.write_all(entry)
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+  let mut name = path.as_os_str().to_owned();
+  name.push(format!(".{index}"));
+  PathBuf::from(name)
+}
+
+/// Shift the rotated files up by one, dropping the oldest and any past [`RETENTION`], and
+/// move the current file to `.1`.
+fn rotate(path: &Path) -> std::io::Result<()> {
+  let expired = |file: &Path| {
+    std::fs::metadata(file)
+      .and_then(|meta| meta.modified())
+      .is_ok_and(|modified| SystemTime::now().duration_since(modified).unwrap_or_default() > RETENTION)
+  };
+  for index in (1..=MAX_FILES).rev() {
+    let from = rotated_path(path, index);
+    if !from.exists() {
+      continue;
+    }
+    if index == MAX_FILES || expired(&from) {
+      std::fs::remove_file(&from)?;
+    } else {
+      std::fs::rename(&from, rotated_path(path, index + 1))?;
+    }
+  }
+  std::fs::rename(path, rotated_path(path, 1))
+}
+
+/// The last `limit` entries at `min_level` or above, oldest first, reading rotated files
+/// as far back as needed.
+pub fn read_tail(path: &Path, limit: usize, min_level: LogLevel) -> std::io::Result<Vec<LogEntry>> {
+  let files = std::iter::once(path.to_path_buf()).chain((1..=MAX_FILES).map(|index| rotated_path(path, index)));
+  let mut tail: Vec<LogEntry> = Vec::new();
+  for file in files {
+    if tail.len() >= limit {
+      break;
+    }
+    let text = match std::fs::read_to_string(&file) {
+      Ok(text) => text,
+      Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+      Err(error) => return Err(error),
+    };
+    let older: Vec<LogEntry> = parse(&text)
+      .into_iter()
+      .filter(|entry| entry.level >= min_level)
+      .collect();
+    let keep = older.len().min(limit - tail.len());
+    tail.splice(0..0, older[older.len() - keep..].iter().cloned());
+  }
+  Ok(tail)
+}
+
+/// Entries of one file; lines that do not start with a `[timestamp]` continue the entry
+/// before them.
+fn parse(text: &str) -> Vec<LogEntry> {
+  let mut entries: Vec<LogEntry> = Vec::new();
+  for line in text.lines() {
+    let header = line
+      .strip_prefix('[')
+      .and_then(|rest| rest.split_once("] "))
+      .filter(|(timestamp, _)| timestamp.len() == 19);
+    match (header, entries.last_mut()) {
+      (Some((timestamp, rest)), _) => entries.push(parse_event(timestamp, rest.trim_start())),
+      (None, Some(entry)) => {
+        entry.message.push('\n');
+        entry.message.push_str(line);
+      }
+      (None, None) => {}
+    }
+  }
+  entries
+}
+
+/// `LEVEL module: message` as written by the subscriber; older entries are a bare message,
+/// read as `info`.
+fn parse_event(timestamp: &str, rest: &str) -> LogEntry {
+  let leveled = rest
+    .split_once(' ')
+    .and_then(|(word, rest)| Some((LogLevel::parse(word)?, rest)));
+  let Some((level, rest)) = leveled else {
+    return LogEntry {
+      timestamp: timestamp.to_string(),
+      level: LogLevel::Info,
+      module: None,
+      message: rest.to_string(),
+    };
+  };
+  let (module, message) = match rest.split_once(": ") {
+    Some((module, message)) if !module.is_empty() && !module.contains(char::is_whitespace) => {
+      (Some(module.to_string()), message)
+    }
+    _ => (None, rest),
+  };
+  LogEntry {
+    timestamp: timestamp.to_string(),
+    level,
+    module,
+    message: message.to_string(),
+  }
+}
+
+/// Delete the log and its rotated files.
+pub fn clear(path: &Path) -> std::io::Result<()> {
+  let _guard = WRITE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+  let rotated = (1..=MAX_FILES).map(|index| rotated_path(path, index));
+  for file in std::iter::once(path.to_path_buf()).chain(rotated) {
+    match std::fs::remove_file(&file) {
+      Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error),
+      _ => {}
+    }
+  }
+  Ok(())
+}
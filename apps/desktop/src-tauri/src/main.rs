@@ -1,51 +1,1423 @@
 ﻿#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod clipboard_events;
+mod file_import;
+mod foreground;
+mod log_file;
+mod operations;
+mod selection;
+
 use std::{
+  cmp::Reverse,
+  collections::{BTreeMap, HashMap, HashSet, VecDeque},
   fs::OpenOptions,
-  io::Write,
+  future::Future,
+  io::{Read, Write},
   path::{Path, PathBuf},
-  sync::{Arc, Mutex},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, PoisonError,
+  },
   thread,
-  time::Duration,
+  time::{Duration, Instant, SystemTime},
 };
 
-use chrono::Local;
-use promptlab_core::analysis::{summarize_prompt_with_vocab, PromptAnalysis};
-use promptlab_core::storage::{Analysis, NewAnalysis, NewPrompt, Prompt, Storage, UpdatePrompt};
-use serde::Deserialize;
+use chrono::{Datelike, Local};
+use log_file::{LevelHandle, LogEntry, LogLevel};
+use operations::{CancellationToken, Operations};
+use promptlab_core::analysis::{
+  canonical_category, canonical_language, detect_prompt_chain, language_display_name,
+  split_sentences as split_text_sentences, summarize_prompt_with_config,
+  AnalysisConfig, AnalysisOverrides, PromptAnalysis, PromptIntent, SummaryStrategy, VocabularyBoost, VocabularyEntry,
+  ANALYZER_VERSION, DEFAULT_VOCABULARY_BOOST, PROMPT_CATEGORIES,
+};
+use promptlab_core::budget::{
+  budget_report, daily_spend, month_start, reached_thresholds, BudgetReport, BudgetSettings, DailySpend,
+};
+use promptlab_core::capture::{
+  classify_locally, evaluate_capture, BurstOverflow, BurstState, BurstTracker, CaptureDecision, CaptureFilter,
+  CaptureRule, LOCAL_CLASSIFIER_MODEL,
+};
+use promptlab_core::compare::{compare_prompts_detailed_with_config, diff_sentences, ComparisonReport, DiffSegment};
+use promptlab_core::dedup::{closest_match, find_near_duplicates, DuplicateOptions};
+use promptlab_core::eval::{
+  calibration_report as build_calibration_report, summarize_ab_preferences, AbSummary, CalibrationReport,
+  LabeledPrediction,
+};
+use promptlab_core::guardrail::{check_llm_output, GuardrailOptions};
+use promptlab_core::llm::{
+  build_provider, check_dns, default_rubric, diagnose_connection, parse_typed_reply, BackendSettings, ChatImage,
+  ChatMessage, ChatRequest, ChatResponse, CircuitBreaker, CircuitStatus, ClassificationReply, ConnectionDiagnostics,
+  DeepAnalysis, DiagnosticCheck, GenerationSettings, LlmError, LlmSettings, LlmTask, LocalLlmSettings, MergeReply,
+  NetworkSettings, OptimizationReply, Provider, ProviderKind, RateLimits, RetryPolicy, RubricCriterion, RubricReply,
+  TaskModels, TokenBucket, TokenUsage, TranslationReply, FALLBACK_CATEGORY, RUBRIC_MAX_SCORE, RUBRIC_MIN_SCORE,
+};
+use promptlab_core::redact::{redact_json, screen_capture, PrivacyAction, PrivacyFilter, PrivacyVerdict, SensitiveKind};
+use promptlab_core::rules::{
+  apply_capture_templates, matching_ignore_rule, CaptureTemplate, ExtractionRule, IgnoreKind, IgnoreRule,
+  TemplateAction,
+};
+use promptlab_core::tags::{apply_tag_merges, cluster_tags, merge_renames, TagCluster, TagClusterOptions, TagMerge};
+use promptlab_core::search::{
+  fuzzy_score, nearest_prompts, rank_text_hits, search_terms, SearchField, SemanticHit, TextHit,
+};
+use promptlab_core::storage::{
+  AbPreference, AbTest, Analysis, Attachment, Capture, CaptureStatus, Classification, ClassifierExample,
+  ClassifierStats, CollectionCount, Job, JobStatus, LlmRejection, LlmUsageMetrics, MergeReport, MergeStatus,
+  MergeSuggestion, MergeVerdict, NewAbCase, NewAbTest, NewAnalysis, NewAttachment, NewClassification,
+  NewClassifierExample, NewJob, NewLlmCall, NewLlmRejection, NewMergeSuggestion, NewPrompt, NewPromptRelation, NewRun,
+  Prompt, PromptRelation, PromptUsage, PromptVersion, RankedPrompt, Run, SourceAppCount, SourceCount, Storage,
+  StorageError, TagCount, TermUsage, UpdatePrompt,
+};
+use promptlab_core::template::{fill_prompt, preview_template, template_variables, TemplatePreview};
+use promptlab_core::text::{content_hash, normalize_text};
+use promptlab_core::vocabulary::{
+  suggest_vocabulary_terms as mine_vocabulary_terms, SuggestionOptions, VocabularySuggestion,
+};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tauri::{
+  ipc::Response,
+  menu::{Menu, MenuItem, PredefinedMenuItem},
   tray::{MouseButton, TrayIcon, TrayIconBuilder, TrayIconEvent},
-  Builder, Manager, State, WindowEvent,
+  AppHandle, Builder, Emitter, Manager, RunEvent, State, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent,
 };
+use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
+use tauri_plugin_deep_link::DeepLinkExt;
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_single_instance::init as single_instance;
+use tokio::sync::{
+  mpsc::{UnboundedReceiver, UnboundedSender},
+  Notify, Semaphore,
+};
+use tracing::{debug, error, info, warn};
 
 struct AppState {
   storage: Storage,
   log_path: PathBuf,
+  logging_path: PathBuf,
+  /// Level of the `tracing` subscriber writing `log_path`, changed by `set_log_level`.
+  log_level: LevelHandle,
   export_dir: PathBuf,
   vocabulary_path: PathBuf,
-  vocabulary: Arc<Mutex<Vec<String>>>,
+  vocabulary: Arc<Mutex<Vec<VocabularyEntry>>>,
+  analysis_config_path: PathBuf,
+  analysis_config: Arc<Mutex<AnalysisConfig>>,
+  /// User-editable `analysis.toml` with extra stopwords, target markers and role patterns.
+  analysis_overrides_path: PathBuf,
+  analysis_overrides_status: Arc<Mutex<AnalysisOverridesStatus>>,
+  vocabulary_suggestions_path: PathBuf,
+  vocabulary_suggestions: Arc<Mutex<VocabularySuggestionState>>,
+  /// Backend settings from `llm_backend.json`, else the environment; the API key may be
+  /// overridden from the OS keyring.
+  llm_settings: Arc<Mutex<LlmSettings>>,
+  llm_backend_path: PathBuf,
+  /// Chat backend chosen by `llm_settings`; `None` when it is not configured (e.g. no API key).
+  /// Replaced in place when the API key changes.
+  llm: Arc<Mutex<Option<LlmClient>>>,
+  /// Wakes the LLM job dispatcher after new jobs are queued in the `jobs` table.
+  llm_jobs_wake: Arc<Notify>,
+  llm_limits_path: PathBuf,
+  llm_limiter: Arc<LlmLimiter>,
+  llm_network_path: PathBuf,
+  /// Proxy, timeout and CA bundle for LLM requests; overrides the environment once saved.
+  llm_network: Arc<Mutex<NetworkSettings>>,
+  llm_models_path: PathBuf,
+  /// Per-task model overrides, shared with `llm` so changes apply to the next call.
+  llm_models: Arc<Mutex<TaskModels>>,
+  llm_generation_path: PathBuf,
+  /// Per-task sampling settings, shared with every client like `llm_models`.
+  llm_generation: Arc<Mutex<GenerationSettings>>,
+  title_settings_path: PathBuf,
+  title_settings: Arc<Mutex<TitleSettings>>,
+  clipboard_settings_path: PathBuf,
+  clipboard_settings: Arc<Mutex<ClipboardSettings>>,
+  /// Capture rule limits and switches, saved in `capture_filter.json`.
+  capture_filter_path: PathBuf,
+  capture_filter: Arc<Mutex<CaptureFilter>>,
+  /// Foreground apps the watcher captures from, saved in `capture_apps.json`.
+  capture_apps_path: PathBuf,
+  capture_apps: Arc<Mutex<CaptureAppSettings>>,
+  /// Sensitive content detectors, saved in `privacy_filter.json`.
+  privacy_filter_path: PathBuf,
+  privacy_filter: Arc<Mutex<PrivacyFilter>>,
+  /// Captures the privacy filter blocked or redacted, newest first; never their content.
+  privacy_events: Arc<Mutex<VecDeque<PrivacyEvent>>>,
+  /// Controls the clipboard watcher task; also carries OS clipboard notifications.
+  watcher_commands: UnboundedSender<WatcherCommand>,
+  /// The task supervising the clipboard watcher, awaited on exit; see `stop_clipboard_watcher`.
+  watcher_task: Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+  /// Whether OS clipboard notifications arrive; the watcher polls otherwise.
+  clipboard_events: Arc<AtomicBool>,
+  /// Recent clipboard texts, newest first, including those the heuristics skipped.
+  clipboard_history: Arc<Mutex<VecDeque<ClipboardEntry>>>,
+  /// Held while a capture looks for duplicates and saves, so the watcher and the capture
+  /// hotkey never both save one copy.
+  capture_lock: Arc<Mutex<()>>,
+  /// Parts copied in merge mode, captured as one text once no part arrives for
+  /// `ClipboardSettings::merge_window_secs` or on `finalize_capture`.
+  pending_merge: Arc<Mutex<Option<PendingMerge>>>,
+  hotkey_settings_path: PathBuf,
+  hotkey_settings: Arc<Mutex<HotkeySettings>>,
+  /// What the clipboard watcher did with what it read since the app started.
+  capture_metrics: Arc<Mutex<CaptureMetrics>>,
+  /// Whether the clipboard watcher runs, when it last read the clipboard and what went wrong.
+  watcher_health: Arc<Mutex<WatcherHealth>>,
+  /// Tray menu line showing whether clipboard capture works.
+  watcher_status_item: MenuItem<tauri::Wry>,
+  /// The prompt the watcher or the hotkey saved last, until it is undone.
+  last_capture: Arc<Mutex<Option<String>>>,
+  /// Content hash of the prompt text the app last put on the clipboard, which the watcher
+  /// does not capture.
+  own_copy: Arc<Mutex<Option<String>>>,
+  /// Tray menu entry that undoes `last_capture`; disabled while there is none.
+  undo_capture_item: MenuItem<tauri::Wry>,
+  /// The prompt the last `promptlab://` link pointed at, until the main window takes it with
+  /// `take_deep_link_prompt` (a link that launched the app arrives before the page loads).
+  deep_link_prompt: Arc<Mutex<Option<String>>>,
+  /// What each open prompt window shows, by window label; dropped when the window closes.
+  prompt_windows: Arc<Mutex<HashMap<String, PromptWindowState>>>,
+  ignore_rules_path: PathBuf,
+  /// Clipboard texts matching one of these are dropped before anything else looks at them.
+  ignore_rules: Arc<Mutex<Vec<IgnoreRule>>>,
+  capture_templates_path: PathBuf,
+  /// Restructure accepted clipboard texts, e.g. wrap them in a scaffold; first match applies.
+  capture_templates: Arc<Mutex<Vec<CaptureTemplate>>>,
+  classifier_settings_path: PathBuf,
+  classifier_settings: Arc<Mutex<ClassifierSettings>>,
+  local_llm_path: PathBuf,
+  /// Local model server (Ollama / LM Studio) that classification is routed to when enabled.
+  local_llm: Arc<Mutex<LocalLlmSettings>>,
+  /// Client for `local_llm`; `None` while it is disabled.
+  local_llm_client: Arc<Mutex<Option<LlmClient>>>,
+  local_llm_limiter: Arc<LlmLimiter>,
+  llm_fallback_path: PathBuf,
+  /// Backends tried, in order, when the active one fails.
+  llm_fallback: Arc<Mutex<FallbackSettings>>,
+  /// Pauses classification after repeated backend failures; reset when a client is rebuilt.
+  classifier_circuit: Arc<Mutex<CircuitBreaker>>,
+  /// Optional redacted log of full requests and replies, shared with every client.
+  llm_debug: Arc<LlmDebugLog>,
+  budget_path: PathBuf,
+  budget: Arc<Mutex<BudgetSettings>>,
+  budget_alerts_path: PathBuf,
+  /// Alerts raised this month; pauses automatic classification once a limit is reached.
+  budget_alerts: Arc<Mutex<BudgetAlertState>>,
+  export_schedule_path: PathBuf,
+  export_schedule: Arc<Mutex<ExportSchedule>>,
+  export_schedule_status_path: PathBuf,
+  /// Outcome of the last scheduled export and when the next one is due.
+  export_schedule_status: Arc<Mutex<ExportScheduleStatus>>,
+  /// Serializes background re-analysis jobs so they never compete for the pool.
+  reanalysis_lock: Arc<Mutex<()>>,
+  /// Held while an embedding backfill runs.
+  embedding_backfill_lock: Arc<tokio::sync::Mutex<()>>,
+  /// Background operations that `cancel_operation` can stop.
+  operations: Operations,
 }
 
 impl AppState {
-  fn log(&self, message: &str) {
-    if let Err(error) = append_log(&self.log_path, message) {
-      eprintln!("failed to write log: {error}");
+  fn llm_client(&self) -> Option<LlmClient> {
+    self.llm.lock().unwrap().clone()
+  }
+
+  /// Client classification runs on: the local server when enabled, else the cloud backend.
+  fn classifier_client(&self) -> Option<LlmClient> {
+    self.local_llm_client.lock().unwrap().clone().or_else(|| self.llm_client())
+  }
+
+  /// Backend settings with the current network settings applied.
+  fn current_llm_settings(&self) -> LlmSettings {
+    let mut settings = self.llm_settings.lock().unwrap().clone();
+    settings.network = self.llm_network.lock().unwrap().clone();
+    settings
+  }
+
+  /// Rebuild the LLM client with the active provider's current API key (keyring first,
+  /// then the environment) and network settings, followed by the fallback providers. When
+  /// the active provider cannot be set up the first usable fallback takes its place.
+  /// In-flight calls finish on the old client.
+  fn reconnect_llm(&self) {
+    let settings = with_stored_api_key(&self.current_llm_settings());
+    let primary = connect_llm(
+      &settings,
+      &self.llm_limiter,
+      &self.llm_models,
+      &self.llm_generation,
+      &self.storage,
+      &self.llm_debug,
+    );
+    let primary = match primary {
+      Ok(client) => Some(client),
+      Err(error) => {
+        warn!(target: "llm", provider = settings.provider.as_str(), %error, "backend not enabled");
+        None
+      }
+    };
+    let client = chain_llm_clients(primary.into_iter().chain(self.connect_fallbacks()).collect());
+    if client.is_none() {
+      warn!(target: "llm", "no backend enabled, using the local analysis only");
+    }
+    let enabled = client.is_some();
+    *self.llm.lock().unwrap() = client;
+    self.classifier_circuit.lock().unwrap().reset();
+    if enabled {
+      self.llm_jobs_wake.notify_one();
+    }
+  }
+
+  /// Clients for the fallback providers, in order. Ollama uses the local model settings when
+  /// they point at Ollama; other providers use their default base URL and model, their stored
+  /// API key and the current network settings. Providers that cannot be set up are skipped.
+  fn connect_fallbacks(&self) -> Vec<LlmClient> {
+    let providers = self.llm_fallback.lock().unwrap().providers.clone();
+    let local = self.local_llm.lock().unwrap().clone();
+    let network = self.llm_network.lock().unwrap().clone();
+    let mut clients = Vec::with_capacity(providers.len());
+    for provider in providers {
+      let (settings, limiter) = if provider == ProviderKind::Ollama {
+        let local = match local.provider {
+          ProviderKind::Ollama => local.clone(),
+          _ => LocalLlmSettings {
+            provider,
+            base_url: None,
+            model: None,
+            ..local.clone()
+          },
+        };
+        (local.llm_settings(), &self.local_llm_limiter)
+      } else {
+        let settings = LlmSettings {
+          provider,
+          network: network.clone(),
+          retry: self.llm_settings.lock().unwrap().retry,
+          ..LlmSettings::default()
+        };
+        (with_stored_api_key(&settings), &self.llm_limiter)
+      };
+      // Per-task overrides name the active provider's models.
+      let models = Arc::new(Mutex::new(TaskModels::default()));
+      let connected = connect_llm(
+        &settings,
+        limiter,
+        &models,
+        &self.llm_generation,
+        &self.storage,
+        &self.llm_debug,
+      );
+      match connected {
+        Ok(client) => clients.push(client),
+        Err(error) => warn!(target: "llm", provider = provider.as_str(), %error, "fallback provider not enabled"),
+      }
+    }
+    clients
+  }
+
+  /// Whether `provider` is the active backend or one of its fallbacks.
+  fn uses_provider(&self, provider: ProviderKind) -> bool {
+    provider == self.llm_settings.lock().unwrap().provider
+      || self.llm_fallback.lock().unwrap().providers.contains(&provider)
+  }
+
+  fn reconnect_local_llm(&self) {
+    let settings = self.local_llm.lock().unwrap().clone();
+    let client = connect_local_llm(
+      &settings,
+      &self.local_llm_limiter,
+      &self.llm_generation,
+      &self.storage,
+      &self.llm_debug,
+    );
+    let enabled = client.is_some();
+    *self.local_llm_client.lock().unwrap() = client;
+    self.classifier_circuit.lock().unwrap().reset();
+    if enabled {
+      self.llm_jobs_wake.notify_one();
+    }
+    if self.uses_provider(ProviderKind::Ollama) {
+      self.reconnect_llm();
     }
   }
 }
 
-fn append_log(path: &PathBuf, message: &str) -> std::io::Result<()> {
-  if let Some(parent) = path.parent() {
-    std::fs::create_dir_all(parent)?;
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReanalysisProgress {
+  /// For `cancel_operation`.
+  operation_id: String,
+  trigger: String,
+  processed: usize,
+  total: usize,
+  failed: usize,
+  done: bool,
+  /// Set with `done` when the operation was cancelled before processing everything.
+  cancelled: bool,
+}
+
+/// A background operation a command started, which can be stopped with `cancel_operation`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartedOperation {
+  operation_id: String,
+  /// Items the operation is going to process.
+  total: usize,
+}
+
+/// Rows of an export written so far, emitted as `export:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportProgress {
+  /// For `cancel_operation`.
+  operation_id: String,
+  path: String,
+  written: usize,
+  total: usize,
+}
+
+/// Outcome of an export, emitted as `export:done` whether or not it succeeded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportDone {
+  operation_id: String,
+  path: String,
+  /// Rows written, also before a cancellation.
+  rows: usize,
+  /// The export was cancelled and the partial file removed.
+  cancelled: bool,
+  error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+  #[default]
+  Csv,
+  /// An array of prompts with their latest summary and tags, readable by `import_files`.
+  Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFrequency {
+  #[default]
+  Daily,
+  Weekly,
+}
+
+/// The prompts a scheduled export writes; all of them when nothing is set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ExportFilter {
+  /// Search terms, matched like `search_prompts` does in every field.
+  query: Option<String>,
+  collection: Option<String>,
+  /// A tag of the latest analysis.
+  tag: Option<String>,
+}
+
+/// A recurring export, saved in `export_schedule.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ExportSchedule {
+  enabled: bool,
+  frequency: ExportFrequency,
+  /// Local time of day, `HH:MM`.
+  time: String,
+  /// Day of a weekly export, 1 (Monday) to 7 (Sunday).
+  weekday: u8,
+  format: ExportFormat,
+  /// The file every run replaces, e.g. in a synced folder.
+  path: String,
+  filter: ExportFilter,
+}
+
+impl Default for ExportSchedule {
+  fn default() -> Self {
+    ExportSchedule {
+      enabled: false,
+      frequency: ExportFrequency::Daily,
+      time: "09:00".into(),
+      weekday: 1,
+      format: ExportFormat::Csv,
+      path: String::new(),
+      filter: ExportFilter::default(),
+    }
   }
-  let mut file = OpenOptions::new().create(true).append(true).open(path)?;
-  writeln!(file, "[{}] {message}", Local::now().format("%Y-%m-%d %H:%M:%S"))?;
-  Ok(())
 }
 
+impl ExportSchedule {
+  /// Trimmed, with empty filter fields dropped.
+  fn normalized(mut self) -> Self {
+    let clean = |value: Option<String>| {
+      value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+    };
+    self.time = self.time.trim().to_string();
+    self.path = self.path.trim().to_string();
+    self.filter = ExportFilter {
+      query: clean(self.filter.query),
+      collection: clean(self.filter.collection),
+      tag: clean(self.filter.tag),
+    };
+    self
+  }
+
+  fn validate(&self) -> Result<(), String> {
+    if chrono::NaiveTime::parse_from_str(&self.time, "%H:%M").is_err() {
+      return Err(format!("无效的时间: {}（应为 HH:MM）", self.time));
+    }
+    if !(1..=7).contains(&self.weekday) {
+      return Err("星期应为 1（周一）到 7（周日）".into());
+    }
+    if self.enabled && !Path::new(&self.path).is_absolute() {
+      return Err("请为定时导出选择完整的文件路径".into());
+    }
+    Ok(())
+  }
+
+  /// The first run due after `after`, in local time.
+  fn next_run(&self, after: chrono::DateTime<Local>) -> Option<chrono::DateTime<Local>> {
+    let time = chrono::NaiveTime::parse_from_str(&self.time, "%H:%M").ok()?;
+    // Today and the next seven days hold a run of either frequency.
+    after
+      .date_naive()
+      .iter_days()
+      .take(8)
+      .filter(|day| {
+        self.frequency == ExportFrequency::Daily || day.weekday().number_from_monday() == u32::from(self.weekday)
+      })
+      .filter_map(|day| day.and_time(time).and_local_timezone(Local).earliest())
+      .find(|at| *at > after)
+  }
+}
+
+/// Saved in `export_schedule_status.json`; returned by `get_export_schedule_status` and
+/// emitted as `export:scheduled` after each scheduled run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ExportScheduleStatus {
+  /// RFC 3339, also when the run failed.
+  last_run_at: Option<String>,
+  last_path: Option<String>,
+  last_rows: usize,
+  /// Why the last run failed; `None` after a successful one.
+  last_error: Option<String>,
+  /// `None` while the schedule is off.
+  next_run_at: Option<String>,
+}
+
+/// Outcome of importing one file with `import_files`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileImportResult {
+  path: String,
+  /// Prompts read from the file.
+  found: usize,
+  imported: usize,
+  /// Prompts already in the library (same content), which are not imported again.
+  duplicates: usize,
+  /// Prompts with an empty body.
+  skipped: usize,
+  prompt_ids: Vec<String>,
+  /// Why the file could not be read, or why importing it stopped early.
+  error: Option<String>,
+}
+
+/// Files and prompts handled so far by `import_files`, emitted as `import:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImportProgress {
+  /// For `cancel_operation`.
+  operation_id: String,
+  files_done: usize,
+  files_total: usize,
+  imported: usize,
+  done: bool,
+  cancelled: bool,
+}
+
+/// Bytes of an attachment file read or written so far, emitted as `attachments:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AttachmentProgress {
+  path: String,
+  transferred: u64,
+  total: u64,
+  done: bool,
+}
+
+/// Result of the last `analysis.toml` load, emitted as `analysis:overrides-reloaded`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AnalysisOverridesStatus {
+  path: String,
+  /// Whether the file exists; a missing file simply means no overrides.
+  present: bool,
+  overrides: AnalysisOverrides,
+  /// Syntax or validation problems. On a syntax error the previous overrides stay active.
+  errors: Vec<String>,
+  loaded_at: Option<String>,
+}
+
+const ANALYSIS_OVERRIDES_POLL: Duration = Duration::from_secs(2);
+/// How often the capture metrics are summarized in the log, when anything was captured.
+const CAPTURE_METRICS_LOG_INTERVAL: Duration = Duration::from_secs(3600);
+
+const CLIPBOARD_POLL_DEFAULT_MS: u64 = 3500;
+const CLIPBOARD_POLL_MIN_MS: u64 = 250;
+const CLIPBOARD_POLL_MAX_MS: u64 = 60_000;
+/// With OS notifications the clipboard is still read this often, in case one is missed.
+const CLIPBOARD_EVENT_SAFETY_POLL: Duration = Duration::from_secs(60);
+/// Wait after a notification so the copying app finishes writing all its formats.
+const CLIPBOARD_SETTLE: Duration = Duration::from_millis(100);
+const CLIPBOARD_HISTORY_LIMIT: usize = 50;
+const CLIPBOARD_BURST_WINDOW_MAX_SECS: u64 = 3600;
+const CLIPBOARD_MERGE_WINDOW_MAX_SECS: u64 = 600;
+/// Content hashes of the latest distinct clipboard texts, which are not processed again.
+const CLIPBOARD_RECENT_HASHES: usize = 32;
+/// Longer texts are left out of the clipboard history.
+const CLIPBOARD_HISTORY_MAX_CHARS: usize = 100_000;
+const PRIVACY_EVENT_LIMIT: usize = 200;
+const HOTKEY_DEFAULT: &str = "CommandOrControl+Alt+P";
+const PICKER_HOTKEY_DEFAULT: &str = "CommandOrControl+Alt+Space";
+/// Label of the quick picker window.
+const PICKER_WINDOW: &str = "picker";
+/// Scheme of the links followed by [`follow_deep_link`], e.g. `promptlab://prompt/{id}`.
+const DEEP_LINK_SCHEME: &str = "promptlab";
+/// Passed to the app when the system starts it at login, so it starts in the tray.
+const AUTOSTART_ARG: &str = "--autostart";
+/// Label prefix of the windows opened by `open_prompt_window`; the prompt id follows.
+const PROMPT_WINDOW_PREFIX: &str = "prompt-";
+const QUICK_SEARCH_LIMIT: usize = 8;
+/// Tag matches rank below equally good title matches.
+const QUICK_SEARCH_TAG_WEIGHT: f64 = 0.8;
+const QUICK_PICK_PREVIEW_CHARS: usize = 160;
+/// Wait after hiding the picker so focus is back in the previous app before pasting.
+const PICKER_PASTE_DELAY: Duration = Duration::from_millis(150);
+/// How long the capture hotkey waits for the copied selection to reach the clipboard.
+const SELECTION_COPY_TIMEOUT: Duration = Duration::from_millis(800);
+const SELECTION_COPY_POLL: Duration = Duration::from_millis(40);
+/// Characters of the captured text shown in the hotkey's confirmation dialog.
+const HOTKEY_PREVIEW_CHARS: usize = 200;
+/// Delay before restarting a stopped clipboard watcher, doubled after each quick failure.
+const WATCHER_RESTART_MIN: Duration = Duration::from_secs(1);
+const WATCHER_RESTART_MAX: Duration = Duration::from_secs(300);
+/// A watcher that ran this long before stopping is restarted after the shortest delay again.
+const WATCHER_STABLE_RUN: Duration = Duration::from_secs(60);
+/// Consecutive failed clipboard reads after which the watcher is restarted.
+const WATCHER_MAX_READ_FAILURES: u32 = 10;
+/// How long quitting the app waits for the watcher to capture pending merge parts and stop.
+const WATCHER_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+const TRAY_ID: &str = "main";
+/// Tray menu item ids.
+const TRAY_WATCHER_STATUS: &str = "watcher_status";
+const TRAY_UNDO_CAPTURE: &str = "undo_capture";
+const TRAY_QUIT: &str = "quit";
+/// Characters of the captured prompt's title shown in the tray's undo entry.
+const TRAY_TITLE_CHARS: usize = 24;
+
+/// Jobs processed concurrently by the LLM dispatcher.
+const LLM_WORKERS: usize = 3;
+/// How often the dispatcher re-checks the queue when nobody wakes it.
+const LLM_JOB_POLL: Duration = Duration::from_secs(5);
+/// LLM calls whose final attempt takes at least this long are logged as warnings.
+const LLM_SLOW_CALL: Duration = Duration::from_secs(20);
+/// Consecutive backend failures after which classification pauses for the cooldown.
+const CLASSIFIER_CIRCUIT_THRESHOLD: u32 = 3;
+const CLASSIFIER_CIRCUIT_COOLDOWN: Duration = Duration::from_secs(300);
+/// A local server answers one request at a time; the cloud rate limit does not apply.
+const LOCAL_LLM_LIMITS: RateLimits = RateLimits {
+  requests_per_minute: 0,
+  burst: 1,
+  max_in_flight: 1,
+};
+const CLASSIFY_JOB: &str = "classify";
+/// Source of classifications re-decided or re-queued by `reapply_classification`.
+const REAPPLY_SOURCE: &str = "reapply";
+const CLASSIFY_JOB_ATTEMPTS: u32 = 3;
+const CLASSIFY_BACKLOG_LIMIT: usize = 100;
+const CLASSIFY_BACKLOG_MAX: usize = 1000;
+/// How often the backend is probed while classification jobs wait for it to be reachable.
+const CLASSIFY_RETRY_PROBE: Duration = Duration::from_secs(30);
+/// Few-shot examples included in each classification request, newest first.
+const CLASSIFIER_EXAMPLE_LIMIT: usize = 12;
+/// Characters of each example's text sent to the model.
+const CLASSIFIER_EXAMPLE_CHARS: usize = 400;
+const TITLE_JOB: &str = "title";
+const TITLE_JOB_ATTEMPTS: u32 = 3;
+/// Title jobs answered by a single request.
+const TITLE_BATCH: usize = 10;
+/// Body characters sent per prompt when asking for a title.
+const TITLE_BODY_CHARS: usize = 600;
+const FALLBACK_TITLE: &str = "剪贴板导入";
+const OCR_JOB: &str = "ocr";
+const OCR_JOB_ATTEMPTS: u32 = 3;
+const OCR_MAX_TOKENS: u32 = 2000;
+/// `kind` in the metadata of prompts saved from clipboard images.
+const IMAGE_CAPTURE_KIND: &str = "image_capture";
+const LIST_JOBS_LIMIT: usize = 200;
+/// Texts per embeddings request (DashScope accepts at most 10).
+const EMBEDDING_BATCH: usize = 10;
+const SEMANTIC_SEARCH_LIMIT: usize = 20;
+const TEXT_SEARCH_LIMIT: usize = 100;
+const SEMANTIC_SEARCH_MIN_SCORE: f32 = 0.3;
+const LIST_RUNS_LIMIT: usize = 50;
+const AB_TEST_MAX_INPUTS: usize = 20;
+const LIST_REJECTIONS_LIMIT: usize = 100;
+const CALIBRATION_BINS: usize = 10;
+const CALIBRATION_MAX_BINS: usize = 50;
+/// The labeled prompts `run_keyword_eval` scores by default, as used by the `keyword_eval` example.
+#[cfg(debug_assertions)]
+const KEYWORD_EVAL_FIXTURES: &str = include_str!("../../../../crates/core/fixtures/keyword_eval.json");
+/// Keywords per prompt compared with the labels, as in the `keyword_eval` example.
+#[cfg(debug_assertions)]
+const KEYWORD_EVAL_TOP_K: usize = 8;
+/// Most used tags sent to the LLM for synonym grouping.
+const TAG_LLM_LIMIT: usize = 300;
+/// Near-duplicate groups judged by the LLM per `suggest_merges` call.
+const MERGE_LLM_LIMIT: usize = 20;
+/// Larger groups are left to the user rather than sent to the LLM.
+const MERGE_GROUP_MAX: usize = 6;
+/// Body characters sent per prompt when asking whether prompts are the same.
+const MERGE_BODY_CHARS: usize = 2000;
+/// Exports report progress every this many rows.
+const EXPORT_PROGRESS_ROWS: usize = 100;
+/// Larger files are refused by `import_files`.
+const IMPORT_FILE_MAX_BYTES: u64 = 50 * 1024 * 1024;
+/// `import_files` reports progress every this many imported prompts, besides once per file.
+const IMPORT_PROGRESS_PROMPTS: usize = 100;
+/// `source` of imported prompts and their analyses.
+const IMPORT_SOURCE: &str = "import";
+const IMPORT_CANCELLED: &str = "导入已取消";
+/// Larger files cannot be attached, since attachments are stored in the database.
+const ATTACHMENT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+/// Attachment files are read and written in chunks of this size...
+const ATTACHMENT_CHUNK_BYTES: usize = 1024 * 1024;
+/// ...with progress reported for files of at least this size.
+const ATTACHMENT_PROGRESS_MIN_BYTES: u64 = 4 * 1024 * 1024;
+/// `llm_debug.log` is rotated once it grows past this size...
+const LLM_DEBUG_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+/// ...keeping this many older files (`llm_debug.log.1` is the newest).
+const LLM_DEBUG_LOG_FILES: usize = 3;
+const READ_LOG_DEFAULT_LINES: usize = 200;
+const READ_LOG_MAX_LINES: usize = 5000;
+
+/// The configured provider plus the call policy shared by every LLM call site.
+#[derive(Clone)]
+struct LlmClient {
+  provider: Arc<dyn Provider>,
+  retry: RetryPolicy,
+  limiter: Arc<LlmLimiter>,
+  settings: LlmSettings,
+  models: Arc<Mutex<TaskModels>>,
+  generation: Arc<Mutex<GenerationSettings>>,
+  /// Every call is recorded in `llm_calls` for usage metrics.
+  storage: Storage,
+  debug_log: Arc<LlmDebugLog>,
+  /// Tried next when this backend fails or times out (see `call_qwen_chat`).
+  fallback: Option<Arc<LlmClient>>,
+}
+
+impl LlmClient {
+  /// Model for `task` under the current overrides; `None` when the backend has no embeddings.
+  fn model_for(&self, task: LlmTask) -> Option<String> {
+    let models = self.models.lock().unwrap();
+    self.settings.task_model(&models, task).map(str::to_string)
+  }
+
+  /// Current sampling settings for every task.
+  fn generation(&self) -> GenerationSettings {
+    *self.generation.lock().unwrap()
+  }
+}
+
+/// Logging options, saved in `logging.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct LoggingSettings {
+  /// Least severe level written to `promptlab.log`.
+  level: LogLevel,
+}
+
+impl Default for LoggingSettings {
+  fn default() -> Self {
+    Self { level: LogLevel::Info }
+  }
+}
+
+/// Debug log options, saved in `llm_debug.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct LlmDebugSettings {
+  /// Write every chat request and reply, redacted, to `llm_debug.log`.
+  enabled: bool,
+}
+
+/// Full chat requests and replies as JSON lines, with API keys and personal data masked.
+/// Kept apart from `promptlab.log` and rotated by size.
+struct LlmDebugLog {
+  path: PathBuf,
+  settings_path: PathBuf,
+  settings: Mutex<LlmDebugSettings>,
+  /// Serializes writes so lines from concurrent calls never interleave.
+  write_lock: Mutex<()>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LlmDebugStatus {
+  enabled: bool,
+  path: String,
+  /// Size of the current file; rotated files are not counted.
+  size_bytes: u64,
+}
+
+impl LlmDebugLog {
+  fn new(path: PathBuf, settings_path: PathBuf) -> Self {
+    let settings = load_llm_debug_settings(&settings_path);
+    Self {
+      path,
+      settings_path,
+      settings: Mutex::new(settings),
+      write_lock: Mutex::new(()),
+    }
+  }
+
+  fn enabled(&self) -> bool {
+    self.settings.lock().unwrap().enabled
+  }
+
+  fn status(&self) -> LlmDebugStatus {
+    LlmDebugStatus {
+      enabled: self.enabled(),
+      path: self.path.display().to_string(),
+      size_bytes: std::fs::metadata(&self.path).map(|meta| meta.len()).unwrap_or(0),
+    }
+  }
+
+  fn rotated_path(&self, index: usize) -> PathBuf {
+    let mut name = self.path.clone().into_os_string();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+  }
+
+  /// Append one entry, masking strings with [`redact_json`]; `secrets` are masked verbatim.
+  fn write(&self, mut entry: Value, secrets: &[&str]) -> std::io::Result<()> {
+    redact_json(&mut entry, secrets);
+    let _guard = self.write_lock.lock().unwrap();
+    if std::fs::metadata(&self.path).is_ok_and(|meta| meta.len() >= LLM_DEBUG_LOG_MAX_BYTES) {
+      for index in (1..LLM_DEBUG_LOG_FILES).rev() {
+        let from = self.rotated_path(index);
+        if from.exists() {
+          std::fs::rename(&from, self.rotated_path(index + 1))?;
+        }
+      }
+      std::fs::rename(&self.path, self.rotated_path(1))?;
+    }
+    if let Some(parent) = self.path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+    writeln!(file, "{entry}")
+  }
+
+  /// Delete the current and rotated files.
+  fn clear(&self) -> std::io::Result<()> {
+    let _guard = self.write_lock.lock().unwrap();
+    let rotated = (1..=LLM_DEBUG_LOG_FILES).map(|index| self.rotated_path(index));
+    for path in std::iter::once(self.path.clone()).chain(rotated) {
+      match std::fs::remove_file(&path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error),
+        _ => {}
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Backends tried when the active one fails, saved in `llm_fallback.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct FallbackSettings {
+  /// Providers tried after the active one, in order.
+  providers: Vec<ProviderKind>,
+  /// When no backend answers, deep analysis falls back to the local heuristic analyzer.
+  heuristic: bool,
+}
+
+impl FallbackSettings {
+  /// Drop the active provider and repeated entries.
+  fn normalized(self, active: ProviderKind) -> Self {
+    let mut providers: Vec<ProviderKind> = Vec::with_capacity(self.providers.len());
+    for provider in self.providers {
+      if provider != active && !providers.contains(&provider) {
+        providers.push(provider);
+      }
+    }
+    Self { providers, ..self }
+  }
+}
+
+/// Title generation options, saved in `title_settings.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct TitleSettings {
+  /// Ask the LLM for a title when the first line of a clipboard import makes a poor one.
+  llm_fallback: bool,
+}
+
+/// Clipboard watcher options, saved in `clipboard.json`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ClipboardSettings {
+  /// How often the clipboard is read where the OS sends no change notifications.
+  poll_interval_ms: u64,
+  /// Hold captures in the inbox for review instead of saving them as prompts.
+  inbox: bool,
+  /// Save copied images (e.g. screenshots) as image capture prompts, the PNG attached.
+  images: bool,
+  /// Send image captures to the LLM backend's vision model to read their text into the body.
+  ocr: bool,
+  /// Show a desktop notification for every prompt the watcher saves.
+  notifications: bool,
+  /// Record the foreground app's process name and window title with each capture.
+  source_app: bool,
+  /// Text captures per `burst_window_secs` beyond which copying counts as a burst and
+  /// further captures go to `burst_overflow`; 0 turns burst protection off.
+  burst_limit: u32,
+  burst_window_secs: u64,
+  burst_overflow: BurstOverflow,
+  /// Append successive copies to one pending text instead of capturing each, for a long prompt
+  /// copied in chunks (e.g. from a PDF). The parts skip the capture rules.
+  merge: bool,
+  /// Seconds without a new part after which the merged text is captured.
+  merge_window_secs: u64,
+  /// Hold captures that nearly repeat an existing prompt in the inbox, where they can be
+  /// kept as a new version of it.
+  versions: bool,
+  /// Shingle similarity (Jaccard, 0.5–1) from which a capture counts as a near-duplicate.
+  version_similarity: f64,
+}
+
+impl Default for ClipboardSettings {
+  fn default() -> Self {
+    Self {
+      poll_interval_ms: CLIPBOARD_POLL_DEFAULT_MS,
+      inbox: false,
+      images: true,
+      ocr: false,
+      notifications: true,
+      source_app: true,
+      burst_limit: 10,
+      burst_window_secs: 60,
+      burst_overflow: BurstOverflow::Inbox,
+      merge: false,
+      merge_window_secs: 20,
+      versions: true,
+      version_similarity: 0.8,
+    }
+  }
+}
+
+impl ClipboardSettings {
+  fn normalized(self) -> Self {
+    Self {
+      poll_interval_ms: self.poll_interval_ms.clamp(CLIPBOARD_POLL_MIN_MS, CLIPBOARD_POLL_MAX_MS),
+      burst_window_secs: self.burst_window_secs.clamp(1, CLIPBOARD_BURST_WINDOW_MAX_SECS),
+      merge_window_secs: self.merge_window_secs.clamp(1, CLIPBOARD_MERGE_WINDOW_MAX_SECS),
+      version_similarity: self.version_similarity.clamp(0.5, 1.0),
+      ..self
+    }
+  }
+}
+
+/// Texts copied in merge mode that are waiting to be captured together.
+#[derive(Debug)]
+struct PendingMerge {
+  parts: Vec<String>,
+  /// Where the first part was copied from.
+  source_app: Option<foreground::SourceApp>,
+  started_at: String,
+  last_part: Instant,
+}
+
+/// Clipboard watcher counters since the app started, reported by `get_capture_metrics`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CaptureMetrics {
+  since: String,
+  /// Clipboard reads.
+  polls: u64,
+  /// New distinct texts read from the clipboard.
+  candidates: u64,
+  /// Texts the app allowlist, the privacy filter or an ignore rule dropped.
+  app_skipped: u64,
+  privacy_blocked: u64,
+  ignored: u64,
+  /// Texts the capture rules rejected, by the deciding rule.
+  rejections: BTreeMap<CaptureRule, u64>,
+  /// Texts already saved as a prompt, waiting in the inbox or rejected from it.
+  duplicates: u64,
+  burst_skipped: u64,
+  /// Texts saved as prompts, and those held in the inbox instead.
+  saved: u64,
+  held: u64,
+  images_saved: u64,
+  /// Finished LLM classifications of saved prompts, and how many found no prompt.
+  llm_classified: u64,
+  llm_not_prompt: u64,
+}
+
+/// Clipboard watcher liveness, reported by `get_watcher_health`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WatcherHealth {
+  /// Whether the watch loop runs; `false` while it waits to be restarted.
+  running: bool,
+  /// Whether clipboard reading is paused with `set_watcher_paused`.
+  paused: bool,
+  /// When the clipboard was last read. With OS change notifications, reads without a copy
+  /// happen only every minute.
+  last_poll_at: Option<String>,
+  restarts: u32,
+  panics: u32,
+  /// Failed clipboard reads, not counting a clipboard that holds no text.
+  read_errors: u64,
+  last_error: Option<String>,
+  last_error_at: Option<String>,
+  /// When the stopped watcher is restarted.
+  next_restart_at: Option<String>,
+}
+
+/// Messages to the clipboard watcher task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WatcherCommand {
+  /// The OS reported a clipboard change.
+  Changed,
+  /// The clipboard settings changed; apply them (e.g. the poll interval) and read again.
+  Reconfigure,
+  /// Stop or resume reading the clipboard.
+  Pause(bool),
+  /// Capture pending merge parts and stop, as the app exits.
+  Shutdown,
+}
+
+/// Foreground apps the clipboard watcher captures from, saved in `capture_apps.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct CaptureAppSettings {
+  /// Capture only while one of `apps` is in the foreground.
+  enabled: bool,
+  /// Process name fragments, matched case-insensitively, e.g. `chrome`, `msedge`, `wechat`.
+  apps: Vec<String>,
+}
+
+impl CaptureAppSettings {
+  /// Trimmed, lowercased fragments without blanks and repeats.
+  fn normalized(self) -> Self {
+    let mut apps: Vec<String> = Vec::new();
+    for app in self.apps.iter().map(|app| app.trim().to_lowercase()) {
+      if !app.is_empty() && !apps.contains(&app) {
+        apps.push(app);
+      }
+    }
+    Self { apps, ..self }
+  }
+
+  /// Whether capture may happen with `app` in the foreground. While the allowlist is on, a
+  /// foreground app whose process is unknown is not allowed.
+  fn allows(&self, app: Option<&foreground::SourceApp>) -> bool {
+    if !self.enabled {
+      return true;
+    }
+    let Some(process) = app.and_then(|app| app.process.as_deref()) else {
+      return false;
+    };
+    let process = process.to_lowercase();
+    self.apps.iter().any(|fragment| process.contains(fragment.as_str()))
+  }
+}
+
+/// The global "capture selection" and quick picker shortcuts, saved in `hotkey.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct HotkeySettings {
+  enabled: bool,
+  /// Accelerator such as `CommandOrControl+Alt+P`.
+  shortcut: String,
+  picker_enabled: bool,
+  /// Opens the quick picker window.
+  picker_shortcut: String,
+  /// Whether a prompt chosen in the picker is also pasted into the app that had focus, rather
+  /// than only copied.
+  picker_paste: bool,
+}
+
+impl Default for HotkeySettings {
+  fn default() -> Self {
+    Self {
+      enabled: true,
+      shortcut: HOTKEY_DEFAULT.into(),
+      picker_enabled: true,
+      picker_shortcut: PICKER_HOTKEY_DEFAULT.into(),
+      picker_paste: true,
+    }
+  }
+}
+
+/// A prompt offered by the quick picker.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct QuickPick {
+  prompt_id: String,
+  title: String,
+  tags: Vec<String>,
+  /// Start of the body on one line.
+  preview: String,
+  /// Template variables to fill in before pasting.
+  variables: Vec<String>,
+  score: f64,
+}
+
+/// A capture the privacy filter stopped or masked. The text itself is never recorded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PrivacyEvent {
+  at: String,
+  /// `clipboard` or `hotkey`.
+  source: &'static str,
+  kinds: Vec<SensitiveKind>,
+  action: PrivacyAction,
+}
+
+/// A text seen on the clipboard, kept in memory only.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClipboardEntry {
+  text: String,
+  copied_at: String,
+  /// Whether the capture rules accepted it; skipped texts can still be saved by hand.
+  potential_prompt: bool,
+  /// The rule that decided, if any, and why.
+  rule: Option<CaptureRule>,
+  reason: String,
+  /// The app in the foreground when the text was copied.
+  source_app: Option<foreground::SourceApp>,
+}
+
+/// `is_prompt` classifier options, saved in `classifier.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ClassifierSettings {
+  /// A text counts as a prompt when its predicted probability of being one reaches this.
+  threshold: f64,
+  /// Replaces `CLASSIFY_SYSTEM_PROMPT`; must still ask for the same JSON fields.
+  system_prompt: Option<String>,
+  /// Hold captures the classifier is unsure about in quarantine instead of the library.
+  quarantine: bool,
+  /// Predicted probabilities of being a prompt from `uncertain_min` to `uncertain_max` count
+  /// as unsure.
+  uncertain_min: f64,
+  uncertain_max: f64,
+}
+
+impl Default for ClassifierSettings {
+  fn default() -> Self {
+    Self {
+      threshold: 0.5,
+      system_prompt: None,
+      quarantine: false,
+      uncertain_min: 0.35,
+      uncertain_max: 0.65,
+    }
+  }
+}
+
+impl ClassifierSettings {
+  /// The instructions classification requests are sent with.
+  fn system_prompt(&self) -> &str {
+    self.system_prompt.as_deref().unwrap_or(CLASSIFY_SYSTEM_PROMPT)
+  }
+
+  /// Whether a capture with this predicted probability of being a prompt goes to quarantine.
+  fn quarantines(&self, score: f64) -> bool {
+    self.quarantine && (self.uncertain_min..=self.uncertain_max).contains(&score)
+  }
+}
+
+/// Which classifications `reapply_classification` goes over.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ReapplyFilter {
+  /// Only prompts whose latest classification came from this source (`clipboard`, `job`, ...).
+  source: Option<String>,
+  prompt_ids: Option<Vec<String>>,
+  /// Ask the classifier again, e.g. after its system prompt was edited, instead of deciding
+  /// on the stored scores.
+  reclassify: bool,
+}
+
+/// A prompt whose stored score no longer reaches the threshold.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReappliedVerdict {
+  prompt_id: String,
+  title: String,
+  /// Predicted probability of being a prompt.
+  score: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReapplyReport {
+  /// Classifications matching the filter.
+  checked: usize,
+  /// Prompts that counted as prompts and would now be rejected.
+  rejected: Vec<ReappliedVerdict>,
+  /// Prompts that were rejected and count as prompts now.
+  accepted: usize,
+  /// Classification jobs queued with `reclassify`.
+  requeued: usize,
+}
+
+/// Token counts and timing of one LLM call, stored with the result it produced.
+#[derive(Debug, Clone, Serialize)]
+struct LlmCallUsage {
+  /// Backend that answered, which may be a fallback of the one asked.
+  provider: &'static str,
+  prompt_tokens: Option<u64>,
+  completion_tokens: Option<u64>,
+  /// Wall-clock time of the final attempt, excluding backoff and rate-limit waits.
+  latency_ms: u64,
+  attempts: u32,
+}
+
+/// Rate limit and in-flight cap applied to every LLM request; limits can change at runtime.
+struct LlmLimiter {
+  state: Mutex<LlmLimiterState>,
+  released: Notify,
+}
+
+struct LlmLimiterState {
+  limits: RateLimits,
+  bucket: TokenBucket,
+  in_flight: usize,
+}
+
+/// Held for the duration of one request; frees the in-flight slot when dropped.
+struct LlmPermit {
+  limiter: Arc<LlmLimiter>,
+}
+
+impl LlmLimiter {
+  fn new(limits: RateLimits) -> Self {
+    Self {
+      state: Mutex::new(LlmLimiterState {
+        bucket: TokenBucket::new(&limits),
+        limits,
+        in_flight: 0,
+      }),
+      released: Notify::new(),
+    }
+  }
+
+  fn limits(&self) -> RateLimits {
+    self.state.lock().unwrap().limits
+  }
+
+  fn reconfigure(&self, limits: RateLimits) {
+    {
+      let mut state = self.state.lock().unwrap();
+      state.bucket.reconfigure(&limits);
+      state.limits = limits;
+    }
+    self.released.notify_waiters();
+  }
+
+  /// Wait for a free in-flight slot and a rate-limit token.
+  async fn acquire(self: &Arc<Self>) -> LlmPermit {
+    loop {
+      let wait = {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight >= state.limits.max_in_flight {
+          None
+        } else {
+          match state.bucket.try_acquire(Instant::now()) {
+            Ok(()) => {
+              state.in_flight += 1;
+              return LlmPermit { limiter: self.clone() };
+            }
+            Err(wait) => Some(wait),
+          }
+        }
+      };
+      match wait {
+        Some(wait) => tokio::time::sleep(wait).await,
+        None => self.released.notified().await,
+      }
+    }
+  }
+}
+
+impl Drop for LlmPermit {
+  fn drop(&mut self) {
+    self.limiter.state.lock().unwrap().in_flight -= 1;
+    self.limiter.released.notify_one();
+  }
+}
+
+/// Cached output of the vocabulary suggestion job plus the terms the user rejected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct VocabularySuggestionState {
+  suggestions: Vec<VocabularySuggestion>,
+  rejected: Vec<String>,
+  generated_at: Option<String>,
+}
+
+/// Budget alerts already raised, saved in `budget_alerts.json`; reset each month.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct BudgetAlertState {
+  month: String,
+  /// Thresholds announced this month, so each alert is raised once.
+  alerted: Vec<f64>,
+  /// Set when a limit is reached (and `pauseClassification` is on); cleared by
+  /// `acknowledge_budget_alert`, a new month or a raised limit.
+  classification_paused: bool,
+  /// The user acknowledged the reached limit; classification stays on for the rest of the month.
+  acknowledged: bool,
+}
+
+/// Returned by `get_budget_status` and emitted as `llm:budget-alert`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BudgetStatus {
+  #[serde(flatten)]
+  report: BudgetReport,
+  alerted: Vec<f64>,
+  classification_paused: bool,
+}
+
+/// What a prompt window opened by `open_prompt_window` shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PromptWindowMode {
+  #[default]
+  Edit,
+  /// Side by side with `compare_with`.
+  Compare,
+  /// Filling in and running the prompt.
+  Playground,
+}
+
+/// The state of one prompt window, kept apart from the main window and the other prompt
+/// windows; read with `get_prompt_window_state` and pushed as `prompt-window:state` to that
+/// window alone when it is opened again.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptWindowState {
+  prompt_id: String,
+  mode: PromptWindowMode,
+  compare_with: Option<String>,
+}
+
+/// Time span of the statistics page, ending today.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DashboardRange {
+  Week,
+  #[default]
+  Month,
+  Quarter,
+  Year,
+}
+
+impl DashboardRange {
+  fn days(self) -> i64 {
+    match self {
+      DashboardRange::Week => 7,
+      DashboardRange::Month => 30,
+      DashboardRange::Quarter => 90,
+      DashboardRange::Year => 365,
+    }
+  }
+}
+
+/// Returned by `get_dashboard_data`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DashboardData {
+  range: DashboardRange,
+  /// Every day of the range, oldest first, including days without activity.
+  days: Vec<DashboardDay>,
+  sources: Vec<SourceCount>,
+  top_tags: Vec<TagCount>,
+  classifier: ClassifierStats,
+  /// Share of classifications that found a prompt; `None` without classifications.
+  classifier_accept_rate: Option<f64>,
+  /// Share of reviewed inbox texts that were kept; `None` when none were reviewed.
+  inbox_accept_rate: Option<f64>,
+  /// Spend over the whole range, in `currency` (see the budget settings).
+  spend: f64,
+  currency: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DashboardDay {
+  /// `YYYY-MM-DD`, local time.
+  date: String,
+  prompts: u64,
+  captures: u64,
+  llm_calls: u64,
+  tokens: u64,
+  spend: f64,
+}
+
+/// Tags listed on the statistics page.
+const DASHBOARD_TOP_TAGS: usize = 20;
+
+/// Delay before the first suggestion run, then the interval between runs.
+const VOCABULARY_SUGGESTION_DELAY: Duration = Duration::from_secs(30);
+const VOCABULARY_SUGGESTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often monthly usage is compared with the budget.
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the export scheduler looks for a due run.
+const EXPORT_SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_VOCABULARY_BOOST: usize = 20;
+
+/// On-disk format of `vocabulary.json` (v2). v1 was a bare array of terms.
+#[derive(Debug, Serialize, Deserialize)]
+struct VocabularyFile {
+  version: u32,
+  entries: Vec<VocabularyEntry>,
+}
+
+const VOCABULARY_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VocabularyImportSummary {
+  /// Distinct terms found in the file.
+  total: usize,
+  added: usize,
+  existing: usize,
+  /// Terms whose weight or category boost was set from the file.
+  boosted: usize,
+  skipped: usize,
+  errors: Vec<String>,
+  /// Re-analysis of the prompts mentioning the imported terms, for `cancel_operation`.
+  operation_id: Option<String>,
+}
+
+/// Pause between prompts in background re-analysis so the UI and watcher stay responsive.
+const REANALYSIS_THROTTLE: Duration = Duration::from_millis(40);
+const REANALYSIS_PROGRESS_EVERY: usize = 10;
+
 #[derive(Debug, Deserialize)]
 struct PromptPayload {
   title: String,
@@ -71,12 +1443,38 @@ struct AnalysisPayload {
   tags: Vec<String>,
   classification: Value,
   qwen_model: Option<String>,
+  analyzer_version: Option<u32>,
 }
 
 #[tauri::command]
 fn summarize_prompt(state: State<AppState>, body: &str) -> PromptAnalysis {
   let vocabulary = state.vocabulary.lock().unwrap().clone();
-  summarize_prompt_with_vocab(body, &vocabulary)
+  let config = state.analysis_config.lock().unwrap().clone();
+  summarize_prompt_with_config(body, &vocabulary, &config)
+}
+
+#[tauri::command]
+fn split_sentences(text: &str) -> Vec<String> {
+  split_text_sentences(text).into_iter().map(str::to_string).collect()
+}
+
+#[tauri::command]
+fn compare_prompts(state: State<AppState>, id_a: String, id_b: String) -> Result<ComparisonReport, String> {
+  let load = |id: &str| {
+    state
+      .storage
+      .get_prompt(id)
+      .map_err(|error| {
+        error!(target: "prompts", prompt_id = %id, %error, "loading a prompt to compare failed");
+        error.to_string()
+      })?
+      .ok_or_else(|| format!("Prompt not found: {id}"))
+  };
+  let a = load(&id_a)?;
+  let b = load(&id_b)?;
+  let vocabulary = state.vocabulary.lock().unwrap().clone();
+  let config = state.analysis_config.lock().unwrap().clone();
+  Ok(compare_prompts_detailed_with_config(&a.body, &b.body, &vocabulary, &config))
 }
 
 #[tauri::command]
@@ -96,12 +1494,11 @@ fn save_prompt(state: State<AppState>, payload: PromptPayload) -> Result<Prompt,
   state
     .storage
     .create_prompt(record)
-    .map(|prompt| {
-      state.log(&format!("创建 Prompt 成功: {}", prompt.id));
-      prompt
+    .inspect(|prompt| {
+      info!(target: "prompts", prompt_id = %prompt.id, "prompt created");
     })
     .map_err(|error| {
-      state.log(&format!("创建 Prompt 失败: {error}"));
+      error!(target: "prompts", %error, "prompt creation failed");
       error.to_string()
     })
 }
@@ -109,15 +1506,79 @@ fn save_prompt(state: State<AppState>, payload: PromptPayload) -> Result<Prompt,
 #[tauri::command]
 fn list_prompts(state: State<AppState>) -> Result<Vec<Prompt>, String> {
   state.storage.list_prompts().map_err(|error| {
-    state.log(&format!("获取 Prompt 列表失败: {error}"));
+    error!(target: "prompts", %error, "listing prompts failed");
     error.to_string()
   })
 }
 
+/// Prompts containing every term of `query` in one of `fields` (title, tags and body by
+/// default), best first: a term in the title counts most, then tags, then the body. CJK runs
+/// are separate terms, so "gpt翻译" finds "GPT 中英翻译". An empty query lists the most
+/// recently updated prompts.
+#[tauri::command]
+fn search_prompts(
+  state: State<AppState>,
+  query: String,
+  fields: Option<Vec<SearchField>>,
+  limit: Option<usize>,
+) -> Result<Vec<TextHit>, String> {
+  let fields = fields
+    .filter(|fields| !fields.is_empty())
+    .unwrap_or_else(|| SearchField::ALL.to_vec());
+  let terms = search_terms(&query);
+  let candidates = state.storage.search_prompts(&terms, &fields).map_err(|error| {
+    error!(target: "search", %error, "prompt search failed");
+    error.to_string()
+  })?;
+  Ok(rank_text_hits(&terms, &fields, candidates, limit.unwrap_or(TEXT_SEARCH_LIMIT).max(1)))
+}
+
+#[tauri::command]
+fn list_prompts_by_intent(state: State<AppState>, intent: PromptIntent) -> Result<Vec<Prompt>, String> {
+  state.storage.list_prompts_by_intent(intent.as_str()).map_err(|error| {
+    error!(target: "prompts", intent = intent.as_str(), %error, "listing prompts by intent failed");
+    error.to_string()
+  })
+}
+
+#[tauri::command]
+fn list_prompts_by_output_language(state: State<AppState>, language: String) -> Result<Vec<Prompt>, String> {
+  let language = language.trim().to_lowercase();
+  state.storage.list_prompts_by_output_language(&language).map_err(|error| {
+    error!(target: "prompts", %language, %error, "listing prompts by output language failed");
+    error.to_string()
+  })
+}
+
+/// Prompts copied from matching apps, e.g. `process: "chrome", title: "ChatGPT"` for ChatGPT
+/// in Chrome or `process: "code"` for VS Code; both match parts, ignoring ASCII case.
+#[tauri::command]
+fn list_prompts_by_source_app(
+  state: State<AppState>,
+  process: Option<String>,
+  title: Option<String>,
+) -> Result<Vec<Prompt>, String> {
+  let clean = |text: Option<String>| text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty());
+  let (process, title) = (clean(process), clean(title));
+  state
+    .storage
+    .list_prompts_by_source_app(process.as_deref(), title.as_deref())
+    .map_err(|error| {
+      error!(target: "prompts", ?process, ?title, %error, "listing prompts by source app failed");
+      error.to_string()
+    })
+}
+
+/// The apps prompts were copied from, for the source app filter.
+#[tauri::command]
+fn list_source_apps(state: State<AppState>) -> Result<Vec<SourceAppCount>, String> {
+  state.storage.list_source_apps().map_err(|error| error.to_string())
+}
+
 #[tauri::command]
 fn get_prompt(state: State<AppState>, id: String) -> Result<Option<Prompt>, String> {
   state.storage.get_prompt(&id).map_err(|error| {
-    state.log(&format!("获取 Prompt {id} 失败: {error}"));
+    error!(target: "prompts", prompt_id = %id, %error, "loading prompt failed");
     error.to_string()
   })
 }
@@ -143,11 +1604,11 @@ fn update_prompt(state: State<AppState>, id: String, payload: UpdatePromptPayloa
     .storage
     .update_prompt(&id, patch)
     .map_err(|error| {
-      state.log(&format!("更新 Prompt {id} 失败: {error}"));
+      error!(target: "prompts", prompt_id = %id, %error, "prompt update failed");
       error.to_string()
     })?
     .ok_or_else(|| {
-      state.log(&format!("更新 Prompt {id} 失败: 未找到"));
+      warn!(target: "prompts", prompt_id = %id, "prompt update failed: not found");
       "Prompt not found".to_string()
     })
 }
@@ -157,14 +1618,536 @@ fn delete_prompt(state: State<AppState>, id: String) -> Result<bool, String> {
   state
     .storage
     .delete_prompt(&id)
-    .map(|result| {
-      state.log(&format!("删除 Prompt {id} => {result}"));
-      result
+    .inspect(|result| {
+      info!(target: "prompts", prompt_id = %id, deleted = *result, "prompt permanently deleted");
+    })
+    .map_err(|error| {
+      error!(target: "prompts", prompt_id = %id, %error, "prompt deletion failed");
+      error.to_string()
     })
+}
+
+/// Delete several prompts at once, in one transaction. Returns the number deleted.
+#[tauri::command]
+fn delete_prompts(state: State<AppState>, ids: Vec<String>) -> Result<usize, String> {
+  let deleted = state.storage.delete_prompts(&ids).map_err(|error| {
+    error!(target: "prompts", requested = ids.len(), %error, "bulk deletion failed");
+    error.to_string()
+  })?;
+  info!(target: "prompts", deleted, requested = ids.len(), "prompts permanently deleted");
+  Ok(deleted)
+}
+
+/// Add `tags` to several prompts' latest analyses, in one transaction. Returns the number of
+/// prompts that gained a tag.
+#[tauri::command]
+fn tag_prompts(state: State<AppState>, ids: Vec<String>, tags: Vec<String>) -> Result<usize, String> {
+  let mut clean: Vec<String> = Vec::new();
+  for tag in tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+    if !clean.iter().any(|other| other == tag) {
+      clean.push(tag.to_string());
+    }
+  }
+  if clean.is_empty() {
+    return Err("标签不能为空".into());
+  }
+  let changed = state.storage.add_prompt_tags(&ids, &clean).map_err(|error| {
+    error!(target: "prompts", requested = ids.len(), %error, "bulk tagging failed");
+    error.to_string()
+  })?;
+  let names = clean.join(", ");
+  info!(target: "prompts", tags = %names, changed, requested = ids.len(), "prompts tagged");
+  Ok(changed)
+}
+
+/// Put several prompts in `collection`, or take them out of their collection when it is
+/// empty or omitted, in one transaction. Returns the number of prompts moved.
+#[tauri::command]
+fn move_prompts_to_collection(
+  state: State<AppState>,
+  ids: Vec<String>,
+  collection: Option<String>,
+) -> Result<usize, String> {
+  let collection = collection
+    .map(|name| name.trim().to_string())
+    .filter(|name| !name.is_empty());
+  let moved = state
+    .storage
+    .set_prompts_collection(&ids, collection.as_deref())
+    .map_err(|error| {
+      error!(target: "prompts", requested = ids.len(), %error, "moving prompts to a collection failed");
+      error.to_string()
+    })?;
+  match &collection {
+    Some(name) => {
+      info!(target: "prompts", collection = %name, moved, requested = ids.len(), "prompts moved to a collection")
+    }
+    None => info!(target: "prompts", moved, requested = ids.len(), "prompts taken out of their collection"),
+  }
+  Ok(moved)
+}
+
+/// Collections prompts were put in, with their prompt counts, for the collection filter.
+#[tauri::command]
+fn list_collections(state: State<AppState>) -> Result<Vec<CollectionCount>, String> {
+  state.storage.list_collections().map_err(|error| error.to_string())
+}
+
+/// Set the language of several prompts, in one transaction; names such as "英文" become their
+/// code, and an empty or omitted `lang` clears it. Returns the number of prompts changed.
+#[tauri::command]
+fn set_language(state: State<AppState>, ids: Vec<String>, lang: Option<String>) -> Result<usize, String> {
+  let language = lang
+    .map(|lang| lang.trim().to_string())
+    .filter(|lang| !lang.is_empty())
+    .map(|lang| canonical_language(&lang).map_or(lang, str::to_string));
+  let changed = state
+    .storage
+    .set_prompts_language(&ids, language.as_deref())
     .map_err(|error| {
-      state.log(&format!("删除 Prompt {id} 失败: {error}"));
+      error!(target: "prompts", requested = ids.len(), %error, "setting the prompt language failed");
       error.to_string()
+    })?;
+  info!(target: "prompts", ?language, changed, requested = ids.len(), "prompt language set");
+  Ok(changed)
+}
+
+/// Undo a clipboard capture by moving its prompt to the trash; `id` defaults to the last
+/// prompt the watcher saved. Returns `false` if there is nothing to undo.
+#[tauri::command]
+fn undo_capture(app: AppHandle, state: State<AppState>, id: Option<String>) -> Result<bool, String> {
+  let Some(id) = id.or_else(|| state.last_capture.lock().unwrap().clone()) else {
+    return Ok(false);
+  };
+  undo_clipboard_capture(&app, &state, &id)
+}
+
+/// Capture the parts copied in merge mode now instead of waiting for the merge window to pass.
+/// Returns the saved prompt; `None` when nothing was pending, the merged text already exists
+/// or it went to the inbox.
+#[tauri::command]
+fn finalize_capture(app: AppHandle, state: State<AppState>) -> Result<Option<Prompt>, String> {
+  finalize_merge(&app, &state)
+}
+
+/// Take a prompt back out of the trash.
+#[tauri::command]
+fn restore_prompt(state: State<AppState>, id: String) -> Result<bool, String> {
+  state
+    .storage
+    .restore_prompt(&id)
+    .inspect(|result| {
+      info!(target: "prompts", prompt_id = %id, restored = *result, "prompt restored");
+    })
+    .map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+fn list_deleted_prompts(state: State<AppState>) -> Result<Vec<Prompt>, String> {
+  state.storage.list_deleted_prompts().map_err(|error| error.to_string())
+}
+
+/// Captures the classifier was unsure about, held back from the library, most recent first.
+#[tauri::command]
+fn list_quarantined(state: State<AppState>) -> Result<Vec<Prompt>, String> {
+  state.storage.list_quarantined_prompts().map_err(|error| error.to_string())
+}
+
+fn quarantined_prompt(state: &AppState, id: &str) -> Result<Prompt, String> {
+  let quarantined = state.storage.list_quarantined_prompts().map_err(|error| error.to_string())?;
+  quarantined
+    .into_iter()
+    .find(|prompt| prompt.id == id)
+    .ok_or_else(|| "该提示词不在隔离区".to_string())
+}
+
+/// Let a quarantined capture into the library, and keep it as an `is_prompt: true` label and
+/// classifier example.
+#[tauri::command]
+fn approve(app: AppHandle, state: State<AppState>, id: String) -> Result<Prompt, String> {
+  let prompt = quarantined_prompt(&state, &id)?;
+  state.storage.release_prompt(&prompt.id).map_err(|error| error.to_string())?;
+  label_with_example(&state, &prompt, true)?;
+  info!(target: "capture", prompt_id = %prompt.id, "quarantined prompt kept and added as a classifier example");
+  let _ = app.emit("prompts:quarantine-resolved", &prompt.id);
+  Ok(prompt)
+}
+
+/// Move a quarantined capture to the trash, and keep it as an `is_prompt: false` label and
+/// classifier example so similar texts are classified as non-prompts from now on.
+#[tauri::command]
+fn discard(app: AppHandle, state: State<AppState>, id: String) -> Result<(), String> {
+  let prompt = quarantined_prompt(&state, &id)?;
+  label_with_example(&state, &prompt, false)?;
+  state.storage.soft_delete_prompt(&prompt.id).map_err(|error| error.to_string())?;
+  info!(target: "capture", prompt_id = %prompt.id, "quarantined prompt discarded and added as a classifier example");
+  let _ = app.emit("prompts:quarantine-resolved", &prompt.id);
+  Ok(())
+}
+
+/// Attachments of a prompt, without their contents.
+#[tauri::command]
+fn list_attachments(state: State<AppState>, prompt_id: String) -> Result<Vec<Attachment>, String> {
+  state.storage.list_attachments(&prompt_id).map_err(|error| error.to_string())
+}
+
+/// Contents of an attachment, received by the frontend as an `ArrayBuffer`.
+#[tauri::command]
+fn get_attachment_data(state: State<AppState>, id: String) -> Result<Response, String> {
+  let attachment = state
+    .storage
+    .get_attachment(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "附件不存在".to_string())?;
+  Ok(Response::new(attachment.bytes))
+}
+
+/// Import prompts from files, e.g. dropped on the window: Markdown (with frontmatter), plain
+/// text, CSV, JSON and JSON Lines (see `file_import::read_prompts`). Each prompt is saved with
+/// its local analysis, tags from the file first; prompts already in the library are skipped.
+/// Runs on a blocking thread, reporting `import:progress`, and stops after the current prompt
+/// when cancelled through the operation id in the events. Returns one result per file.
+#[tauri::command]
+async fn import_files(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  paths: Vec<String>,
+) -> Result<Vec<FileImportResult>, String> {
+  let started = Instant::now();
+  let token = state.operations.start("import");
+  let handle = app.clone();
+  let results = tauri::async_runtime::spawn_blocking(move || import_prompt_files(&handle, &paths, &token))
+    .await
+    .map_err(|error| error.to_string())?;
+  let imported: usize = results.iter().map(|result| result.imported).sum();
+  let duplicates: usize = results.iter().map(|result| result.duplicates).sum();
+  let failed = results.iter().filter(|result| result.error.is_some()).count();
+  info!(
+    target: "import",
+    files = results.len(),
+    imported,
+    duplicates,
+    failed,
+    duration_ms = started.elapsed().as_millis() as u64,
+    "files imported"
+  );
+  Ok(results)
+}
+
+fn import_prompt_files(app: &AppHandle, paths: &[String], token: &CancellationToken) -> Vec<FileImportResult> {
+  let state = app.state::<AppState>();
+  let mut imported = 0;
+  let progress = |files_done: usize, imported: usize, done: bool| {
+    let _ = app.emit(
+      "import:progress",
+      ImportProgress {
+        operation_id: token.id().to_string(),
+        files_done,
+        files_total: paths.len(),
+        imported,
+        done,
+        cancelled: done && token.is_cancelled(),
+      },
+    );
+  };
+  progress(0, 0, false);
+
+  let mut results = Vec::with_capacity(paths.len());
+  for (index, path) in paths.iter().enumerate() {
+    let mut result = FileImportResult {
+      path: path.clone(),
+      ..FileImportResult::default()
+    };
+    let prompts = match token.is_cancelled() {
+      true => Err(IMPORT_CANCELLED.to_string()),
+      false => read_import_file(Path::new(path)),
+    };
+    match prompts {
+      Err(error) => result.error = Some(error),
+      Ok(prompts) => {
+        result.found = prompts.len();
+        for prompt in prompts {
+          if token.is_cancelled() {
+            result.error = Some(IMPORT_CANCELLED.into());
+            break;
+          }
+          if prompt.body.trim().is_empty() {
+            result.skipped += 1;
+            continue;
+          }
+          match save_imported_prompt(&state, path, prompt) {
+            Ok(Some(saved)) => {
+              result.imported += 1;
+              result.prompt_ids.push(saved.id);
+              imported += 1;
+              if imported % IMPORT_PROGRESS_PROMPTS == 0 {
+                progress(index, imported, false);
+              }
+            }
+            Ok(None) => result.duplicates += 1,
+            Err(error) => {
+              result.error = Some(error.to_string());
+              break;
+            }
+          }
+        }
+      }
+    }
+    results.push(result);
+    progress(index + 1, imported, false);
+  }
+  progress(paths.len(), imported, true);
+  results
+}
+
+fn read_import_file(path: &Path) -> Result<Vec<file_import::ImportedPrompt>, String> {
+  let size = std::fs::metadata(path).map_err(|error| format!("无法读取文件: {error}"))?.len();
+  if size > IMPORT_FILE_MAX_BYTES {
+    return Err(format!("文件过大，上限为 {} MB", IMPORT_FILE_MAX_BYTES / (1024 * 1024)));
+  }
+  file_import::read_prompts(path)
+}
+
+/// Save an imported prompt with its local analysis, listing the file's tags before the
+/// suggested ones. Returns `None` for text already in the library.
+fn save_imported_prompt(
+  state: &AppState,
+  path: &str,
+  imported: file_import::ImportedPrompt,
+) -> Result<Option<Prompt>, StorageError> {
+  if state.storage.find_prompt_by_content(&imported.body)?.is_some() {
+    return Ok(None);
+  }
+  let vocab = state.vocabulary.lock().unwrap().clone();
+  let config = state.analysis_config.lock().unwrap().clone();
+  let analysis = summarize_prompt_with_config(&imported.body, &vocab, &config);
+  let mut metadata = analysis_metadata(&analysis, &imported.body);
+  metadata["source"] = json!(IMPORT_SOURCE);
+  metadata["import_file"] = json!(path);
+  let prompt = state.storage.create_prompt(NewPrompt {
+    title: imported
+      .title
+      .filter(|title| !title.trim().is_empty())
+      .unwrap_or_else(|| derive_title(&imported.body)),
+    body: imported.body,
+    language: imported
+      .language
+      .map(|language| canonical_language(&language).map_or(language, str::to_string)),
+    model_hint: imported.model_hint,
+    metadata,
+  })?;
+
+  let mut tags = imported.tags;
+  for tag in &analysis.suggested_tags {
+    if !tags.contains(tag) {
+      tags.push(tag.clone());
+    }
+  }
+  let classification = analysis_classification(&analysis, IMPORT_SOURCE);
+  state.storage.create_analysis(NewAnalysis {
+    prompt_id: prompt.id.clone(),
+    summary: analysis.summary,
+    tags,
+    classification,
+    qwen_model: Some("local-nlp".into()),
+    analyzer_version: Some(ANALYZER_VERSION),
+  })?;
+  Ok(Some(prompt))
+}
+
+/// Attach a file from disk to a prompt, under the file's name. Files over
+/// `ATTACHMENT_MAX_BYTES` are refused; large ones report reading progress on
+/// `attachments:progress`.
+#[tauri::command]
+async fn add_attachment(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  prompt_id: String,
+  path: String,
+) -> Result<Attachment, String> {
+  let file_path = PathBuf::from(&path);
+  let filename = file_path
+    .file_name()
+    .and_then(|name| name.to_str())
+    .ok_or_else(|| format!("无效的文件路径: {}", file_path.display()))?
+    .to_string();
+  state
+    .storage
+    .get_prompt(&prompt_id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "Prompt not found".to_string())?;
+  let storage = state.storage.clone();
+  let attachment = tauri::async_runtime::spawn_blocking(move || {
+    let file = std::fs::File::open(&file_path).map_err(|error| error.to_string())?;
+    let total = file.metadata().map_err(|error| error.to_string())?.len();
+    check_attachment_size(total)?;
+    let mut bytes = Vec::with_capacity(total as usize);
+    // One byte past the limit, to notice a file that grew while it was read.
+    let read = transfer_attachment(&app, &path, total, file.take(ATTACHMENT_MAX_BYTES + 1), &mut bytes)
+      .map_err(|error| error.to_string())?;
+    check_attachment_size(read)?;
+    storage
+      .add_attachment(NewAttachment {
+        prompt_id,
+        filename,
+        bytes,
+      })
+      .map_err(|error| error.to_string())
+  })
+  .await
+  .map_err(|error| error.to_string())??;
+  info!(target: "attachments", prompt_id = %attachment.prompt_id, filename = %attachment.filename, "attachment added");
+  Ok(attachment)
+}
+
+/// Write an attachment's contents to `path`, replacing any file there; large ones report
+/// writing progress on `attachments:progress`. Returns the path written.
+#[tauri::command]
+async fn save_attachment_to(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  id: String,
+  path: String,
+) -> Result<String, String> {
+  let storage = state.storage.clone();
+  let target = path.clone();
+  let attachment = tauri::async_runtime::spawn_blocking(move || {
+    let attachment = storage
+      .get_attachment(&id)
+      .map_err(|error| error.to_string())?
+      .ok_or_else(|| "附件不存在".to_string())?;
+    let file = std::fs::File::create(&target).map_err(|error| error.to_string())?;
+    let mut writer = std::io::BufWriter::new(file);
+    transfer_attachment(
+      &app,
+      &target,
+      attachment.bytes.len() as u64,
+      attachment.bytes.as_slice(),
+      &mut writer,
+    )
+    .and_then(|_| writer.flush())
+    .map_err(|error| error.to_string())?;
+    Ok::<_, String>(attachment)
+  })
+  .await
+  .map_err(|error| error.to_string())??;
+  info!(target: "attachments", filename = %attachment.filename, %path, "attachment saved");
+  Ok(path)
+}
+
+fn check_attachment_size(bytes: u64) -> Result<(), String> {
+  if bytes > ATTACHMENT_MAX_BYTES {
+    return Err(format!(
+      "附件过大（{:.1} MB），上限为 {} MB",
+      bytes as f64 / (1024.0 * 1024.0),
+      ATTACHMENT_MAX_BYTES / (1024 * 1024)
+    ));
+  }
+  Ok(())
+}
+
+/// Copy `reader` to `writer` in chunks of `ATTACHMENT_CHUNK_BYTES`, emitting
+/// `attachments:progress` for `path` after each chunk when `total` is at least
+/// `ATTACHMENT_PROGRESS_MIN_BYTES`. Returns the number of bytes copied.
+fn transfer_attachment(
+  app: &AppHandle,
+  path: &str,
+  total: u64,
+  mut reader: impl Read,
+  mut writer: impl Write,
+) -> std::io::Result<u64> {
+  let report = total >= ATTACHMENT_PROGRESS_MIN_BYTES;
+  let progress = |transferred: u64, done: bool| AttachmentProgress {
+    path: path.to_string(),
+    transferred,
+    total,
+    done,
+  };
+  let mut buffer = vec![0u8; ATTACHMENT_CHUNK_BYTES];
+  let mut transferred = 0u64;
+  loop {
+    let read = match reader.read(&mut buffer) {
+      Ok(0) => break,
+      Ok(read) => read,
+      Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+      Err(error) => return Err(error),
+    };
+    writer.write_all(&buffer[..read])?;
+    transferred += read as u64;
+    if report {
+      let _ = app.emit("attachments:progress", progress(transferred, false));
+    }
+  }
+  if report {
+    let _ = app.emit("attachments:progress", progress(transferred, true));
+  }
+  Ok(transferred)
+}
+
+#[tauri::command]
+fn delete_attachment(state: State<AppState>, id: String) -> Result<bool, String> {
+  state
+    .storage
+    .delete_attachment(&id)
+    .inspect(|result| {
+      info!(target: "attachments", attachment_id = %id, deleted = *result, "attachment deleted");
     })
+    .map_err(|error| error.to_string())
+}
+
+/// Split a multi-step prompt into child prompts, one per step, linked back to the parent
+/// (`chain_step`) and to each other in order (`next_step`). The steps are saved together, so
+/// a failure leaves none behind.
+#[tauri::command]
+fn split_prompt_chain(state: State<AppState>, id: String) -> Result<Vec<Prompt>, String> {
+  let parent = state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "Prompt not found".to_string())?;
+  let chain = detect_prompt_chain(&parent.body).ok_or_else(|| "未检测到多步骤提示词".to_string())?;
+
+  let steps = chain
+    .steps
+    .iter()
+    .map(|step| {
+      let body = if chain.preamble.is_empty() {
+        step.text.clone()
+      } else {
+        format!("{}\n\n{}", chain.preamble, step.text)
+      };
+      let mut record = NewPrompt::new(format!("{} · 第{}步", parent.title, step.index), body);
+      record.language = parent.language.clone();
+      record.model_hint = parent.model_hint.clone();
+      record.metadata = json!({
+        "source": "chain-split",
+        "parent_id": parent.id,
+        "step": step.index,
+        "preamble": chain.preamble
+      });
+      (step.index, record)
+    })
+    .collect();
+  let children = state.storage.create_chain_steps(&parent.id, steps).map_err(|error| {
+    error!(target: "prompts", prompt_id = %id, %error, "saving the chain steps failed");
+    error.to_string()
+  })?;
+  for child in &children {
+    if let Err(error) = reanalyze_prompt(&state, child, "chain-split") {
+      error!(target: "analysis", prompt_id = %child.id, %error, "chain step analysis failed");
+    }
+  }
+
+  info!(target: "prompts", prompt_id = %id, steps = children.len(), "prompt split into a chain");
+  Ok(children)
+}
+
+#[tauri::command]
+fn list_prompt_relations(state: State<AppState>, prompt_id: String) -> Result<Vec<PromptRelation>, String> {
+  state.storage.list_relations_for_prompt(&prompt_id).map_err(|error| {
+    error!(target: "prompts", %prompt_id, %error, "listing prompt relations failed");
+    error.to_string()
+  })
 }
 
 #[tauri::command]
@@ -175,6 +2158,7 @@ fn record_analysis(state: State<AppState>, payload: AnalysisPayload) -> Result<A
     tags,
     classification,
     qwen_model,
+    analyzer_version,
   } = payload;
   let entry = NewAnalysis {
     prompt_id,
@@ -182,24 +2166,25 @@ fn record_analysis(state: State<AppState>, payload: AnalysisPayload) -> Result<A
     tags,
     classification,
     qwen_model,
+    analyzer_version,
   };
 
   state.storage.create_analysis(entry).map_err(|error| {
-    state.log(&format!("写入分析失败: {error}"));
+    error!(target: "analysis", %error, "saving analysis failed");
     error.to_string()
   })
 }
 
 #[tauri::command]
 fn list_analyses(state: State<AppState>, prompt_id: String) -> Result<Vec<Analysis>, String> {
-  state.log(&format!("list_analyses called with prompt_id={prompt_id}"));
+  debug!(target: "analysis", %prompt_id, "listing analyses");
   match state.storage.list_analyses_for_prompt(&prompt_id) {
     Ok(list) => {
-      state.log(&format!("list_analyses prompt_id={prompt_id} -> {} rows", list.len()));
+      debug!(target: "analysis", %prompt_id, rows = list.len(), "analyses listed");
       Ok(list)
     }
     Err(error) => {
-      state.log(&format!("获取 Prompt {prompt_id} 分析失败: {error}"));
+      error!(target: "analysis", %prompt_id, %error, "listing analyses failed");
       Err(error.to_string())
     }
   }
@@ -211,13 +2196,47 @@ fn latest_analysis(state: State<AppState>, prompt_id: String) -> Result<Option<A
     .storage
     .latest_analysis_for_prompt(&prompt_id)
     .map_err(|error| {
-      state.log(&format!("获取 Prompt {prompt_id} 最新分析失败: {error}"));
+      error!(target: "analysis", %prompt_id, %error, "loading the latest analysis failed");
       error.to_string()
     })
 }
 
+/// Re-analyze, in the background, every prompt whose latest analysis predates the current
+/// analyzer, reporting progress on `analysis:reanalysis-progress`.
+#[tauri::command]
+fn reanalyze_outdated(app: AppHandle, state: State<AppState>) -> Result<StartedOperation, String> {
+  let outdated = state
+    .storage
+    .list_prompts_with_outdated_analysis(ANALYZER_VERSION)
+    .map_err(|error| {
+      error!(target: "analysis", %error, "querying outdated analyses failed");
+      error.to_string()
+    })?;
+  let count = outdated.len();
+  info!(target: "analysis", count, analyzer_version = ANALYZER_VERSION, "reanalysis of outdated prompts started");
+  let operation_id = spawn_reanalysis(
+    app,
+    "analysis:reanalysis-progress",
+    "analyzer-upgrade".into(),
+    move |_| Ok(outdated),
+  );
+  Ok(StartedOperation {
+    operation_id,
+    total: count,
+  })
+}
+
+/// Export every prompt with its latest analysis to CSV, at `target_path` or in a timestamped
+/// file in the exports folder. The file is written on a blocking thread, reporting
+/// `export:progress` every `EXPORT_PROGRESS_ROWS` rows and `export:done` at the end, also when
+/// it fails or is cancelled through the operation id in the events. Returns the path written.
 #[tauri::command]
-fn export_prompts_csv(state: State<AppState>, target_path: Option<String>) -> Result<String, String> {
+async fn export_prompts_csv(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  target_path: Option<String>,
+) -> Result<String, String> {
+  let started = Instant::now();
   let file_path = if let Some(custom_path) = target_path {
     let path = PathBuf::from(custom_path);
     if let Some(parent) = path.parent() {
@@ -229,21 +2248,75 @@ fn export_prompts_csv(state: State<AppState>, target_path: Option<String>) -> Re
     let file_name = format!("prompts-{}.csv", Local::now().format("%Y%m%d-%H%M%S"));
     state.export_dir.join(file_name)
   };
-  let prompts = state
-    .storage
-    .list_prompts()
-    .map_err(|error| {
-      state.log(&format!("导出 prompts 失败: {error}"));
-      error.to_string()
-    })?;
+  let path = file_path.to_string_lossy().to_string();
+  let prompts = state.storage.list_prompts().map_err(|error| {
+    error!(target: "export", %error, "loading prompts to export failed");
+    error.to_string()
+  })?;
+  let token = state.operations.start("export");
+  let operation_id = token.id().to_string();
+  let handle = app.clone();
+  let outcome = tauri::async_runtime::spawn_blocking(move || write_prompts_csv(&handle, &file_path, prompts, &token))
+    .await
+    .map_err(|error| error.to_string())
+    .and_then(|outcome| outcome);
+  let (rows, cancelled) = outcome.clone().unwrap_or_default();
+  let _ = app.emit(
+    "export:done",
+    ExportDone {
+      operation_id,
+      path: path.clone(),
+      rows,
+      cancelled,
+      error: outcome.err(),
+    },
+  );
+  if cancelled {
+    info!(
+      target: "export",
+      rows,
+      %path,
+      duration_ms = started.elapsed().as_millis() as u64,
+      "export cancelled, partial file removed"
+    );
+    return Err("导出已取消".into());
+  }
+  info!(target: "export", rows, %path, duration_ms = started.elapsed().as_millis() as u64, "prompts exported");
+  Ok(path)
+}
 
-  let mut file = std::fs::File::create(&file_path).map_err(|error| {
-    state.log(&format!("创建导出文件失败: {error}"));
+/// Write `prompts` to a CSV file for `export_prompts_csv` and scheduled exports. Returns the
+/// number of prompts written and whether `token` cancelled the export, in which case the
+/// partial file is removed.
+fn write_prompts_csv(
+  app: &AppHandle,
+  file_path: &Path,
+  prompts: Vec<Prompt>,
+  token: &CancellationToken,
+) -> Result<(usize, bool), String> {
+  let state = app.state::<AppState>();
+  let path = file_path.to_string_lossy().to_string();
+  let progress = |written: usize, total: usize| {
+    let _ = app.emit(
+      "export:progress",
+      ExportProgress {
+        operation_id: token.id().to_string(),
+        path: path.clone(),
+        written,
+        total,
+      },
+    );
+  };
+  let total = prompts.len();
+  progress(0, total);
+
+  let mut file = std::fs::File::create(file_path).map_err(|error| {
+    error!(target: "export", %path, %error, "creating the export file failed");
     error.to_string()
   })?;
   // Write UTF-8 BOM to improve compatibility with Excel
   if let Err(err) = file.write_all(&[0xEF, 0xBB, 0xBF]) {
-    state.log(&format!("写入 BOM 失败: {err}"));
+    warn!(target: "export", error = %err, "writing the BOM failed");
   }
   let mut writer = csv::Writer::from_writer(file);
   writer
@@ -262,7 +2335,12 @@ fn export_prompts_csv(state: State<AppState>, target_path: Option<String>) -> Re
     ])
     .map_err(|error| error.to_string())?;
 
-  for prompt in prompts {
+  for (index, prompt) in prompts.into_iter().enumerate() {
+    if token.is_cancelled() {
+      drop(writer);
+      let _ = std::fs::remove_file(file_path);
+      return Ok((index, true));
+    }
     let analyses = state
       .storage
       .list_analyses_for_prompt(&prompt.id)
@@ -289,236 +2367,7007 @@ fn export_prompts_csv(state: State<AppState>, target_path: Option<String>) -> Re
         classification,
       ])
       .map_err(|error| error.to_string())?;
+    if (index + 1) % EXPORT_PROGRESS_ROWS == 0 {
+      progress(index + 1, total);
+    }
   }
   writer.flush().map_err(|error| error.to_string())?;
-  Ok(file_path.to_string_lossy().to_string())
+  progress(total, total);
+  Ok((total, false))
+}
+
+/// Write `prompts` as a JSON array, each with its latest summary, tags and classification.
+fn write_prompts_json(state: &AppState, file_path: &Path, prompts: Vec<Prompt>) -> Result<usize, String> {
+  let mut records = Vec::with_capacity(prompts.len());
+  for prompt in prompts {
+    let analyses = state
+      .storage
+      .list_analyses_for_prompt(&prompt.id)
+      .map_err(|error| error.to_string())?;
+    let latest = analyses.first();
+    records.push(json!({
+      "id": prompt.id,
+      "title": prompt.title,
+      "body": prompt.body,
+      "language": prompt.language,
+      "model_hint": prompt.model_hint,
+      "metadata": prompt.metadata,
+      "created_at": prompt.created_at.to_rfc3339(),
+      "updated_at": prompt.updated_at.to_rfc3339(),
+      "latest_summary": latest.map(|entry| entry.summary.clone()),
+      "latest_tags": latest.map(|entry| entry.tags.clone()).unwrap_or_default(),
+      "classification": latest.map(|entry| entry.classification.clone()),
+    }));
+  }
+  let payload = serde_json::to_string_pretty(&records).map_err(|error| error.to_string())?;
+  std::fs::write(file_path, payload).map_err(|error| error.to_string())?;
+  Ok(records.len())
+}
+
+/// The prompts `filter` selects, most recently updated first.
+fn filtered_prompts(state: &AppState, filter: &ExportFilter) -> Result<Vec<Prompt>, StorageError> {
+  let mut prompts = match filter.query.as_deref() {
+    Some(query) => state
+      .storage
+      .search_prompts(&search_terms(query), &SearchField::ALL)?
+      .into_iter()
+      .map(|(prompt, _)| prompt)
+      .collect(),
+    None => state.storage.list_prompts()?,
+  };
+  if let Some(collection) = &filter.collection {
+    prompts.retain(|prompt| prompt.metadata.get("collection").and_then(Value::as_str) == Some(collection.as_str()));
+  }
+  if let Some(tag) = &filter.tag {
+    let tags = state.storage.list_latest_tags()?;
+    prompts.retain(|prompt| tags.get(&prompt.id).is_some_and(|tags| tags.contains(tag)));
+  }
+  prompts.sort_by_key(|prompt| Reverse(prompt.updated_at));
+  Ok(prompts)
+}
+
+/// Export the prompts `schedule` selects to its file: written next to it first and then
+/// moved over it, so a synced folder never holds half an export. Returns the rows written.
+fn write_scheduled_export(app_handle: &AppHandle, schedule: &ExportSchedule) -> Result<usize, String> {
+  let state = app_handle.state::<AppState>();
+  let prompts = filtered_prompts(&state, &schedule.filter).map_err(|error| error.to_string())?;
+  let path = PathBuf::from(&schedule.path);
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+  }
+  let mut partial = path.clone().into_os_string();
+  partial.push(".partial");
+  let partial = PathBuf::from(partial);
+  let rows = match schedule.format {
+    ExportFormat::Csv => {
+      let token = state.operations.start("scheduled-export");
+      match write_prompts_csv(app_handle, &partial, prompts, &token)? {
+        (_, true) => return Err("导出已取消".into()),
+        (rows, false) => rows,
+      }
+    }
+    ExportFormat::Json => write_prompts_json(&state, &partial, prompts)?,
+  };
+  std::fs::rename(&partial, &path).map_err(|error| {
+    let _ = std::fs::remove_file(&partial);
+    error.to_string()
+  })?;
+  Ok(rows)
+}
+
+/// Run the scheduled export now, record the outcome and the next due time, and emit
+/// `export:scheduled`. A failure is also shown as a desktop notification.
+fn run_scheduled_export(app_handle: &AppHandle, schedule: &ExportSchedule) -> ExportScheduleStatus {
+  let state = app_handle.state::<AppState>();
+  let outcome = write_scheduled_export(app_handle, schedule);
+  let now = Local::now();
+  let status = {
+    let mut status = state.export_schedule_status.lock().unwrap();
+    status.last_run_at = Some(now.to_rfc3339());
+    status.last_path = Some(schedule.path.clone());
+    status.last_rows = *outcome.as_ref().unwrap_or(&0);
+    status.last_error = outcome.as_ref().err().cloned();
+    status.next_run_at = schedule
+      .enabled
+      .then(|| schedule.next_run(now))
+      .flatten()
+      .map(|at| at.to_rfc3339());
+    status.clone()
+  };
+  if let Err(error) = persist_export_schedule_status(&state.export_schedule_status_path, &status) {
+    error!(target: "export", %error, "saving the export schedule status failed");
+  }
+  match &outcome {
+    Ok(rows) => info!(target: "export", rows, path = %schedule.path, "scheduled export written"),
+    Err(error) => {
+      error!(target: "export", path = %schedule.path, %error, "scheduled export failed");
+      let shown = app_handle
+        .notification()
+        .builder()
+        .title("定时导出失败")
+        .body(format!("{}\n{error}", schedule.path))
+        .show();
+      if let Err(err) = shown {
+        warn!(target: "export", error = %err, "export failure notification failed");
+      }
+    }
+  }
+  let _ = app_handle.emit("export:scheduled", &status);
+  status
 }
 
 #[tauri::command]
-fn list_vocabulary(state: State<AppState>) -> Vec<String> {
-  let mut vocab = state.vocabulary.lock().unwrap().clone();
-  vocab.sort();
-  vocab
+fn get_export_schedule(state: State<AppState>) -> ExportSchedule {
+  state.export_schedule.lock().unwrap().clone()
 }
 
 #[tauri::command]
-fn add_vocabulary_entry(state: State<AppState>, term: String) -> Result<Vec<String>, String> {
-  let normalized = normalize_vocab_term(&term);
-  if normalized.is_empty() {
-    return Err("请输入有效的词条".into());
+fn get_export_schedule_status(state: State<AppState>) -> ExportScheduleStatus {
+  state.export_schedule_status.lock().unwrap().clone()
+}
+
+/// Save the recurring export and work out its next run; the status is returned.
+#[tauri::command]
+fn configure_export_schedule(
+  app: AppHandle,
+  state: State<AppState>,
+  schedule: ExportSchedule,
+) -> Result<ExportScheduleStatus, String> {
+  let schedule = schedule.normalized();
+  schedule.validate()?;
+  persist_export_schedule(&state.export_schedule_path, &schedule).map_err(|error| error.to_string())?;
+  let status = {
+    let mut status = state.export_schedule_status.lock().unwrap();
+    status.next_run_at = schedule
+      .enabled
+      .then(|| schedule.next_run(Local::now()))
+      .flatten()
+      .map(|at| at.to_rfc3339());
+    status.clone()
+  };
+  if let Err(error) = persist_export_schedule_status(&state.export_schedule_status_path, &status) {
+    error!(target: "export", %error, "saving the export schedule status failed");
   }
-  let mut vocab = state.vocabulary.lock().unwrap();
-  if !vocab.iter().any(|item| normalize_vocab_term(item) == normalized) {
-    vocab.push(normalized.clone());
-    persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
-    state.log(&format!("新增词条: {normalized}"));
+  match &status.next_run_at {
+    Some(next) => info!(target: "export", path = %schedule.path, %next, "export schedule enabled"),
+    None => info!(target: "export", "export schedule disabled"),
   }
-  let mut list = vocab.clone();
-  list.sort();
-  Ok(list)
+  announce_settings(&app, "export_schedule", &schedule);
+  *state.export_schedule.lock().unwrap() = schedule;
+  Ok(status)
 }
 
+/// Run the configured export at once, e.g. to try a new path; the schedule is unchanged.
 #[tauri::command]
-fn remove_vocabulary_entry(state: State<AppState>, term: String) -> Result<Vec<String>, String> {
-  let cleaned = normalize_vocab_term(&term);
-  let mut vocab = state.vocabulary.lock().unwrap();
-  let before = vocab.len();
-  vocab.retain(|item| *item != cleaned);
-  if vocab.len() != before {
-    persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
-    state.log(&format!("删除词条: {cleaned}"));
+async fn run_export_schedule_now(app: AppHandle, state: State<'_, AppState>) -> Result<ExportScheduleStatus, String> {
+  let schedule = state.export_schedule.lock().unwrap().clone();
+  if schedule.path.is_empty() {
+    return Err("尚未设置定时导出的文件路径".into());
   }
-  let mut list = vocab.clone();
-  list.sort();
-  Ok(list)
+  tauri::async_runtime::spawn_blocking(move || run_scheduled_export(&app, &schedule))
+    .await
+    .map_err(|error| error.to_string())
 }
 
-fn main() {
-  Builder::default()
-    .plugin(tauri_plugin_shell::init())
-    .plugin(tauri_plugin_dialog::init())
+#[tauri::command]
+fn list_vocabulary(state: State<AppState>) -> Vec<String> {
+  vocabulary_terms(&state.vocabulary.lock().unwrap())
+}
+
+#[tauri::command]
+fn list_vocabulary_entries(state: State<AppState>) -> Vec<VocabularyEntry> {
+  let mut entries = state.vocabulary.lock().unwrap().clone();
+  entries.sort_by(|a, b| a.term.cmp(&b.term));
+  entries
+}
+
+#[tauri::command]
+fn add_vocabulary_entry(
+  app: AppHandle,
+  state: State<AppState>,
+  term: String,
+  weight: Option<usize>,
+) -> Result<Vec<String>, String> {
+  let normalized = normalize_vocab_term(&term);
+  if normalized.is_empty() {
+    return Err("请输入有效的词条".into());
+  }
+  validate_vocabulary_weight(weight)?;
+  let mut vocab = state.vocabulary.lock().unwrap();
+  if let Some(owner) = vocab.iter().find(|entry| entry.aliases.contains(&normalized)) {
+    return Err(format!("“{normalized}” 已是词条 “{}” 的同义词", owner.term));
+  }
+  match vocab.iter().position(|entry| entry.term == normalized) {
+    Some(index) if weight.is_none() || vocab[index].weight == weight => return Ok(vocabulary_terms(&vocab)),
+    Some(index) => vocab[index].weight = weight,
+    None => vocab.push(VocabularyEntry {
+      weight,
+      ..VocabularyEntry::new(normalized.clone())
+    }),
+  }
+  persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
+  info!(target: "vocabulary", term = %normalized, weight = %describe_weight(weight), "term added");
+  spawn_vocabulary_reanalysis(app, normalized.clone(), vec![normalized]);
+  Ok(vocabulary_terms(&vocab))
+}
+
+/// Change the weight of an existing vocabulary term; `None` restores the default ×3.
+#[tauri::command]
+fn update_vocabulary_entry(
+  app: AppHandle,
+  state: State<AppState>,
+  term: String,
+  weight: Option<usize>,
+) -> Result<VocabularyEntry, String> {
+  let cleaned = normalize_vocab_term(&term);
+  validate_vocabulary_weight(weight)?;
+  let mut vocab = state.vocabulary.lock().unwrap();
+  let Some(entry) = vocab.iter_mut().find(|entry| entry.term == cleaned) else {
+    return Err(format!("词条不存在: {cleaned}"));
+  };
+  if entry.weight == weight {
+    return Ok(entry.clone());
+  }
+  entry.weight = weight;
+  let updated = entry.clone();
+  persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
+  info!(target: "vocabulary", term = %cleaned, weight = %describe_weight(weight), "term weight changed");
+  spawn_vocabulary_reanalysis(app, cleaned, updated.spellings().map(str::to_string).collect());
+  Ok(updated)
+}
+
+#[tauri::command]
+fn remove_vocabulary_entry(app: AppHandle, state: State<AppState>, term: String) -> Result<Vec<String>, String> {
+  let cleaned = normalize_vocab_term(&term);
+  let mut vocab = state.vocabulary.lock().unwrap();
+  if let Some(index) = vocab.iter().position(|entry| entry.term == cleaned) {
+    let removed = vocab.remove(index);
+    persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
+    info!(target: "vocabulary", term = %cleaned, "term removed");
+    let needles = removed.spellings().map(str::to_string).collect();
+    spawn_vocabulary_reanalysis(app, cleaned, needles);
+  }
+  Ok(vocabulary_terms(&vocab))
+}
+
+/// Per-term usage across the library, least used first, to spot dead terms worth pruning.
+#[tauri::command]
+fn vocabulary_stats(state: State<AppState>) -> Result<Vec<TermUsage>, String> {
+  let entries = state.vocabulary.lock().unwrap().clone();
+  let mut stats = Vec::with_capacity(entries.len());
+  for entry in &entries {
+    let spellings: Vec<String> = entry.spellings().map(str::to_string).collect();
+    let usage = state.storage.term_usage(&entry.term, &spellings).map_err(|error| {
+      error!(target: "vocabulary", term = %entry.term, %error, "counting term usage failed");
+      error.to_string()
+    })?;
+    stats.push(usage);
+  }
+  stats.sort_by(|a, b| {
+    (a.mentions, a.tagged)
+      .cmp(&(b.mentions, b.tagged))
+      .then_with(|| a.term.cmp(&b.term))
+  });
+  Ok(stats)
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LlmProviderStatus {
+  settings: LlmSettings,
+  model: String,
+  base_url: String,
+  enabled: bool,
+}
+
+/// Which LLM backend captures are classified with, and whether it is usable.
+#[tauri::command]
+fn get_llm_provider(state: State<AppState>) -> LlmProviderStatus {
+  let settings = state.current_llm_settings();
+  LlmProviderStatus {
+    model: settings.model().to_string(),
+    base_url: settings.base_url().to_string(),
+    settings,
+    enabled: state.llm_client().is_some(),
+  }
+}
+
+/// Provider, base URL, models and retries of the LLM backend: those saved by
+/// `set_llm_backend`, else the `PROMPTLAB_LLM_*` environment variables read at startup.
+#[tauri::command]
+fn get_llm_backend(state: State<AppState>) -> BackendSettings {
+  state.llm_settings.lock().unwrap().backend()
+}
+
+/// Switch the LLM backend or change its base URL, models or retries without restarting. Saved
+/// to `llm_backend.json`, which from then on takes precedence over the environment; API keys
+/// stay in the keyring (see `set_api_key`). The client is rebuilt right away.
+#[tauri::command]
+fn set_llm_backend(
+  app: AppHandle,
+  state: State<AppState>,
+  settings: BackendSettings,
+) -> Result<LlmProviderStatus, String> {
+  let settings = settings.normalized();
+  settings.validate()?;
+  persist_llm_backend(&state.llm_backend_path, &settings).map_err(|error| error.to_string())?;
+  {
+    let mut current = state.llm_settings.lock().unwrap();
+    *current = current.clone().with_backend(settings.clone());
+  }
+  // The new provider cannot be its own fallback.
+  {
+    let mut fallback = state.llm_fallback.lock().unwrap();
+    *fallback = fallback.clone().normalized(settings.provider);
+  }
+  info!(
+    target: "llm",
+    provider = settings.provider.as_str(),
+    base_url = settings.base_url.as_deref().unwrap_or(settings.provider.default_base_url()),
+    model = settings.model.as_deref().unwrap_or(settings.provider.default_model()),
+    "backend changed"
+  );
+  state.reconnect_llm();
+  announce_settings(&app, "llmBackend", &settings);
+  Ok(get_llm_provider(state))
+}
+
+/// Emit `settings:changed` with `{ section, settings }` after a settings section was saved,
+/// so every open window shows the values now in effect.
+fn announce_settings(app_handle: &AppHandle, section: &str, settings: &impl Serialize) {
+  let _ = app_handle.emit("settings:changed", json!({ "section": section, "settings": settings }));
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LocalLlmStatus {
+  settings: LocalLlmSettings,
+  base_url: String,
+  model: String,
+  /// Whether classification currently runs on the local server.
+  enabled: bool,
+}
+
+fn local_llm_status(state: &AppState) -> LocalLlmStatus {
+  let settings = state.local_llm.lock().unwrap().clone();
+  LocalLlmStatus {
+    base_url: settings.base_url().to_string(),
+    model: settings.llm_settings().model().to_string(),
+    enabled: state.local_llm_client.lock().unwrap().is_some(),
+    settings,
+  }
+}
+
+/// Providers tried after the active one and whether deep analysis may end with the local
+/// heuristic analyzer.
+#[tauri::command]
+fn get_llm_fallback(state: State<AppState>) -> FallbackSettings {
+  state.llm_fallback.lock().unwrap().clone()
+}
+
+/// Save the fallback chain and rebuild the client with it. The active provider is dropped
+/// from the list; providers that cannot be set up yet (e.g. no API key) are skipped until
+/// they can.
+#[tauri::command]
+fn set_llm_fallback(state: State<AppState>, settings: FallbackSettings) -> Result<FallbackSettings, String> {
+  let provider = state.llm_settings.lock().unwrap().provider;
+  let settings = settings.normalized(provider);
+  persist_llm_fallback(&state.llm_fallback_path, &settings).map_err(|error| error.to_string())?;
+  *state.llm_fallback.lock().unwrap() = settings.clone();
+  state.reconnect_llm();
+  let chain: Vec<&str> = std::iter::once(provider)
+    .chain(settings.providers.iter().copied())
+    .map(|provider| provider.as_str())
+    .chain(settings.heuristic.then_some("heuristic"))
+    .collect();
+  info!(target: "llm", chain = %chain.join(" → "), "fallback chain changed");
+  Ok(settings)
+}
+
+#[tauri::command]
+fn get_local_llm(state: State<AppState>) -> LocalLlmStatus {
+  local_llm_status(&state)
+}
+
+/// Configure the local model server (Ollama, or LM Studio via `openai_compatible`). While
+/// enabled, clipboard and batch classification run on it instead of the cloud backend, so
+/// they keep working offline; other LLM features still use the cloud backend.
+#[tauri::command]
+fn set_local_llm(state: State<AppState>, settings: LocalLlmSettings) -> Result<LocalLlmStatus, String> {
+  let settings = settings.normalized();
+  settings.validate()?;
+  persist_local_llm(&state.local_llm_path, &settings).map_err(|error| error.to_string())?;
+  *state.local_llm.lock().unwrap() = settings;
+  state.reconnect_local_llm();
+  let status = local_llm_status(&state);
+  info!(
+    target: "llm",
+    provider = status.settings.provider.as_str(),
+    base_url = %status.base_url,
+    model = %status.model,
+    enabled = status.enabled,
+    "local model changed"
+  );
+  Ok(status)
+}
+
+/// Models available on the local server: the saved one, or `provider` / `base_url` when
+/// given, so a model can be picked before the settings are saved.
+#[tauri::command]
+async fn list_local_models(
+  state: State<'_, AppState>,
+  provider: Option<String>,
+  base_url: Option<String>,
+) -> Result<Vec<String>, String> {
+  let mut settings = state.local_llm.lock().unwrap().clone();
+  if let Some(provider) = provider {
+    let provider = parse_provider(&provider)?;
+    if provider != settings.provider {
+      settings.provider = provider;
+      settings.base_url = None;
+    }
+  }
+  if base_url.is_some() {
+    settings.base_url = base_url;
+  }
+  let settings = LocalLlmSettings {
+    enabled: false,
+    ..settings.normalized()
+  };
+  settings.validate()?;
+  let provider = build_provider(&settings.llm_settings()).map_err(|error| error.to_string())?;
+  provider.list_models().await.map_err(|error| {
+    error!(target: "llm", base_url = %settings.base_url(), %error, "listing local models failed");
+    error.to_string()
+  })
+}
+
+/// Models offered by `provider` (`/models`, or `/api/tags` for Ollama), so the settings can
+/// list real model names. The active backend is queried at its configured base URL; other
+/// providers at their default one, with their stored API key and the current network settings.
+#[tauri::command]
+async fn list_models(state: State<'_, AppState>, provider: String) -> Result<Vec<String>, String> {
+  let provider = parse_provider(&provider)?;
+  let current = state.current_llm_settings();
+  let settings = if provider == current.provider {
+    current
+  } else {
+    LlmSettings {
+      provider,
+      network: current.network,
+      retry: current.retry,
+      ..LlmSettings::default()
+    }
+  };
+  let settings = with_stored_api_key(&settings);
+  let backend = build_provider(&settings).map_err(|error| error.to_string())?;
+  let _permit = state.llm_limiter.acquire().await;
+  let mut models = backend.list_models().await.map_err(|error| {
+    error!(target: "llm", provider = provider.as_str(), %error, "listing models failed");
+    error.to_string()
+  })?;
+  models.sort();
+  models.dedup();
+  Ok(models)
+}
+
+#[tauri::command]
+fn get_llm_debug_log(state: State<AppState>) -> LlmDebugStatus {
+  state.llm_debug.status()
+}
+
+/// Turn the request/response debug log on or off. Entries are redacted, but the log still
+/// holds prompt texts, so it is meant to stay off outside debugging sessions.
+#[tauri::command]
+fn set_llm_debug_log(state: State<AppState>, enabled: bool) -> Result<LlmDebugStatus, String> {
+  let settings = LlmDebugSettings { enabled };
+  persist_llm_debug_settings(&state.llm_debug.settings_path, &settings).map_err(|error| error.to_string())?;
+  *state.llm_debug.settings.lock().unwrap() = settings;
+  info!(target: "llm", enabled, "debug log switched");
+  Ok(state.llm_debug.status())
+}
+
+/// Delete `llm_debug.log` and its rotated files.
+#[tauri::command]
+fn clear_llm_debug_log(state: State<AppState>) -> Result<LlmDebugStatus, String> {
+  state.llm_debug.clear().map_err(|error| {
+    error!(target: "llm", %error, "clearing the debug log failed");
+    error.to_string()
+  })?;
+  info!(target: "llm", "debug log cleared");
+  Ok(state.llm_debug.status())
+}
+
+/// The last `tail_lines` entries of `promptlab.log` and its rotated files, oldest first, at
+/// `level_filter` or above (`debug`, `info`, `warn`, `error`; all when omitted), for showing
+/// recent activity and errors.
+#[tauri::command]
+fn read_log(
+  state: State<AppState>,
+  tail_lines: Option<usize>,
+  level_filter: Option<LogLevel>,
+) -> Result<Vec<LogEntry>, String> {
+  let limit = tail_lines.unwrap_or(READ_LOG_DEFAULT_LINES).clamp(1, READ_LOG_MAX_LINES);
+  let min_level = level_filter.unwrap_or(LogLevel::Debug);
+  log_file::read_tail(&state.log_path, limit, min_level).map_err(|error| error.to_string())
+}
+
+/// Delete `promptlab.log` and its rotated files.
+#[tauri::command]
+fn clear_logs(state: State<AppState>) -> Result<(), String> {
+  log_file::clear(&state.log_path).map_err(|error| error.to_string())?;
+  info!(target: "app", "log cleared");
+  Ok(())
+}
+
+#[tauri::command]
+fn get_log_level(state: State<AppState>) -> LogLevel {
+  load_logging_settings(&state.logging_path).level
+}
+
+/// Write events at `level` (`debug`, `info`, `warn`, `error`) or above to `promptlab.log`
+/// from now on; `debug` adds every LLM call with its duration and token counts.
+#[tauri::command]
+fn set_log_level(state: State<AppState>, level: LogLevel) -> Result<LogLevel, String> {
+  persist_logging_settings(&state.logging_path, &LoggingSettings { level }).map_err(|error| error.to_string())?;
+  state.log_level.set(level)?;
+  info!(target: "app", ?level, "log level changed");
+  Ok(level)
+}
+
+/// Send a minimal request with the current key and network settings and report which step
+/// fails: DNS, connection/proxy, TLS, authentication or quota, plus the round-trip latency.
+#[tauri::command]
+async fn test_llm_connection(state: State<'_, AppState>) -> Result<ConnectionDiagnostics, String> {
+  let settings = with_stored_api_key(&state.current_llm_settings());
+  let base_url = settings.base_url().to_string();
+  let dns = tauri::async_runtime::spawn_blocking(move || check_dns(&base_url))
+    .await
+    .unwrap_or_else(|error| DiagnosticCheck::failed(error.to_string()));
+  let diagnostics = match build_provider(&settings) {
+    Ok(provider) => {
+      let _permit = state.llm_limiter.acquire().await;
+      diagnose_connection(provider.as_ref(), &settings, dns).await
+    }
+    Err(error) => ConnectionDiagnostics::unavailable(&settings, dns, &error),
+  };
+  match &diagnostics.message {
+    None => info!(
+      target: "llm",
+      provider = settings.provider.as_str(),
+      latency_ms = diagnostics.latency_ms.unwrap_or_default(),
+      "connection test passed"
+    ),
+    Some(message) => {
+      warn!(target: "llm", provider = settings.provider.as_str(), error = %message, "connection test failed")
+    }
+  }
+  Ok(diagnostics)
+}
+
+fn parse_provider(name: &str) -> Result<ProviderKind, String> {
+  ProviderKind::parse(name).ok_or_else(|| format!("未知的模型后端: {name}"))
+}
+
+/// Store the API key for `provider` in the OS keyring. Keys for the active backend take
+/// effect immediately, so keys can be rotated without restarting.
+#[tauri::command]
+fn set_api_key(
+  app: AppHandle,
+  state: State<AppState>,
+  provider: String,
+  key: String,
+) -> Result<LlmProviderStatus, String> {
+  let provider = parse_provider(&provider)?;
+  let key = key.trim();
+  if key.is_empty() {
+    return Err("API Key 不能为空".into());
+  }
+  keyring_entry(provider)
+    .and_then(|entry| entry.set_password(key))
+    .map_err(|error| {
+      error!(target: "keyring", provider = provider.as_str(), %error, "saving the API key failed");
+      error.to_string()
+    })?;
+  info!(target: "keyring", provider = provider.as_str(), "API key updated");
+  if state.uses_provider(provider) {
+    state.reconnect_llm();
+  }
+  // The status never includes the key itself.
+  let status = get_llm_provider(state);
+  announce_settings(&app, "llmProvider", &status);
+  Ok(status)
+}
+
+/// Remove the keyring entry for `provider`; the environment key, if any, applies again.
+#[tauri::command]
+fn clear_api_key(app: AppHandle, state: State<AppState>, provider: String) -> Result<LlmProviderStatus, String> {
+  let provider = parse_provider(&provider)?;
+  match keyring_entry(provider).and_then(|entry| entry.delete_credential()) {
+    Ok(()) | Err(keyring::Error::NoEntry) => {}
+    Err(error) => {
+      error!(target: "keyring", provider = provider.as_str(), %error, "deleting the API key failed");
+      return Err(error.to_string());
+    }
+  }
+  info!(target: "keyring", provider = provider.as_str(), "API key cleared");
+  if state.uses_provider(provider) {
+    state.reconnect_llm();
+  }
+  // The status never includes the key itself.
+  let status = get_llm_provider(state);
+  announce_settings(&app, "llmProvider", &status);
+  Ok(status)
+}
+
+/// Ask the configured LLM for a structured analysis of one prompt and store it as a new
+/// analysis row tagged with the model name. With the heuristic fallback enabled, a prompt
+/// no backend could analyze gets a local analysis instead (see [`heuristic_analysis`]).
+#[tauri::command]
+async fn analyze_with_qwen(state: State<'_, AppState>, prompt_id: String) -> Result<Analysis, String> {
+  let started = Instant::now();
+  let prompt = state
+    .storage
+    .get_prompt(&prompt_id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  let heuristic = state.llm_fallback.lock().unwrap().heuristic;
+  let Some(client) = state.llm_client() else {
+    if heuristic {
+      return heuristic_analysis(&state, &prompt, "LLM not configured");
+    }
+    return Err("未配置 LLM，无法进行模型分析".into());
+  };
+  let request = ChatRequest {
+    model: client.model_for(LlmTask::Summarization),
+    messages: vec![ChatMessage::system(deep_analysis_system_prompt()), ChatMessage::user(&prompt.body)],
+    temperature: Some(0.3),
+    top_p: None,
+    max_tokens: Some(1500),
+    json_output: true,
+  };
+  let result = match call_structured(&client, "analyze", Some(&prompt.id), request, DeepAnalysis::from_reply).await {
+    Ok((response, usage, analysis)) => analysis.map(|analysis| (analysis, response.model, usage)),
+    Err(error) => Err(error),
+  };
+  let (analysis, model, usage) = match result {
+    Ok(result) => result,
+    Err(error) if heuristic && error.is_backend_failure() => {
+      warn!(
+        target: "analysis",
+        %prompt_id,
+        %error,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "model analysis failed, using the local analysis"
+      );
+      return heuristic_analysis(&state, &prompt, &error.to_string());
+    }
+    Err(error) => {
+      error!(
+        target: "analysis",
+        %prompt_id,
+        %error,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "model analysis failed"
+      );
+      return Err(error.to_string());
+    }
+  };
+  let classification = json!({
+    "topic": analysis.category,
+    "category": analysis.category,
+    "role": analysis.role,
+    "targets": analysis.audience,
+    "audience": analysis.audience,
+    "keywords": analysis.tags,
+    "quality_notes": analysis.quality_notes,
+    "source": "qwen",
+    "provider": usage.provider,
+    "usage": usage
+  });
+  let record = NewAnalysis {
+    prompt_id: prompt.id,
+    summary: analysis.summary,
+    tags: analysis.tags,
+    classification,
+    qwen_model: Some(model),
+    analyzer_version: None,
+  };
+  let saved = state.storage.create_analysis(record).map_err(|error| error.to_string())?;
+  info!(
+    target: "analysis",
+    %prompt_id,
+    model = saved.qwen_model.as_deref().unwrap_or_default(),
+    duration_ms = started.elapsed().as_millis() as u64,
+    "model analysis saved"
+  );
+  Ok(saved)
+}
+
+/// Have the configured LLM score a prompt 1-5 on each `rubric` criterion (default:
+/// clarity, specificity, constraint completeness, format definition) with a rationale.
+/// The result is stored as a new analysis that keeps the latest analysis's summary, tags and
+/// fields and adds `quality` (scores and rationales) and `quality_score` (their mean).
+#[tauri::command]
+async fn score_prompt(
+  state: State<'_, AppState>,
+  id: String,
+  rubric: Option<Vec<RubricCriterion>>,
+) -> Result<Analysis, String> {
+  let Some(client) = state.llm_client() else {
+    return Err("未配置 LLM，无法评分".into());
+  };
+  let prompt = state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  let rubric = normalize_rubric(rubric.unwrap_or_else(default_rubric))?;
+  let request = ChatRequest {
+    model: client.model_for(LlmTask::Summarization),
+    messages: vec![ChatMessage::system(rubric_system_prompt(&rubric)), ChatMessage::user(&prompt.body)],
+    temperature: Some(0.2),
+    top_p: None,
+    max_tokens: Some(1500),
+    json_output: true,
+  };
+  let parse = |content: &str| RubricReply::from_reply(content, &rubric);
+  let result = match call_structured(&client, "score", Some(&prompt.id), request, parse).await {
+    Ok((response, usage, reply)) => reply.map(|reply| (reply, response.model, usage)),
+    Err(error) => Err(error),
+  };
+  let (reply, model, usage) = result.map_err(|error| {
+    error!(target: "analysis", prompt_id = %id, %error, "prompt scoring failed");
+    error.to_string()
+  })?;
+
+  let latest = state
+    .storage
+    .latest_analysis_for_prompt(&prompt.id)
+    .map_err(|error| error.to_string())?;
+  let overall = (reply.overall() * 100.0).round() / 100.0;
+  let mut classification = match latest.as_ref().map(|analysis| &analysis.classification) {
+    Some(Value::Object(fields)) => fields.clone(),
+    _ => serde_json::Map::new(),
+  };
+  classification.insert(
+    "quality".into(),
+    json!({
+      "rubric": rubric,
+      "scores": reply.scores,
+      "summary": reply.summary,
+      "model": model,
+      "usage": usage
+    }),
+  );
+  classification.insert("quality_score".into(), json!(overall));
+  let record = match latest {
+    Some(latest) => NewAnalysis {
+      prompt_id: prompt.id,
+      summary: latest.summary,
+      tags: latest.tags,
+      classification: Value::Object(classification),
+      qwen_model: latest.qwen_model,
+      analyzer_version: latest.analyzer_version,
+    },
+    None => NewAnalysis {
+      prompt_id: prompt.id,
+      summary: reply.summary,
+      tags: Vec::new(),
+      classification: Value::Object(classification),
+      qwen_model: Some(model),
+      analyzer_version: None,
+    },
+  };
+  let saved = state.storage.create_analysis(record).map_err(|error| error.to_string())?;
+  info!(target: "analysis", prompt_id = %id, score = overall, "prompt scored");
+  Ok(saved)
+}
+
+/// Scored prompts, best first, by the `quality_score` of their latest analysis.
+#[tauri::command]
+fn list_prompts_by_quality(state: State<AppState>) -> Result<Vec<RankedPrompt>, String> {
+  state.storage.list_prompts_by_quality().map_err(|error| {
+    error!(target: "prompts", %error, "listing prompts by quality failed");
+    error.to_string()
+  })
+}
+
+/// Last link of the fallback chain: the local analyzer's result saved in place of a deep
+/// analysis, with `provider = "heuristic"` and why no backend answered.
+fn heuristic_analysis(state: &AppState, prompt: &Prompt, reason: &str) -> Result<Analysis, String> {
+  let vocabulary = state.vocabulary.lock().unwrap().clone();
+  let config = state.analysis_config.lock().unwrap().clone();
+  let analysis = summarize_prompt_with_config(&prompt.body, &vocabulary, &config);
+  let mut classification = analysis_classification(&analysis, "qwen");
+  classification["provider"] = json!("heuristic");
+  classification["fallback_reason"] = json!(reason);
+  let record = NewAnalysis {
+    prompt_id: prompt.id.clone(),
+    summary: analysis.summary,
+    tags: analysis.suggested_tags,
+    classification,
+    qwen_model: Some("local-nlp".into()),
+    analyzer_version: Some(ANALYZER_VERSION),
+  };
+  let saved = state.storage.create_analysis(record).map_err(|error| error.to_string())?;
+  info!(target: "analysis", prompt_id = %prompt.id, "local analysis used instead of the model");
+  Ok(saved)
+}
+
+/// A rewrite produced by `optimize_prompt`, linked to its original.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OptimizedPrompt {
+  prompt: Prompt,
+  relation: PromptRelation,
+  /// Model's own list of what it changed.
+  changes: Vec<String>,
+  diff: Vec<DiffSegment>,
+}
+
+/// Rewrite a prompt with the configured LLM towards `goal` (defaults to general clarity),
+/// save the result as a new prompt linked from the original with an `optimized` relation
+/// and keep the sentence diff on the relation.
+#[tauri::command]
+async fn optimize_prompt(
+  state: State<'_, AppState>,
+  id: String,
+  goal: Option<String>,
+) -> Result<OptimizedPrompt, String> {
+  let Some(client) = state.llm_client() else {
+    return Err("未配置 LLM，无法优化提示词".into());
+  };
+  let original = state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  let goal = goal
+    .map(|goal| goal.trim().to_string())
+    .filter(|goal| !goal.is_empty())
+    .unwrap_or_else(|| DEFAULT_OPTIMIZE_GOAL.to_string());
+  let goal_line = format!("优化目标：{goal}");
+  let mut request = ChatRequest {
+    model: client.model_for(LlmTask::Optimization),
+    messages: vec![
+      ChatMessage::system(OPTIMIZE_SYSTEM_PROMPT),
+      ChatMessage::user(format!("{goal_line}\n\n原始提示词：\n{}", original.body)),
+    ],
+    ..ChatRequest::default()
+  };
+  client.generation().optimization.apply(&mut request);
+  let (response, usage, reply) =
+    call_structured(&client, "optimize", Some(&original.id), request, OptimizationReply::from_reply)
+      .await
+      .map_err(|error| {
+        error!(target: "llm", prompt_id = %id, %error, "prompt optimization failed");
+        error.to_string()
+      })?;
+  let model = response.model.clone();
+  let OptimizationReply { optimized, changes } = match reply {
+    Ok(reply) => reply,
+    Err(error) => {
+      // A reply cut off at the token limit is rarely valid JSON; report it as truncated.
+      if response.truncated {
+        guard_llm_output(&state, "optimize", &original, &model, &response.content, true, &[])?;
+      }
+      error!(target: "llm", prompt_id = %id, %error, "prompt optimization failed");
+      return Err(error.to_string());
+    }
+  };
+  guard_llm_output(
+    &state,
+    "optimize",
+    &original,
+    &model,
+    &optimized,
+    response.truncated,
+    &[OPTIMIZE_SYSTEM_PROMPT, &goal_line, "原始提示词："],
+  )?;
+  let diff = diff_sentences(&original.body, &optimized);
+
+  let mut record = NewPrompt::new(format!("{} · 优化", original.title), optimized);
+  record.language = original.language.clone();
+  record.model_hint = original.model_hint.clone();
+  record.metadata = json!({
+    "source": "optimize",
+    "parent_id": original.id,
+    "goal": goal,
+    "model": model,
+    "usage": usage
+  });
+  let prompt = state.storage.create_prompt(record).map_err(|error| error.to_string())?;
+  let relation = state
+    .storage
+    .add_relation(NewPromptRelation {
+      source_id: original.id.clone(),
+      target_id: prompt.id.clone(),
+      relation: "optimized".into(),
+      metadata: json!({
+        "goal": goal,
+        "model": model,
+        "changes": changes,
+        "diff": diff
+      }),
+    })
+    .map_err(|error| error.to_string())?;
+  if let Err(error) = reanalyze_prompt(&state, &prompt, "optimize") {
+    error!(target: "analysis", prompt_id = %prompt.id, %error, "optimized prompt analysis failed");
+  }
+  info!(target: "llm", prompt_id = %id, optimized_id = %prompt.id, %model, "prompt optimized");
+  Ok(OptimizedPrompt {
+    prompt,
+    relation,
+    changes,
+    diff,
+  })
+}
+
+/// Translate a prompt with the configured LLM into `target_lang` (ISO 639-1 code or name,
+/// e.g. `en`, `英文`). The translation is saved as a new prompt with that `language`, linked
+/// from the original by a `translation` relation, and inherits its metadata and latest tags.
+#[tauri::command]
+async fn translate_prompt(state: State<'_, AppState>, id: String, target_lang: String) -> Result<Prompt, String> {
+  let Some(client) = state.llm_client() else {
+    return Err("未配置 LLM，无法翻译提示词".into());
+  };
+  let language = canonical_language(&target_lang).ok_or_else(|| format!("不支持的目标语言: {target_lang}"))?;
+  let language_name = language_display_name(language).unwrap_or(language);
+  let original = state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  if original.language.as_deref() == Some(language) {
+    return Err(format!("提示词已经是{language_name}"));
+  }
+  let language_line = format!("目标语言：{language_name}（{language}）");
+  let request = ChatRequest {
+    model: None,
+    messages: vec![
+      ChatMessage::system(TRANSLATE_SYSTEM_PROMPT),
+      ChatMessage::user(format!(
+        "{language_line}\n\n标题：{}\n\n正文：\n{}",
+        original.title, original.body
+      )),
+    ],
+    temperature: Some(0.2),
+    top_p: None,
+    max_tokens: Some(2000),
+    json_output: true,
+  };
+  let (response, usage, reply) =
+    call_structured(&client, "translate", Some(&original.id), request, TranslationReply::from_reply)
+      .await
+      .map_err(|error| {
+        error!(target: "llm", prompt_id = %id, %error, "prompt translation failed");
+        error.to_string()
+      })?;
+  let model = response.model.clone();
+  let TranslationReply { title, body } = match reply {
+    Ok(reply) => reply,
+    Err(error) => {
+      if response.truncated {
+        guard_llm_output(&state, "translate", &original, &model, &response.content, true, &[])?;
+      }
+      error!(target: "llm", prompt_id = %id, %error, "prompt translation failed");
+      return Err(error.to_string());
+    }
+  };
+  guard_llm_output(
+    &state,
+    "translate",
+    &original,
+    &model,
+    &body,
+    response.truncated,
+    &[TRANSLATE_SYSTEM_PROMPT, &language_line],
+  )?;
+  let title = title.unwrap_or_else(|| format!("{} · {language_name}", original.title));
+
+  let mut metadata = match &original.metadata {
+    Value::Object(map) => Value::Object(map.clone()),
+    _ => json!({}),
+  };
+  metadata["source"] = json!("translate");
+  metadata["parent_id"] = json!(original.id);
+  metadata["translated_from"] = json!(original.language);
+  metadata["model"] = json!(model);
+  metadata["usage"] = json!(usage);
+  if let Some(map) = metadata.as_object_mut() {
+    // `raw` and `structured` describe the original text, not the translation.
+    map.remove("raw");
+    map.remove("structured");
+  }
+  let mut record = NewPrompt::new(title, body);
+  record.language = Some(language.to_string());
+  record.model_hint = original.model_hint.clone();
+  record.metadata = metadata;
+  let prompt = state.storage.create_prompt(record).map_err(|error| error.to_string())?;
+
+  state
+    .storage
+    .add_relation(NewPromptRelation {
+      source_id: original.id.clone(),
+      target_id: prompt.id.clone(),
+      relation: "translation".into(),
+      metadata: json!({ "from": original.language, "to": language, "model": model }),
+    })
+    .map_err(|error| error.to_string())?;
+
+  let copied = match state.storage.latest_analysis_for_prompt(&original.id) {
+    Ok(Some(latest)) => {
+      let mut classification = latest.classification;
+      classification["source"] = json!("translate");
+      classification["translated_from"] = json!(original.id);
+      state
+        .storage
+        .create_analysis(NewAnalysis {
+          prompt_id: prompt.id.clone(),
+          summary: latest.summary,
+          tags: latest.tags,
+          classification,
+          qwen_model: latest.qwen_model,
+          analyzer_version: latest.analyzer_version,
+        })
+        .map(|_| ())
+    }
+    Ok(None) => reanalyze_prompt(&state, &prompt, "translate").map(|_| ()),
+    Err(error) => Err(error),
+  };
+  if let Err(error) = copied {
+    error!(target: "llm", prompt_id = %prompt.id, %error, "copying tags to the translation failed");
+  }
+  info!(
+    target: "llm",
+    prompt_id = %id,
+    translation_id = %prompt.id,
+    language = %language_name,
+    %model,
+    "prompt translated"
+  );
+  Ok(prompt)
+}
+
+/// Check an optimize/translate output against the guardrails before it becomes a prompt.
+/// A refused output is kept in `llm_rejections` with the reason, which is also the error.
+fn guard_llm_output(
+  state: &AppState,
+  task: &str,
+  original: &Prompt,
+  model: &str,
+  output: &str,
+  truncated: bool,
+  instructions: &[&str],
+) -> Result<(), String> {
+  let options = GuardrailOptions::default();
+  let Err(rejection) = check_llm_output(&original.body, output, truncated, instructions, &options) else {
+    return Ok(());
+  };
+  let record = NewLlmRejection {
+    prompt_id: original.id.clone(),
+    task: task.to_string(),
+    model: model.to_string(),
+    reason: rejection.reason.as_str().to_string(),
+    detail: rejection.detail.clone(),
+    output: output.to_string(),
+  };
+  if let Err(error) = state.storage.record_llm_rejection(record) {
+    error!(target: "llm", prompt_id = %original.id, %error, "saving the rejected output failed");
+  }
+  warn!(target: "llm", %task, prompt_id = %original.id, detail = %rejection.detail, "model output rejected");
+  Err(format!("模型输出未通过检查: {}", rejection.detail))
+}
+
+/// Optimize/translate outputs refused by the guardrails, newest first.
+#[tauri::command]
+fn list_llm_rejections(
+  state: State<AppState>,
+  prompt_id: Option<String>,
+  limit: Option<usize>,
+) -> Result<Vec<LlmRejection>, String> {
+  state
+    .storage
+    .list_llm_rejections(prompt_id.as_deref(), limit.unwrap_or(LIST_REJECTIONS_LIMIT))
+    .map_err(|error| error.to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SemanticMatch {
+  prompt: Prompt,
+  score: f32,
+}
+
+/// Embed every prompt that has no embedding yet (or changed since) in the background,
+/// reporting progress on `embeddings:backfill-progress`; `cancel_operation` stops it after the
+/// current batch.
+#[tauri::command]
+fn backfill_embeddings(app: AppHandle, state: State<AppState>) -> Result<StartedOperation, String> {
+  let Some(client) = state.llm_client() else {
+    return Err("未配置 LLM，无法生成向量".into());
+  };
+  let Some(model) = client.model_for(LlmTask::Embedding) else {
+    return Err("当前模型后端不支持向量".into());
+  };
+  let Ok(running) = state.embedding_backfill_lock.clone().try_lock_owned() else {
+    return Err("向量回填正在进行".into());
+  };
+  let prompts = state
+    .storage
+    .list_prompts_missing_embedding(&model)
+    .map_err(|error| error.to_string())?;
+  let total = prompts.len();
+  let storage = state.storage.clone();
+  let token = state.operations.start("embedding-backfill");
+  let operation_id = token.id().to_string();
+  tauri::async_runtime::spawn(async move {
+    let _running = running;
+    let started = Instant::now();
+    let progress = |processed: usize, failed: usize, done: bool| ReanalysisProgress {
+      operation_id: token.id().to_string(),
+      trigger: "embedding-backfill".into(),
+      processed,
+      total,
+      failed,
+      done,
+      cancelled: done && token.is_cancelled(),
+    };
+    let (mut processed, mut failed) = (0, 0);
+    for batch in prompts.chunks(EMBEDDING_BATCH) {
+      if token.is_cancelled() {
+        break;
+      }
+      let inputs: Vec<String> = batch.iter().map(|prompt| prompt.body.clone()).collect();
+      match embed_texts(&client, &model, &inputs).await {
+        Ok(vectors) => {
+          for (prompt, vector) in batch.iter().zip(&vectors) {
+            if let Err(error) = storage.upsert_embedding(&prompt.id, &model, vector) {
+              failed += 1;
+              error!(target: "embeddings", prompt_id = %prompt.id, %model, %error, "saving embedding failed");
+            }
+          }
+        }
+        Err(error) => {
+          failed += batch.len();
+          error!(target: "embeddings", %model, batch = batch.len(), %error, "embedding batch failed");
+        }
+      }
+      processed += batch.len();
+      let _ = app.emit("embeddings:backfill-progress", progress(processed, failed, processed == total));
+    }
+    if processed < total || total == 0 {
+      let _ = app.emit("embeddings:backfill-progress", progress(processed, failed, true));
+    }
+    info!(
+      target: "embeddings",
+      operation_id = token.id(),
+      %model,
+      processed,
+      total,
+      failed,
+      cancelled = token.is_cancelled(),
+      duration_ms = started.elapsed().as_millis() as u64,
+      "embedding backfill finished"
+    );
+  });
+  info!(target: "embeddings", total, "embedding backfill started");
+  Ok(StartedOperation { operation_id, total })
+}
+
+/// Sampling options for `run_prompt`; unset values use the playground settings (see
+/// `set_llm_generation`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct RunParams {
+  temperature: Option<f32>,
+  top_p: Option<f32>,
+  max_tokens: Option<u32>,
+  /// Sent as a system message before the prompt.
+  system: Option<String>,
+}
+
+/// Template variables (`{name}` / `{{name}}`) a prompt expects, for the playground form.
+#[tauri::command]
+fn prompt_template_variables(state: State<AppState>, id: String) -> Result<Vec<String>, String> {
+  let prompt = state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  Ok(template_variables(&prompt.body))
+}
+
+/// Preview a stored prompt (`id`) or unsaved text (`body`) with its template variables filled
+/// from `variables`, listing placeholders still missing a value and variables the text does
+/// not use, so the form can validate before copying or running it.
+#[tauri::command]
+fn render_template(
+  state: State<AppState>,
+  id: Option<String>,
+  body: Option<String>,
+  variables: Option<BTreeMap<String, String>>,
+) -> Result<TemplatePreview, String> {
+  let body = match (id, body) {
+    (Some(id), None) => {
+      state
+        .storage
+        .get_prompt(&id)
+        .map_err(|error| error.to_string())?
+        .ok_or_else(|| "提示词不存在".to_string())?
+        .body
+    }
+    (None, Some(body)) => body,
+    _ => return Err("预览模板需要提供 id 或 body 之一".into()),
+  };
+  Ok(preview_template(&body, &variables.unwrap_or_default()))
+}
+
+/// Fill the prompt's template variables, run it against `model` (the default chat model
+/// when omitted) and store the output in the `runs` table.
+#[tauri::command]
+async fn run_prompt(
+  state: State<'_, AppState>,
+  id: String,
+  model: Option<String>,
+  params: Option<RunParams>,
+  variables: Option<BTreeMap<String, String>>,
+) -> Result<Run, String> {
+  let Some(client) = state.llm_client() else {
+    return Err("未配置 LLM，无法运行提示词".into());
+  };
+  let prompt = state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  let params = params.unwrap_or_default();
+  let variables = variables.unwrap_or_default();
+  let input = fill_prompt(&prompt.body, &variables).map_err(|error| error.to_string())?;
+  let model = model.map(|model| model.trim().to_string()).filter(|model| !model.is_empty());
+  let run = execute_run(&client, &state.storage, &prompt.id, model, &params, variables, input)
+    .await
+    .map_err(|error| {
+      error!(target: "llm", prompt_id = %id, %error, "prompt run failed");
+      error
+    })?;
+  info!(target: "llm", prompt_id = %id, model = %run.model, latency_ms = run.latency_ms, "prompt run");
+  Ok(run)
+}
+
+/// Send an already filled-in prompt and store the output as a run.
+async fn execute_run(
+  client: &LlmClient,
+  storage: &Storage,
+  prompt_id: &str,
+  model: Option<String>,
+  params: &RunParams,
+  variables: BTreeMap<String, String>,
+  input: String,
+) -> Result<Run, String> {
+  let mut messages = Vec::new();
+  if let Some(system) = params.system.as_deref().map(str::trim).filter(|system| !system.is_empty()) {
+    messages.push(ChatMessage::system(system));
+  }
+  messages.push(ChatMessage::user(&input));
+  let mut request = ChatRequest {
+    model,
+    messages,
+    ..ChatRequest::default()
+  };
+  client.generation().playground.apply(&mut request);
+  request.temperature = params.temperature.or(request.temperature);
+  request.top_p = params.top_p.or(request.top_p);
+  request.max_tokens = params.max_tokens.or(request.max_tokens);
+  let (response, usage) = call_qwen_chat(client, "run", Some(prompt_id), request)
+    .await
+    .map_err(|error| error.to_string())?;
+  storage
+    .create_run(NewRun {
+      prompt_id: prompt_id.to_string(),
+      provider: client.provider.kind().as_str().to_string(),
+      model: response.model,
+      params: json!(params),
+      variables,
+      input,
+      output: response.content,
+      prompt_tokens: usage.prompt_tokens,
+      completion_tokens: usage.completion_tokens,
+      latency_ms: usage.latency_ms,
+    })
+    .map_err(|error| error.to_string())
+}
+
+/// An A/B test with its current win rates.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AbTestReport {
+  test: AbTest,
+  summary: AbSummary,
+}
+
+fn ab_test_report(test: AbTest) -> AbTestReport {
+  let summary = summarize_ab_preferences(test.cases.iter().map(|case| case.preference));
+  AbTestReport { test, summary }
+}
+
+fn ab_relation_metadata(report: &AbTestReport) -> Value {
+  json!({
+    "test_id": report.test.id,
+    "model": report.test.model,
+    "summary": report.summary
+  })
+}
+
+/// Run prompts A and B over the same test inputs (template variables per input; plain
+/// prompts get the `input` value appended) with one model and store the paired outputs.
+/// The win-rate summary lives on an `ab_test` relation from A to B.
+#[tauri::command]
+async fn ab_test(
+  state: State<'_, AppState>,
+  id_a: String,
+  id_b: String,
+  test_inputs: Vec<BTreeMap<String, String>>,
+  model: Option<String>,
+) -> Result<AbTestReport, String> {
+  let Some(client) = state.llm_client() else {
+    return Err("未配置 LLM，无法进行 A/B 测试".into());
+  };
+  if id_a == id_b {
+    return Err("请选择两个不同的提示词".into());
+  }
+  if test_inputs.is_empty() || test_inputs.len() > AB_TEST_MAX_INPUTS {
+    return Err(format!("测试输入需为 1 到 {AB_TEST_MAX_INPUTS} 条"));
+  }
+  let load = |id: &str| {
+    state
+      .storage
+      .get_prompt(id)
+      .map_err(|error| error.to_string())?
+      .ok_or_else(|| "提示词不存在".to_string())
+  };
+  let (prompt_a, prompt_b) = (load(&id_a)?, load(&id_b)?);
+  // Render everything first so a missing variable fails before any tokens are spent.
+  let mut inputs = Vec::with_capacity(test_inputs.len());
+  for variables in &test_inputs {
+    let input_a = fill_prompt(&prompt_a.body, variables).map_err(|error| format!("提示词 A: {error}"))?;
+    let input_b = fill_prompt(&prompt_b.body, variables).map_err(|error| format!("提示词 B: {error}"))?;
+    inputs.push((input_a, input_b));
+  }
+  let model = model
+    .map(|model| model.trim().to_string())
+    .filter(|model| !model.is_empty())
+    .unwrap_or_else(|| client.provider.model().to_string());
+  let params = RunParams::default();
+  let mut cases = Vec::with_capacity(inputs.len());
+  for (variables, (input_a, input_b)) in test_inputs.into_iter().zip(inputs) {
+    let log_failure = |error: String| {
+      error!(target: "llm", prompt_a = %id_a, prompt_b = %id_b, %error, "A/B test failed");
+      error
+    };
+    let run_a = execute_run(
+      &client,
+      &state.storage,
+      &prompt_a.id,
+      Some(model.clone()),
+      &params,
+      variables.clone(),
+      input_a,
+    )
+    .await
+    .map_err(log_failure)?;
+    let run_b = execute_run(
+      &client,
+      &state.storage,
+      &prompt_b.id,
+      Some(model.clone()),
+      &params,
+      variables.clone(),
+      input_b,
+    )
+    .await
+    .map_err(log_failure)?;
+    cases.push(NewAbCase {
+      variables,
+      run_a_id: run_a.id,
+      run_b_id: run_b.id,
+    });
+  }
+  let test = state
+    .storage
+    .create_ab_test(NewAbTest {
+      prompt_a_id: prompt_a.id.clone(),
+      prompt_b_id: prompt_b.id.clone(),
+      model,
+      relation_id: None,
+      cases,
+    })
+    .map_err(|error| error.to_string())?;
+  let mut report = ab_test_report(test);
+  let relation = state
+    .storage
+    .add_relation(NewPromptRelation {
+      source_id: prompt_a.id,
+      target_id: prompt_b.id,
+      relation: "ab_test".into(),
+      metadata: ab_relation_metadata(&report),
+    })
+    .map_err(|error| error.to_string())?;
+  state
+    .storage
+    .set_ab_test_relation(&report.test.id, &relation.id)
+    .map_err(|error| error.to_string())?;
+  report.test.relation_id = Some(relation.id);
+  info!(
+    target: "llm",
+    prompt_a = %id_a,
+    prompt_b = %id_b,
+    cases = report.summary.cases,
+    model = %report.test.model,
+    "A/B test finished"
+  );
+  Ok(report)
+}
+
+/// Record which output of an A/B case was better (`a`, `b` or `tie`; `null` clears it)
+/// and refresh the win rates stored with both prompts.
+#[tauri::command]
+fn record_ab_preference(
+  state: State<AppState>,
+  case_id: String,
+  preference: Option<AbPreference>,
+) -> Result<AbTestReport, String> {
+  let test_id = state
+    .storage
+    .set_ab_preference(&case_id, preference)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "测试用例不存在".to_string())?;
+  let test = state
+    .storage
+    .get_ab_test(&test_id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "A/B 测试不存在".to_string())?;
+  let report = ab_test_report(test);
+  if let Some(relation_id) = &report.test.relation_id {
+    state
+      .storage
+      .update_relation_metadata(relation_id, &ab_relation_metadata(&report))
+      .map_err(|error| error.to_string())?;
+  }
+  Ok(report)
+}
+
+#[tauri::command]
+fn get_ab_test(state: State<AppState>, id: String) -> Result<Option<AbTestReport>, String> {
+  let test = state.storage.get_ab_test(&id).map_err(|error| error.to_string())?;
+  Ok(test.map(ab_test_report))
+}
+
+/// A/B tests a prompt took part in, newest first.
+#[tauri::command]
+fn list_ab_tests(state: State<AppState>, prompt_id: String) -> Result<Vec<AbTestReport>, String> {
+  let tests = state
+    .storage
+    .list_ab_tests_for_prompt(&prompt_id)
+    .map_err(|error| error.to_string())?;
+  Ok(tests.into_iter().map(ab_test_report).collect())
+}
+
+/// Past playground runs of a prompt, newest first.
+#[tauri::command]
+fn list_runs(state: State<AppState>, prompt_id: String, limit: Option<usize>) -> Result<Vec<Run>, String> {
+  state
+    .storage
+    .list_runs_for_prompt(&prompt_id, limit.unwrap_or(LIST_RUNS_LIMIT))
+    .map_err(|error| error.to_string())
+}
+
+/// Prompts closest in meaning to `query`, by embedding similarity. Only prompts that
+/// have been embedded (see `backfill_embeddings`) can match.
+#[tauri::command]
+async fn semantic_search(
+  state: State<'_, AppState>,
+  query: String,
+  limit: Option<usize>,
+) -> Result<Vec<SemanticMatch>, String> {
+  let Some(client) = state.llm_client() else {
+    return Err("未配置 LLM，无法语义搜索".into());
+  };
+  let Some(model) = client.model_for(LlmTask::Embedding) else {
+    return Err("当前模型后端不支持向量".into());
+  };
+  let query = query.trim().to_string();
+  if query.is_empty() {
+    return Ok(Vec::new());
+  }
+  let vector = embed_texts(&client, &model, &[query])
+    .await
+    .map_err(|error| {
+      error!(target: "search", %error, "semantic search failed");
+      error.to_string()
+    })?
+    .remove(0);
+  let candidates = state.storage.list_embeddings(&model).map_err(|error| error.to_string())?;
+  let hits: Vec<SemanticHit> = nearest_prompts(
+    &vector,
+    &candidates,
+    limit.unwrap_or(SEMANTIC_SEARCH_LIMIT),
+    SEMANTIC_SEARCH_MIN_SCORE,
+  );
+  let mut matches = Vec::with_capacity(hits.len());
+  for hit in hits {
+    if let Some(prompt) = state.storage.get_prompt(&hit.prompt_id).map_err(|error| error.to_string())? {
+      matches.push(SemanticMatch {
+        prompt,
+        score: hit.score,
+      });
+    }
+  }
+  Ok(matches)
+}
+
+/// Queue LLM classification for the given prompts (all prompts when omitted). Prompts that
+/// already have a pending or running job are skipped. Returns the number of jobs queued.
+#[tauri::command]
+fn classify_prompts_with_qwen(state: State<AppState>, prompt_ids: Option<Vec<String>>) -> Result<usize, String> {
+  if state.classifier_client().is_none() {
+    return Err("未配置 LLM，无法进行模型分类".into());
+  }
+  let prompt_ids = match prompt_ids {
+    Some(ids) => ids,
+    None => state
+      .storage
+      .list_prompts()
+      .map_err(|error| error.to_string())?
+      .into_iter()
+      .map(|prompt| prompt.id)
+      .collect(),
+  };
+  let mut queued = 0;
+  for prompt_id in &prompt_ids {
+    match enqueue_classification(&state.storage, prompt_id, "job") {
+      Ok(Some(_)) => queued += 1,
+      Ok(None) => {}
+      Err(error) => {
+        error!(target: "jobs", %prompt_id, %error, "classification enqueue failed");
+        return Err(error.to_string());
+      }
+    }
+  }
+  state.llm_jobs_wake.notify_one();
+  info!(target: "jobs", queued, requested = prompt_ids.len(), "classification jobs queued");
+  Ok(queued)
+}
+
+/// Queue classification for up to `limit` prompts that have never been classified (newest
+/// first), e.g. imported or legacy ones, so they gain an `is_prompt` verdict. Returns the
+/// number of jobs queued, which `cancel_operation` cancels while they wait.
+#[tauri::command]
+fn classify_backlog(state: State<AppState>, limit: Option<usize>) -> Result<StartedOperation, String> {
+  if state.classifier_client().is_none() {
+    return Err("未配置 LLM，无法进行模型分类".into());
+  }
+  let limit = limit.unwrap_or(CLASSIFY_BACKLOG_LIMIT).min(CLASSIFY_BACKLOG_MAX);
+  let prompts = state
+    .storage
+    .list_unclassified_prompts(CLASSIFY_JOB, limit)
+    .map_err(|error| error.to_string())?;
+  let operation_id = state.operations.new_id("classify-backlog");
+  let mut queued = 0;
+  for prompt in &prompts {
+    match enqueue_operation_classification(&state.storage, &prompt.id, "backlog", &operation_id) {
+      Ok(Some(_)) => queued += 1,
+      Ok(None) => {}
+      Err(error) => {
+        error!(target: "jobs", prompt_id = %prompt.id, %error, "classification enqueue failed");
+        return Err(error.to_string());
+      }
+    }
+  }
+  state.llm_jobs_wake.notify_one();
+  info!(target: "jobs", queued, %operation_id, "unclassified prompts queued");
+  Ok(StartedOperation {
+    operation_id,
+    total: queued,
+  })
+}
+
+/// Re-run the `is_prompt` decision over the latest stored classification of each prompt in
+/// `filter` (all classified prompts when omitted), after the threshold or the classifier
+/// prompt changed. Without `reclassify` the stored scores are compared with the current
+/// threshold, without calling the LLM: each changed verdict is saved as a new classification
+/// from `reapply`, and prompts that would now be rejected are returned. Prompts the user
+/// labeled are left alone. With `reclassify` the prompts are queued for the classifier again.
+#[tauri::command]
+fn reapply_classification(state: State<AppState>, filter: Option<ReapplyFilter>) -> Result<ReapplyReport, String> {
+  let filter = filter.unwrap_or_default();
+  let selected: Option<HashSet<String>> = filter.prompt_ids.map(|ids| ids.into_iter().collect());
+  let classifications: Vec<Classification> = state
+    .storage
+    .list_latest_classifications(filter.source.as_deref())
+    .map_err(|error| error.to_string())?
+    .into_iter()
+    .filter(|classification| selected.as_ref().is_none_or(|ids| ids.contains(&classification.prompt_id)))
+    .collect();
+  let mut report = ReapplyReport {
+    checked: classifications.len(),
+    ..ReapplyReport::default()
+  };
+
+  if filter.reclassify {
+    if state.classifier_client().is_none() {
+      return Err("未配置 LLM，无法进行模型分类".into());
+    }
+    for classification in &classifications {
+      match enqueue_classification(&state.storage, &classification.prompt_id, REAPPLY_SOURCE) {
+        Ok(Some(_)) => report.requeued += 1,
+        Ok(None) => {}
+        Err(error) => {
+          error!(target: "jobs", prompt_id = %classification.prompt_id, %error, "classification enqueue failed");
+          return Err(error.to_string());
+        }
+      }
+    }
+    state.llm_jobs_wake.notify_one();
+    info!(target: "jobs", requeued = report.requeued, checked = report.checked, "classifications requeued");
+    return Ok(report);
+  }
+
+  let threshold = state.classifier_settings.lock().unwrap().threshold;
+  for classification in classifications {
+    let score = classification.prompt_score();
+    let is_prompt = score >= threshold;
+    if is_prompt == classification.is_prompt {
+      continue;
+    }
+    let labeled = state
+      .storage
+      .get_prompt_label(&classification.prompt_id)
+      .map_err(|error| error.to_string())?;
+    if labeled.is_some() {
+      continue;
+    }
+    state
+      .storage
+      .record_classification(NewClassification {
+        prompt_id: classification.prompt_id.clone(),
+        source: REAPPLY_SOURCE.into(),
+        is_prompt,
+        confidence: if is_prompt { score } else { 1.0 - score },
+        model: classification.model,
+      })
+      .map_err(|error| error.to_string())?;
+    if is_prompt {
+      report.accepted += 1;
+      continue;
+    }
+    let title = state
+      .storage
+      .get_prompt(&classification.prompt_id)
+      .map_err(|error| error.to_string())?
+      .map(|prompt| prompt.title)
+      .unwrap_or_default();
+    report.rejected.push(ReappliedVerdict {
+      prompt_id: classification.prompt_id,
+      title,
+      score,
+    });
+  }
+  info!(
+    target: "classifier",
+    threshold,
+    checked = report.checked,
+    rejected = report.rejected.len(),
+    accepted = report.accepted,
+    "classifications re-judged"
+  );
+  Ok(report)
+}
+
+/// Queue LLM title generation for prompts whose title is the import placeholder or
+/// markdown noise (all such prompts when `prompt_ids` is omitted), whatever the automatic
+/// fallback setting. Jobs are answered in batches. Returns the number of jobs queued.
+#[tauri::command]
+fn generate_titles(state: State<AppState>, prompt_ids: Option<Vec<String>>) -> Result<usize, String> {
+  if state.llm_client().is_none() {
+    return Err("未配置 LLM，无法生成标题".into());
+  }
+  let selected: Option<HashSet<String>> = prompt_ids.map(|ids| ids.into_iter().collect());
+  let prompts: Vec<Prompt> = state
+    .storage
+    .list_prompts()
+    .map_err(|error| error.to_string())?
+    .into_iter()
+    .filter(|prompt| selected.as_ref().is_none_or(|ids| ids.contains(&prompt.id)))
+    .filter(|prompt| is_junk_title(&prompt.title))
+    .collect();
+  let mut queued = 0;
+  for prompt in &prompts {
+    match enqueue_title(&state.storage, prompt) {
+      Ok(Some(_)) => queued += 1,
+      Ok(None) => {}
+      Err(error) => {
+        error!(target: "jobs", prompt_id = %prompt.id, %error, "title enqueue failed");
+        return Err(error.to_string());
+      }
+    }
+  }
+  state.llm_jobs_wake.notify_one();
+  info!(target: "jobs", queued, requested = prompts.len(), "title jobs queued");
+  Ok(queued)
+}
+
+/// Queue OCR for image captures whose body is still empty (only those in `prompt_ids` when
+/// given). Returns the number of jobs queued.
+#[tauri::command]
+fn recognize_image_text(state: State<AppState>, prompt_ids: Option<Vec<String>>) -> Result<usize, String> {
+  if state.llm_client().is_none() {
+    return Err("未配置 LLM，无法识别图片文字".into());
+  }
+  let selected: Option<HashSet<String>> = prompt_ids.map(|ids| ids.into_iter().collect());
+  let prompts: Vec<Prompt> = state
+    .storage
+    .list_prompts()
+    .map_err(|error| error.to_string())?
+    .into_iter()
+    .filter(|prompt| selected.as_ref().is_none_or(|ids| ids.contains(&prompt.id)))
+    .filter(|prompt| prompt.metadata["kind"] == IMAGE_CAPTURE_KIND && prompt.body.trim().is_empty())
+    .collect();
+  let mut queued = 0;
+  for prompt in &prompts {
+    match enqueue_ocr(&state.storage, &prompt.id) {
+      Ok(Some(_)) => queued += 1,
+      Ok(None) => {}
+      Err(error) => {
+        error!(target: "jobs", prompt_id = %prompt.id, %error, "ocr enqueue failed");
+        return Err(error.to_string());
+      }
+    }
+  }
+  state.llm_jobs_wake.notify_one();
+  info!(target: "jobs", queued, requested = prompts.len(), "ocr jobs queued");
+  Ok(queued)
+}
+
+#[tauri::command]
+fn get_title_settings(state: State<AppState>) -> TitleSettings {
+  state.title_settings.lock().unwrap().clone()
+}
+
+/// Turn the automatic LLM title fallback for clipboard imports on or off.
+#[tauri::command]
+fn set_title_settings(state: State<AppState>, settings: TitleSettings) -> Result<TitleSettings, String> {
+  persist_title_settings(&state.title_settings_path, &settings).map_err(|error| error.to_string())?;
+  *state.title_settings.lock().unwrap() = settings.clone();
+  info!(target: "jobs", llm_fallback = settings.llm_fallback, "title settings changed");
+  Ok(settings)
+}
+
+/// Clipboard poll interval and whether OS change notifications replace polling.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClipboardStatus {
+  settings: ClipboardSettings,
+  event_driven: bool,
+}
+
+fn clipboard_status(state: &AppState) -> ClipboardStatus {
+  ClipboardStatus {
+    settings: *state.clipboard_settings.lock().unwrap(),
+    event_driven: state.clipboard_events.load(Ordering::Relaxed),
+  }
+}
+
+#[tauri::command]
+fn get_clipboard_settings(state: State<AppState>) -> ClipboardStatus {
+  clipboard_status(&state)
+}
+
+/// Set the clipboard poll interval (clamped to 250 ms – 60 s; only used where the OS sends no
+/// change notifications), whether captures go to the inbox, the burst limit (window clamped
+/// to 1 s – 1 h) and merge mode (window clamped to 1 s – 10 min). Applies immediately; turning
+/// merge mode off captures the pending parts.
+#[tauri::command]
+fn set_clipboard_settings(
+  app: AppHandle,
+  state: State<AppState>,
+  settings: ClipboardSettings,
+) -> Result<ClipboardStatus, String> {
+  let settings = settings.normalized();
+  persist_clipboard_settings(&state.clipboard_settings_path, &settings).map_err(|error| error.to_string())?;
+  *state.clipboard_settings.lock().unwrap() = settings;
+  let _ = state.watcher_commands.send(WatcherCommand::Reconfigure);
+  info!(
+    target: "clipboard",
+    poll_interval_ms = settings.poll_interval_ms,
+    inbox = settings.inbox,
+    burst_limit = settings.burst_limit,
+    burst_window_secs = settings.burst_window_secs,
+    merge = settings.merge,
+    merge_window_secs = settings.merge_window_secs,
+    "settings changed"
+  );
+  announce_settings(&app, "clipboard", &settings);
+  Ok(clipboard_status(&state))
+}
+
+/// Whether the system starts the app at login.
+#[tauri::command]
+fn get_autostart(app: AppHandle) -> Result<bool, String> {
+  app.autolaunch().is_enabled().map_err(|error| error.to_string())
+}
+
+/// Start the app at login (in the tray, with the clipboard watcher running) or stop doing so:
+/// a registry entry on Windows, a launch agent on macOS and an autostart desktop file on
+/// Linux. Returns the state now in effect.
+#[tauri::command]
+fn set_autostart(app: AppHandle, enabled: bool) -> Result<bool, String> {
+  let autolaunch = app.autolaunch();
+  let changed = if enabled {
+    autolaunch.enable()
+  } else {
+    autolaunch.disable()
+  };
+  changed.map_err(|error| {
+    error!(target: "app", %error, "changing autostart failed");
+    error.to_string()
+  })?;
+  let enabled = autolaunch.is_enabled().map_err(|error| error.to_string())?;
+  info!(target: "app", enabled, "autostart changed");
+  announce_settings(&app, "autostart", &enabled);
+  Ok(enabled)
+}
+
+#[tauri::command]
+fn get_watcher_health(state: State<AppState>) -> WatcherHealth {
+  state.watcher_health.lock().unwrap().clone()
+}
+
+/// Pause or resume clipboard capture until the app exits. While paused the clipboard is not
+/// read; parts pending in merge mode are still captured when their window passes.
+#[tauri::command]
+fn set_watcher_paused(state: State<AppState>, paused: bool) -> Result<(), String> {
+  state
+    .watcher_commands
+    .send(WatcherCommand::Pause(paused))
+    .map_err(|_| "剪贴板监听已停止".to_string())?;
+  info!(target: "clipboard", paused, "watcher pause changed");
+  Ok(())
+}
+
+/// What the clipboard watcher did since the app started: reads, candidates, why texts were
+/// dropped, saves and LLM verdicts, for tuning the capture rules.
+#[tauri::command]
+fn get_capture_metrics(state: State<AppState>) -> CaptureMetrics {
+  state.capture_metrics.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn get_hotkey_settings(state: State<AppState>) -> HotkeySettings {
+  state.hotkey_settings.lock().unwrap().clone()
+}
+
+/// Change or switch off the "capture selection" and quick picker hotkeys. The previous hotkeys
+/// stay registered when a new one is invalid or taken by another program.
+#[tauri::command]
+fn set_hotkey_settings(
+  app: AppHandle,
+  state: State<AppState>,
+  settings: HotkeySettings,
+) -> Result<HotkeySettings, String> {
+  let settings = HotkeySettings {
+    shortcut: settings.shortcut.trim().to_string(),
+    picker_shortcut: settings.picker_shortcut.trim().to_string(),
+    ..settings
+  };
+  if settings.enabled && settings.picker_enabled && settings.shortcut == settings.picker_shortcut {
+    return Err("捕获快捷键与快速选择快捷键不能相同".into());
+  }
+  if let Err(error) = register_hotkeys(&app, &settings) {
+    let previous = state.hotkey_settings.lock().unwrap().clone();
+    let _ = register_hotkeys(&app, &previous);
+    return Err(error);
+  }
+  persist_hotkey_settings(&state.hotkey_settings_path, &settings).map_err(|error| error.to_string())?;
+  *state.hotkey_settings.lock().unwrap() = settings.clone();
+  info!(
+    target: "hotkey",
+    capture = %settings.shortcut,
+    capture_enabled = settings.enabled,
+    picker = %settings.picker_shortcut,
+    picker_enabled = settings.picker_enabled,
+    "hotkeys changed"
+  );
+  announce_settings(&app, "hotkeys", &settings);
+  Ok(settings)
+}
+
+/// Prompts whose title or latest analysis tags fuzzily match `query`, best first, for the
+/// quick picker; the most recently updated prompts when `query` is empty.
+#[tauri::command]
+fn quick_search(state: State<AppState>, query: String, limit: Option<usize>) -> Result<Vec<QuickPick>, String> {
+  let query = query.trim();
+  let mut tags = state.storage.list_latest_tags().map_err(|error| error.to_string())?;
+  let prompts = state.storage.list_prompts().map_err(|error| error.to_string())?;
+  let mut scored: Vec<(f64, Prompt, Vec<String>)> = prompts
+    .into_iter()
+    .filter_map(|prompt| {
+      let tags = tags.remove(&prompt.id).unwrap_or_default();
+      if query.is_empty() {
+        return Some((0.0, prompt, tags));
+      }
+      let title = fuzzy_score(query, &prompt.title);
+      let tag = fuzzy_score(query, &tags.join(" ")).map(|score| score * QUICK_SEARCH_TAG_WEIGHT);
+      let score = match (title, tag) {
+        (Some(title), Some(tag)) => title.max(tag),
+        (title, tag) => title.or(tag)?,
+      };
+      Some((score, prompt, tags))
+    })
+    .collect();
+  // Stable, so equal scores keep the most recently updated first.
+  scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+  scored.truncate(limit.unwrap_or(QUICK_SEARCH_LIMIT).max(1));
+  Ok(
+    scored
+      .into_iter()
+      .map(|(score, prompt, tags)| QuickPick {
+        preview: prompt
+          .body
+          .split_whitespace()
+          .collect::<Vec<_>>()
+          .join(" ")
+          .chars()
+          .take(QUICK_PICK_PREVIEW_CHARS)
+          .collect(),
+        variables: template_variables(&prompt.body),
+        prompt_id: prompt.id,
+        title: prompt.title,
+        tags,
+        score,
+      })
+      .collect(),
+  )
+}
+
+/// Copy a prompt to the clipboard with its template variables filled from `variables` (a
+/// prompt without placeholders gets `input` appended, as for runs) and count the use. The
+/// clipboard watcher does not capture the copied text as a new prompt. Returns the updated
+/// usage.
+#[tauri::command]
+fn copy_prompt(
+  state: State<AppState>,
+  id: String,
+  variables: Option<BTreeMap<String, String>>,
+) -> Result<PromptUsage, String> {
+  let usage = copy_prompt_to_clipboard(&state, &id, &variables.unwrap_or_default())?;
+  info!(target: "prompts", prompt_id = %id, use_count = usage.use_count, "prompt copied");
+  Ok(usage)
+}
+
+/// Fill the prompt's variables, count the use and put the text on the clipboard, marked as
+/// the app's own copy for the watcher. A trashed prompt is refused before the clipboard is
+/// touched.
+fn copy_prompt_to_clipboard(
+  state: &AppState,
+  id: &str,
+  variables: &BTreeMap<String, String>,
+) -> Result<PromptUsage, String> {
+  let prompt = state
+    .storage
+    .get_prompt(id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  let text = fill_prompt(&prompt.body, variables).map_err(|error| error.to_string())?;
+  let usage = state
+    .storage
+    .record_prompt_use(id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词已在回收站中".to_string())?;
+  // Recorded before the copy, so the watcher woken by it already knows it.
+  *state.own_copy.lock().unwrap() = Some(content_hash(text.trim()));
+  arboard::Clipboard::new()
+    .and_then(|mut clipboard| clipboard.set_text(text))
+    .map_err(|error| format!("无法写入剪贴板: {error}"))?;
+  Ok(usage)
+}
+
+/// Copy a prompt like `copy_prompt` and hide the quick picker; with `paste`
+/// (`HotkeySettings::picker_paste` when omitted), also press Ctrl+V in the app that had focus
+/// before. Returns whether the paste was sent; a failed paste only leaves the text copied.
+#[tauri::command]
+async fn paste_prompt(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  id: String,
+  variables: Option<BTreeMap<String, String>>,
+  paste: Option<bool>,
+) -> Result<bool, String> {
+  copy_prompt_to_clipboard(&state, &id, &variables.unwrap_or_default())?;
+  info!(target: "picker", prompt_id = %id, "prompt copied");
+  hide_quick_picker(app);
+  if !paste.unwrap_or_else(|| state.hotkey_settings.lock().unwrap().picker_paste) {
+    return Ok(false);
+  }
+  tokio::time::sleep(PICKER_PASTE_DELAY).await;
+  let pasted = tauri::async_runtime::spawn_blocking(selection::paste)
+    .await
+    .unwrap_or_else(|error| Err(error.to_string()));
+  if let Err(err) = &pasted {
+    warn!(target: "picker", error = %err, "paste failed, text left on the clipboard");
+  }
+  Ok(pasted.is_ok())
+}
+
+#[tauri::command]
+fn hide_quick_picker(app: AppHandle) {
+  if let Some(window) = app.get_webview_window(PICKER_WINDOW) {
+    let _ = window.hide();
+  }
+}
+
+/// Open the prompt in a window of its own, next to the main window, which stays usable; a
+/// prompt that already has a window gets it focused and switched to `mode` instead of a
+/// second one. `compare_with` is the other prompt in `compare` mode. Returns the window label.
+/// Async because building a window from a synchronous command deadlocks on Windows.
+#[tauri::command]
+async fn open_prompt_window(
+  app: AppHandle,
+  state: State<'_, AppState>,
+  id: String,
+  mode: Option<PromptWindowMode>,
+  compare_with: Option<String>,
+) -> Result<String, String> {
+  let prompt = state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  let mode = mode.unwrap_or_default();
+  let compare_with = compare_with.filter(|other| !other.is_empty() && *other != id);
+  if mode == PromptWindowMode::Compare && compare_with.is_none() {
+    return Err("对比模式需要另一条提示词".into());
+  }
+  let label = format!("{PROMPT_WINDOW_PREFIX}{id}");
+  let window_state = PromptWindowState {
+    prompt_id: id,
+    mode,
+    compare_with,
+  };
+  state
+    .prompt_windows
+    .lock()
+    .unwrap()
+    .insert(label.clone(), window_state.clone());
+
+  if let Some(window) = app.get_webview_window(&label) {
+    let _ = app.emit_to(label.as_str(), "prompt-window:state", &window_state);
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+    return Ok(label);
+  }
+  let built = WebviewWindowBuilder::new(&app, &label, WebviewUrl::default())
+    .title(format!("{} - PromptLab", prompt.title))
+    .inner_size(900.0, 680.0)
+    .min_inner_size(480.0, 360.0)
+    .build();
+  if let Err(error) = built {
+    state.prompt_windows.lock().unwrap().remove(&label);
+    error!(target: "app", %label, %error, "opening the prompt window failed");
+    return Err(error.to_string());
+  }
+  Ok(label)
+}
+
+/// The prompt the last `promptlab://` link pointed at, if the main window has not shown it
+/// yet; cleared by the call.
+#[tauri::command]
+fn take_deep_link_prompt(state: State<AppState>) -> Option<String> {
+  state.deep_link_prompt.lock().unwrap().take()
+}
+
+/// The state of the calling prompt window; `None` in the main window and the quick picker.
+#[tauri::command]
+fn get_prompt_window_state(window: WebviewWindow, state: State<AppState>) -> Option<PromptWindowState> {
+  state.prompt_windows.lock().unwrap().get(window.label()).cloned()
+}
+
+#[tauri::command]
+fn get_capture_filter(state: State<AppState>) -> CaptureFilter {
+  state.capture_filter.lock().unwrap().clone()
+}
+
+/// Tune which copied texts count as potential prompts: the limits, e.g. lift `maxChars` for
+/// long system prompts, and which rules run. Applies from the next copy.
+#[tauri::command]
+fn set_capture_filter(
+  app: AppHandle,
+  state: State<AppState>,
+  filter: CaptureFilter,
+) -> Result<CaptureFilter, String> {
+  filter.validate()?;
+  persist_capture_filter(&state.capture_filter_path, &filter).map_err(|error| error.to_string())?;
+  *state.capture_filter.lock().unwrap() = filter.clone();
+  let disabled: Vec<&str> = CaptureRule::ALL
+    .into_iter()
+    .filter(|rule| !filter.is_enabled(*rule))
+    .map(CaptureRule::as_str)
+    .collect();
+  info!(
+    target: "clipboard",
+    min_chars = filter.min_chars,
+    max_chars = ?filter.max_chars,
+    max_lines = ?filter.max_lines,
+    max_chat_lines = ?filter.max_chat_lines,
+    max_url_hits = ?filter.max_url_hits,
+    disabled_rules = ?disabled,
+    "capture filter changed"
+  );
+  announce_settings(&app, "captureFilter", &filter);
+  Ok(filter)
+}
+
+/// A capture rule with its current switch, for the settings screen.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CaptureRuleStatus {
+  rule: CaptureRule,
+  enabled: bool,
+  description: &'static str,
+}
+
+/// The capture rules in the order they are checked.
+#[tauri::command]
+fn list_capture_rules(state: State<AppState>) -> Vec<CaptureRuleStatus> {
+  let filter = state.capture_filter.lock().unwrap();
+  CaptureRule::ALL
+    .into_iter()
+    .map(|rule| CaptureRuleStatus {
+      rule,
+      enabled: filter.is_enabled(rule),
+      description: rule.description(),
+    })
+    .collect()
+}
+
+/// Run the capture rules over `text` and show how each decided, e.g. to see why a copy was
+/// skipped. Ignore rules are not applied.
+#[tauri::command]
+fn explain_capture(state: State<AppState>, text: String) -> CaptureDecision {
+  evaluate_capture(&text, &state.capture_filter.lock().unwrap())
+}
+
+#[tauri::command]
+fn get_capture_apps(state: State<AppState>) -> CaptureAppSettings {
+  state.capture_apps.lock().unwrap().clone()
+}
+
+/// Let the clipboard watcher capture only while one of the listed apps is in the foreground,
+/// e.g. browsers and chat clients but not the IDE. Apps are process name fragments, as
+/// `list_source_apps` shows them. The capture hotkey is not restricted. Applies from the next
+/// copy.
+#[tauri::command]
+fn set_capture_apps(state: State<AppState>, settings: CaptureAppSettings) -> Result<CaptureAppSettings, String> {
+  let settings = settings.normalized();
+  if settings.enabled && settings.apps.is_empty() {
+    return Err("应用白名单为空，开启后将不会捕获任何内容".into());
+  }
+  persist_capture_apps(&state.capture_apps_path, &settings).map_err(|error| error.to_string())?;
+  *state.capture_apps.lock().unwrap() = settings.clone();
+  info!(target: "capture", enabled = settings.enabled, apps = ?settings.apps, "app allowlist changed");
+  Ok(settings)
+}
+
+#[tauri::command]
+fn get_privacy_filter(state: State<AppState>) -> PrivacyFilter {
+  state.privacy_filter.lock().unwrap().clone()
+}
+
+/// Choose which kinds of sensitive content keep clipboard and hotkey captures from being
+/// saved, and whether such captures are dropped or kept masked. Applies from the next copy.
+#[tauri::command]
+fn set_privacy_filter(state: State<AppState>, filter: PrivacyFilter) -> Result<PrivacyFilter, String> {
+  persist_privacy_filter(&state.privacy_filter_path, &filter).map_err(|error| error.to_string())?;
+  *state.privacy_filter.lock().unwrap() = filter.clone();
+  let enabled: Vec<&str> = SensitiveKind::ALL
+    .into_iter()
+    .filter(|kind| filter.is_enabled(*kind))
+    .map(SensitiveKind::as_str)
+    .collect();
+  info!(target: "capture", action = ?filter.action, detectors = ?enabled, "privacy filter changed");
+  Ok(filter)
+}
+
+/// A privacy detector with its current switch, for the settings screen.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PrivacyDetectorStatus {
+  kind: SensitiveKind,
+  enabled: bool,
+  description: &'static str,
+}
+
+#[tauri::command]
+fn list_privacy_detectors(state: State<AppState>) -> Vec<PrivacyDetectorStatus> {
+  let filter = state.privacy_filter.lock().unwrap();
+  SensitiveKind::ALL
+    .into_iter()
+    .map(|kind| PrivacyDetectorStatus {
+      kind,
+      enabled: filter.is_enabled(kind),
+      description: kind.description(),
+    })
+    .collect()
+}
+
+/// Captures the privacy filter blocked or redacted since the app started, newest first.
+#[tauri::command]
+fn list_privacy_events(state: State<AppState>) -> Vec<PrivacyEvent> {
+  state.privacy_events.lock().unwrap().iter().cloned().collect()
+}
+
+#[tauri::command]
+fn list_ignore_rules(state: State<AppState>) -> Vec<IgnoreRule> {
+  state.ignore_rules.lock().unwrap().clone()
+}
+
+/// Add a capture ignore rule: a regex, a prefix (case-insensitive) or a character-length
+/// range outside of which texts are ignored.
+#[tauri::command]
+fn add_ignore_rule(
+  state: State<AppState>,
+  kind: IgnoreKind,
+  pattern: Option<String>,
+  min_chars: Option<usize>,
+  max_chars: Option<usize>,
+) -> Result<IgnoreRule, String> {
+  let rule = IgnoreRule::new(kind, pattern.as_deref().unwrap_or_default(), min_chars, max_chars)
+    .map_err(|error| error.to_string())?;
+  let mut rules = state.ignore_rules.lock().unwrap();
+  rules.push(rule.clone());
+  persist_ignore_rules(&state.ignore_rules_path, &rules).map_err(|error| error.to_string())?;
+  info!(target: "capture", kind = ?rule.kind, pattern = %rule.pattern, "ignore rule added");
+  Ok(rule)
+}
+
+#[tauri::command]
+fn update_ignore_rule(
+  state: State<AppState>,
+  id: String,
+  pattern: Option<String>,
+  min_chars: Option<usize>,
+  max_chars: Option<usize>,
+  enabled: Option<bool>,
+) -> Result<IgnoreRule, String> {
+  let mut rules = state.ignore_rules.lock().unwrap();
+  let Some(index) = rules.iter().position(|rule| rule.id == id) else {
+    return Err(format!("规则不存在: {id}"));
+  };
+  let mut rule = rules[index].clone();
+  if let Some(pattern) = pattern {
+    rule.pattern = pattern;
+  }
+  if min_chars.is_some() || max_chars.is_some() {
+    rule.min_chars = min_chars;
+    rule.max_chars = max_chars;
+  }
+  if let Some(enabled) = enabled {
+    rule.enabled = enabled;
+  }
+  rule.validate().map_err(|error| error.to_string())?;
+  rules[index] = rule.clone();
+  persist_ignore_rules(&state.ignore_rules_path, &rules).map_err(|error| error.to_string())?;
+  info!(target: "capture", rule_id = %rule.id, enabled = rule.enabled, "ignore rule updated");
+  Ok(rule)
+}
+
+#[tauri::command]
+fn remove_ignore_rule(state: State<AppState>, id: String) -> Result<bool, String> {
+  let mut rules = state.ignore_rules.lock().unwrap();
+  let before = rules.len();
+  rules.retain(|rule| rule.id != id);
+  let removed = rules.len() != before;
+  if removed {
+    persist_ignore_rules(&state.ignore_rules_path, &rules).map_err(|error| error.to_string())?;
+    info!(target: "capture", rule_id = %id, "ignore rule removed");
+  }
+  Ok(removed)
+}
+
+/// Zero the hit counters of every ignore rule.
+#[tauri::command]
+fn reset_ignore_rule_hits(state: State<AppState>) -> Result<Vec<IgnoreRule>, String> {
+  let mut rules = state.ignore_rules.lock().unwrap();
+  rules.iter_mut().for_each(|rule| rule.hits = 0);
+  persist_ignore_rules(&state.ignore_rules_path, &rules).map_err(|error| error.to_string())?;
+  Ok(rules.clone())
+}
+
+#[tauri::command]
+fn list_capture_templates(state: State<AppState>) -> Vec<CaptureTemplate> {
+  state.capture_templates.lock().unwrap().clone()
+}
+
+/// Add a capture template: `wrap` puts captured texts into `body` at `{{text}}`, `extract`
+/// keeps what follows the first match of the regex `body` (or its first group). It applies
+/// to texts containing `textPattern` (a regex) copied from one of `apps`; empty for all.
+#[tauri::command]
+fn add_capture_template(
+  state: State<AppState>,
+  name: String,
+  action: TemplateAction,
+  body: String,
+  text_pattern: Option<String>,
+  apps: Option<Vec<String>>,
+) -> Result<CaptureTemplate, String> {
+  let template = CaptureTemplate::new(
+    &name,
+    action,
+    &body,
+    text_pattern.as_deref().unwrap_or_default(),
+    apps.unwrap_or_default(),
+  )
+  .map_err(|error| error.to_string())?;
+  let mut templates = state.capture_templates.lock().unwrap();
+  templates.push(template.clone());
+  persist_capture_templates(&state.capture_templates_path, &templates).map_err(|error| error.to_string())?;
+  info!(target: "capture", name = %template.name, action = ?template.action, "capture template added");
+  Ok(template)
+}
+
+/// Replace the template with `template.id`; its hit count is kept.
+#[tauri::command]
+fn update_capture_template(state: State<AppState>, mut template: CaptureTemplate) -> Result<CaptureTemplate, String> {
+  template.name = template.name.trim().to_string();
+  template.validate().map_err(|error| error.to_string())?;
+  let mut templates = state.capture_templates.lock().unwrap();
+  let Some(index) = templates.iter().position(|existing| existing.id == template.id) else {
+    return Err(format!("模板不存在: {}", template.id));
+  };
+  template.hits = templates[index].hits;
+  templates[index] = template.clone();
+  persist_capture_templates(&state.capture_templates_path, &templates).map_err(|error| error.to_string())?;
+  info!(target: "capture", template_id = %template.id, enabled = template.enabled, "capture template updated");
+  Ok(template)
+}
+
+#[tauri::command]
+fn remove_capture_template(state: State<AppState>, id: String) -> Result<bool, String> {
+  let mut templates = state.capture_templates.lock().unwrap();
+  let before = templates.len();
+  templates.retain(|template| template.id != id);
+  let removed = templates.len() != before;
+  if removed {
+    persist_capture_templates(&state.capture_templates_path, &templates).map_err(|error| error.to_string())?;
+    info!(target: "capture", template_id = %id, "capture template removed");
+  }
+  Ok(removed)
+}
+
+/// Recent clipboard texts, newest first (index 0), whether or not they were saved.
+#[tauri::command]
+fn get_clipboard_history(state: State<AppState>) -> Vec<ClipboardEntry> {
+  state.clipboard_history.lock().unwrap().iter().cloned().collect()
+}
+
+/// Save the clipboard history entry at `index` (as listed by `get_clipboard_history`) as a
+/// prompt, e.g. one the heuristics skipped. Returns the existing prompt when the text is
+/// already saved.
+#[tauri::command]
+fn save_from_history(state: State<AppState>, index: usize) -> Result<Prompt, String> {
+  let (text, source_app) = state
+    .clipboard_history
+    .lock()
+    .unwrap()
+    .get(index)
+    .map(|entry| (entry.text.clone(), entry.source_app.clone()))
+    .ok_or_else(|| "剪贴板历史中没有这一项".to_string())?;
+  if let Some(prompt) = state.storage.find_prompt_by_content(&text).map_err(|error| error.to_string())? {
+    return Ok(prompt);
+  }
+  let prompt =
+    save_clipboard_prompt(&state, &text, "clipboard", source_app.as_ref()).map_err(|error| error.to_string())?;
+  info!(target: "clipboard", prompt_id = %prompt.id, "prompt saved from the history");
+  Ok(prompt)
+}
+
+/// Clipboard captures waiting for review, newest first; other statuses (`accepted`,
+/// `rejected`) on request.
+#[tauri::command]
+fn list_inbox(state: State<AppState>, status: Option<String>) -> Result<Vec<Capture>, String> {
+  let status = match status.as_deref() {
+    None => CaptureStatus::Pending,
+    Some(status) => CaptureStatus::parse(status).ok_or_else(|| format!("未知的收件箱状态: {status}"))?,
+  };
+  state.storage.list_captures(Some(status)).map_err(|error| error.to_string())
+}
+
+fn pending_capture(state: &AppState, id: &str) -> Result<Capture, String> {
+  let capture = state
+    .storage
+    .get_capture(id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "收件箱记录不存在".to_string())?;
+  if capture.status != CaptureStatus::Pending {
+    return Err("收件箱记录已处理".into());
+  }
+  Ok(capture)
+}
+
+/// Save a capture as a prompt, as the clipboard watcher does without the inbox, and label
+/// it as a prompt for calibration.
+#[tauri::command]
+fn accept_capture(state: State<AppState>, id: String) -> Result<Prompt, String> {
+  let capture = pending_capture(&state, &id)?;
+  let prompt = match state.storage.find_prompt_by_content(&capture.text).map_err(|error| error.to_string())? {
+    Some(prompt) => prompt,
+    None => save_clipboard_prompt(&state, &capture.text, "clipboard", None).map_err(|error| error.to_string())?,
+  };
+  state
+    .storage
+    .set_prompt_label(&prompt.id, Some(true))
+    .map_err(|error| error.to_string())?;
+  state
+    .storage
+    .resolve_capture(&capture.id, CaptureStatus::Accepted, Some(&prompt.id))
+    .map_err(|error| error.to_string())?;
+  info!(target: "capture", capture_id = %capture.id, prompt_id = %prompt.id, "inbox capture accepted");
+  Ok(prompt)
+}
+
+/// Keep a capture as a new version of an existing prompt instead of a new prompt: the prompt's
+/// body becomes the captured text, its former title and body go to `list_prompt_versions`.
+/// `prompt_id` defaults to the prompt the capture nearly repeats.
+#[tauri::command]
+fn accept_capture_as_version(state: State<AppState>, id: String, prompt_id: Option<String>) -> Result<Prompt, String> {
+  let capture = pending_capture(&state, &id)?;
+  let prompt_id = prompt_id
+    .or_else(|| capture.similar_prompt_id.clone())
+    .ok_or_else(|| "该收件箱记录没有相似的提示词，请指定要更新的提示词".to_string())?;
+  let prompt = state
+    .storage
+    .add_prompt_version(&prompt_id, &capture.text, "capture")
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "提示词不存在".to_string())?;
+  if let Err(error) = reanalyze_prompt(&state, &prompt, "version") {
+    error!(target: "analysis", prompt_id = %prompt.id, %error, "new version analysis failed");
+  }
+  state
+    .storage
+    .set_prompt_label(&prompt.id, Some(true))
+    .map_err(|error| error.to_string())?;
+  state
+    .storage
+    .resolve_capture(&capture.id, CaptureStatus::Accepted, Some(&prompt.id))
+    .map_err(|error| error.to_string())?;
+  info!(target: "capture", capture_id = %capture.id, prompt_id = %prompt.id, "inbox capture saved as a new version");
+  Ok(prompt)
+}
+
+/// Earlier titles and bodies of a prompt, most recently replaced first.
+#[tauri::command]
+fn list_prompt_versions(state: State<AppState>, prompt_id: String) -> Result<Vec<PromptVersion>, String> {
+  state
+    .storage
+    .list_prompt_versions(&prompt_id)
+    .map_err(|error| error.to_string())
+}
+
+/// Discard a capture. Its text becomes an `is_prompt: false` classifier example, so similar
+/// texts are classified as non-prompts from now on.
+#[tauri::command]
+fn reject_capture(state: State<AppState>, id: String) -> Result<Capture, String> {
+  let capture = pending_capture(&state, &id)?;
+  state
+    .storage
+    .create_classifier_example(NewClassifierExample {
+      text: capture.text.clone(),
+      is_prompt: false,
+      prompt_id: None,
+    })
+    .map_err(|error| error.to_string())?;
+  state
+    .storage
+    .resolve_capture(&capture.id, CaptureStatus::Rejected, None)
+    .map_err(|error| error.to_string())?;
+  info!(target: "capture", capture_id = %capture.id, "inbox capture rejected and added as a classifier example");
+  state
+    .storage
+    .get_capture(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "收件箱记录不存在".to_string())
+}
+
+/// The latest `is_prompt` verdict of every classified prompt, optionally only those from
+/// `source` (`clipboard`, `job` or `backlog`).
+#[tauri::command]
+fn list_classifications(state: State<AppState>, source: Option<String>) -> Result<Vec<Classification>, String> {
+  state
+    .storage
+    .list_latest_classifications(source.as_deref())
+    .map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+fn get_classifier_settings(state: State<AppState>) -> ClassifierSettings {
+  state.classifier_settings.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn set_classifier_settings(
+  app: AppHandle,
+  state: State<AppState>,
+  mut settings: ClassifierSettings,
+) -> Result<ClassifierSettings, String> {
+  if !(0.0..=1.0).contains(&settings.threshold) {
+    return Err("阈值必须在 0 到 1 之间".into());
+  }
+  if !(0.0..=1.0).contains(&settings.uncertain_min)
+    || !(0.0..=1.0).contains(&settings.uncertain_max)
+    || settings.uncertain_min > settings.uncertain_max
+  {
+    return Err("不确定区间必须在 0 到 1 之间，且下限不大于上限".into());
+  }
+  // Saving the default text (or nothing) keeps following future changes to the default.
+  settings.system_prompt = settings
+    .system_prompt
+    .map(|prompt| prompt.trim().to_string())
+    .filter(|prompt| !prompt.is_empty() && prompt != CLASSIFY_SYSTEM_PROMPT);
+  persist_classifier_settings(&state.classifier_settings_path, &settings).map_err(|error| error.to_string())?;
+  *state.classifier_settings.lock().unwrap() = settings.clone();
+  info!(
+    target: "classifier",
+    threshold = settings.threshold,
+    custom_prompt = settings.system_prompt.is_some(),
+    quarantine = settings.quarantine,
+    uncertain_min = settings.uncertain_min,
+    uncertain_max = settings.uncertain_max,
+    "settings changed"
+  );
+  announce_settings(&app, "classifier", &settings);
+  Ok(settings)
+}
+
+/// The built-in classifier system prompt, for showing and editing in settings.
+#[tauri::command]
+fn default_classifier_prompt() -> String {
+  CLASSIFY_SYSTEM_PROMPT.to_string()
+}
+
+/// Drop the custom classifier system prompt and go back to the built-in one.
+#[tauri::command]
+fn reset_classifier_prompt(state: State<AppState>) -> Result<ClassifierSettings, String> {
+  let mut settings = state.classifier_settings.lock().unwrap().clone();
+  settings.system_prompt = None;
+  persist_classifier_settings(&state.classifier_settings_path, &settings).map_err(|error| error.to_string())?;
+  *state.classifier_settings.lock().unwrap() = settings.clone();
+  info!(target: "classifier", "system prompt reset to the default");
+  Ok(settings)
+}
+
+/// Mark a prompt as really being a prompt or not, for calibrating the classifier;
+/// `None` removes the label.
+#[tauri::command]
+fn label_prompt(state: State<AppState>, id: String, is_prompt: Option<bool>) -> Result<(), String> {
+  state
+    .storage
+    .get_prompt(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "Prompt not found".to_string())?;
+  state
+    .storage
+    .set_prompt_label(&id, is_prompt)
+    .map_err(|error| error.to_string())
+}
+
+/// How well the classifier's confidence matches the labels: accuracy per confidence bin,
+/// ROC points and the confusion matrix at the configured threshold.
+#[tauri::command]
+fn calibration_report(state: State<AppState>, bins: Option<usize>) -> Result<CalibrationReport, String> {
+  let samples: Vec<LabeledPrediction> = state
+    .storage
+    .list_labeled_classifications()
+    .map_err(|error| error.to_string())?
+    .into_iter()
+    .map(|(classification, label)| LabeledPrediction {
+      score: classification.prompt_score(),
+      label,
+    })
+    .collect();
+  let threshold = state.classifier_settings.lock().unwrap().threshold;
+  let bins = bins.unwrap_or(CALIBRATION_BINS).clamp(1, CALIBRATION_MAX_BINS);
+  Ok(build_calibration_report(&samples, threshold, bins))
+}
+
+/// Debug builds only: precision, recall and F1 of each keyword algorithm on the bundled
+/// fixtures, or on the fixture file at `path`, keeping the top `limit` keywords per prompt.
+/// The in-app counterpart of `cargo run -p promptlab-core --example keyword_eval`.
+#[cfg(debug_assertions)]
+#[tauri::command]
+fn run_keyword_eval(
+  path: Option<String>,
+  limit: Option<usize>,
+) -> Result<Vec<promptlab_core::eval::AlgorithmReport>, String> {
+  let data = match path {
+    Some(path) => std::fs::read_to_string(path).map_err(|error| error.to_string())?,
+    None => KEYWORD_EVAL_FIXTURES.to_string(),
+  };
+  let fixtures: Vec<promptlab_core::eval::KeywordFixture> =
+    serde_json::from_str(&data).map_err(|error| format!("无效的评测数据: {error}"))?;
+  let limit = limit.unwrap_or(KEYWORD_EVAL_TOP_K).max(1);
+  Ok(promptlab_core::eval::evaluate_keyword_algorithms(&fixtures, limit))
+}
+
+/// Propose tag merges: clusters of library tags that differ only in case, width or
+/// separators or are near-identical spellings, plus synonyms the LLM groups together
+/// (e.g. "LLM" and "大模型") when `use_llm` is set. Nothing is changed until the merges
+/// are approved through `merge_tags`.
+#[tauri::command]
+async fn normalize_tags(state: State<'_, AppState>, use_llm: Option<bool>) -> Result<Vec<TagCluster>, String> {
+  let tags = state.storage.list_tag_counts().map_err(|error| error.to_string())?;
+  let llm_groups = if use_llm.unwrap_or(false) && tags.len() > 1 {
+    let Some(client) = state.llm_client() else {
+      return Err("未配置 LLM，无法判断同义标签".into());
+    };
+    let names: Vec<&str> = tags.iter().take(TAG_LLM_LIMIT).map(|tag| tag.tag.as_str()).collect();
+    group_tags_with_llm(&client, &names).await.map_err(|error| {
+      warn!(target: "tags", %error, "synonym grouping failed");
+      error.to_string()
+    })?
+  } else {
+    Vec::new()
+  };
+  let clusters = cluster_tags(&tags, &llm_groups, &TagClusterOptions::default());
+  info!(target: "tags", tags = tags.len(), clusters = clusters.len(), "tag merges suggested");
+  Ok(clusters)
+}
+
+/// Apply approved tag merges to every analysis, renaming each alias to its canonical tag;
+/// `from` and `into` add one more merge, every tag in `from` becoming `into`. Returns the
+/// number of analyses changed.
+#[tauri::command]
+fn merge_tags(
+  state: State<AppState>,
+  merges: Option<Vec<TagMerge>>,
+  from: Option<Vec<String>>,
+  into: Option<String>,
+) -> Result<usize, String> {
+  let mut merges = merges.unwrap_or_default();
+  match (from, into) {
+    (Some(aliases), Some(canonical)) => merges.push(TagMerge { canonical, aliases }),
+    (None, None) => {}
+    _ => return Err("合并标签需要同时提供 from 与 into".into()),
+  }
+  let renames = merge_renames(&merges);
+  if renames.is_empty() {
+    return Ok(0);
+  }
+  let changed = rewrite_tags(&state, |tags| apply_tag_merges(tags, &renames))?;
+  info!(target: "tags", aliases = renames.len(), changed, "tags merged");
+  Ok(changed)
+}
+
+/// Rename a tag in every analysis, older ones included; where the prompt already has `to`,
+/// the two become one. Returns the number of analyses changed.
+#[tauri::command]
+fn rename_tag(state: State<AppState>, from: String, to: String) -> Result<usize, String> {
+  let (from, to) = (from.trim(), to.trim());
+  if from.is_empty() || to.is_empty() {
+    return Err("标签不能为空".into());
+  }
+  if from == to {
+    return Ok(0);
+  }
+  let renames = HashMap::from([(from.to_string(), to.to_string())]);
+  let changed = rewrite_tags(&state, |tags| apply_tag_merges(tags, &renames))?;
+  info!(target: "tags", %from, %to, changed, "tag renamed");
+  Ok(changed)
+}
+
+/// Remove a tag from every analysis, older ones included. Returns the number of analyses
+/// changed.
+#[tauri::command]
+fn delete_tag(state: State<AppState>, tag: String) -> Result<usize, String> {
+  let tag = tag.trim();
+  if tag.is_empty() {
+    return Err("标签不能为空".into());
+  }
+  let changed = rewrite_tags(&state, |tags| tags.iter().filter(|other| *other != tag).cloned().collect())?;
+  info!(target: "tags", %tag, changed, "tag deleted");
+  Ok(changed)
+}
+
+/// Rewrite the `tags` and the classification `keywords` of every analysis in one transaction,
+/// so a failure leaves all of them unchanged.
+fn rewrite_tags(state: &AppState, rewrite: impl Fn(&[String]) -> Vec<String>) -> Result<usize, String> {
+  state.storage.rewrite_analysis_tags(rewrite).map_err(|error| {
+    error!(target: "tags", %error, "rewriting tags failed");
+    error.to_string()
+  })
+}
+
+/// Find near-duplicate prompts locally and keep each new group as a pending merge
+/// suggestion; pending groups that no longer hold (a prompt was edited or deleted) are
+/// dropped. With `use_llm`, up to `MERGE_LLM_LIMIT` pending groups not judged yet are sent
+/// to the LLM, which decides whether they mean the same and proposes one merged version.
+/// Returns the pending suggestions.
+#[tauri::command]
+async fn suggest_merges(state: State<'_, AppState>, use_llm: Option<bool>) -> Result<Vec<MergeSuggestion>, String> {
+  let client = match use_llm.unwrap_or(false) {
+    true => Some(state.llm_client().ok_or_else(|| "未配置 LLM，无法判断重复提示词".to_string())?),
+    false => None,
+  };
+  let prompts = state.storage.list_prompts().map_err(|error| error.to_string())?;
+  let bodies: Vec<(String, String)> = prompts
+    .iter()
+    .map(|prompt| (prompt.id.clone(), prompt.body.clone()))
+    .collect();
+  let groups = tauri::async_runtime::spawn_blocking(move || {
+    let texts = bodies.iter().map(|(id, body)| (id.as_str(), body.as_str()));
+    find_near_duplicates(texts, &DuplicateOptions::default())
+  })
+  .await
+  .map_err(|error| error.to_string())?;
+
+  let sorted = |ids: &[String]| {
+    let mut ids = ids.to_vec();
+    ids.sort();
+    ids
+  };
+  let current: HashSet<Vec<String>> = groups.iter().map(|group| sorted(&group.prompt_ids)).collect();
+  let pending = state
+    .storage
+    .list_merge_suggestions(Some(MergeStatus::Pending))
+    .map_err(|error| error.to_string())?;
+  for stale in pending.iter().filter(|suggestion| !current.contains(&sorted(&suggestion.prompt_ids))) {
+    state.storage.delete_merge_suggestion(&stale.id).map_err(|error| error.to_string())?;
+  }
+  let mut added = 0;
+  for group in groups {
+    let suggestion = NewMergeSuggestion {
+      prompt_ids: group.prompt_ids,
+      similarity: group.similarity,
+    };
+    if state.storage.save_merge_suggestion(suggestion).map_err(|error| error.to_string())? {
+      added += 1;
+    }
+  }
+
+  if let Some(client) = client {
+    let by_id: HashMap<&str, &Prompt> = prompts.iter().map(|prompt| (prompt.id.as_str(), prompt)).collect();
+    let unjudged: Vec<MergeSuggestion> = state
+      .storage
+      .list_merge_suggestions(Some(MergeStatus::Pending))
+      .map_err(|error| error.to_string())?
+      .into_iter()
+      .filter(|suggestion| suggestion.same_prompt.is_none() && suggestion.prompt_ids.len() <= MERGE_GROUP_MAX)
+      .take(MERGE_LLM_LIMIT)
+      .collect();
+    for suggestion in unjudged {
+      let members: Vec<&Prompt> = suggestion
+        .prompt_ids
+        .iter()
+        .filter_map(|id| by_id.get(id.as_str()).copied())
+        .collect();
+      let (model, truncated, reply) = match judge_duplicates_with_llm(&client, &members).await {
+        Ok(judged) => judged,
+        Err(error) => {
+          error!(target: "merge", suggestion_id = %suggestion.id, %error, "duplicate check failed");
+          if error.is_backend_failure() {
+            break;
+          }
+          continue;
+        }
+      };
+      if reply.same {
+        // Length and leak checks are measured against the longest member.
+        let Some(longest) = members.iter().max_by_key(|prompt| prompt.body.chars().count()) else {
+          continue;
+        };
+        let instructions = [MERGE_SYSTEM_PROMPT];
+        if guard_llm_output(&state, "merge", longest, &model, &reply.merged, truncated, &instructions).is_err() {
+          continue;
+        }
+      }
+      let verdict = MergeVerdict {
+        same_prompt: reply.same,
+        merged_body: Some(reply.merged).filter(|merged| reply.same && !merged.is_empty()),
+        reason: Some(reply.reason).filter(|reason| !reason.is_empty()),
+        model,
+      };
+      state
+        .storage
+        .set_merge_verdict(&suggestion.id, verdict)
+        .map_err(|error| error.to_string())?;
+    }
+  }
+
+  let pending = state
+    .storage
+    .list_merge_suggestions(Some(MergeStatus::Pending))
+    .map_err(|error| error.to_string())?;
+  info!(target: "merge", added, pending = pending.len(), "merge suggestions updated");
+  Ok(pending)
+}
+
+/// Merge suggestions, optionally only `pending`, `accepted` or `rejected` ones.
+#[tauri::command]
+fn list_merge_suggestions(state: State<AppState>, status: Option<MergeStatus>) -> Result<Vec<MergeSuggestion>, String> {
+  state
+    .storage
+    .list_merge_suggestions(status)
+    .map_err(|error| error.to_string())
+}
+
+/// Accept a merge suggestion: `body` (or the LLM's merged version) becomes a new prompt
+/// titled after the oldest member and linked from every member by a `merged` relation.
+/// The members are kept unless `delete_originals` is set.
+#[tauri::command]
+fn accept_merge_suggestion(
+  state: State<AppState>,
+  id: String,
+  body: Option<String>,
+  delete_originals: Option<bool>,
+) -> Result<Prompt, String> {
+  let suggestion = state
+    .storage
+    .get_merge_suggestion(&id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "合并建议不存在".to_string())?;
+  if suggestion.status != MergeStatus::Pending {
+    return Err("合并建议已处理".into());
+  }
+  let body = body
+    .map(|body| body.trim().to_string())
+    .filter(|body| !body.is_empty())
+    .or_else(|| suggestion.merged_body.clone())
+    .ok_or_else(|| "没有合并版本，请提供合并后的正文".to_string())?;
+  let mut originals = Vec::with_capacity(suggestion.prompt_ids.len());
+  for prompt_id in &suggestion.prompt_ids {
+    if let Some(prompt) = state.storage.get_prompt(prompt_id).map_err(|error| error.to_string())? {
+      originals.push(prompt);
+    }
+  }
+  let Some(oldest) = originals.iter().min_by_key(|prompt| prompt.created_at) else {
+    return Err("待合并的提示词已不存在".into());
+  };
+
+  let mut record = NewPrompt::new(oldest.title.clone(), body);
+  record.language = oldest.language.clone();
+  record.model_hint = oldest.model_hint.clone();
+  record.metadata = json!({
+    "source": "merge",
+    "merged_from": suggestion.prompt_ids,
+    "suggestion_id": suggestion.id,
+    "model": suggestion.model
+  });
+  let prompt = state.storage.create_prompt(record).map_err(|error| error.to_string())?;
+  for original in &originals {
+    state
+      .storage
+      .add_relation(NewPromptRelation {
+        source_id: original.id.clone(),
+        target_id: prompt.id.clone(),
+        relation: "merged".into(),
+        metadata: json!({ "suggestion_id": suggestion.id }),
+      })
+      .map_err(|error| error.to_string())?;
+  }
+  if let Err(error) = reanalyze_prompt(&state, &prompt, "merge") {
+    error!(target: "analysis", prompt_id = %prompt.id, %error, "merged prompt analysis failed");
+  }
+  state
+    .storage
+    .resolve_merge_suggestion(&id, MergeStatus::Accepted, Some(&prompt.id))
+    .map_err(|error| error.to_string())?;
+  if delete_originals.unwrap_or(false) {
+    for original in &originals {
+      if let Err(error) = state.storage.delete_prompt(&original.id) {
+        error!(target: "merge", prompt_id = %original.id, %error, "deleting a merged prompt failed");
+      }
+    }
+  }
+  info!(
+    target: "merge",
+    suggestion_id = %id,
+    merged = originals.len(),
+    prompt_id = %prompt.id,
+    "merge suggestion accepted"
+  );
+  Ok(prompt)
+}
+
+/// Reject a merge suggestion; the same group is not suggested again.
+#[tauri::command]
+fn reject_merge_suggestion(state: State<AppState>, id: String) -> Result<bool, String> {
+  let rejected = state
+    .storage
+    .resolve_merge_suggestion(&id, MergeStatus::Rejected, None)
+    .map_err(|error| error.to_string())?;
+  if rejected {
+    info!(target: "merge", suggestion_id = %id, "merge suggestion rejected");
+  }
+  Ok(rejected)
+}
+
+/// Fold `duplicate_ids` into `primary_id`: their analyses, attachments, tags, notes and use
+/// counts move to the primary, each is linked to it by a `merged` relation, and the
+/// duplicates go to the trash, all in one transaction.
+#[tauri::command]
+fn merge_prompts(
+  state: State<AppState>,
+  primary_id: String,
+  duplicate_ids: Vec<String>,
+) -> Result<MergeReport, String> {
+  if duplicate_ids.iter().all(|id| *id == primary_id) {
+    return Err("请选择要合并的重复提示词".into());
+  }
+  let report = state
+    .storage
+    .merge_prompts(&primary_id, &duplicate_ids)
+    .map_err(|error| {
+      error!(target: "merge", %primary_id, %error, "merging prompts failed");
+      error.to_string()
+    })?
+    .ok_or_else(|| "主提示词不存在或已删除".to_string())?;
+  info!(
+    target: "merge",
+    %primary_id,
+    merged = report.merged_ids.len(),
+    skipped = report.skipped_ids.len(),
+    analyses = report.analyses,
+    attachments = report.attachments,
+    "prompts merged"
+  );
+  Ok(report)
+}
+
+/// Few-shot examples shown to the `is_prompt` classifier, newest first.
+#[tauri::command]
+fn list_classifier_examples(state: State<AppState>) -> Result<Vec<ClassifierExample>, String> {
+  state.storage.list_classifier_examples().map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+fn add_classifier_example(state: State<AppState>, text: String, is_prompt: bool) -> Result<ClassifierExample, String> {
+  let text = text.trim().to_string();
+  if text.is_empty() {
+    return Err("示例内容不能为空".into());
+  }
+  let example = state
+    .storage
+    .create_classifier_example(NewClassifierExample {
+      text,
+      is_prompt,
+      prompt_id: None,
+    })
+    .map_err(|error| error.to_string())?;
+  info!(target: "classifier", example_id = %example.id, is_prompt, "classifier example added");
+  Ok(example)
+}
+
+/// Turn a misclassified capture into a few-shot example with the correct label (e.g.
+/// "这不是一个 prompt"), and record the label for calibration too. A prompt already used
+/// as an example has its example updated instead.
+#[tauri::command]
+fn add_classifier_example_from_prompt(
+  state: State<AppState>,
+  prompt_id: String,
+  is_prompt: bool,
+) -> Result<ClassifierExample, String> {
+  let prompt = state
+    .storage
+    .get_prompt(&prompt_id)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "Prompt not found".to_string())?;
+  let example = label_with_example(&state, &prompt, is_prompt)?;
+  info!(target: "classifier", prompt_id = %prompt.id, is_prompt, "prompt added as a classifier example");
+  Ok(example)
+}
+
+/// Record `is_prompt` as the prompt's calibration label and as a few-shot example of its
+/// text, updating the example if the prompt already has one.
+fn label_with_example(state: &AppState, prompt: &Prompt, is_prompt: bool) -> Result<ClassifierExample, String> {
+  let existing = state
+    .storage
+    .find_classifier_example_by_prompt(&prompt.id)
+    .map_err(|error| error.to_string())?;
+  let example = match existing {
+    Some(example) => state
+      .storage
+      .update_classifier_example(&example.id, Some(prompt.body.clone()), Some(is_prompt))
+      .map_err(|error| error.to_string())?
+      .ok_or_else(|| "Classifier example not found".to_string())?,
+    None => state
+      .storage
+      .create_classifier_example(NewClassifierExample {
+        text: prompt.body.clone(),
+        is_prompt,
+        prompt_id: Some(prompt.id.clone()),
+      })
+      .map_err(|error| error.to_string())?,
+  };
+  state
+    .storage
+    .set_prompt_label(&prompt.id, Some(is_prompt))
+    .map_err(|error| error.to_string())?;
+  Ok(example)
+}
+
+#[tauri::command]
+fn update_classifier_example(
+  state: State<AppState>,
+  id: String,
+  text: Option<String>,
+  is_prompt: Option<bool>,
+) -> Result<ClassifierExample, String> {
+  let text = text.map(|text| text.trim().to_string());
+  if text.as_deref() == Some("") {
+    return Err("示例内容不能为空".into());
+  }
+  state
+    .storage
+    .update_classifier_example(&id, text, is_prompt)
+    .map_err(|error| error.to_string())?
+    .ok_or_else(|| "Classifier example not found".to_string())
+}
+
+#[tauri::command]
+fn delete_classifier_example(state: State<AppState>, id: String) -> Result<bool, String> {
+  let deleted = state
+    .storage
+    .delete_classifier_example(&id)
+    .map_err(|error| error.to_string())?;
+  if deleted {
+    info!(target: "classifier", example_id = %id, "classifier example deleted");
+  }
+  Ok(deleted)
+}
+
+/// Whether classification is paused after repeated LLM failures, and for how long.
+#[tauri::command]
+fn get_classifier_circuit(state: State<AppState>) -> CircuitStatus {
+  state.classifier_circuit.lock().unwrap().status(Instant::now())
+}
+
+/// Resume classification right away, e.g. after fixing the API key or network.
+#[tauri::command]
+fn reset_classifier_circuit(state: State<AppState>) -> CircuitStatus {
+  let mut circuit = state.classifier_circuit.lock().unwrap();
+  circuit.reset();
+  state.llm_jobs_wake.notify_one();
+  info!(target: "classifier", "circuit breaker reset");
+  circuit.status(Instant::now())
+}
+
+/// Background jobs, newest first, optionally filtered by status.
+#[tauri::command]
+fn list_jobs(state: State<AppState>, status: Option<JobStatus>, limit: Option<usize>) -> Result<Vec<Job>, String> {
+  state
+    .storage
+    .list_jobs(status, limit.unwrap_or(LIST_JOBS_LIMIT))
+    .map_err(|error| error.to_string())
+}
+
+/// Cancel a pending or running job; returns `false` if it had already finished.
+#[tauri::command]
+fn cancel_job(state: State<AppState>, id: String) -> Result<bool, String> {
+  let cancelled = state.storage.cancel_job(&id).map_err(|error| error.to_string())?;
+  if cancelled {
+    info!(target: "jobs", job_id = %id, "job cancelled");
+  }
+  Ok(cancelled)
+}
+
+/// Stop a background operation by the id its command returned or its progress events carry:
+/// exports and re-analysis stop after the current item and report what they processed,
+/// backlog classification cancels the jobs still queued. Returns `false` if the operation had
+/// already finished.
+#[tauri::command]
+fn cancel_operation(state: State<AppState>, operation_id: String) -> Result<bool, String> {
+  let stopping = state.operations.cancel(&operation_id);
+  let jobs = state
+    .storage
+    .cancel_operation_jobs(&operation_id)
+    .map_err(|error| error.to_string())?;
+  if stopping || jobs > 0 {
+    info!(target: "jobs", %operation_id, jobs, "operation cancelled");
+  }
+  Ok(stopping || jobs > 0)
+}
+
+#[tauri::command]
+fn get_llm_network(state: State<AppState>) -> NetworkSettings {
+  state.llm_network.lock().unwrap().clone()
+}
+
+/// Change the proxy, request timeout and extra CA bundle used for LLM requests. The
+/// client is rebuilt right away; invalid settings are rejected before anything is saved.
+#[tauri::command]
+fn set_llm_network(
+  app: AppHandle,
+  state: State<AppState>,
+  network: NetworkSettings,
+) -> Result<NetworkSettings, String> {
+  let network = network.normalized();
+  network.validate()?;
+  // Surface bad proxy URLs and unreadable certificates now rather than on the next call.
+  network.http_client().map_err(|error| error.to_string())?;
+  persist_llm_network(&state.llm_network_path, &network).map_err(|error| error.to_string())?;
+  *state.llm_network.lock().unwrap() = network.clone();
+  info!(
+    target: "llm",
+    proxy = ?network.proxy.as_deref().map(redact_proxy),
+    timeout_secs = network.timeout_secs,
+    ca_bundle = ?network.ca_bundle_path,
+    "network settings changed"
+  );
+  state.reconnect_llm();
+  announce_settings(&app, "llmNetwork", &network);
+  Ok(network)
+}
+
+/// Drop the credentials from a proxy URL before it is logged.
+fn redact_proxy(proxy: &str) -> String {
+  match (proxy.find("://"), proxy.rfind('@')) {
+    (Some(scheme), Some(at)) if at > scheme => format!("{}***{}", &proxy[..scheme + 3], &proxy[at..]),
+    _ => proxy.to_string(),
+  }
+}
+
+/// Per-task model overrides next to the models actually used for each task.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LlmTaskModelsStatus {
+  configured: TaskModels,
+  effective: TaskModels,
+}
+
+fn llm_task_models_status(state: &AppState) -> LlmTaskModelsStatus {
+  let configured = state.llm_models.lock().unwrap().clone();
+  let settings = state.llm_settings.lock().unwrap().clone();
+  let effective = |task| settings.task_model(&configured, task).map(str::to_string);
+  LlmTaskModelsStatus {
+    effective: TaskModels {
+      classification: effective(LlmTask::Classification),
+      summarization: effective(LlmTask::Summarization),
+      optimization: effective(LlmTask::Optimization),
+      embedding: effective(LlmTask::Embedding),
+      ocr: effective(LlmTask::Ocr),
+    },
+    configured,
+  }
+}
+
+#[tauri::command]
+fn get_llm_task_models(state: State<AppState>) -> LlmTaskModelsStatus {
+  llm_task_models_status(&state)
+}
+
+/// Choose which model handles classification, summarization, optimization, embedding and
+/// OCR; blank entries fall back to the defaults. Applies to the next call.
+#[tauri::command]
+fn set_llm_task_models(state: State<AppState>, models: TaskModels) -> Result<LlmTaskModelsStatus, String> {
+  let models = models.normalized();
+  persist_llm_models(&state.llm_models_path, &models).map_err(|error| error.to_string())?;
+  *state.llm_models.lock().unwrap() = models;
+  let status = llm_task_models_status(&state);
+  let effective = &status.effective;
+  info!(
+    target: "llm",
+    classification = ?effective.classification,
+    summarization = ?effective.summarization,
+    optimization = ?effective.optimization,
+    embedding = ?effective.embedding,
+    ocr = ?effective.ocr,
+    "task models changed"
+  );
+  Ok(status)
+}
+
+#[tauri::command]
+fn get_llm_generation(state: State<AppState>) -> GenerationSettings {
+  *state.llm_generation.lock().unwrap()
+}
+
+/// Temperature, top_p, max_tokens and response format for classification, optimization and
+/// playground runs. Applies to the next call.
+#[tauri::command]
+fn set_llm_generation(state: State<AppState>, settings: GenerationSettings) -> Result<GenerationSettings, String> {
+  settings.validate()?;
+  persist_llm_generation(&state.llm_generation_path, &settings).map_err(|error| error.to_string())?;
+  *state.llm_generation.lock().unwrap() = settings;
+  info!(target: "llm", "generation settings changed");
+  Ok(settings)
+}
+
+/// Calls, failures, tokens and latency per task and model, over the last `days` days
+/// (all time when omitted).
+#[tauri::command]
+fn llm_usage_metrics(state: State<AppState>, days: Option<u32>) -> Result<Vec<LlmUsageMetrics>, String> {
+  let since = days.map(|days| chrono::Utc::now() - chrono::Duration::days(i64::from(days)));
+  state.storage.llm_usage_metrics(since).map_err(|error| error.to_string())
+}
+
+/// Data of the statistics page over `range` (default: the last 30 days): prompts saved and
+/// texts held in the inbox per day, LLM calls, tokens and spend per day (priced with the
+/// budget settings), where prompts came from, the most used tags and how often the
+/// classifier and the inbox review accepted.
+#[tauri::command]
+fn get_dashboard_data(state: State<AppState>, range: Option<DashboardRange>) -> Result<DashboardData, String> {
+  let range = range.unwrap_or_default();
+  let today = Local::now().date_naive();
+  let first = today - chrono::Duration::days(range.days() - 1);
+  let since = first
+    .and_hms_opt(0, 0, 0)
+    .and_then(|start| start.and_local_timezone(Local).earliest())
+    .map(|start| start.with_timezone(&chrono::Utc))
+    .ok_or_else(|| format!("无效的起始日期: {first}"))?;
+  let stats = state
+    .storage
+    .dashboard_stats(since, DASHBOARD_TOP_TAGS)
+    .map_err(|error| error.to_string())?;
+  let settings = state.budget.lock().unwrap().clone();
+  let spend: HashMap<String, DailySpend> = daily_spend(&stats.llm_usage, &settings)
+    .into_iter()
+    .map(|day| (day.date.clone(), day))
+    .collect();
+  let activity: HashMap<&str, _> = stats.daily.iter().map(|day| (day.date.as_str(), day)).collect();
+
+  let days: Vec<DashboardDay> = first
+    .iter_days()
+    .take_while(|date| *date <= today)
+    .map(|date| {
+      let date = date.format("%Y-%m-%d").to_string();
+      let activity = activity.get(date.as_str());
+      let spend = spend.get(&date);
+      DashboardDay {
+        prompts: activity.map_or(0, |day| day.prompts),
+        captures: activity.map_or(0, |day| day.captures),
+        llm_calls: spend.map_or(0, |day| day.calls),
+        tokens: spend.map_or(0, |day| day.tokens),
+        spend: spend.map_or(0.0, |day| day.spend),
+        date,
+      }
+    })
+    .collect();
+  let rate = |accepted: u64, total: u64| (total > 0).then(|| accepted as f64 / total as f64);
+  let classifier = stats.classifier;
+  Ok(DashboardData {
+    range,
+    spend: days.iter().map(|day| day.spend).sum(),
+    days,
+    sources: stats.sources,
+    top_tags: stats.top_tags,
+    classifier_accept_rate: rate(classifier.accepted, classifier.classified),
+    inbox_accept_rate: rate(
+      classifier.inbox_accepted,
+      classifier.inbox_accepted + classifier.inbox_rejected,
+    ),
+    classifier,
+    currency: settings.currency,
+  })
+}
+
+/// This month's token use and spend against the budget, and whether classification is paused.
+#[tauri::command]
+fn get_budget_status(app: AppHandle, state: State<AppState>) -> Result<BudgetStatus, String> {
+  check_budget(&app, &state).map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+fn get_budget_settings(state: State<AppState>) -> BudgetSettings {
+  state.budget.lock().unwrap().clone()
+}
+
+/// Change the monthly limits, model prices and alert thresholds; usage is re-checked at once.
+#[tauri::command]
+fn set_budget_settings(
+  app: AppHandle,
+  state: State<AppState>,
+  settings: BudgetSettings,
+) -> Result<BudgetStatus, String> {
+  let settings = settings.normalized();
+  settings.validate()?;
+  persist_budget_settings(&state.budget_path, &settings).map_err(|error| error.to_string())?;
+  info!(
+    target: "llm",
+    monthly_tokens = ?settings.monthly_tokens,
+    monthly_spend = ?settings.monthly_spend,
+    currency = %settings.currency,
+    "budget changed"
+  );
+  announce_settings(&app, "budget", &settings);
+  *state.budget.lock().unwrap() = settings;
+  check_budget(&app, &state).map_err(|error| error.to_string())
+}
+
+/// Resume automatic classification after a budget limit paused it; it stays on until the
+/// end of the month unless the limit is raised and reached again.
+#[tauri::command]
+fn acknowledge_budget_alert(app: AppHandle, state: State<AppState>) -> Result<BudgetStatus, String> {
+  {
+    let mut alerts = state.budget_alerts.lock().unwrap();
+    alerts.acknowledged = true;
+    alerts.classification_paused = false;
+    persist_budget_alerts(&state.budget_alerts_path, &alerts).map_err(|error| error.to_string())?;
+  }
+  info!(target: "llm", "budget alert acknowledged, automatic classification resumed");
+  state.llm_jobs_wake.notify_one();
+  check_budget(&app, &state).map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+fn get_llm_rate_limits(state: State<AppState>) -> RateLimits {
+  state.llm_limiter.limits()
+}
+
+/// Change the request rate / burst / in-flight cap shared by all LLM calls; applies immediately.
+#[tauri::command]
+fn set_llm_rate_limits(state: State<AppState>, limits: RateLimits) -> Result<RateLimits, String> {
+  limits.validate()?;
+  persist_llm_limits(&state.llm_limits_path, &limits).map_err(|error| error.to_string())?;
+  state.llm_limiter.reconfigure(limits);
+  info!(
+    target: "llm",
+    requests_per_minute = limits.requests_per_minute,
+    burst = limits.burst,
+    max_in_flight = limits.max_in_flight,
+    "rate limits changed"
+  );
+  Ok(limits)
+}
+
+/// Frequent library terms not yet in the vocabulary, from the background job's last run
+/// (or a fresh run when `refresh` is set).
+#[tauri::command]
+fn suggest_vocabulary_terms(
+  state: State<AppState>,
+  refresh: Option<bool>,
+) -> Result<Vec<VocabularySuggestion>, String> {
+  if refresh.unwrap_or(false) {
+    refresh_vocabulary_suggestions(&state).map_err(|error| {
+      error!(target: "vocabulary", %error, "generating term suggestions failed");
+      error.to_string()
+    })?;
+  }
+  let vocab = state.vocabulary.lock().unwrap().clone();
+  let suggestions = state.vocabulary_suggestions.lock().unwrap().suggestions.clone();
+  Ok(
+    suggestions
+      .into_iter()
+      .filter(|suggestion| !vocab.iter().any(|entry| entry.spellings().any(|spelling| spelling == suggestion.term)))
+      .collect(),
+  )
+}
+
+/// Add a suggested term to the vocabulary.
+#[tauri::command]
+fn accept_vocabulary_suggestion(app: AppHandle, state: State<AppState>, term: String) -> Result<Vec<String>, String> {
+  {
+    let mut cache = state.vocabulary_suggestions.lock().unwrap();
+    cache.suggestions.retain(|suggestion| suggestion.term != term);
+    persist_vocabulary_suggestions(&state.vocabulary_suggestions_path, &cache).map_err(|error| error.to_string())?;
+  }
+  info!(target: "vocabulary", %term, "term suggestion accepted");
+  add_vocabulary_entry(app, state, term, None)
+}
+
+/// Dismiss a suggestion; rejected terms are never suggested again.
+#[tauri::command]
+fn reject_vocabulary_suggestion(state: State<AppState>, term: String) -> Result<Vec<VocabularySuggestion>, String> {
+  let mut cache = state.vocabulary_suggestions.lock().unwrap();
+  cache.suggestions.retain(|suggestion| suggestion.term != term);
+  if !cache.rejected.contains(&term) {
+    cache.rejected.push(term.clone());
+  }
+  persist_vocabulary_suggestions(&state.vocabulary_suggestions_path, &cache).map_err(|error| error.to_string())?;
+  info!(target: "vocabulary", %term, "term suggestion rejected");
+  Ok(cache.suggestions.clone())
+}
+
+/// Replace the aliases of a vocabulary term. An alias may belong to only one term.
+#[tauri::command]
+fn set_vocabulary_aliases(
+  app: AppHandle,
+  state: State<AppState>,
+  term: String,
+  aliases: Vec<String>,
+) -> Result<VocabularyEntry, String> {
+  let cleaned = normalize_vocab_term(&term);
+  let mut vocab = state.vocabulary.lock().unwrap();
+  let Some(index) = vocab.iter().position(|entry| entry.term == cleaned) else {
+    return Err(format!("词条不存在: {cleaned}"));
+  };
+
+  let mut next: Vec<String> = Vec::new();
+  for alias in aliases {
+    let alias = normalize_vocab_term(&alias);
+    if alias.is_empty() || alias == cleaned || next.contains(&alias) {
+      continue;
+    }
+    if let Some(owner) = vocab
+      .iter()
+      .find(|entry| entry.term != cleaned && entry.spellings().any(|spelling| spelling == alias))
+    {
+      return Err(format!("“{alias}” 已属于词条 “{}”", owner.term));
+    }
+    next.push(alias);
+  }
+
+  let mut needles: Vec<String> = vocab[index].spellings().map(str::to_string).collect();
+  needles.extend(next.iter().cloned());
+  vocab[index].aliases = next;
+  persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
+  info!(target: "vocabulary", term = %cleaned, aliases = ?vocab[index].aliases, "term aliases changed");
+  spawn_vocabulary_reanalysis(app, cleaned, needles);
+  Ok(vocab[index].clone())
+}
+
+/// Merge a glossary file into the vocabulary. Accepts CSV, TSV or plain text (one term per
+/// line); optional second and third columns carry a weight and categories ("编程/数据").
+/// A weight without categories becomes the entry weight, with categories a scoped boost.
+#[tauri::command]
+fn import_vocabulary(app: AppHandle, state: State<AppState>, path: String) -> Result<VocabularyImportSummary, String> {
+  let rows = read_glossary_rows(Path::new(&path)).map_err(|error| {
+    error!(target: "vocabulary", %path, %error, "reading the vocabulary file failed");
+    error
+  })?;
+
+  let mut summary = VocabularyImportSummary::default();
+  let mut touched: Vec<String> = Vec::new();
+  let mut boosts_changed = false;
+  let mut vocab = state.vocabulary.lock().unwrap();
+  let mut config = state.analysis_config.lock().unwrap();
+  for (line, row) in rows {
+    let term = normalize_vocab_term(row.first().map(String::as_str).unwrap_or_default());
+    if term.is_empty() {
+      summary.skipped += 1;
+      continue;
+    }
+    let weight = row.get(1).map(|cell| cell.trim()).filter(|cell| !cell.is_empty());
+    let factor = match weight.map(|cell| cell.parse::<f64>()) {
+      None => None,
+      Some(Ok(value)) if (1.0..=MAX_VOCABULARY_BOOST as f64).contains(&value) => Some(value.round() as usize),
+      Some(_) => {
+        summary.errors.push(format!("第 {line} 行: 无效的加权倍数 {}", weight.unwrap_or_default()));
+        summary.skipped += 1;
+        continue;
+      }
+    };
+    let mut categories: Vec<String> = Vec::new();
+    for category in row.get(2).into_iter().flat_map(|cell| cell.split(['/', '|', '、', ';'])) {
+      if category.trim().is_empty() {
+        continue;
+      }
+      match canonical_category(category) {
+        Some(name) if !categories.iter().any(|item| item == name) => categories.push(name.to_string()),
+        Some(_) => {}
+        None => summary.errors.push(format!("第 {line} 行: 未知分类 {}，已忽略", category.trim())),
+      }
+    }
+
+    let index = match vocab
+      .iter()
+      .position(|entry| entry.term == term || entry.aliases.contains(&term))
+    {
+      Some(index) => {
+        summary.existing += 1;
+        index
+      }
+      None => {
+        vocab.push(VocabularyEntry::new(term.clone()));
+        summary.added += 1;
+        vocab.len() - 1
+      }
+    };
+    let entry = &mut vocab[index];
+    if !categories.is_empty() {
+      config.vocabulary_boosts.retain(|boost| boost.term != entry.term);
+      config.vocabulary_boosts.push(VocabularyBoost {
+        term: entry.term.clone(),
+        categories,
+        factor: factor.or(entry.weight).unwrap_or(DEFAULT_VOCABULARY_BOOST),
+      });
+      boosts_changed = true;
+      summary.boosted += 1;
+    } else if factor.is_some() {
+      entry.weight = factor;
+      summary.boosted += 1;
+    }
+    touched.push(entry.term.clone());
+  }
+
+  vocab.sort_by(|a, b| a.term.cmp(&b.term));
+  persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
+  if boosts_changed {
+    persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+  }
+  info!(
+    target: "vocabulary",
+    %path,
+    added = summary.added,
+    existing = summary.existing,
+    boosted = summary.boosted,
+    skipped = summary.skipped,
+    "vocabulary imported"
+  );
+
+  touched.sort();
+  touched.dedup();
+  summary.total = touched.len();
+  if !touched.is_empty() {
+    summary.operation_id = Some(spawn_vocabulary_reanalysis(app, "vocabulary-import".into(), touched));
+  }
+  Ok(summary)
+}
+
+#[tauri::command]
+fn list_suppressed_terms(state: State<AppState>) -> Vec<String> {
+  let mut terms = state.analysis_config.lock().unwrap().suppressed_terms.clone();
+  terms.sort();
+  terms
+}
+
+#[tauri::command]
+fn add_suppressed_term(state: State<AppState>, term: String) -> Result<Vec<String>, String> {
+  let normalized = normalize_vocab_term(&term);
+  if normalized.is_empty() {
+    return Err("请输入有效的屏蔽词".into());
+  }
+  let mut config = state.analysis_config.lock().unwrap();
+  if !config.suppressed_terms.contains(&normalized) {
+    config.suppressed_terms.push(normalized.clone());
+    persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+    info!(target: "vocabulary", term = %normalized, "suppressed term added");
+  }
+  let mut list = config.suppressed_terms.clone();
+  list.sort();
+  Ok(list)
+}
+
+#[tauri::command]
+fn remove_suppressed_term(state: State<AppState>, term: String) -> Result<Vec<String>, String> {
+  let cleaned = normalize_vocab_term(&term);
+  let mut config = state.analysis_config.lock().unwrap();
+  let before = config.suppressed_terms.len();
+  config.suppressed_terms.retain(|item| *item != cleaned);
+  if config.suppressed_terms.len() != before {
+    persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+    info!(target: "vocabulary", term = %cleaned, "suppressed term removed");
+  }
+  let mut list = config.suppressed_terms.clone();
+  list.sort();
+  Ok(list)
+}
+
+#[tauri::command]
+fn list_vocabulary_boosts(state: State<AppState>) -> Vec<VocabularyBoost> {
+  let mut boosts = state.analysis_config.lock().unwrap().vocabulary_boosts.clone();
+  boosts.sort_by(|a, b| a.term.cmp(&b.term));
+  boosts
+}
+
+#[tauri::command]
+fn list_prompt_categories() -> Vec<&'static str> {
+  PROMPT_CATEGORIES.iter().map(|(category, _)| *category).collect()
+}
+
+/// Set (or replace) the boost factor of a vocabulary term, optionally scoped to categories.
+/// The term is added to the vocabulary if it is not there yet.
+#[tauri::command]
+fn set_vocabulary_boost(
+  app: AppHandle,
+  state: State<AppState>,
+  term: String,
+  factor: usize,
+  categories: Option<Vec<String>>,
+) -> Result<Vec<VocabularyBoost>, String> {
+  let normalized = normalize_vocab_term(&term);
+  if normalized.is_empty() {
+    return Err("请输入有效的词条".into());
+  }
+  if !(1..=MAX_VOCABULARY_BOOST).contains(&factor) {
+    return Err(format!("加权倍数需在 1~{MAX_VOCABULARY_BOOST} 之间"));
+  }
+  let mut scoped = Vec::new();
+  for category in categories.unwrap_or_default() {
+    let Some(name) = canonical_category(&category) else {
+      let known: Vec<&str> = PROMPT_CATEGORIES.iter().map(|(name, _)| *name).collect();
+      return Err(format!("未知分类: {category}（可选: {}）", known.join("、")));
+    };
+    if !scoped.iter().any(|item| item == name) {
+      scoped.push(name.to_string());
+    }
+  }
+
+  {
+    let mut vocab = state.vocabulary.lock().unwrap();
+    if !vocab.iter().any(|entry| entry.term == normalized) {
+      vocab.push(VocabularyEntry::new(normalized.clone()));
+      persist_vocabulary(&state.vocabulary_path, &vocab).map_err(|error| error.to_string())?;
+      info!(target: "vocabulary", term = %normalized, "term added");
+    }
+  }
+
+  let mut config = state.analysis_config.lock().unwrap();
+  config.vocabulary_boosts.retain(|boost| boost.term != normalized);
+  config.vocabulary_boosts.push(VocabularyBoost {
+    term: normalized.clone(),
+    categories: scoped.clone(),
+    factor,
+  });
+  persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+  info!(target: "vocabulary", term = %normalized, factor, categories = ?scoped, "term boosted");
+  spawn_vocabulary_reanalysis(app, normalized.clone(), vec![normalized]);
+  let mut boosts = config.vocabulary_boosts.clone();
+  boosts.sort_by(|a, b| a.term.cmp(&b.term));
+  Ok(boosts)
+}
+
+#[tauri::command]
+fn remove_vocabulary_boost(
+  app: AppHandle,
+  state: State<AppState>,
+  term: String,
+) -> Result<Vec<VocabularyBoost>, String> {
+  let cleaned = normalize_vocab_term(&term);
+  let mut config = state.analysis_config.lock().unwrap();
+  let before = config.vocabulary_boosts.len();
+  config.vocabulary_boosts.retain(|boost| boost.term != cleaned);
+  if config.vocabulary_boosts.len() != before {
+    persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+    info!(target: "vocabulary", term = %cleaned, "term boost removed");
+    spawn_vocabulary_reanalysis(app, cleaned.clone(), vec![cleaned]);
+  }
+  let mut boosts = config.vocabulary_boosts.clone();
+  boosts.sort_by(|a, b| a.term.cmp(&b.term));
+  Ok(boosts)
+}
+
+#[tauri::command]
+fn list_extraction_rules(state: State<AppState>) -> Vec<ExtractionRule> {
+  state.analysis_config.lock().unwrap().extraction_rules.clone()
+}
+
+#[tauri::command]
+fn add_extraction_rule(state: State<AppState>, field: String, pattern: String) -> Result<ExtractionRule, String> {
+  let rule = ExtractionRule::new(&field, &pattern).map_err(|error| error.to_string())?;
+  let mut config = state.analysis_config.lock().unwrap();
+  if config.extraction_rules.iter().any(|item| item.field == rule.field) {
+    return Err(format!("字段已存在: {}", rule.field));
+  }
+  config.extraction_rules.push(rule.clone());
+  persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+  info!(target: "analysis", field = %rule.field, pattern = %rule.pattern, "extraction rule added");
+  Ok(rule)
+}
+
+#[tauri::command]
+fn update_extraction_rule(
+  state: State<AppState>,
+  id: String,
+  field: Option<String>,
+  pattern: Option<String>,
+  enabled: Option<bool>,
+) -> Result<ExtractionRule, String> {
+  let mut config = state.analysis_config.lock().unwrap();
+  let Some(index) = config.extraction_rules.iter().position(|rule| rule.id == id) else {
+    return Err(format!("规则不存在: {id}"));
+  };
+  let mut rule = config.extraction_rules[index].clone();
+  if let Some(field) = field {
+    rule.field = field.trim().to_string();
+  }
+  if let Some(pattern) = pattern {
+    rule.pattern = pattern;
+  }
+  if let Some(enabled) = enabled {
+    rule.enabled = enabled;
+  }
+  rule.validate().map_err(|error| error.to_string())?;
+  if config.extraction_rules.iter().any(|item| item.id != rule.id && item.field == rule.field) {
+    return Err(format!("字段已存在: {}", rule.field));
+  }
+  config.extraction_rules[index] = rule.clone();
+  persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+  info!(
+    target: "analysis",
+    field = %rule.field,
+    pattern = %rule.pattern,
+    enabled = rule.enabled,
+    "extraction rule updated"
+  );
+  Ok(rule)
+}
+
+#[tauri::command]
+fn remove_extraction_rule(state: State<AppState>, id: String) -> Result<bool, String> {
+  let mut config = state.analysis_config.lock().unwrap();
+  let before = config.extraction_rules.len();
+  config.extraction_rules.retain(|rule| rule.id != id);
+  let removed = config.extraction_rules.len() != before;
+  if removed {
+    persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+    info!(target: "analysis", rule_id = %id, "extraction rule removed");
+  }
+  Ok(removed)
+}
+
+#[tauri::command]
+fn get_analysis_config(state: State<AppState>) -> AnalysisConfig {
+  state.analysis_config.lock().unwrap().clone()
+}
+
+#[tauri::command]
+fn set_summary_strategy(
+  state: State<AppState>,
+  strategy: SummaryStrategy,
+  max_length: Option<usize>,
+) -> Result<AnalysisConfig, String> {
+  if matches!(max_length, Some(0)) {
+    return Err("摘要长度必须大于 0".into());
+  }
+  let mut config = state.analysis_config.lock().unwrap();
+  config.summary_strategy = strategy;
+  if let Some(max_length) = max_length {
+    config.summary_max_length = max_length;
+  }
+  persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+  info!(
+    target: "analysis",
+    strategy = ?config.summary_strategy,
+    max_length = config.summary_max_length,
+    "summary strategy changed"
+  );
+  Ok(config.clone())
+}
+
+#[tauri::command]
+fn set_deterministic_analysis_ids(state: State<AppState>, enabled: bool) -> Result<AnalysisConfig, String> {
+  let mut config = state.analysis_config.lock().unwrap();
+  config.deterministic_ids = enabled;
+  persist_analysis_config(&state.analysis_config_path, &config).map_err(|error| error.to_string())?;
+  info!(target: "analysis", deterministic = enabled, "analysis id mode changed");
+  Ok(config.clone())
+}
+
+#[tauri::command]
+fn reload_analysis_config(app: AppHandle, state: State<AppState>) -> AnalysisOverridesStatus {
+  let status = apply_analysis_overrides(&state);
+  let _ = app.emit("analysis:overrides-reloaded", status.clone());
+  status
+}
+
+fn main() {
+  Builder::default()
+    .plugin(tauri_plugin_shell::init())
+    .plugin(tauri_plugin_dialog::init())
+    .plugin(tauri_plugin_notification::init())
+    .plugin(
+      tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| {
+          if is_picker_hotkey(app, shortcut) {
+            if event.state() == ShortcutState::Pressed {
+              show_quick_picker(app);
+            }
+          } else if event.state() == ShortcutState::Released {
+            // On release, so the hotkey's own key is no longer down when the copy is sent.
+            let app_handle = app.clone();
+            thread::spawn(move || capture_selection(&app_handle));
+          }
+        })
+        .build(),
+    )
     .plugin(single_instance(|app, _argv, _cwd| {
       if let Some(window) = app.get_webview_window("main") {
         let _ = window.show();
         let _ = window.set_focus();
       }
-    }))
-    .on_window_event(|window, event| {
-      if let WindowEvent::CloseRequested { api, .. } = event {
-        // Hide to tray instead of quitting.
-        api.prevent_close();
-        let _ = window.hide();
+    }))
+    // After the single instance plugin, which hands it links opened while the app runs.
+    .plugin(tauri_plugin_deep_link::init())
+    .plugin(tauri_plugin_autostart::init(
+      MacosLauncher::LaunchAgent,
+      Some(vec![AUTOSTART_ARG]),
+    ))
+    .on_window_event(|window, event| match event {
+      // Prompt windows really close; the others hide to tray instead of quitting.
+      WindowEvent::CloseRequested { api, .. } if !window.label().starts_with(PROMPT_WINDOW_PREFIX) => {
+        api.prevent_close();
+        let _ = window.hide();
+      }
+      WindowEvent::Destroyed if window.label().starts_with(PROMPT_WINDOW_PREFIX) => {
+        let state = window.state::<AppState>();
+        state.prompt_windows.lock().unwrap().remove(window.label());
+      }
+      // The quick picker goes away like a popup once focus moves elsewhere.
+      WindowEvent::Focused(false) if window.label() == PICKER_WINDOW => {
+        let _ = window.hide();
+      }
+      _ => {}
+    })
+    .setup(|app| {
+      let app_handle = app.handle();
+      let path_api = app_handle.path();
+      let data_dir = path_api
+        .app_data_dir()
+        .or_else(|_| path_api.app_config_dir())
+        .or_else(|_| path_api.resource_dir())
+        .or_else(|_| std::env::current_dir())?;
+      std::fs::create_dir_all(&data_dir)?;
+      let db_path = data_dir.join("promptlab.db");
+      let storage = Storage::new(db_path)?;
+      let log_path = data_dir.join("promptlab.log");
+      let logging_path = data_dir.join("logging.json");
+      let log_level = log_file::init(&log_path, load_logging_settings(&logging_path).level);
+      let export_dir = data_dir.join("exports");
+      let vocabulary_path = data_dir.join("vocabulary.json");
+      let vocabulary = Arc::new(Mutex::new(load_vocabulary(&vocabulary_path)));
+      let analysis_config_path = data_dir.join("analysis_config.json");
+      let analysis_config = Arc::new(Mutex::new(load_analysis_config(&analysis_config_path)));
+      let analysis_overrides_path = data_dir.join("analysis.toml");
+      let vocabulary_suggestions_path = data_dir.join("vocabulary_suggestions.json");
+      let vocabulary_suggestions = Arc::new(Mutex::new(load_vocabulary_suggestions(&vocabulary_suggestions_path)));
+      let mut llm_settings = LlmSettings::from_env().unwrap_or_else(|error| {
+        warn!(target: "llm", %error, "invalid LLM settings in the environment, using the defaults");
+        LlmSettings::default()
+      });
+      let llm_backend_path = data_dir.join("llm_backend.json");
+      if let Some(backend) = load_llm_backend(&llm_backend_path) {
+        llm_settings = llm_settings.with_backend(backend);
+      }
+      let llm_network_path = data_dir.join("llm_network.json");
+      if let Some(network) = load_llm_network(&llm_network_path) {
+        llm_settings.network = network;
+      }
+      let llm_network = Arc::new(Mutex::new(llm_settings.network.clone()));
+      let llm_limits_path = data_dir.join("llm_limits.json");
+      let llm_limiter = Arc::new(LlmLimiter::new(load_llm_limits(&llm_limits_path)));
+      let llm_models_path = data_dir.join("llm_models.json");
+      let llm_models = Arc::new(Mutex::new(load_llm_models(&llm_models_path)));
+      let llm_generation_path = data_dir.join("llm_generation.json");
+      let llm_generation = Arc::new(Mutex::new(load_llm_generation(&llm_generation_path)));
+      let llm_debug = Arc::new(LlmDebugLog::new(
+        data_dir.join("llm_debug.log"),
+        data_dir.join("llm_debug.json"),
+      ));
+      // Connected with its fallbacks once the state is managed.
+      let llm = Arc::new(Mutex::new(None));
+      let title_settings_path = data_dir.join("title_settings.json");
+      let title_settings = Arc::new(Mutex::new(load_title_settings(&title_settings_path)));
+      let clipboard_settings_path = data_dir.join("clipboard.json");
+      let clipboard_settings = Arc::new(Mutex::new(load_clipboard_settings(&clipboard_settings_path)));
+      let capture_filter_path = data_dir.join("capture_filter.json");
+      let capture_filter = Arc::new(Mutex::new(load_capture_filter(&capture_filter_path)));
+      let capture_apps_path = data_dir.join("capture_apps.json");
+      let capture_apps = Arc::new(Mutex::new(load_capture_apps(&capture_apps_path)));
+      let privacy_filter_path = data_dir.join("privacy_filter.json");
+      let privacy_filter = Arc::new(Mutex::new(load_privacy_filter(&privacy_filter_path)));
+      let hotkey_settings_path = data_dir.join("hotkey.json");
+      let hotkey_settings = load_hotkey_settings(&hotkey_settings_path);
+      let (watcher_commands, watcher_inbox) = tokio::sync::mpsc::unbounded_channel();
+      let ignore_rules_path = data_dir.join("ignore_rules.json");
+      let ignore_rules = Arc::new(Mutex::new(load_ignore_rules(&ignore_rules_path)));
+      let capture_templates_path = data_dir.join("capture_templates.json");
+      let capture_templates = Arc::new(Mutex::new(load_capture_templates(&capture_templates_path)));
+      let classifier_settings_path = data_dir.join("classifier.json");
+      let classifier_settings = Arc::new(Mutex::new(load_classifier_settings(&classifier_settings_path)));
+      let local_llm_path = data_dir.join("local_llm.json");
+      let local_llm_settings = load_local_llm(&local_llm_path);
+      let local_llm_limiter = Arc::new(LlmLimiter::new(LOCAL_LLM_LIMITS));
+      let local_llm_client = connect_local_llm(
+        &local_llm_settings,
+        &local_llm_limiter,
+        &llm_generation,
+        &storage,
+        &llm_debug,
+      );
+      let local_llm_client = Arc::new(Mutex::new(local_llm_client));
+      let llm_fallback_path = data_dir.join("llm_fallback.json");
+      let llm_fallback = load_llm_fallback(&llm_fallback_path).normalized(llm_settings.provider);
+      let llm_jobs_wake = Arc::new(Notify::new());
+      let budget_path = data_dir.join("budget.json");
+      let budget = Arc::new(Mutex::new(load_budget_settings(&budget_path)));
+      let budget_alerts_path = data_dir.join("budget_alerts.json");
+      let budget_alerts = Arc::new(Mutex::new(load_budget_alerts(&budget_alerts_path)));
+      let export_schedule_path = data_dir.join("export_schedule.json");
+      let export_schedule = Arc::new(Mutex::new(load_export_schedule(&export_schedule_path)));
+      let export_schedule_status_path = data_dir.join("export_schedule_status.json");
+      let export_schedule_status = Arc::new(Mutex::new(load_export_schedule_status(&export_schedule_status_path)));
+      let classifier_circuit = Arc::new(Mutex::new(CircuitBreaker::new(
+        CLASSIFIER_CIRCUIT_THRESHOLD,
+        CLASSIFIER_CIRCUIT_COOLDOWN,
+      )));
+      let watcher_status_item =
+        MenuItem::with_id(app, TRAY_WATCHER_STATUS, "剪贴板捕获：启动中", false, None::<&str>)?;
+      let undo_capture_item = MenuItem::with_id(app, TRAY_UNDO_CAPTURE, "撤销上次捕获", false, None::<&str>)?;
+      let tray_menu = Menu::with_items(
+        app,
+        &[
+          &watcher_status_item,
+          &undo_capture_item,
+          &PredefinedMenuItem::separator(app)?,
+          &MenuItem::with_id(app, TRAY_QUIT, "退出 PromptLab", true, None::<&str>)?,
+        ],
+      )?;
+
+      app.manage(AppState {
+        storage,
+        log_path,
+        logging_path,
+        log_level,
+        export_dir,
+        vocabulary_path,
+        vocabulary,
+        analysis_config_path,
+        analysis_config,
+        analysis_overrides_path,
+        analysis_overrides_status: Arc::new(Mutex::new(AnalysisOverridesStatus::default())),
+        vocabulary_suggestions_path,
+        vocabulary_suggestions,
+        llm_settings: Arc::new(Mutex::new(llm_settings)),
+        llm_backend_path,
+        llm,
+        llm_jobs_wake,
+        llm_limits_path,
+        llm_limiter,
+        llm_network_path,
+        llm_network,
+        llm_models_path,
+        llm_models,
+        llm_generation_path,
+        llm_generation,
+        title_settings_path,
+        title_settings,
+        clipboard_settings_path,
+        clipboard_settings,
+        capture_filter_path,
+        capture_filter,
+        capture_apps_path,
+        capture_apps,
+        privacy_filter_path,
+        privacy_filter,
+        privacy_events: Arc::new(Mutex::new(VecDeque::with_capacity(PRIVACY_EVENT_LIMIT))),
+        watcher_commands,
+        watcher_task: Arc::new(Mutex::new(None)),
+        clipboard_events: Arc::new(AtomicBool::new(false)),
+        clipboard_history: Arc::new(Mutex::new(VecDeque::with_capacity(CLIPBOARD_HISTORY_LIMIT))),
+        pending_merge: Arc::new(Mutex::new(None)),
+        capture_lock: Arc::new(Mutex::new(())),
+        hotkey_settings_path,
+        hotkey_settings: Arc::new(Mutex::new(hotkey_settings.clone())),
+        capture_metrics: Arc::new(Mutex::new(CaptureMetrics {
+          since: chrono::Utc::now().to_rfc3339(),
+          ..CaptureMetrics::default()
+        })),
+        watcher_health: Arc::new(Mutex::new(WatcherHealth::default())),
+        watcher_status_item,
+        last_capture: Arc::new(Mutex::new(None)),
+        own_copy: Arc::new(Mutex::new(None)),
+        undo_capture_item,
+        deep_link_prompt: Arc::new(Mutex::new(None)),
+        prompt_windows: Arc::new(Mutex::new(HashMap::new())),
+        ignore_rules_path,
+        ignore_rules,
+        capture_templates_path,
+        capture_templates,
+        classifier_settings_path,
+        classifier_settings,
+        local_llm_path,
+        local_llm: Arc::new(Mutex::new(local_llm_settings)),
+        local_llm_client,
+        local_llm_limiter,
+        llm_fallback_path,
+        llm_fallback: Arc::new(Mutex::new(llm_fallback)),
+        classifier_circuit,
+        llm_debug,
+        budget_path,
+        budget,
+        budget_alerts_path,
+        budget_alerts,
+        export_schedule_path,
+        export_schedule,
+        export_schedule_status_path,
+        export_schedule_status,
+        reanalysis_lock: Arc::new(Mutex::new(())),
+        embedding_backfill_lock: Arc::new(tokio::sync::Mutex::new(())),
+        operations: Operations::default(),
+      });
+      app.state::<AppState>().reconnect_llm();
+      if let Err(error) = register_hotkeys(app_handle, &hotkey_settings) {
+        warn!(target: "hotkey", %error, "hotkeys not registered");
+      }
+
+      let _tray: TrayIcon = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("PromptLab")
+        .menu(&tray_menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app_handle, event| match event.id().as_ref() {
+          TRAY_UNDO_CAPTURE => {
+            let state = app_handle.state::<AppState>();
+            let last = state.last_capture.lock().unwrap().clone();
+            if let Some(id) = last {
+              if let Err(error) = undo_clipboard_capture(app_handle, &state, &id) {
+                error!(target: "clipboard", prompt_id = %id, %error, "undoing the capture failed");
+              }
+            }
+          }
+          TRAY_QUIT => {
+            let app_handle = app_handle.clone();
+            app_handle
+              .dialog()
+              .message("确定要退出 PromptLab 吗？")
+              .title("退出应用")
+              .kind(MessageDialogKind::Warning)
+              .buttons(MessageDialogButtons::OkCancel)
+              .show(move |ok| {
+                if ok {
+                  app_handle.exit(0);
+                }
+              });
+          }
+          _ => {}
+        })
+        .on_tray_icon_event(|tray, event| {
+          if let TrayIconEvent::Click {
+            button: MouseButton::Left,
+            ..
+          } = event
+          {
+            if let Some(window) = tray.app_handle().get_webview_window("main") {
+              let _ = window.show();
+              let _ = window.set_focus();
+            }
+          }
+        })
+        .build(app)?;
+
+      apply_analysis_overrides(&app_handle.state::<AppState>());
+      start_analysis_overrides_watcher(app_handle.clone());
+      start_vocabulary_suggestion_job(app_handle.clone());
+      start_budget_monitor(app_handle.clone());
+      start_llm_workers(app_handle.clone());
+      start_classification_retry_monitor(app_handle.clone());
+      start_clipboard_watcher(app_handle.clone(), watcher_inbox);
+      start_capture_metrics_log(app_handle.clone());
+      start_export_scheduler(app_handle.clone());
+      listen_for_deep_links(app_handle);
+      // Started at login: stay in the tray until the user opens the window.
+      if std::env::args().any(|arg| arg == AUTOSTART_ARG) {
+        if let Some(window) = app.get_webview_window("main") {
+          let _ = window.hide();
+        }
+      }
+
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      summarize_prompt,
+      split_sentences,
+      compare_prompts,
+      save_prompt,
+      list_prompts,
+      search_prompts,
+      list_prompts_by_intent,
+      list_prompts_by_output_language,
+      list_prompts_by_source_app,
+      list_source_apps,
+      get_prompt,
+      update_prompt,
+      delete_prompt,
+      delete_prompts,
+      tag_prompts,
+      move_prompts_to_collection,
+      list_collections,
+      set_language,
+      undo_capture,
+      finalize_capture,
+      get_hotkey_settings,
+      set_hotkey_settings,
+      quick_search,
+      paste_prompt,
+      copy_prompt,
+      hide_quick_picker,
+      open_prompt_window,
+      get_prompt_window_state,
+      take_deep_link_prompt,
+      get_watcher_health,
+      set_watcher_paused,
+      get_capture_metrics,
+      restore_prompt,
+      list_deleted_prompts,
+      list_quarantined,
+      approve,
+      discard,
+      list_attachments,
+      get_attachment_data,
+      import_files,
+      add_attachment,
+      save_attachment_to,
+      delete_attachment,
+      split_prompt_chain,
+      list_prompt_relations,
+      record_analysis,
+      list_analyses,
+      latest_analysis,
+      reanalyze_outdated,
+      export_prompts_csv,
+      get_export_schedule,
+      get_export_schedule_status,
+      configure_export_schedule,
+      run_export_schedule_now,
+      list_vocabulary,
+      list_vocabulary_entries,
+      add_vocabulary_entry,
+      update_vocabulary_entry,
+      remove_vocabulary_entry,
+      set_vocabulary_aliases,
+      vocabulary_stats,
+      suggest_vocabulary_terms,
+      accept_vocabulary_suggestion,
+      reject_vocabulary_suggestion,
+      import_vocabulary,
+      list_suppressed_terms,
+      add_suppressed_term,
+      remove_suppressed_term,
+      list_vocabulary_boosts,
+      list_prompt_categories,
+      set_vocabulary_boost,
+      remove_vocabulary_boost,
+      list_extraction_rules,
+      add_extraction_rule,
+      update_extraction_rule,
+      remove_extraction_rule,
+      get_analysis_config,
+      set_summary_strategy,
+      set_deterministic_analysis_ids,
+      reload_analysis_config,
+      get_llm_provider,
+      get_llm_backend,
+      set_llm_backend,
+      test_llm_connection,
+      set_api_key,
+      clear_api_key,
+      get_llm_rate_limits,
+      set_llm_rate_limits,
+      llm_usage_metrics,
+      get_budget_status,
+      get_dashboard_data,
+      get_budget_settings,
+      set_budget_settings,
+      acknowledge_budget_alert,
+      get_llm_task_models,
+      set_llm_task_models,
+      get_llm_generation,
+      set_llm_generation,
+      get_llm_network,
+      set_llm_network,
+      analyze_with_qwen,
+      score_prompt,
+      list_prompts_by_quality,
+      optimize_prompt,
+      translate_prompt,
+      backfill_embeddings,
+      semantic_search,
+      prompt_template_variables,
+      render_template,
+      run_prompt,
+      list_runs,
+      ab_test,
+      record_ab_preference,
+      get_ab_test,
+      list_ab_tests,
+      classify_prompts_with_qwen,
+      classify_backlog,
+      reapply_classification,
+      list_jobs,
+      cancel_job,
+      cancel_operation,
+      generate_titles,
+      recognize_image_text,
+      list_llm_rejections,
+      get_title_settings,
+      set_title_settings,
+      get_clipboard_settings,
+      set_clipboard_settings,
+      get_autostart,
+      set_autostart,
+      get_capture_filter,
+      set_capture_filter,
+      list_capture_rules,
+      explain_capture,
+      get_capture_apps,
+      set_capture_apps,
+      get_privacy_filter,
+      set_privacy_filter,
+      list_privacy_detectors,
+      list_privacy_events,
+      get_clipboard_history,
+      list_ignore_rules,
+      add_ignore_rule,
+      update_ignore_rule,
+      remove_ignore_rule,
+      reset_ignore_rule_hits,
+      list_capture_templates,
+      add_capture_template,
+      update_capture_template,
+      remove_capture_template,
+      save_from_history,
+      list_inbox,
+      accept_capture,
+      accept_capture_as_version,
+      list_prompt_versions,
+      reject_capture,
+      get_local_llm,
+      set_local_llm,
+      get_llm_fallback,
+      set_llm_fallback,
+      list_local_models,
+      list_models,
+      get_llm_debug_log,
+      set_llm_debug_log,
+      clear_llm_debug_log,
+      read_log,
+      clear_logs,
+      get_log_level,
+      set_log_level,
+      list_classifications,
+      normalize_tags,
+      merge_tags,
+      rename_tag,
+      delete_tag,
+      suggest_merges,
+      list_merge_suggestions,
+      accept_merge_suggestion,
+      reject_merge_suggestion,
+      merge_prompts,
+      get_classifier_settings,
+      set_classifier_settings,
+      default_classifier_prompt,
+      reset_classifier_prompt,
+      get_classifier_circuit,
+      reset_classifier_circuit,
+      label_prompt,
+      calibration_report,
+      #[cfg(debug_assertions)]
+      run_keyword_eval,
+      list_classifier_examples,
+      add_classifier_example,
+      add_classifier_example_from_prompt,
+      update_classifier_example,
+      delete_classifier_example
+    ])
+    .build(tauri::generate_context!())
+    .expect("error while building PromptLab desktop app")
+    .run(|app_handle, event| {
+      if let RunEvent::Exit = event {
+        stop_clipboard_watcher(app_handle);
+      }
+    });
+}
+
+/// Save copied texts that look like prompts, or hold them in the inbox for review when
+/// `ClipboardSettings::inbox` is on. Reads the clipboard when the OS reports a change (see
+/// `clipboard_events`), otherwise every `ClipboardSettings::poll_interval_ms`.
+///
+/// The watch loop runs as an async task controlled through `AppState::watcher_commands`. A
+/// supervisor task restarts it with backoff when it fails or panics, logging why, until the
+/// app exits (see `stop_clipboard_watcher`).
+fn start_clipboard_watcher(app_handle: AppHandle, commands: UnboundedReceiver<WatcherCommand>) {
+  let state = app_handle.state::<AppState>();
+  let changes = state.watcher_commands.clone();
+  let event_driven = match clipboard_events::listen(move || changes.send(WatcherCommand::Changed).is_ok()) {
+    Ok(()) => true,
+    Err(err) => {
+      warn!(target: "clipboard", error = %err, "change notifications unavailable, polling");
+      false
+    }
+  };
+  state.clipboard_events.store(event_driven, Ordering::Relaxed);
+
+  // Shared with each run of the loop, so the channel outlives a run that panics.
+  let commands = Arc::new(tokio::sync::Mutex::new(commands));
+  let supervisor = tauri::async_runtime::spawn({
+    let app_handle = app_handle.clone();
+    async move {
+      let state = app_handle.state::<AppState>();
+      let mut backoff = WATCHER_RESTART_MIN;
+      loop {
+        set_watcher_running(&app_handle, &state, None);
+        let started = Instant::now();
+        let run = tauri::async_runtime::spawn({
+          let app_handle = app_handle.clone();
+          let commands = commands.clone();
+          async move { watch_clipboard(&app_handle, &mut *commands.lock().await, event_driven).await }
+        })
+        .await;
+        let error = match run {
+          Ok(Ok(())) => {
+            state.watcher_health.lock().unwrap().running = false;
+            info!(target: "clipboard", "watcher shut down");
+            return;
+          }
+          Ok(Err(error)) => error,
+          Err(tauri::Error::JoinError(error)) if error.is_panic() => {
+            state.watcher_health.lock().unwrap().panics += 1;
+            format!("clipboard watcher panicked: {}", panic_message(error.into_panic().as_ref()))
+          }
+          Err(error) => format!("clipboard watcher task failed: {error}"),
+        };
+        if started.elapsed() >= WATCHER_STABLE_RUN {
+          backoff = WATCHER_RESTART_MIN;
+        }
+        error!(
+          target: "clipboard",
+          %error,
+          restart_in_secs = backoff.as_secs(),
+          "watcher stopped, restarting"
+        );
+        record_watcher_error(&state, &error);
+        set_watcher_running(&app_handle, &state, Some(backoff));
+        if !wait_for_watcher_restart(&state, &mut *commands.lock().await, backoff).await {
+          state.watcher_health.lock().unwrap().next_restart_at = None;
+          info!(target: "clipboard", "watcher shut down");
+          return;
+        }
+        backoff = (backoff * 2).min(WATCHER_RESTART_MAX);
+        state.watcher_health.lock().unwrap().restarts += 1;
+      }
+    }
+  });
+  *state.watcher_task.lock().unwrap() = Some(supervisor);
+}
+
+/// Sleep until a stopped watcher is due to restart, still taking commands. Returns `false` if
+/// the watcher is to shut down instead.
+async fn wait_for_watcher_restart(
+  state: &AppState,
+  commands: &mut UnboundedReceiver<WatcherCommand>,
+  delay: Duration,
+) -> bool {
+  let restart_at = tokio::time::Instant::now() + delay;
+  loop {
+    match tokio::time::timeout_at(restart_at, commands.recv()).await {
+      Err(_) => return true,
+      Ok(None | Some(WatcherCommand::Shutdown)) => return false,
+      Ok(Some(WatcherCommand::Pause(paused))) => state.watcher_health.lock().unwrap().paused = paused,
+      Ok(Some(_)) => {}
+    }
+  }
+}
+
+/// Ask the clipboard watcher to capture pending merge parts and stop, and wait for it up to
+/// `WATCHER_SHUTDOWN_TIMEOUT`. Called as the app exits.
+fn stop_clipboard_watcher(app_handle: &AppHandle) {
+  let state = app_handle.state::<AppState>();
+  let Some(task) = state.watcher_task.lock().unwrap().take() else {
+    return;
+  };
+  if state.watcher_commands.send(WatcherCommand::Shutdown).is_err() {
+    return;
+  }
+  let stopped = tauri::async_runtime::block_on(tokio::time::timeout(WATCHER_SHUTDOWN_TIMEOUT, task));
+  if stopped.is_err() {
+    warn!(target: "clipboard", "watcher did not stop in time");
+  }
+}
+
+/// Mark the watcher as running, or as stopped until it is restarted after `restart_in`, in
+/// its health, the tray tooltip and menu. Emits `clipboard:watcher-health` with the health.
+fn set_watcher_running(app_handle: &AppHandle, state: &AppState, restart_in: Option<Duration>) {
+  {
+    let mut health = state.watcher_health.lock().unwrap();
+    health.running = restart_in.is_none();
+    health.next_restart_at = restart_in
+      .and_then(|delay| chrono::Duration::from_std(delay).ok())
+      .map(|delay| (chrono::Utc::now() + delay).to_rfc3339());
+  }
+  announce_watcher_health(app_handle, state);
+}
+
+/// Show the watcher's health in the tray tooltip and menu, and emit it with
+/// `clipboard:watcher-health`.
+fn announce_watcher_health(app_handle: &AppHandle, state: &AppState) {
+  let health = state.watcher_health.lock().unwrap().clone();
+  let (tooltip, status) = if !health.running {
+    ("PromptLab：剪贴板捕获已中断，正在重试", "剪贴板捕获：已中断，正在重试")
+  } else if health.paused {
+    ("PromptLab：剪贴板捕获已暂停", "剪贴板捕获：已暂停")
+  } else {
+    ("PromptLab", "剪贴板捕获：运行中")
+  };
+  if let Some(tray) = app_handle.tray_by_id(TRAY_ID) {
+    let _ = tray.set_tooltip(Some(tooltip));
+  }
+  let _ = state.watcher_status_item.set_text(status);
+  let _ = app_handle.emit("clipboard:watcher-health", health);
+}
+
+fn record_watcher_error(state: &AppState, error: &str) {
+  let mut health = state.watcher_health.lock().unwrap();
+  health.last_error = Some(error.to_string());
+  health.last_error_at = Some(chrono::Utc::now().to_rfc3339());
+}
+
+/// The message a panic was raised with, when it is a string.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+  payload
+    .downcast_ref::<&str>()
+    .copied()
+    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+    .unwrap_or("unknown panic")
+}
+
+/// What the clipboard holds.
+enum ClipboardContent {
+  Text(String),
+  Image(arboard::ImageData<'static>),
+  /// Neither text nor an image to capture (e.g. a file list, or images are not captured).
+  Empty,
+}
+
+/// Read the clipboard's text, or its image when it has no text and `images` is set. Blocks, so
+/// the watcher runs it with `spawn_blocking`.
+fn read_clipboard(images: bool) -> Result<ClipboardContent, String> {
+  let mut clipboard = arboard::Clipboard::new().map_err(|err| format!("clipboard init failed: {err}"))?;
+  match clipboard.get_text() {
+    Ok(text) if !text.is_empty() => return Ok(ClipboardContent::Text(text)),
+    // Not text (e.g. an image), or text that cannot be decoded.
+    Ok(_) | Err(arboard::Error::ContentNotAvailable | arboard::Error::ConversionFailure) => {}
+    Err(err) => return Err(format!("clipboard read failed: {err}")),
+  }
+  if images {
+    if let Ok(image) = clipboard.get_image() {
+      return Ok(ClipboardContent::Image(image));
+    }
+  }
+  Ok(ClipboardContent::Empty)
+}
+
+/// The clipboard watch loop. Returns `Ok` once told to shut down, and why it stopped when the
+/// clipboard keeps failing to be read; the supervisor in `start_clipboard_watcher` then
+/// restarts it.
+async fn watch_clipboard(
+  app_handle: &AppHandle,
+  commands: &mut UnboundedReceiver<WatcherCommand>,
+  event_driven: bool,
+) -> Result<(), String> {
+  let state = app_handle.state::<AppState>();
+
+  let mut recent = VecDeque::with_capacity(CLIPBOARD_RECENT_HASHES);
+  let mut burst = BurstTracker::default();
+  let mut read_failures = 0;
+  // A command that arrived while notifications were drained, handled before waiting again.
+  let mut deferred = None;
+  loop {
+    let paused = state.watcher_health.lock().unwrap().paused;
+    let wait = if event_driven {
+      CLIPBOARD_EVENT_SAFETY_POLL
+    } else {
+      Duration::from_millis(state.clipboard_settings.lock().unwrap().poll_interval_ms)
+    };
+    let wait = match (paused, pending_merge_left(&state)) {
+      (_, Some(left)) => Some(left.min(wait)),
+      (true, None) => None,
+      (false, None) => Some(wait),
+    };
+    let command = match (deferred.take(), wait) {
+      (Some(command), _) => Some(Some(command)),
+      (None, Some(wait)) => tokio::time::timeout(wait, commands.recv()).await.ok(),
+      (None, None) => Some(commands.recv().await),
+    };
+    match command {
+      // Timed out: poll.
+      None => {}
+      Some(None | Some(WatcherCommand::Shutdown)) => {
+        if pending_merge_left(&state).is_some() {
+          if let Err(err) = finalize_merge(app_handle, &state) {
+            error!(target: "clipboard", error = %err, "merge save failed");
+          }
+        }
+        return Ok(());
+      }
+      Some(Some(WatcherCommand::Pause(paused))) => {
+        state.watcher_health.lock().unwrap().paused = paused;
+        announce_watcher_health(app_handle, &state);
+        continue;
+      }
+      Some(Some(WatcherCommand::Changed | WatcherCommand::Reconfigure)) => {
+        tokio::time::sleep(CLIPBOARD_SETTLE).await;
+        // Later notifications and settings changes are covered by this read.
+        while let Ok(command) = commands.try_recv() {
+          if matches!(command, WatcherCommand::Pause(_) | WatcherCommand::Shutdown) {
+            deferred = Some(command);
+            break;
+          }
+        }
+      }
+    }
+    if pending_merge_left(&state) == Some(Duration::ZERO) {
+      if let Err(err) = finalize_merge(app_handle, &state) {
+        error!(target: "clipboard", error = %err, "merge save failed");
+      }
+    }
+    if state.watcher_health.lock().unwrap().paused {
+      continue;
+    }
+    let images = state.clipboard_settings.lock().unwrap().images;
+    let read = tauri::async_runtime::spawn_blocking(move || read_clipboard(images))
+      .await
+      .unwrap_or_else(|error| Err(format!("clipboard read failed: {error}")));
+    state.watcher_health.lock().unwrap().last_poll_at = Some(chrono::Utc::now().to_rfc3339());
+    count_capture(&state, |metrics| metrics.polls += 1);
+    let text = match read {
+      Ok(ClipboardContent::Text(text)) => {
+        read_failures = 0;
+        text
+      }
+      Ok(ClipboardContent::Image(image)) => {
+        read_failures = 0;
+        if let Some(prompt) = capture_clipboard_image(&state, &image, &mut recent) {
+          announce_capture(app_handle, &state, &prompt);
+        }
+        continue;
+      }
+      Ok(ClipboardContent::Empty) => {
+        read_failures = 0;
+        continue;
+      }
+      Err(err) => {
+        read_failures += 1;
+        state.watcher_health.lock().unwrap().read_errors += 1;
+        record_watcher_error(&state, &err);
+        if read_failures >= WATCHER_MAX_READ_FAILURES {
+          return Err(format!("{read_failures} clipboard reads in a row failed: {err}"));
+        }
+        continue;
+      }
+    };
+    let candidate = text.trim();
+    let hash = content_hash(candidate);
+    if seen_recently(&mut recent, hash.clone()) || is_own_copy(&state, &hash) {
+      continue;
+    }
+    count_capture(&state, |metrics| metrics.candidates += 1);
+    let source_app = match allowed_source_app(&state) {
+      Ok(app) => app,
+      Err(app) => {
+        count_capture(&state, |metrics| metrics.app_skipped += 1);
+        info!(target: "clipboard", %app, "text skipped: the app is not on the allowlist");
+        continue;
+      }
+    };
+    // Screened first, so blocked texts are not even kept in the history.
+    let Some(candidate) = screen_privacy(app_handle, &state, candidate, "clipboard") else {
+      count_capture(&state, |metrics| metrics.privacy_blocked += 1);
+      continue;
+    };
+    let candidate = candidate.as_str();
+    // Ignored texts (e.g. passwords) are not kept in the history either.
+    if is_ignored_capture(&state, candidate) {
+      count_capture(&state, |metrics| metrics.ignored += 1);
+      continue;
+    }
+    let decision = evaluate_capture(candidate, &state.capture_filter.lock().unwrap());
+    remember_clipboard_text(&state.clipboard_history, candidate, &decision, source_app.clone());
+    if state.clipboard_settings.lock().unwrap().merge {
+      append_merge_part(app_handle, &state, candidate, source_app);
+      continue;
+    }
+    if !decision.accepted {
+      if let Some(rule) = decision.rule {
+        count_capture(&state, |metrics| *metrics.rejections.entry(rule).or_default() += 1);
+      }
+      let rule = decision.rule.map_or("-", CaptureRule::as_str);
+      info!(target: "clipboard", rule, reason = %decision.reason, "text skipped");
+      continue;
+    }
+    let templated = apply_capture_template(&state, candidate, source_app.as_ref());
+    let candidate = templated.as_deref().unwrap_or(candidate);
+
+    // The guard protects no data, so a panic that poisoned it must not stop later captures.
+    let _capture = state.capture_lock.lock().unwrap_or_else(PoisonError::into_inner);
+    match state.storage.find_prompt_by_content(candidate) {
+      Ok(Some(_)) => {
+        count_capture(&state, |metrics| metrics.duplicates += 1);
+        continue;
+      }
+      Ok(None) => {}
+      Err(err) => {
+        error!(target: "clipboard", error = %err, "duplicate lookup failed");
+        continue;
+      }
+    }
+
+    // Near-duplicates of a prompt are held for review, to be kept as a new version of it.
+    let similar = similar_prompt(&state, candidate);
+    let settings = *state.clipboard_settings.lock().unwrap();
+    let burst_state = if settings.inbox || similar.is_some() {
+      BurstState::Calm
+    } else {
+      let window = Duration::from_secs(settings.burst_window_secs);
+      burst.record(Instant::now(), settings.burst_limit as usize, window)
+    };
+    if burst_state == BurstState::Started {
+      announce_burst(app_handle, &settings);
+    }
+    let overflow = (burst_state != BurstState::Calm).then_some(settings.burst_overflow);
+    if overflow == Some(BurstOverflow::Skip) {
+      count_capture(&state, |metrics| metrics.burst_skipped += 1);
+      info!(target: "clipboard", "text skipped during a copy burst");
+      continue;
+    }
+
+    if settings.inbox || similar.is_some() || overflow == Some(BurstOverflow::Inbox) {
+      // Texts waiting in the inbox or already rejected are not captured again.
+      match state.storage.find_capture_by_text(candidate) {
+        Ok(Some(capture)) if capture.status != CaptureStatus::Accepted => {
+          count_capture(&state, |metrics| metrics.duplicates += 1);
+          continue;
+        }
+        Ok(_) => {}
+        Err(err) => {
+          error!(target: "clipboard", error = %err, "inbox lookup failed");
+          continue;
+        }
+      }
+      let similar_to = similar.as_ref().map(|(id, similarity)| (id.as_str(), *similarity));
+      match state.storage.create_capture(candidate, "clipboard", similar_to) {
+        Ok(capture) => {
+          count_capture(&state, |metrics| metrics.held += 1);
+          info!(
+            target: "clipboard",
+            capture_id = %capture.id,
+            version_of = similar_to.map(|(id, _)| id),
+            similarity = similar_to.map(|(_, similarity)| similarity),
+            "text held for review"
+          );
+          let _ = app_handle.emit("inbox:captured", &capture.id);
+        }
+        Err(err) => {
+          error!(target: "clipboard", error = %err, "capture save failed");
+        }
+      }
+      continue;
+    }
+
+    match save_clipboard_prompt(&state, candidate, "clipboard", source_app.as_ref()) {
+      Ok(prompt) => {
+        count_capture(&state, |metrics| metrics.saved += 1);
+        announce_capture(app_handle, &state, &prompt);
+      }
+      Err(err) => {
+        error!(target: "clipboard", error = %err, "prompt save failed");
+      }
+    }
+  }
+}
+
+fn count_capture(state: &AppState, update: impl FnOnce(&mut CaptureMetrics)) {
+  update(&mut state.capture_metrics.lock().unwrap());
+}
+
+/// Summarize the capture metrics in the log every `CAPTURE_METRICS_LOG_INTERVAL` in which
+/// new texts were read or classified.
+fn start_capture_metrics_log(app_handle: AppHandle) {
+  thread::spawn(move || {
+    let mut logged = (0, 0);
+    loop {
+      thread::sleep(CAPTURE_METRICS_LOG_INTERVAL);
+      let state = app_handle.state::<AppState>();
+      let metrics = state.capture_metrics.lock().unwrap().clone();
+      if (metrics.candidates, metrics.llm_classified) == logged {
+        continue;
+      }
+      logged = (metrics.candidates, metrics.llm_classified);
+      let rejections: Vec<String> = metrics
+        .rejections
+        .iter()
+        .map(|(rule, count)| format!("{}={count}", rule.as_str()))
+        .collect();
+      info!(
+        target: "clipboard",
+        since = %metrics.since,
+        polls = metrics.polls,
+        candidates = metrics.candidates,
+        saved = metrics.saved,
+        held = metrics.held,
+        duplicates = metrics.duplicates,
+        rejected = %rejections.join(","),
+        ignored = metrics.ignored,
+        privacy_blocked = metrics.privacy_blocked,
+        app_skipped = metrics.app_skipped,
+        burst_skipped = metrics.burst_skipped,
+        images = metrics.images_saved,
+        llm_classified = metrics.llm_classified,
+        llm_not_prompt = metrics.llm_not_prompt,
+        "capture metrics"
+      );
+    }
+  });
+}
+
+/// Time left until the pending merge is captured: zero once its window has passed or merge
+/// mode was turned off, `None` when nothing is pending.
+fn pending_merge_left(state: &AppState) -> Option<Duration> {
+  let settings = *state.clipboard_settings.lock().unwrap();
+  let pending = state.pending_merge.lock().unwrap();
+  let pending = pending.as_ref()?;
+  if !settings.merge {
+    return Some(Duration::ZERO);
+  }
+  Some(Duration::from_secs(settings.merge_window_secs).saturating_sub(pending.last_part.elapsed()))
+}
+
+/// Add a copied text to the pending merge, starting one if needed, and emit
+/// `clipboard:merge-pending` with the part count.
+fn append_merge_part(app_handle: &AppHandle, state: &AppState, text: &str, source_app: Option<foreground::SourceApp>) {
+  let mut pending = state.pending_merge.lock().unwrap();
+  let merge = pending.get_or_insert_with(|| PendingMerge {
+    parts: Vec::new(),
+    source_app,
+    started_at: chrono::Utc::now().to_rfc3339(),
+    last_part: Instant::now(),
+  });
+  merge.parts.push(text.to_string());
+  merge.last_part = Instant::now();
+  debug!(target: "clipboard", parts = merge.parts.len(), "merge part added");
+  let _ = app_handle.emit(
+    "clipboard:merge-pending",
+    json!({
+      "parts": merge.parts.len(),
+      "chars": merge.parts.iter().map(|part| part.chars().count()).sum::<usize>(),
+      "startedAt": merge.started_at,
+    }),
+  );
+}
+
+/// Join the pending merge's parts with line breaks and capture the text like a single copy:
+/// held in the inbox when that is on or the text nearly repeats a prompt, otherwise saved as
+/// a prompt. Emits
+/// `clipboard:merge-pending` with `null` once the parts are taken.
+fn finalize_merge(app_handle: &AppHandle, state: &AppState) -> Result<Option<Prompt>, String> {
+  let Some(pending) = state.pending_merge.lock().unwrap().take() else {
+    return Ok(None);
+  };
+  let _ = app_handle.emit("clipboard:merge-pending", Value::Null);
+  let text = pending.parts.join("\n");
+  let text = apply_capture_template(state, &text, pending.source_app.as_ref()).unwrap_or(text);
+  let _capture = state.capture_lock.lock().unwrap_or_else(PoisonError::into_inner);
+  if state
+    .storage
+    .find_prompt_by_content(&text)
+    .map_err(|error| error.to_string())?
+    .is_some()
+  {
+    count_capture(state, |metrics| metrics.duplicates += 1);
+    return Ok(None);
+  }
+  let similar = similar_prompt(state, &text);
+  if state.clipboard_settings.lock().unwrap().inbox || similar.is_some() {
+    let similar_to = similar.as_ref().map(|(id, similarity)| (id.as_str(), *similarity));
+    let capture = state
+      .storage
+      .create_capture(&text, "clipboard", similar_to)
+      .map_err(|error| error.to_string())?;
+    info!(target: "clipboard", parts = pending.parts.len(), capture_id = %capture.id, "merged parts held for review");
+    count_capture(state, |metrics| metrics.held += 1);
+    let _ = app_handle.emit("inbox:captured", &capture.id);
+    return Ok(None);
+  }
+  let prompt = save_clipboard_prompt(state, &text, "clipboard", pending.source_app.as_ref())
+    .map_err(|error| error.to_string())?;
+  info!(target: "clipboard", parts = pending.parts.len(), prompt_id = %prompt.id, "merged parts saved");
+  count_capture(state, |metrics| metrics.saved += 1);
+  announce_capture(app_handle, state, &prompt);
+  Ok(Some(prompt))
+}
+
+/// Tell the user about a prompt the watcher just saved: a desktop notification unless
+/// switched off, on top of [`remember_capture`].
+fn announce_capture(app_handle: &AppHandle, state: &AppState, prompt: &Prompt) {
+  remember_capture(app_handle, state, prompt);
+  if !state.clipboard_settings.lock().unwrap().notifications {
+    return;
+  }
+  let shown = app_handle
+    .notification()
+    .builder()
+    .title("已保存提示词")
+    .body(format!("{}\n可在托盘菜单中撤销", prompt.title))
+    .show();
+  if let Err(err) = shown {
+    warn!(target: "clipboard", error = %err, "capture notification failed");
+  }
+}
+
+/// Log, emit `clipboard:burst-started` and, unless notifications are off, tell the user that
+/// captures are being held in the inbox or skipped until the copying slows down.
+fn announce_burst(app_handle: &AppHandle, settings: &ClipboardSettings) {
+  warn!(
+    target: "clipboard",
+    limit = settings.burst_limit,
+    window_secs = settings.burst_window_secs,
+    overflow = ?settings.burst_overflow,
+    "copy burst, captures throttled"
+  );
+  let _ = app_handle.emit(
+    "clipboard:burst-started",
+    json!({
+      "limit": settings.burst_limit,
+      "windowSecs": settings.burst_window_secs,
+      "overflow": settings.burst_overflow,
+    }),
+  );
+  if !settings.notifications {
+    return;
+  }
+  let body = match settings.burst_overflow {
+    BurstOverflow::Inbox => "复制过于频繁，之后的捕获先放入收件箱等待确认",
+    BurstOverflow::Skip => "复制过于频繁，暂停保存，可在剪贴板历史中找回",
+  };
+  let shown = app_handle.notification().builder().title("剪贴板捕获已限流").body(body).show();
+  if let Err(err) = shown {
+    warn!(target: "clipboard", error = %err, "burst notification failed");
+  }
+}
+
+/// Make `prompt` the capture the tray menu's undo entry removes, and emit `clipboard:captured`
+/// so an open window can highlight it.
+fn remember_capture(app_handle: &AppHandle, state: &AppState, prompt: &Prompt) {
+  *state.last_capture.lock().unwrap() = Some(prompt.id.clone());
+  let label: String = prompt.title.chars().take(TRAY_TITLE_CHARS).collect();
+  let _ = state.undo_capture_item.set_text(format!("撤销捕获：{label}"));
+  let _ = state.undo_capture_item.set_enabled(true);
+  let _ = app_handle.emit("clipboard:captured", json!({ "promptId": prompt.id, "title": prompt.title }));
+}
+
+/// The capture hotkey: copy the focused app's selection and save it as a prompt at once,
+/// without waiting for the clipboard watcher and regardless of the capture rules and the
+/// inbox, since the user picked it. A small dialog confirms the capture; choosing "撤销" moves
+/// the prompt to the trash again, while closing it keeps the prompt.
+fn capture_selection(app_handle: &AppHandle) {
+  let state = app_handle.state::<AppState>();
+  // Held from before the copy so the watcher, woken by it, finds the saved prompt.
+  let capture = state.capture_lock.lock().unwrap_or_else(PoisonError::into_inner);
+  let source_app = capture_source_app(&state);
+  let text = match read_selection() {
+    Ok(text) => text,
+    Err(err) => {
+      error!(target: "hotkey", error = %err, "clipboard read failed");
+      String::new()
+    }
+  };
+  let text = text.trim();
+  let dialog = |message: String| {
+    app_handle
+      .dialog()
+      .message(message)
+      .title("捕获选中文本")
+      .kind(MessageDialogKind::Info)
+  };
+  if text.is_empty() {
+    dialog("没有读取到文本，请先选中要捕获的文字。".into()).show(|_| {});
+    return;
+  }
+  let Some(text) = screen_privacy(app_handle, &state, text, "hotkey") else {
+    dialog("选中的文本包含密钥、密码或证件号等敏感信息，未保存。".into()).show(|_| {});
+    return;
+  };
+  let text = text.as_str();
+  let prompt = match state.storage.find_prompt_by_content(text) {
+    Ok(Some(existing)) => {
+      dialog(format!("已存在相同的提示词：{}", existing.title)).show(|_| {});
+      return;
+    }
+    Ok(None) => save_clipboard_prompt(&state, text, "hotkey", source_app.as_ref()),
+    Err(err) => Err(err),
+  };
+  drop(capture);
+  let prompt = match prompt {
+    Ok(prompt) => prompt,
+    Err(err) => {
+      error!(target: "hotkey", error = %err, "hotkey capture failed");
+      dialog(format!("保存失败：{err}")).kind(MessageDialogKind::Error).show(|_| {});
+      return;
+    }
+  };
+  remember_capture(app_handle, &state, &prompt);
+
+  let mut preview: String = prompt.body.chars().take(HOTKEY_PREVIEW_CHARS).collect();
+  if preview.len() < prompt.body.len() {
+    preview.push('…');
+  }
+  let app_handle = app_handle.clone();
+  dialog(format!("{}\n\n{preview}", prompt.title))
+    .title("已捕获选中文本")
+    .buttons(MessageDialogButtons::OkCancelCustom("撤销".into(), "保留".into()))
+    .show(move |undo| {
+      if !undo {
+        return;
+      }
+      let state = app_handle.state::<AppState>();
+      if let Err(error) = undo_clipboard_capture(&app_handle, &state, &prompt.id) {
+        error!(target: "hotkey", prompt_id = %prompt.id, %error, "undoing the capture failed");
+      }
+    });
+}
+
+/// Register the `promptlab://` scheme with the system (Windows and Linux; macOS reads it
+/// from the bundle) and follow the links that launched the app or arrive while it runs.
+fn listen_for_deep_links(app_handle: &AppHandle) {
+  #[cfg(any(windows, target_os = "linux"))]
+  if let Err(err) = app_handle.deep_link().register_all() {
+    warn!(target: "deep_link", error = %err, "scheme registration failed");
+  }
+  let links = app_handle.clone();
+  app_handle.deep_link().on_open_url(move |event| {
+    let app_handle = links.clone();
+    let urls = event.urls();
+    thread::spawn(move || urls.iter().for_each(|url| follow_deep_link(&app_handle, url)));
+  });
+  match app_handle.deep_link().get_current() {
+    Ok(Some(urls)) => urls.iter().for_each(|url| follow_deep_link(app_handle, url)),
+    Ok(None) => {}
+    Err(err) => warn!(target: "deep_link", error = %err, "startup link unreadable"),
+  }
+}
+
+/// Follow a `promptlab://` link: `promptlab://prompt/{id}` shows the main window on that
+/// prompt and `promptlab://capture?text=...` saves the text as the capture hotkey does (the
+/// privacy filter still applies) and then shows the new prompt, or the saved one with the
+/// same text. The main window gets the prompt id as `deep-link:prompt`. Failures are shown
+/// in a dialog. Only the scheme and host are logged: the query may carry captured text.
+fn follow_deep_link(app_handle: &AppHandle, url: &tauri::Url) {
+  let state = app_handle.state::<AppState>();
+  let host = url.host_str().unwrap_or_default();
+  let query_len = url.query().map_or(0, str::len);
+  let target = match (url.scheme(), url.host_str()) {
+    (DEEP_LINK_SCHEME, Some("prompt")) => {
+      let id = url.path().trim_matches('/');
+      match state.storage.get_prompt(id) {
+        Ok(Some(prompt)) => Ok(prompt.id),
+        Ok(None) => Err("链接指向的提示词不存在".to_string()),
+        Err(err) => Err(err.to_string()),
+      }
+    }
+    (DEEP_LINK_SCHEME, Some("capture")) => capture_from_link(app_handle, &state, url),
+    _ => Err("无法识别的链接".to_string()),
+  };
+  let prompt_id = match target {
+    Ok(prompt_id) => prompt_id,
+    Err(message) => {
+      warn!(
+        target: "deep_link",
+        scheme = url.scheme(),
+        host,
+        query_len,
+        error = %message,
+        "link not followed"
+      );
+      app_handle
+        .dialog()
+        .message(message)
+        .title("打开链接")
+        .kind(MessageDialogKind::Warning)
+        .show(|_| {});
+      return;
+    }
+  };
+  info!(
+    target: "deep_link",
+    scheme = url.scheme(),
+    host,
+    query_len,
+    prompt_id = %prompt_id,
+    "link followed"
+  );
+  *state.deep_link_prompt.lock().unwrap() = Some(prompt_id.clone());
+  if let Some(window) = app_handle.get_webview_window("main") {
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+  }
+  let _ = app_handle.emit_to("main", "deep-link:prompt", &prompt_id);
+}
+
+/// Save the `text` of a `promptlab://capture` link, or find the prompt that already has it.
+fn capture_from_link(app_handle: &AppHandle, state: &AppState, url: &tauri::Url) -> Result<String, String> {
+  let text = url
+    .query_pairs()
+    .find(|(key, _)| key == "text")
+    .map(|(_, text)| text.trim().to_string())
+    .filter(|text| !text.is_empty())
+    .ok_or_else(|| "链接缺少要保存的文本（text 参数）".to_string())?;
+  let Some(text) = screen_privacy(app_handle, state, &text, "deep_link") else {
+    return Err("链接中的文本包含密钥、密码或证件号等敏感信息，未保存".into());
+  };
+  let _capture = state.capture_lock.lock().unwrap_or_else(PoisonError::into_inner);
+  match state.storage.find_prompt_by_content(&text) {
+    Ok(Some(existing)) => return Ok(existing.id),
+    Ok(None) => {}
+    Err(err) => return Err(err.to_string()),
+  }
+  let prompt = save_clipboard_prompt(state, &text, "deep_link", None).map_err(|err| {
+    error!(target: "deep_link", error = %err, "link capture failed");
+    err.to_string()
+  })?;
+  remember_capture(app_handle, state, &prompt);
+  Ok(prompt.id)
+}
+
+/// Copy the selection and return the clipboard text once it changes. Falls back to what is
+/// already on the clipboard when the copy cannot be sent or nothing new arrives in time, so
+/// copying by hand and then pressing the hotkey works everywhere.
+fn read_selection() -> Result<String, String> {
+  let mut clipboard = arboard::Clipboard::new().map_err(|error| error.to_string())?;
+  let before = clipboard.get_text().ok();
+  match selection::copy_selection() {
+    Ok(()) => {
+      let deadline = Instant::now() + SELECTION_COPY_TIMEOUT;
+      while Instant::now() < deadline {
+        thread::sleep(SELECTION_COPY_POLL);
+        match clipboard.get_text() {
+          Ok(text) if before.as_ref() != Some(&text) => return Ok(text),
+          _ => {}
+        }
+      }
+    }
+    Err(err) => {
+      warn!(target: "hotkey", error = %err, "copy unavailable, using the clipboard");
+    }
+  }
+  clipboard.get_text().map_err(|error| error.to_string())
+}
+
+/// Register the capture and quick picker hotkeys from `settings` in place of any earlier ones.
+fn register_hotkeys(app_handle: &AppHandle, settings: &HotkeySettings) -> Result<(), String> {
+  let shortcuts = app_handle.global_shortcut();
+  shortcuts.unregister_all().map_err(|error| error.to_string())?;
+  for (enabled, accelerator) in [
+    (settings.enabled, &settings.shortcut),
+    (settings.picker_enabled, &settings.picker_shortcut),
+  ] {
+    if !enabled {
+      continue;
+    }
+    shortcuts
+      .register(parse_hotkey(accelerator)?)
+      .map_err(|error| format!("无法注册快捷键 {accelerator}（可能已被占用）: {error}"))?;
+  }
+  Ok(())
+}
+
+fn parse_hotkey(accelerator: &str) -> Result<Shortcut, String> {
+  accelerator
+    .parse()
+    .map_err(|error| format!("无效的快捷键 {accelerator}: {error}"))
+}
+
+/// Whether `shortcut` is the quick picker's hotkey rather than the capture hotkey.
+fn is_picker_hotkey(app_handle: &AppHandle, shortcut: &Shortcut) -> bool {
+  let settings = app_handle.state::<AppState>().hotkey_settings.lock().unwrap().clone();
+  settings.picker_enabled && parse_hotkey(&settings.picker_shortcut).is_ok_and(|picker| picker == *shortcut)
+}
+
+/// Show the quick picker, creating its window on first use, and emit `picker:opened` so it
+/// starts a new search.
+fn show_quick_picker(app_handle: &AppHandle) {
+  let window = match app_handle.get_webview_window(PICKER_WINDOW) {
+    Some(window) => window,
+    None => {
+      let built = WebviewWindowBuilder::new(app_handle, PICKER_WINDOW, WebviewUrl::default())
+        .title("PromptLab 快速选择")
+        .inner_size(640.0, 420.0)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .visible(false)
+        .build();
+      match built {
+        Ok(window) => window,
+        Err(error) => {
+          error!(target: "picker", %error, "opening the quick picker failed");
+          return;
+        }
+      }
+    }
+  };
+  let _ = window.center();
+  let _ = window.show();
+  let _ = window.set_focus();
+  let _ = app_handle.emit("picker:opened", ());
+}
+
+/// Move a captured prompt to the trash and, if it was the last capture, disable the tray's
+/// undo entry. Emits `clipboard:capture-undone` with the prompt id.
+fn undo_clipboard_capture(app_handle: &AppHandle, state: &AppState, id: &str) -> Result<bool, String> {
+  let deleted = state.storage.soft_delete_prompt(id).map_err(|error| error.to_string())?;
+  let was_last = {
+    let mut last = state.last_capture.lock().unwrap();
+    last.as_deref() == Some(id) && last.take().is_some()
+  };
+  // Menu updates wait for the main thread, so the lock is released first.
+  if was_last {
+    let _ = state.undo_capture_item.set_text("撤销上次捕获");
+    let _ = state.undo_capture_item.set_enabled(false);
+  }
+  if deleted {
+    info!(target: "clipboard", prompt_id = %id, "capture undone");
+    let _ = app_handle.emit("clipboard:capture-undone", id);
+  }
+  Ok(deleted)
+}
+
+/// Whether `hash` is among the recently copied texts; records it as the most recent either
+/// way. Keeps the watcher from re-reading what is still on the clipboard, or what was copied
+/// again with different whitespace or quotes.
+fn seen_recently(recent: &mut VecDeque<String>, hash: String) -> bool {
+  let seen = match recent.iter().position(|item| *item == hash) {
+    Some(position) => {
+      recent.remove(position);
+      true
+    }
+    None => false,
+  };
+  recent.truncate(CLIPBOARD_RECENT_HASHES - 1);
+  recent.push_front(hash);
+  seen
+}
+
+/// Whether `hash` is the prompt text the app put on the clipboard last (see
+/// `copy_prompt_to_clipboard`), which is then forgotten, so copying the text again later is
+/// handled as usual.
+fn is_own_copy(state: &AppState, hash: &str) -> bool {
+  let mut own_copy = state.own_copy.lock().unwrap();
+  let own = own_copy.as_deref() == Some(hash);
+  if own {
+    *own_copy = None;
+  }
+  own
+}
+
+/// Save a newly copied image unless it is still on the clipboard or already saved. Returns
+/// the saved prompt.
+fn capture_clipboard_image(
+  state: &AppState,
+  image: &arboard::ImageData,
+  recent: &mut VecDeque<String>,
+) -> Option<Prompt> {
+  let hash = image_hash(image);
+  if seen_recently(recent, hash.clone()) {
+    return None;
+  }
+  match state.storage.find_prompt_by_image_hash(&hash) {
+    Ok(Some(_)) => return None,
+    Ok(None) => {}
+    Err(err) => {
+      error!(target: "clipboard", error = %err, "image lookup failed");
+      return None;
+    }
+  }
+  let source_app = match allowed_source_app(state) {
+    Ok(app) => app,
+    Err(app) => {
+      count_capture(state, |metrics| metrics.app_skipped += 1);
+      info!(target: "clipboard", %app, "image skipped: the app is not on the allowlist");
+      return None;
+    }
+  };
+  match save_clipboard_image(state, image, &hash, source_app.as_ref()) {
+    Ok(prompt) => {
+      count_capture(state, |metrics| metrics.images_saved += 1);
+      info!(target: "clipboard", prompt_id = %prompt.id, "image saved");
+      if state.clipboard_settings.lock().unwrap().ocr {
+        match enqueue_ocr(&state.storage, &prompt.id) {
+          Ok(_) => state.llm_jobs_wake.notify_one(),
+          Err(err) => {
+            error!(target: "clipboard", prompt_id = %prompt.id, error = %err, "ocr enqueue failed");
+          }
+        }
       }
+      Some(prompt)
+    }
+    Err(err) => {
+      error!(target: "clipboard", error = %err, "image save failed");
+      None
+    }
+  }
+}
+
+/// FNV-1a over an image's size and RGBA pixels, 16 hex digits like `content_hash`.
+fn image_hash(image: &arboard::ImageData) -> String {
+  let size = [image.width as u64, image.height as u64];
+  let hash = size
+    .iter()
+    .flat_map(|side| side.to_le_bytes())
+    .chain(image.bytes.iter().copied())
+    .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+      (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+  format!("{hash:016x}")
+}
+
+/// Save a copied image as an image capture prompt: an empty body, the image's size and hash
+/// in the metadata and the picture attached as PNG. Images skip the inbox and the
+/// classifiers, which only read text, until OCR fills the body.
+fn save_clipboard_image(
+  state: &AppState,
+  image: &arboard::ImageData,
+  hash: &str,
+  source_app: Option<&foreground::SourceApp>,
+) -> Result<Prompt, String> {
+  let (width, height) = (image.width as u32, image.height as u32);
+  let pixels = image::RgbaImage::from_raw(width, height, image.bytes.to_vec())
+    .ok_or_else(|| format!("clipboard image data does not match its size {width}x{height}"))?;
+  let mut png = Vec::new();
+  pixels
+    .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+    .map_err(|error| error.to_string())?;
+
+  let captured_at = Local::now();
+  let mut metadata = json!({
+    "source": "clipboard",
+    "kind": IMAGE_CAPTURE_KIND,
+    "image_hash": hash,
+    "width": width,
+    "height": height,
+    "format": "png",
+    "size": png.len(),
+  });
+  if let Some(app) = source_app {
+    metadata["source_app"] = json!(app);
+  }
+  let prompt = state
+    .storage
+    .create_prompt(NewPrompt {
+      title: format!("图片捕获 {}", captured_at.format("%Y-%m-%d %H:%M")),
+      body: String::new(),
+      language: None,
+      model_hint: None,
+      metadata,
     })
-    .setup(|app| {
-      let app_handle = app.handle();
-      let path_api = app_handle.path();
-      let data_dir = path_api
-        .app_data_dir()
-        .or_else(|_| path_api.app_config_dir())
-        .or_else(|_| path_api.resource_dir())
-        .or_else(|_| std::env::current_dir())?;
-      std::fs::create_dir_all(&data_dir)?;
-      let db_path = data_dir.join("promptlab.db");
-      let storage = Storage::new(db_path)?;
-      let log_path = data_dir.join("promptlab.log");
-      let export_dir = data_dir.join("exports");
-      let vocabulary_path = data_dir.join("vocabulary.json");
-      let vocabulary = Arc::new(Mutex::new(load_vocabulary(&vocabulary_path)));
+    .map_err(|error| error.to_string())?;
+  let attachment = NewAttachment {
+    prompt_id: prompt.id.clone(),
+    filename: format!("clipboard-{}.png", captured_at.format("%Y%m%d-%H%M%S")),
+    bytes: png,
+  };
+  if let Err(err) = state.storage.add_attachment(attachment) {
+    // A capture without its picture is useless; do not leave it behind.
+    let _ = state.storage.delete_prompt(&prompt.id);
+    return Err(err.to_string());
+  }
+  Ok(prompt)
+}
+
+/// The existing prompt a captured `text` nearly repeats and their similarity, when holding
+/// such captures as possible new versions is on.
+fn similar_prompt(state: &AppState, text: &str) -> Option<(String, f64)> {
+  let settings = *state.clipboard_settings.lock().unwrap();
+  if !settings.versions {
+    return None;
+  }
+  let prompts = match state.storage.list_prompts() {
+    Ok(prompts) => prompts,
+    Err(err) => {
+      error!(target: "clipboard", error = %err, "similar prompt lookup failed");
+      return None;
+    }
+  };
+  let options = DuplicateOptions {
+    min_similarity: settings.version_similarity,
+    ..DuplicateOptions::default()
+  };
+  let candidates = prompts.iter().map(|prompt| (prompt.id.as_str(), prompt.body.as_str()));
+  closest_match(text, candidates, &options).map(|(id, similarity)| (id.to_string(), similarity))
+}
+
+/// Run the privacy filter over a copied `text`. Returns the text to capture, masked when the
+/// filter redacts, or `None` when it blocks. Each hit is logged and emitted as
+/// `capture:privacy-filtered` with the kinds found, never the text.
+fn screen_privacy(app_handle: &AppHandle, state: &AppState, text: &str, source: &'static str) -> Option<String> {
+  let verdict = screen_capture(text, &state.privacy_filter.lock().unwrap());
+  let (kinds, action, kept) = match verdict {
+    PrivacyVerdict::Clean => return Some(text.to_string()),
+    PrivacyVerdict::Blocked(kinds) => (kinds, PrivacyAction::Block, None),
+    PrivacyVerdict::Redacted(kinds, redacted) => (kinds, PrivacyAction::Redact, Some(redacted)),
+  };
+  let names: Vec<&str> = kinds.iter().map(|kind| kind.as_str()).collect();
+  info!(
+    target: "capture",
+    source,
+    action = if kept.is_some() { "redacted" } else { "blocked" },
+    kinds = %names.join(","),
+    "text screened by the privacy filter"
+  );
+  let event = PrivacyEvent {
+    at: chrono::Utc::now().to_rfc3339(),
+    source,
+    kinds,
+    action,
+  };
+  let _ = app_handle.emit("capture:privacy-filtered", &event);
+  let mut events = state.privacy_events.lock().unwrap();
+  events.truncate(PRIVACY_EVENT_LIMIT - 1);
+  events.push_front(event);
+  kept
+}
+
+/// Whether a user ignore rule drops `text`; counts the hit on the first matching rule.
+fn is_ignored_capture(state: &AppState, text: &str) -> bool {
+  let mut rules = state.ignore_rules.lock().unwrap();
+  let Some(index) = matching_ignore_rule(text, &rules) else {
+    return false;
+  };
+  rules[index].hits += 1;
+  if let Err(err) = persist_ignore_rules(&state.ignore_rules_path, &rules) {
+    error!(target: "capture", error = %err, "ignore rule hit count save failed");
+  }
+  info!(target: "capture", rule_id = %rules[index].id, "text ignored by rule");
+  true
+}
+
+/// `text` restructured by the first capture template that applies to it, counting the hit;
+/// `None` when none applies.
+fn apply_capture_template(state: &AppState, text: &str, source_app: Option<&foreground::SourceApp>) -> Option<String> {
+  let mut templates = state.capture_templates.lock().unwrap();
+  let process = source_app.and_then(|app| app.process.as_deref());
+  let (index, result) = apply_capture_templates(text, process, &templates)?;
+  templates[index].hits += 1;
+  if let Err(err) = persist_capture_templates(&state.capture_templates_path, &templates) {
+    error!(target: "capture", error = %err, "capture template hit count save failed");
+  }
+  info!(target: "capture", template_id = %templates[index].id, "text restructured by template");
+  Some(result)
+}
+
+/// Put `text` at the front of the clipboard history unless it is already there.
+fn remember_clipboard_text(
+  history: &Mutex<VecDeque<ClipboardEntry>>,
+  text: &str,
+  decision: &CaptureDecision,
+  source_app: Option<foreground::SourceApp>,
+) {
+  if text.is_empty() || text.chars().count() > CLIPBOARD_HISTORY_MAX_CHARS {
+    return;
+  }
+  let mut history = history.lock().unwrap();
+  if history.front().is_some_and(|entry| entry.text == text) {
+    return;
+  }
+  history.truncate(CLIPBOARD_HISTORY_LIMIT - 1);
+  history.push_front(ClipboardEntry {
+    text: text.to_string(),
+    copied_at: chrono::Utc::now().to_rfc3339(),
+    potential_prompt: decision.accepted,
+    rule: decision.rule,
+    reason: decision.reason.clone(),
+    source_app,
+  });
+}
+
+/// The foreground app, unless recording it is switched off or the platform cannot tell.
+fn capture_source_app(state: &AppState) -> Option<foreground::SourceApp> {
+  if !state.clipboard_settings.lock().unwrap().source_app {
+    return None;
+  }
+  foreground::source_app().ok()
+}
+
+/// The foreground app for a watcher capture, checked against the app allowlist: the app to
+/// record, if any, or `Err` with the process name when the allowlist keeps the capture out.
+fn allowed_source_app(state: &AppState) -> Result<Option<foreground::SourceApp>, String> {
+  let allowlist = state.capture_apps.lock().unwrap().clone();
+  if !allowlist.enabled {
+    return Ok(capture_source_app(state));
+  }
+  let app = foreground::source_app().ok();
+  if !allowlist.allows(app.as_ref()) {
+    return Err(app.and_then(|app| app.process).unwrap_or_else(|| "an unknown app".into()));
+  }
+  Ok(app.filter(|_| state.clipboard_settings.lock().unwrap().source_app))
+}
+
+/// Save a copied text as a prompt with its local analysis, then queue the LLM classification
+/// (and a title, for junk first lines) when a backend is available. `source` is `clipboard`,
+/// `hotkey` or `deep_link`; `source_app` is where the text was copied from, if known.
+fn save_clipboard_prompt(
+  state: &AppState,
+  candidate: &str,
+  source: &str,
+  source_app: Option<&foreground::SourceApp>,
+) -> Result<Prompt, StorageError> {
+  let vocab = state.vocabulary.lock().unwrap().clone();
+  let config = state.analysis_config.lock().unwrap().clone();
+  let analysis = summarize_prompt_with_config(candidate, &vocab, &config);
+  let title = derive_title(candidate);
+
+  let mut metadata = analysis_metadata(&analysis, candidate);
+  metadata["source"] = json!(source);
+  if let Some(app) = source_app {
+    metadata["source_app"] = json!(app);
+  }
+  let new_prompt = NewPrompt {
+    title: title.to_string(),
+    body: candidate.to_string(), // keep original text in prompt body
+    language: None,
+    model_hint: None,
+    metadata,
+  };
+
+  let prompt = state.storage.create_prompt(new_prompt)?;
+  info!(target: "capture", source, prompt_id = %prompt.id, "prompt saved");
+  queue_capture_analysis(state, &prompt, analysis, source);
+  Ok(prompt)
+}
+
+/// Metadata recorded from the local analysis of a captured text, for tracing it back.
+fn analysis_metadata(analysis: &PromptAnalysis, text: &str) -> Value {
+  json!({
+    "raw": text,
+    "structured": build_structured_body(analysis, text),
+    "tags": analysis.suggested_tags,
+    "theme": analysis.theme,
+    "topic": analysis.topic,
+    "role": analysis.role,
+    "targets": analysis.target_entities,
+    "intent": analysis.intent,
+    "output_language": analysis.output_language
+  })
+}
+
+/// Store the local analysis of a captured prompt, then queue the LLM classification (and a
+/// title, for junk first lines) when a backend is available.
+fn queue_capture_analysis(state: &AppState, prompt: &Prompt, analysis: PromptAnalysis, source: &str) {
+  let mut classification = analysis_classification(&analysis, source);
+
+  let cloud_ready = state.llm.lock().unwrap().is_some();
+  let local_ready = state.local_llm_client.lock().unwrap().is_some();
+  let classifier_ready = cloud_ready || local_ready;
+  let budget_paused = cloud_ready && !local_ready && state.budget_alerts.lock().unwrap().classification_paused;
+  // Without a model to ask, the local classifier gives the verdict right away.
+  let local_verdict =
+    (!classifier_ready || budget_paused).then(|| classify_capture_locally(state, prompt, source, &mut classification));
+
+  let record = NewAnalysis {
+    prompt_id: prompt.id.clone(),
+    summary: analysis.summary,
+    tags: analysis.suggested_tags,
+    classification,
+    qwen_model: Some("local-nlp".into()),
+    analyzer_version: Some(ANALYZER_VERSION),
+  };
+
+  if let Err(err) = state.storage.create_analysis(record) {
+    error!(target: "capture", source, prompt_id = %prompt.id, error = %err, "analysis save failed");
+  }
+  if let Some(verdict) = local_verdict {
+    quarantine_if_unsure(state, &verdict);
+    if let Err(err) = state.storage.record_classification(verdict) {
+      error!(target: "capture", source, prompt_id = %prompt.id, error = %err, "local classification save failed");
+    }
+  }
+
+  // Hand off to the job queue so the next copy is picked up right away.
+  // While the circuit is open the job waits in the queue, and when the backend is
+  // unreachable it is set aside until `start_classification_retry_monitor` sees it back.
+  if budget_paused {
+    info!(target: "capture", prompt_id = %prompt.id, "llm budget reached, classified locally");
+  } else if classifier_ready {
+    match enqueue_classification(&state.storage, &prompt.id, source) {
+      Ok(_) => state.llm_jobs_wake.notify_one(),
+      Err(err) => {
+        error!(target: "capture", source, prompt_id = %prompt.id, error = %err, "classification enqueue failed");
+      }
+    }
+  }
+  if cloud_ready && state.title_settings.lock().unwrap().llm_fallback && is_junk_title(&prompt.title) {
+    match enqueue_title(&state.storage, prompt) {
+      Ok(_) => state.llm_jobs_wake.notify_one(),
+      Err(err) => {
+        error!(target: "capture", source, prompt_id = %prompt.id, error = %err, "title enqueue failed");
+      }
+    }
+  }
+}
+
+/// The local classifier's `is_prompt` verdict on a captured prompt, for when no LLM classifies
+/// it. Written into `classification` the way an LLM verdict is, with `provider: "heuristic"`
+/// and the signals that decided it; the classifier threshold applies as for the LLM.
+fn classify_capture_locally(
+  state: &AppState,
+  prompt: &Prompt,
+  source: &str,
+  classification: &mut Value,
+) -> NewClassification {
+  let local = classify_locally(&prompt.body);
+  let threshold = state.classifier_settings.lock().unwrap().threshold;
+  let is_prompt = local.score >= threshold;
+  let confidence = if is_prompt { local.score } else { 1.0 - local.score };
+  classification["is_prompt"] = json!(is_prompt);
+  classification["confidence"] = json!(confidence);
+  classification["provider"] = json!("heuristic");
+  classification["signals"] = json!(local.signals);
+  info!(target: "capture", source, prompt_id = %prompt.id, is_prompt, score = local.score, "classified locally");
+  NewClassification {
+    prompt_id: prompt.id.clone(),
+    source: source.to_string(),
+    is_prompt,
+    confidence,
+    model: LOCAL_CLASSIFIER_MODEL.into(),
+  }
+}
+
+/// Quarantine a captured prompt when the classifier is unsure about it (see
+/// [`ClassifierSettings::quarantines`]). Only clipboard and OCR captures qualify, and prompts
+/// the user already labeled stay where they are. Returns whether the prompt was quarantined.
+fn quarantine_if_unsure(state: &AppState, verdict: &NewClassification) -> bool {
+  let score = if verdict.is_prompt { verdict.confidence } else { 1.0 - verdict.confidence };
+  if !matches!(verdict.source.as_str(), "clipboard" | OCR_JOB)
+    || !state.classifier_settings.lock().unwrap().quarantines(score)
+  {
+    return false;
+  }
+  let quarantined = state
+    .storage
+    .get_prompt_label(&verdict.prompt_id)
+    .and_then(|label| match label {
+      Some(_) => Ok(false),
+      None => state.storage.quarantine_prompt(&verdict.prompt_id),
+    });
+  match quarantined {
+    Ok(true) => {
+      info!(target: "capture", source = %verdict.source, prompt_id = %verdict.prompt_id, score, "quarantined");
+      true
+    }
+    Ok(false) => false,
+    Err(err) => {
+      error!(target: "capture", prompt_id = %verdict.prompt_id, error = %err, "quarantine failed");
+      false
+    }
+  }
+}
+
+/// Poll `analysis.toml` and hot-reload it into the analysis config whenever it changes.
+fn start_analysis_overrides_watcher(app_handle: AppHandle) {
+  thread::spawn(move || {
+    let state = app_handle.state::<AppState>();
+    let modified_at = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let mut last: Option<SystemTime> = modified_at(&state.analysis_overrides_path);
+    loop {
+      thread::sleep(ANALYSIS_OVERRIDES_POLL);
+      let current = modified_at(&state.analysis_overrides_path);
+      if current == last {
+        continue;
+      }
+      last = current;
+      let status = apply_analysis_overrides(&state);
+      let _ = app_handle.emit("analysis:overrides-reloaded", status);
+    }
+  });
+}
+
+/// Periodically mine the library for vocabulary suggestions and announce them with
+/// `vocabulary:suggestions-updated` (payload: number of suggestions).
+fn start_vocabulary_suggestion_job(app_handle: AppHandle) {
+  thread::spawn(move || {
+    thread::sleep(VOCABULARY_SUGGESTION_DELAY);
+    loop {
+      let state = app_handle.state::<AppState>();
+      match refresh_vocabulary_suggestions(&state) {
+        Ok(count) => {
+          let _ = app_handle.emit("vocabulary:suggestions-updated", count);
+        }
+        Err(error) => error!(target: "vocabulary", %error, "generating term suggestions failed"),
+      }
+      thread::sleep(VOCABULARY_SUGGESTION_INTERVAL);
+    }
+  });
+}
+
+/// Compare this month's usage with the budget every minute (see [`check_budget`]).
+fn start_budget_monitor(app_handle: AppHandle) {
+  thread::spawn(move || loop {
+    let state = app_handle.state::<AppState>();
+    if let Err(error) = check_budget(&app_handle, &state) {
+      error!(target: "llm", %error, "budget check failed");
+    }
+    thread::sleep(BUDGET_CHECK_INTERVAL);
+  });
+}
+
+/// Run the scheduled export when it is due, checking every minute. A run missed while the
+/// app was closed happens once at the next start.
+fn start_export_scheduler(app_handle: AppHandle) {
+  thread::spawn(move || loop {
+    let state = app_handle.state::<AppState>();
+    let schedule = state.export_schedule.lock().unwrap().clone();
+    let next_run_at = state.export_schedule_status.lock().unwrap().next_run_at.clone();
+    let due = next_run_at
+      .and_then(|next| chrono::DateTime::parse_from_rfc3339(&next).ok())
+      .map(|next| next <= Local::now());
+    match due {
+      Some(true) if schedule.enabled => {
+        run_scheduled_export(&app_handle, &schedule);
+      }
+      // A schedule saved without its status, e.g. after the status file was deleted.
+      None if schedule.enabled => {
+        let mut status = state.export_schedule_status.lock().unwrap();
+        status.next_run_at = schedule.next_run(Local::now()).map(|at| at.to_rfc3339());
+        if let Err(error) = persist_export_schedule_status(&state.export_schedule_status_path, &status) {
+          error!(target: "export", %error, "saving the export schedule status failed");
+        }
+      }
+      _ => {}
+    }
+    thread::sleep(EXPORT_SCHEDULE_CHECK_INTERVAL);
+  });
+}
+
+/// Total this month's LLM usage and update the alert state: each newly reached threshold
+/// is logged and announced with `llm:budget-alert` (payload: the status), and reaching a
+/// limit pauses automatic classification unless the user already acknowledged it.
+fn check_budget(app_handle: &AppHandle, state: &AppState) -> Result<BudgetStatus, StorageError> {
+  let now = Local::now();
+  let settings = state.budget.lock().unwrap().clone();
+  let usage = state.storage.llm_token_usage(month_start(&now))?;
+  let report = budget_report(&now.format("%Y-%m").to_string(), &usage, &settings);
+  let reached = reached_thresholds(&settings.alert_thresholds, report.used_ratio);
+
+  let mut alerts = state.budget_alerts.lock().unwrap();
+  let previous = alerts.clone();
+  if alerts.month != report.month {
+    *alerts = BudgetAlertState {
+      month: report.month.clone(),
+      ..BudgetAlertState::default()
+    };
+  }
+  // A raised limit un-reaches thresholds, which may then be announced again.
+  alerts.alerted.retain(|threshold| reached.contains(threshold));
+  let fresh: Vec<f64> = reached
+    .into_iter()
+    .filter(|threshold| !alerts.alerted.contains(threshold))
+    .collect();
+  alerts.alerted.extend(&fresh);
+  if !report.exceeded() {
+    alerts.acknowledged = false;
+  }
+  alerts.classification_paused = report.exceeded() && settings.pause_classification && !alerts.acknowledged;
+  let status = BudgetStatus {
+    report,
+    alerted: alerts.alerted.clone(),
+    classification_paused: alerts.classification_paused,
+  };
+  if *alerts != previous {
+    if let Err(error) = persist_budget_alerts(&state.budget_alerts_path, &alerts) {
+      error!(target: "llm", %error, "saving the budget alert state failed");
+    }
+  }
+  let resumed = previous.classification_paused && !alerts.classification_paused;
+  drop(alerts);
+
+  if let Some(threshold) = fresh.last() {
+    warn!(
+      target: "llm",
+      used_ratio = status.report.used_ratio.unwrap_or_default(),
+      threshold = *threshold,
+      classification_paused = status.classification_paused,
+      "budget threshold reached"
+    );
+    let _ = app_handle.emit("llm:budget-alert", status.clone());
+  }
+  if resumed {
+    state.llm_jobs_wake.notify_one();
+  }
+  Ok(status)
+}
+
+fn refresh_vocabulary_suggestions(state: &AppState) -> Result<usize, StorageError> {
+  let prompts = state.storage.list_prompts()?;
+  let vocab = state.vocabulary.lock().unwrap().clone();
+  let suppressed = state.analysis_config.lock().unwrap().suppressed_terms.clone();
+  let mut cache = state.vocabulary_suggestions.lock().unwrap();
+  let options = SuggestionOptions {
+    excluded: cache.rejected.iter().cloned().chain(suppressed).collect(),
+    ..SuggestionOptions::default()
+  };
+  cache.suggestions = mine_vocabulary_terms(prompts.iter().map(|prompt| prompt.body.as_str()), &vocab, &options);
+  cache.generated_at = Some(Local::now().to_rfc3339());
+  persist_vocabulary_suggestions(&state.vocabulary_suggestions_path, &cache)?;
+  info!(target: "vocabulary", count = cache.suggestions.len(), "term suggestions updated");
+  Ok(cache.suggestions.len())
+}
+
+/// Load `analysis.toml` into the shared analysis config. Invalid entries are skipped, a file
+/// that fails to parse keeps the previously loaded overrides; either way errors are reported.
+fn apply_analysis_overrides(state: &AppState) -> AnalysisOverridesStatus {
+  let path = &state.analysis_overrides_path;
+  let mut status = state.analysis_overrides_status.lock().unwrap();
+  status.path = path.display().to_string();
+  status.loaded_at = Some(Local::now().to_rfc3339());
+  status.errors.clear();
+
+  let source = match std::fs::read_to_string(path) {
+    Ok(source) => Some(source),
+    Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+    Err(error) => {
+      error!(target: "analysis", %error, "reading analysis.toml failed");
+      status.errors.push(error.to_string());
+      return status.clone();
+    }
+  };
+  status.present = source.is_some();
+
+  let overrides = match source.as_deref().map(AnalysisOverrides::from_toml) {
+    None => AnalysisOverrides::default(),
+    Some(Ok((overrides, errors))) => {
+      status.errors = errors;
+      overrides
+    }
+    Some(Err(error)) => {
+      error!(target: "analysis", %error, "parsing analysis.toml failed");
+      status.errors.push(error.to_string());
+      return status.clone();
+    }
+  };
+  for error in &status.errors {
+    warn!(target: "analysis", %error, "invalid entry in analysis.toml");
+  }
+
+  state.analysis_config.lock().unwrap().overrides = overrides.clone();
+  info!(
+    target: "analysis",
+    stopwords = overrides.stopwords.len(),
+    target_markers = overrides.target_markers.len(),
+    role_patterns = overrides.role_patterns.len(),
+    "analysis.toml loaded"
+  );
+  status.overrides = overrides;
+  status.clone()
+}
+
+/// Re-run local analysis for every prompt mentioning any of `needles` after a vocabulary change.
+/// Progress is reported through `vocabulary:reanalysis-progress` events. Returns the operation
+/// id.
+fn spawn_vocabulary_reanalysis(app_handle: AppHandle, trigger: String, needles: Vec<String>) -> String {
+  spawn_reanalysis(app_handle, "vocabulary:reanalysis-progress", trigger, move |storage| {
+    let mut prompts: Vec<Prompt> = Vec::new();
+    for needle in &needles {
+      for prompt in storage.list_prompts_mentioning(needle)? {
+        if !prompts.iter().any(|item| item.id == prompt.id) {
+          prompts.push(prompt);
+        }
+      }
+    }
+    Ok(prompts)
+  })
+}
+
+/// Run local re-analysis in the background over the prompts picked by `select`,
+/// one job at a time, throttled, with progress events emitted on `event`. Returns the
+/// operation id, which `cancel_operation` takes to stop after the current prompt.
+fn spawn_reanalysis<F>(app_handle: AppHandle, event: &'static str, trigger: String, select: F) -> String
+where
+  F: FnOnce(&Storage) -> Result<Vec<Prompt>, StorageError> + Send + 'static,
+{
+  let token = app_handle.state::<AppState>().operations.start("reanalysis");
+  let operation_id = token.id().to_string();
+  thread::spawn(move || {
+    let state = app_handle.state::<AppState>();
+    let _running = state.reanalysis_lock.lock().unwrap();
+    let prompts = match select(&state.storage) {
+      Ok(prompts) => prompts,
+      Err(error) => {
+        error!(target: "analysis", %trigger, %error, "selecting prompts to reanalyze failed");
+        return;
+      }
+    };
+
+    let started = Instant::now();
+    let total = prompts.len();
+    let progress = |processed: usize, failed: usize, done: bool| ReanalysisProgress {
+      operation_id: token.id().to_string(),
+      trigger: trigger.clone(),
+      processed,
+      total,
+      failed,
+      done,
+      cancelled: done && token.is_cancelled(),
+    };
+    let _ = app_handle.emit(event, progress(0, 0, false));
+    let (mut processed, mut failed) = (0, 0);
+    for prompt in &prompts {
+      if token.is_cancelled() {
+        break;
+      }
+      if let Err(error) = reanalyze_prompt(&state, prompt, &trigger) {
+        failed += 1;
+        error!(target: "analysis", prompt_id = %prompt.id, %trigger, %error, "reanalysis failed");
+      }
+      processed += 1;
+      if processed % REANALYSIS_PROGRESS_EVERY == 0 && processed < total {
+        let _ = app_handle.emit(event, progress(processed, failed, false));
+      }
+      thread::sleep(REANALYSIS_THROTTLE);
+    }
+
+    let _ = app_handle.emit(event, progress(processed, failed, true));
+    if token.is_cancelled() {
+      info!(
+        target: "analysis",
+        %trigger,
+        processed,
+        total,
+        failed,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "reanalysis cancelled"
+      );
+    } else {
+      info!(
+        target: "analysis",
+        %trigger,
+        total,
+        failed,
+        duration_ms = started.elapsed().as_millis() as u64,
+        "reanalysis finished"
+      );
+    }
+  });
+  operation_id
+}
 
-      app.manage(AppState {
-        storage,
-        log_path,
-        export_dir,
-        vocabulary_path,
-        vocabulary,
-      });
+/// Regenerate the local analysis for a stored prompt, keeping the original capture source.
+fn reanalyze_prompt(state: &AppState, prompt: &Prompt, trigger: &str) -> Result<Analysis, StorageError> {
+  let vocabulary = state.vocabulary.lock().unwrap().clone();
+  let config = state.analysis_config.lock().unwrap().clone();
+  let analysis = summarize_prompt_with_config(&prompt.body, &vocabulary, &config);
 
-      let _tray: TrayIcon = TrayIconBuilder::new()
-        .on_tray_icon_event(|tray, event| match event {
-          TrayIconEvent::Click { button, .. } => {
-            if button == MouseButton::Left {
-              if let Some(window) = tray.app_handle().get_webview_window("main") {
-                let _ = window.show();
-                let _ = window.set_focus();
-              }
-            } else if button == MouseButton::Right {
-              let app_handle = tray.app_handle().clone();
-              app_handle
-                .dialog()
-                .message("确定要退出 PromptLab 吗？")
-                .title("退出应用")
-                .kind(MessageDialogKind::Warning)
-                .buttons(MessageDialogButtons::OkCancel)
-                .show(move |ok| {
-                  if ok {
-                    app_handle.exit(0);
-                  }
-                });
-            }
-          }
-          _ => {}
-        })
-        .build(app)?;
+  let latest = state.storage.latest_analysis_for_prompt(&prompt.id)?;
+  if config.deterministic_ids {
+    // Same content, vocabulary and analyzer: the stored result is already current.
+    if let Some(latest) = latest.as_ref() {
+      if latest.classification.get("analysis_id").and_then(Value::as_str) == Some(analysis.id.as_str()) {
+        return Ok(latest.clone());
+      }
+    }
+  }
+  let source = latest
+    .and_then(|latest| latest.classification.get("source").and_then(Value::as_str).map(str::to_string))
+    .or_else(|| prompt.metadata.get("source").and_then(Value::as_str).map(str::to_string))
+    .unwrap_or_else(|| "manual".to_string());
+  let mut classification = analysis_classification(&analysis, &source);
+  classification["trigger"] = json!(trigger);
 
-      start_clipboard_watcher(app_handle.clone());
+  state.storage.create_analysis(NewAnalysis {
+    prompt_id: prompt.id.clone(),
+    summary: analysis.summary,
+    tags: analysis.suggested_tags,
+    classification,
+    qwen_model: Some("local-nlp".into()),
+    analyzer_version: Some(ANALYZER_VERSION),
+  })
+}
 
-      Ok(())
-    })
-    .invoke_handler(tauri::generate_handler![
-      summarize_prompt,
-      save_prompt,
-      list_prompts,
-      get_prompt,
-      update_prompt,
-      delete_prompt,
-      record_analysis,
-      list_analyses,
-      latest_analysis,
-      export_prompts_csv,
-      list_vocabulary,
-      add_vocabulary_entry,
-      remove_vocabulary_entry
-    ])
-    .run(tauri::generate_context!())
-    .expect("error while running PromptLab desktop app");
+fn analysis_classification(analysis: &PromptAnalysis, source: &str) -> Value {
+  let mut classification = json!({
+    "topic": analysis.theme.clone().or(analysis.topic.clone()).unwrap_or_default(),
+    "theme": analysis.theme,
+    "targets": analysis.target_entities,
+    "keywords": analysis.suggested_tags,
+    "length": analysis.length,
+    "role": analysis.role,
+    "intent": analysis.intent,
+    "chain_steps": analysis.chain_steps,
+    "output_language": analysis.output_language,
+    "analysis_id": analysis.id,
+    "source": source
+  });
+  // Rule fields never shadow built-in keys (enforced when rules are saved).
+  for (field, value) in &analysis.extracted_fields {
+    if classification.get(field).is_none() {
+      classification[field] = value.clone();
+    }
+  }
+  classification
+}
+
+const CLASSIFY_SYSTEM_PROMPT: &str = concat!(
+  "你是提示词分类助手。阅读用户给出的 Prompt，只输出一个 JSON 对象，字段：",
+  "summary（一句话中文摘要）、tags（3-8 个关键词数组）、topic（主题）、",
+  "role（Prompt 设定的角色，没有则为空字符串）、targets（面向的对象数组）、",
+  "intent（generation/rewriting/extraction/coding/translation/rolePlay/evaluation/other 之一）、",
+  "is_prompt（布尔值：这段文字是否是写给 AI 的可复用提示词，",
+  "而不是普通聊天、文章或代码）、",
+  "confidence（0 到 1 的数字，表示对 is_prompt 判断的把握）。不要输出其他内容。"
+);
+
+/// The classifier instructions followed by the newest user-confirmed examples, if any.
+fn classify_system_prompt(instructions: &str, examples: &[ClassifierExample]) -> String {
+  let mut prompt = instructions.to_string();
+  if examples.is_empty() {
+    return prompt;
+  }
+  prompt.push_str("\n\n以下是用户确认过的 is_prompt 判断示例，遇到相似文字时以此为准：");
+  for example in examples.iter().take(CLASSIFIER_EXAMPLE_LIMIT) {
+    let text: String = example.text.trim().chars().take(CLASSIFIER_EXAMPLE_CHARS).collect();
+    prompt.push_str(&format!("\n\n文字：{}\nis_prompt: {}", json!(text), example.is_prompt));
+  }
+  prompt
+}
+
+fn connect_llm(
+  settings: &LlmSettings,
+  limiter: &Arc<LlmLimiter>,
+  models: &Arc<Mutex<TaskModels>>,
+  generation: &Arc<Mutex<GenerationSettings>>,
+  storage: &Storage,
+  debug_log: &Arc<LlmDebugLog>,
+) -> Result<LlmClient, LlmError> {
+  let provider = build_provider(settings)?;
+  Ok(LlmClient {
+    provider: Arc::from(provider),
+    retry: settings.retry,
+    limiter: limiter.clone(),
+    settings: settings.clone(),
+    models: models.clone(),
+    generation: generation.clone(),
+    storage: storage.clone(),
+    debug_log: debug_log.clone(),
+    fallback: None,
+  })
+}
+
+/// Link `clients` into a fallback chain in the given order; returns its head.
+fn chain_llm_clients(clients: Vec<LlmClient>) -> Option<LlmClient> {
+  let mut clients = clients.into_iter().rev();
+  let mut head = clients.next()?;
+  for mut client in clients {
+    client.fallback = Some(Arc::new(head));
+    head = client;
+  }
+  Some(head)
+}
+
+/// Client for the local model server; `None` when it is disabled or cannot be set up.
+fn connect_local_llm(
+  settings: &LocalLlmSettings,
+  limiter: &Arc<LlmLimiter>,
+  generation: &Arc<Mutex<GenerationSettings>>,
+  storage: &Storage,
+  debug_log: &Arc<LlmDebugLog>,
+) -> Option<LlmClient> {
+  if !settings.enabled {
+    return None;
+  }
+  // Per-task overrides name cloud models, so the local client always uses its own model.
+  let models = Arc::new(Mutex::new(TaskModels::default()));
+  match connect_llm(&settings.llm_settings(), limiter, &models, generation, storage, debug_log) {
+    Ok(client) => Some(client),
+    Err(error) => {
+      warn!(target: "llm", provider = settings.provider.as_str(), %error, "local model not enabled");
+      None
+    }
+  }
+}
+
+const KEYRING_SERVICE: &str = "promptlab";
+
+fn keyring_entry(provider: ProviderKind) -> keyring::Result<keyring::Entry> {
+  keyring::Entry::new(KEYRING_SERVICE, &format!("{}_api_key", provider.as_str()))
+}
+
+/// API key stored for `provider` in the OS keyring, if any.
+fn load_api_key(provider: ProviderKind) -> keyring::Result<Option<String>> {
+  match keyring_entry(provider)?.get_password() {
+    Ok(key) => Ok(Some(key)),
+    Err(keyring::Error::NoEntry) => Ok(None),
+    Err(error) => Err(error),
+  }
+}
+
+/// `settings` with the API key from the keyring when one is stored for its provider.
+fn with_stored_api_key(settings: &LlmSettings) -> LlmSettings {
+  let mut settings = settings.clone();
+  match load_api_key(settings.provider) {
+    Ok(Some(key)) => settings.api_key = Some(key),
+    Ok(None) => {}
+    Err(error) => {
+      error!(target: "keyring", provider = settings.provider.as_str(), %error, "reading the API key failed");
+    }
+  }
+  settings
+}
+
+fn enqueue_classification(storage: &Storage, prompt_id: &str, source: &str) -> Result<Option<Job>, StorageError> {
+  storage.enqueue_job(NewJob {
+    kind: CLASSIFY_JOB.into(),
+    prompt_id: Some(prompt_id.to_string()),
+    payload: json!({ "source": source }),
+    max_attempts: CLASSIFY_JOB_ATTEMPTS,
+  })
+}
+
+/// Queue classification on behalf of a cancellable operation (see `cancel_operation`).
+fn enqueue_operation_classification(
+  storage: &Storage,
+  prompt_id: &str,
+  source: &str,
+  operation: &str,
+) -> Result<Option<Job>, StorageError> {
+  storage.enqueue_job(NewJob {
+    kind: CLASSIFY_JOB.into(),
+    prompt_id: Some(prompt_id.to_string()),
+    payload: json!({ "source": source, "operation": operation }),
+    max_attempts: CLASSIFY_JOB_ATTEMPTS,
+  })
+}
+
+/// Queue a title job; the payload keeps the current title so an edit made while the job
+/// waits is never overwritten.
+fn enqueue_title(storage: &Storage, prompt: &Prompt) -> Result<Option<Job>, StorageError> {
+  storage.enqueue_job(NewJob {
+    kind: TITLE_JOB.into(),
+    prompt_id: Some(prompt.id.clone()),
+    payload: json!({ "title": prompt.title }),
+    max_attempts: TITLE_JOB_ATTEMPTS,
+  })
+}
+
+fn enqueue_ocr(storage: &Storage, prompt_id: &str) -> Result<Option<Job>, StorageError> {
+  storage.enqueue_job(NewJob {
+    kind: OCR_JOB.into(),
+    prompt_id: Some(prompt_id.to_string()),
+    payload: json!({}),
+    max_attempts: OCR_JOB_ATTEMPTS,
+  })
+}
+
+/// What the dispatcher runs next, with the client to run it on: one classification, else
+/// one OCR job, else a batch of title jobs answered by one request.
+enum LlmWork {
+  Classify(LlmClient, Job),
+  Ocr(LlmClient, Job),
+  Titles(LlmClient, Vec<Job>),
+}
+
+/// Claim work for whichever clients are available: classification needs `classifier`
+/// (local or cloud), OCR and titles need the cloud client.
+fn claim_llm_work(
+  storage: &Storage,
+  classifier: Option<&LlmClient>,
+  cloud: Option<&LlmClient>,
+) -> Result<Option<LlmWork>, StorageError> {
+  if let Some(client) = classifier {
+    if let Some(job) = storage.claim_next_job(CLASSIFY_JOB)? {
+      return Ok(Some(LlmWork::Classify(client.clone(), job)));
+    }
+  }
+  let Some(client) = cloud else {
+    return Ok(None);
+  };
+  if let Some(job) = storage.claim_next_job(OCR_JOB)? {
+    return Ok(Some(LlmWork::Ocr(client.clone(), job)));
+  }
+  let mut jobs = Vec::new();
+  while jobs.len() < TITLE_BATCH {
+    match storage.claim_next_job(TITLE_JOB) {
+      Ok(Some(job)) => jobs.push(job),
+      Ok(None) => break,
+      // Run what was already claimed rather than leave it stuck in `running`.
+      Err(err) if jobs.is_empty() => return Err(err),
+      Err(_) => break,
+    }
+  }
+  Ok((!jobs.is_empty()).then(|| LlmWork::Titles(client.clone(), jobs)))
 }
 
-fn start_clipboard_watcher(app_handle: tauri::AppHandle) {
+/// Spawn the LLM dispatcher on the async runtime. It drains `classify` jobs, then `ocr`
+/// jobs, then batches of `title` jobs, from the `jobs` table, running up to `LLM_WORKERS` at
+/// once; jobs interrupted by a previous shutdown are requeued first. Emits
+/// `analysis:llm-classified`, `prompts:ocr-completed` and `prompts:title-generated` (prompt
+/// id) per success and `jobs:updated` whenever a job finishes. Jobs wait in the queue while
+/// no LLM is configured and each job runs on the client current at claim time;
+/// classification prefers the local server when enabled.
+fn start_llm_workers(app_handle: AppHandle) {
   let state = app_handle.state::<AppState>();
+  let llm = state.llm.clone();
+  let local_llm = state.local_llm_client.clone();
+  let classifier_settings = state.classifier_settings.clone();
+  let circuit = state.classifier_circuit.clone();
+  let budget_alerts = state.budget_alerts.clone();
   let storage = state.storage.clone();
-  let vocab = state.vocabulary.clone();
-  let log_path = state.log_path.clone();
+  let wake = state.llm_jobs_wake.clone();
+  match storage.requeue_interrupted_jobs() {
+    Ok(0) => {}
+    Ok(count) => info!(target: "jobs", count, "requeued interrupted llm jobs"),
+    Err(err) => error!(target: "jobs", error = %err, "requeue of interrupted llm jobs failed"),
+  }
+  let workers = Arc::new(Semaphore::new(LLM_WORKERS));
+  tauri::async_runtime::spawn(async move {
+    loop {
+      let Ok(permit) = workers.clone().acquire_owned().await else {
+        break;
+      };
+      let cloud = llm.lock().unwrap().clone();
+      // While the circuit is open, or the budget is used up and classification would go to the
+      // cloud, classification jobs stay queued; prompts keep their local analysis.
+      let classifier = local_llm
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| cloud.clone().filter(|_| !budget_alerts.lock().unwrap().classification_paused))
+        .filter(|_| circuit.lock().unwrap().allows(Instant::now()));
+      let work = match claim_llm_work(&storage, classifier.as_ref(), cloud.as_ref()) {
+        Ok(Some(work)) => work,
+        Ok(None) => {
+          drop(permit);
+          let _ = tokio::time::timeout(LLM_JOB_POLL, wake.notified()).await;
+          continue;
+        }
+        Err(err) => {
+          drop(permit);
+          error!(target: "jobs", error = %err, "claiming an llm job failed");
+          tokio::time::sleep(LLM_JOB_POLL).await;
+          continue;
+        }
+      };
+      let app_handle = app_handle.clone();
+      let storage = storage.clone();
+      let classifier_settings = classifier_settings.clone();
+      let circuit = circuit.clone();
+      tauri::async_runtime::spawn(async move {
+        let jobs = match work {
+          LlmWork::Classify(client, job) => {
+            let instructions = classifier_settings.lock().unwrap().system_prompt().to_string();
+            run_classification_job(&app_handle, &client, &storage, &job, &instructions, &circuit).await;
+            vec![job]
+          }
+          LlmWork::Ocr(client, job) => {
+            run_ocr_job(&app_handle, &client, &storage, &job).await;
+            vec![job]
+          }
+          LlmWork::Titles(client, jobs) => {
+            run_title_jobs(&app_handle, &client, &storage, &jobs).await;
+            jobs
+          }
+        };
+        drop(permit);
+        for job in jobs {
+          let _ = app_handle.emit("jobs:updated", &job.id);
+        }
+      });
+    }
+  });
+}
 
-  thread::spawn(move || {
-    let mut clipboard = match arboard::Clipboard::new() {
-      Ok(cb) => cb,
-      Err(err) => {
-        let _ = append_log(&log_path, &format!("clipboard init failed: {err}"));
-        return;
+/// Classify one prompt with `instructions` as the system prompt (see `classify_system_prompt`),
+/// reporting the backend's health to `circuit`.
+async fn run_classification_job(
+  app_handle: &AppHandle,
+  client: &LlmClient,
+  storage: &Storage,
+  job: &Job,
+  instructions: &str,
+  circuit: &Mutex<CircuitBreaker>,
+) {
+  let started = Instant::now();
+  let fail = |error: &str, retryable: bool| {
+    log_job_failure(job, error, retryable, started);
+    if let Err(err) = storage.fail_job(&job.id, error, retryable) {
+      log_job_update_failure(job, &err);
+    }
+  };
+  let prompt = match job.prompt_id.as_deref().map(|id| storage.get_prompt(id)) {
+    Some(Ok(Some(prompt))) => prompt,
+    Some(Err(err)) => return fail(&err.to_string(), true),
+    _ => return fail("prompt not found", false),
+  };
+  let source = job.payload["source"].as_str().unwrap_or(CLASSIFY_JOB);
+  let examples = storage.list_classifier_examples().unwrap_or_else(|err| {
+    warn!(target: "jobs", job_id = %job.id, error = %err, "few-shot examples unavailable");
+    Vec::new()
+  });
+  let classified = classify_prompt_with_qwen(client, &prompt.id, &prompt.body, source, instructions, &examples).await;
+  record_classifier_outcome(app_handle, circuit, classified.as_ref().map(|_| ()));
+  let (record, verdict) = match classified {
+    Ok(result) => result,
+    Err(err) if err.is_connection_failure() => {
+      warn!(
+        target: "jobs",
+        job_id = %job.id,
+        prompt_id = %prompt.id,
+        error = %err,
+        "classification waiting for the backend"
+      );
+      if let Err(err) = storage.park_job(&job.id, &err.to_string()) {
+        log_job_update_failure(job, &err);
       }
-    };
+      return;
+    }
+    Err(err) => return fail(&err.to_string(), err.is_retryable()),
+  };
+  // The job may have been cancelled while the request was in flight.
+  if !matches!(storage.get_job(&job.id), Ok(Some(current)) if current.status == JobStatus::Running) {
+    return;
+  }
+  if let Err(err) = storage.create_analysis(record) {
+    return fail(&err.to_string(), true);
+  }
+  let is_prompt = verdict.is_prompt;
+  let state = app_handle.state::<AppState>();
+  if quarantine_if_unsure(&state, &verdict) {
+    let _ = app_handle.emit("prompts:quarantined", &prompt.id);
+  }
+  if let Err(err) = storage.record_classification(verdict) {
+    error!(target: "jobs", job_id = %job.id, prompt_id = %prompt.id, error = %err, "classification save failed");
+  }
+  count_capture(&state, |metrics| {
+    metrics.llm_classified += 1;
+    metrics.llm_not_prompt += u64::from(!is_prompt);
+  });
+  if let Err(err) = storage.complete_job(&job.id) {
+    log_job_update_failure(job, &err);
+  }
+  info!(
+    target: "jobs",
+    job_id = %job.id,
+    prompt_id = %prompt.id,
+    source,
+    is_prompt,
+    duration_ms = started.elapsed().as_millis() as u64,
+    "prompt classified"
+  );
+  let _ = app_handle.emit("analysis:llm-classified", &prompt.id);
+  // The backend answered, so jobs set aside during an outage can run again.
+  resume_waiting_classifications(storage);
+}
+
+/// Log a failed job attempt with the time it took.
+fn log_job_failure(job: &Job, error: &str, retryable: bool, started: Instant) {
+  warn!(
+    target: "jobs",
+    job_id = %job.id,
+    kind = %job.kind,
+    prompt_id = job.prompt_id.as_deref(),
+    attempt = job.attempts,
+    retryable,
+    duration_ms = started.elapsed().as_millis() as u64,
+    %error,
+    "llm job attempt failed"
+  );
+}
+
+fn log_job_update_failure(job: &Job, error: &StorageError) {
+  error!(target: "jobs", job_id = %job.id, %error, "llm job update failed");
+}
+
+/// Queue classification jobs parked while the backend was unreachable again.
+fn resume_waiting_classifications(storage: &Storage) -> usize {
+  match storage.resume_waiting_jobs(CLASSIFY_JOB) {
+    Ok(0) => 0,
+    Ok(count) => {
+      info!(target: "jobs", count, "backend reachable, requeued waiting classification jobs");
+      count
+    }
+    Err(err) => {
+      error!(target: "jobs", error = %err, "requeue of waiting classification jobs failed");
+      0
+    }
+  }
+}
 
-    let mut last = String::new();
+/// While classification jobs wait for the backend (see `run_classification_job`), probe the
+/// classifier's backend every `CLASSIFY_RETRY_PROBE` by listing its models, which costs no
+/// tokens. Once it answers, even with an error status, the jobs are queued again.
+fn start_classification_retry_monitor(app_handle: AppHandle) {
+  tauri::async_runtime::spawn(async move {
     loop {
-      thread::sleep(Duration::from_millis(3500));
-      let Ok(text) = clipboard.get_text() else {
+      tokio::time::sleep(CLASSIFY_RETRY_PROBE).await;
+      let state = app_handle.state::<AppState>();
+      match state.storage.count_jobs(CLASSIFY_JOB, JobStatus::Waiting) {
+        Ok(0) => continue,
+        Ok(_) => {}
+        Err(err) => {
+          error!(target: "jobs", error = %err, "counting waiting classification jobs failed");
+          continue;
+        }
+      }
+      let Some(client) = state.classifier_client() else {
         continue;
       };
-      let candidate = text.trim();
-      if !is_potential_prompt(candidate) {
+      let probe = {
+        let _permit = client.limiter.acquire().await;
+        client.provider.list_models().await
+      };
+      if matches!(&probe, Err(err) if err.is_connection_failure()) {
         continue;
       }
-      if candidate == last {
+      if resume_waiting_classifications(&state.storage) > 0 {
+        state.llm_jobs_wake.notify_one();
+      }
+    }
+  });
+}
+
+/// Feed one classification result to the circuit breaker; announce with
+/// `llm:circuit-changed` (payload: the new status) when it trips or recovers.
+fn record_classifier_outcome(app_handle: &AppHandle, circuit: &Mutex<CircuitBreaker>, outcome: Result<(), &LlmError>) {
+  let now = Instant::now();
+  let mut breaker = circuit.lock().unwrap();
+  let changed = match outcome {
+    Ok(()) => breaker.record_success(),
+    Err(err) if err.is_backend_failure() => breaker.record_failure(now, err.to_string()),
+    Err(_) => false,
+  };
+  if !changed {
+    return;
+  }
+  let status = breaker.status(now);
+  drop(breaker);
+  match status.retry_in_secs {
+    Some(secs) => warn!(
+      target: "llm",
+      retry_in_secs = secs,
+      last_error = status.last_error.as_deref(),
+      "classifier circuit open"
+    ),
+    None => info!(target: "llm", "classifier circuit closed"),
+  }
+  let _ = app_handle.emit("llm:circuit-changed", status);
+}
+
+/// Read the text in an image capture's screenshot and make it the prompt body, analyzed and
+/// queued for classification like a copied text. A body typed in meanwhile is kept.
+async fn run_ocr_job(app_handle: &AppHandle, client: &LlmClient, storage: &Storage, job: &Job) {
+  let started = Instant::now();
+  let fail = |error: &str, retryable: bool| {
+    log_job_failure(job, error, retryable, started);
+    if let Err(err) = storage.fail_job(&job.id, error, retryable) {
+      log_job_update_failure(job, &err);
+    }
+  };
+  let complete = || {
+    if let Err(err) = storage.complete_job(&job.id) {
+      log_job_update_failure(job, &err);
+    }
+  };
+  let prompt = match job.prompt_id.as_deref().map(|id| storage.get_prompt(id)) {
+    Some(Ok(Some(prompt))) => prompt,
+    Some(Err(err)) => return fail(&err.to_string(), true),
+    _ => return fail("prompt not found", false),
+  };
+  if !prompt.body.trim().is_empty() {
+    return complete();
+  }
+  let image = match capture_image(storage, &prompt.id) {
+    Ok(Some(image)) => image,
+    Ok(None) => return fail("image attachment not found", false),
+    Err(err) => return fail(&err.to_string(), true),
+  };
+  let (model, text) = match read_image_text_with_llm(client, &prompt.id, image.bytes).await {
+    Ok(result) => result,
+    Err(err) => return fail(&err.to_string(), err.is_retryable()),
+  };
+  // The job may have been cancelled, or the body edited, while the request was in flight.
+  if !matches!(storage.get_job(&job.id), Ok(Some(current)) if current.status == JobStatus::Running) {
+    return;
+  }
+  let prompt = match storage.get_prompt(&prompt.id) {
+    Ok(Some(prompt)) if prompt.body.trim().is_empty() => prompt,
+    Ok(_) => return complete(),
+    Err(err) => return fail(&err.to_string(), true),
+  };
+
+  let state = app_handle.state::<AppState>();
+  let mut metadata = match prompt.metadata {
+    Value::Object(fields) => fields,
+    _ => serde_json::Map::new(),
+  };
+  let mut update = UpdatePrompt::default();
+  let analysis = text.as_deref().map(|text| {
+    let vocab = state.vocabulary.lock().unwrap().clone();
+    let config = state.analysis_config.lock().unwrap().clone();
+    summarize_prompt_with_config(text, &vocab, &config)
+  });
+  if let (Some(text), Some(analysis)) = (&text, &analysis) {
+    if let Value::Object(fields) = analysis_metadata(analysis, text) {
+      metadata.extend(fields);
+    }
+    update.body = Some(text.clone());
+  }
+  let ocr = json!({
+    "model": model,
+    "chars": text.as_deref().map_or(0, |text| text.chars().count()),
+    "at": chrono::Utc::now().to_rfc3339(),
+  });
+  metadata.insert("ocr".into(), ocr);
+  update.metadata = Some(Value::Object(metadata));
+  let updated = match storage.update_prompt(&prompt.id, update) {
+    Ok(Some(updated)) => updated,
+    Ok(None) => return fail("prompt not found", false),
+    Err(err) => return fail(&err.to_string(), true),
+  };
+  complete();
+  match analysis {
+    Some(analysis) => queue_capture_analysis(&state, &updated, analysis, OCR_JOB),
+    None => {
+      info!(target: "jobs", job_id = %job.id, prompt_id = %prompt.id, "ocr found no text");
+    }
+  }
+  let _ = app_handle.emit("prompts:ocr-completed", &prompt.id);
+}
+
+/// The PNG saved with an image capture.
+fn capture_image(storage: &Storage, prompt_id: &str) -> Result<Option<Attachment>, StorageError> {
+  let Some(listed) = storage
+    .list_attachments(prompt_id)?
+    .into_iter()
+    .find(|attachment| attachment.filename.to_ascii_lowercase().ends_with(".png"))
+  else {
+    return Ok(None);
+  };
+  storage.get_attachment(&listed.id)
+}
+
+/// Transcribe the text in a PNG with the OCR model. Returns the model that answered and the
+/// text, `None` when the image has none.
+async fn read_image_text_with_llm(
+  client: &LlmClient,
+  prompt_id: &str,
+  png: Vec<u8>,
+) -> Result<(String, Option<String>), LlmError> {
+  let request = ChatRequest {
+    model: client.model_for(LlmTask::Ocr),
+    messages: vec![ChatMessage::user_with_images(OCR_PROMPT, vec![ChatImage::png(png)])],
+    temperature: Some(0.0),
+    top_p: None,
+    max_tokens: Some(OCR_MAX_TOKENS),
+    json_output: false,
+  };
+  let (response, _) = call_qwen_chat(client, "ocr", Some(prompt_id), request).await?;
+  Ok((response.model, clean_ocr_text(&response.content)))
+}
+
+async fn run_title_jobs(app_handle: &AppHandle, client: &LlmClient, storage: &Storage, jobs: &[Job]) {
+  let started = Instant::now();
+  let fail = |job: &Job, error: &str, retryable: bool| {
+    log_job_failure(job, error, retryable, started);
+    if let Err(err) = storage.fail_job(&job.id, error, retryable) {
+      log_job_update_failure(job, &err);
+    }
+  };
+  let mut batch = Vec::with_capacity(jobs.len());
+  for job in jobs {
+    match job.prompt_id.as_deref().map(|id| storage.get_prompt(id)) {
+      Some(Ok(Some(prompt))) => batch.push((job, prompt)),
+      Some(Err(err)) => fail(job, &err.to_string(), true),
+      _ => fail(job, "prompt not found", false),
+    }
+  }
+  if batch.is_empty() {
+    return;
+  }
+  let prompts: Vec<&Prompt> = batch.iter().map(|(_, prompt)| prompt).collect();
+  let titles = match generate_titles_with_llm(client, &prompts).await {
+    Ok(titles) => titles,
+    Err(err) => {
+      for (job, _) in &batch {
+        fail(job, &err.to_string(), err.is_retryable());
+      }
+      return;
+    }
+  };
+  for ((job, prompt), title) in batch.iter().zip(titles) {
+    // The job may have been cancelled while the request was in flight.
+    if !matches!(storage.get_job(&job.id), Ok(Some(current)) if current.status == JobStatus::Running) {
+      continue;
+    }
+    let Some(title) = title else {
+      fail(job, "no usable title in response", true);
+      continue;
+    };
+    let queued_title = job.payload["title"].as_str();
+    if queued_title.is_none_or(|queued| queued == prompt.title) {
+      let update = UpdatePrompt {
+        title: Some(title),
+        ..UpdatePrompt::default()
+      };
+      if let Err(err) = storage.update_prompt(&prompt.id, update) {
+        fail(job, &err.to_string(), true);
         continue;
       }
-      last = candidate.to_string();
+      let _ = app_handle.emit("prompts:title-generated", &prompt.id);
+    }
+    if let Err(err) = storage.complete_job(&job.id) {
+      log_job_update_failure(job, &err);
+    }
+  }
+}
+
+/// Ask the LLM for a short title for each prompt in one request. Entries line up with
+/// `prompts`; `None` where the reply had no usable title.
+async fn generate_titles_with_llm(client: &LlmClient, prompts: &[&Prompt]) -> Result<Vec<Option<String>>, LlmError> {
+  let items: Vec<Value> = prompts
+    .iter()
+    .enumerate()
+    .map(|(index, prompt)| {
+      let body: String = prompt.body.chars().take(TITLE_BODY_CHARS).collect();
+      json!({ "id": index + 1, "body": body })
+    })
+    .collect();
+  let request = ChatRequest {
+    model: client.model_for(LlmTask::Summarization),
+    messages: vec![
+      ChatMessage::system(TITLE_SYSTEM_PROMPT),
+      ChatMessage::user(Value::Array(items).to_string()),
+    ],
+    temperature: Some(0.3),
+    top_p: None,
+    max_tokens: Some(100 + 60 * prompts.len() as u32),
+    json_output: true,
+  };
+  let prompt_id = match prompts {
+    [prompt] => Some(prompt.id.as_str()),
+    _ => None,
+  };
+  let (_, _, reply) = call_structured(client, "title", prompt_id, request, parse_typed_reply::<TitlesReply>).await?;
+  let mut titles = vec![None; prompts.len()];
+  for item in reply?.titles {
+    let id = item.id.as_u64().or_else(|| item.id.as_str().and_then(|id| id.trim().parse().ok()));
+    let slot = id
+      .and_then(|id| usize::try_from(id).ok())
+      .and_then(|id| id.checked_sub(1))
+      .and_then(|index| titles.get_mut(index));
+    if let Some(slot) = slot {
+      *slot = clean_generated_title(&item.title);
+    }
+  }
+  Ok(titles)
+}
+
+#[derive(Deserialize)]
+struct TitlesReply {
+  titles: Vec<GeneratedTitle>,
+}
+
+#[derive(Deserialize)]
+struct GeneratedTitle {
+  /// 1-based position in the request; some models send it as a string.
+  id: Value,
+  title: String,
+}
+
+/// Ask the LLM which of `tags` are synonyms; returns groups of two or more tags.
+async fn group_tags_with_llm(client: &LlmClient, tags: &[&str]) -> Result<Vec<Vec<String>>, LlmError> {
+  let request = ChatRequest {
+    model: client.model_for(LlmTask::Summarization),
+    messages: vec![
+      ChatMessage::system(TAG_GROUP_SYSTEM_PROMPT),
+      ChatMessage::user(json!(tags).to_string()),
+    ],
+    temperature: Some(0.0),
+    top_p: None,
+    max_tokens: Some(2000),
+    json_output: true,
+  };
+  let (_, _, reply) = call_structured(client, "tags", None, request, parse_typed_reply::<TagGroupsReply>).await?;
+  Ok(reply?.groups.into_iter().filter(|group| group.len() > 1).collect())
+}
+
+/// Ask the LLM whether near-duplicate `prompts` mean the same. Returns the model, whether
+/// the reply was cut off and the parsed verdict.
+async fn judge_duplicates_with_llm(
+  client: &LlmClient,
+  prompts: &[&Prompt],
+) -> Result<(String, bool, MergeReply), LlmError> {
+  let items: Vec<Value> = prompts
+    .iter()
+    .enumerate()
+    .map(|(index, prompt)| {
+      let body: String = prompt.body.chars().take(MERGE_BODY_CHARS).collect();
+      json!({ "id": index + 1, "title": prompt.title, "body": body })
+    })
+    .collect();
+  let request = ChatRequest {
+    model: client.model_for(LlmTask::Optimization),
+    messages: vec![
+      ChatMessage::system(MERGE_SYSTEM_PROMPT),
+      ChatMessage::user(Value::Array(items).to_string()),
+    ],
+    temperature: Some(0.2),
+    top_p: None,
+    max_tokens: Some(2000),
+    json_output: true,
+  };
+  let prompt_id = prompts.first().map(|prompt| prompt.id.as_str());
+  let (response, _, reply) = call_structured(client, "merge", prompt_id, request, MergeReply::from_reply).await?;
+  Ok((response.model, response.truncated, reply?))
+}
+
+#[derive(Deserialize)]
+struct TagGroupsReply {
+  groups: Vec<Vec<String>>,
+}
+
+/// Send a request whose reply must parse with `parse`. When it does not (and was not cut
+/// off at the token limit), the model gets one more turn with the parse error and is asked
+/// for corrected JSON. Returns the last response, its usage and its parse result.
+async fn call_structured<T>(
+  client: &LlmClient,
+  task: &str,
+  prompt_id: Option<&str>,
+  mut request: ChatRequest,
+  parse: impl Fn(&str) -> Result<T, LlmError>,
+) -> Result<(ChatResponse, LlmCallUsage, Result<T, LlmError>), LlmError> {
+  let (response, usage) = call_qwen_chat(client, task, prompt_id, request.clone()).await?;
+  let error = match parse(&response.content) {
+    Ok(parsed) => return Ok((response, usage, Ok(parsed))),
+    Err(error) if response.truncated => return Ok((response, usage, Err(error))),
+    Err(error) => error,
+  };
+  warn!(target: "llm", task, prompt_id, %error, "reply rejected, asking for a fix");
+  request.messages.push(ChatMessage::assistant(response.content));
+  request.messages.push(ChatMessage::user(format!("{JSON_FIX_PROMPT}\n\n错误：{error}")));
+  let (response, usage) = call_qwen_chat(client, task, prompt_id, request).await?;
+  let parsed = parse(&response.content);
+  Ok((response, usage, parsed))
+}
+
+/// Send one chat request to the configured backend (see [`call_with_retry`]) and record
+/// its token usage and latency under `task`. When the backend fails or times out the
+/// request moves down the fallback chain, on each fallback's own model; the returned usage
+/// names the backend that answered.
+async fn call_qwen_chat(
+  client: &LlmClient,
+  task: &str,
+  prompt_id: Option<&str>,
+  mut request: ChatRequest,
+) -> Result<(ChatResponse, LlmCallUsage), LlmError> {
+  let mut client = client;
+  loop {
+    let model = request.model.clone().unwrap_or_else(|| client.provider.model().to_string());
+    let (result, usage) = call_with_retry(client, || client.provider.chat(&request)).await;
+    let usage = |tokens: Option<&TokenUsage>| LlmCallUsage {
+      prompt_tokens: tokens.map(|tokens| tokens.prompt_tokens),
+      completion_tokens: tokens.map(|tokens| tokens.completion_tokens),
+      ..usage
+    };
+    let error = match result {
+      Ok(response) => {
+        let usage = usage(response.usage.as_ref());
+        record_llm_call(client, task, prompt_id, &response.model, &usage, None);
+        log_llm_exchange(client, task, prompt_id, &request, Ok(&response), &usage);
+        return Ok((response, usage));
+      }
+      Err(error) => error,
+    };
+    let usage = usage(None);
+    record_llm_call(client, task, prompt_id, &model, &usage, Some(&error));
+    log_llm_exchange(client, task, prompt_id, &request, Err(&error), &usage);
+    let Some(next) = client.fallback.as_deref().filter(|_| error.is_backend_failure()) else {
+      return Err(error);
+    };
+    warn!(
+      target: "llm",
+      task,
+      prompt_id,
+      provider = client.provider.kind().as_str(),
+      fallback = next.provider.kind().as_str(),
+      %error,
+      "call failed, falling back"
+    );
+    request.model = None;
+    client = next;
+  }
+}
 
-      match storage.find_prompt_by_body(candidate) {
-        Ok(Some(_)) => continue,
-        Ok(None) => {}
-        Err(err) => {
-          let _ = append_log(&log_path, &format!("clipboard lookup failed: {err}"));
-          continue;
+/// Write a chat call to the debug log when it is enabled; a failure here only gets logged.
+fn log_llm_exchange(
+  client: &LlmClient,
+  task: &str,
+  prompt_id: Option<&str>,
+  request: &ChatRequest,
+  result: Result<&ChatResponse, &LlmError>,
+  usage: &LlmCallUsage,
+) {
+  if !client.debug_log.enabled() {
+    return;
+  }
+  let entry = json!({
+    "time": Local::now().to_rfc3339(),
+    "task": task,
+    "promptId": prompt_id,
+    "provider": client.provider.kind().as_str(),
+    "baseUrl": client.settings.base_url(),
+    "request": request,
+    "response": result.ok(),
+    "error": result.err().map(|error| error.to_string()),
+    "latencyMs": usage.latency_ms,
+    "attempts": usage.attempts,
+  });
+  let secrets: Vec<&str> = client.settings.api_key.as_deref().into_iter().collect();
+  if let Err(error) = client.debug_log.write(entry, &secrets) {
+    error!(target: "llm", %error, "debug log write failed");
+  }
+}
+
+/// Embed texts with `model`, under the same limits and retries. Callers resolve the model
+/// once so a batch job never mixes vectors from two models.
+async fn embed_texts(client: &LlmClient, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+  let (result, usage) = call_with_retry(client, || client.provider.embed(model, inputs)).await;
+  record_llm_call(client, "embed", None, model, &usage, result.as_ref().err());
+  result
+}
+
+/// Store one call in `llm_calls`; a failure here only gets logged.
+fn record_llm_call(
+  client: &LlmClient,
+  task: &str,
+  prompt_id: Option<&str>,
+  model: &str,
+  usage: &LlmCallUsage,
+  error: Option<&LlmError>,
+) {
+  let record = NewLlmCall {
+    task: task.to_string(),
+    prompt_id: prompt_id.map(str::to_string),
+    provider: client.provider.kind().as_str().to_string(),
+    model: model.to_string(),
+    prompt_tokens: usage.prompt_tokens,
+    completion_tokens: usage.completion_tokens,
+    latency_ms: usage.latency_ms,
+    attempts: usage.attempts,
+    error: error.map(|error| error.to_string()),
+  };
+  if usage.latency_ms >= LLM_SLOW_CALL.as_millis() as u64 {
+    warn!(
+      target: "llm",
+      task,
+      prompt_id,
+      provider = usage.provider,
+      model,
+      duration_ms = usage.latency_ms,
+      attempts = usage.attempts,
+      "slow llm call"
+    );
+  } else {
+    debug!(
+      target: "llm",
+      task,
+      prompt_id,
+      provider = usage.provider,
+      model,
+      duration_ms = usage.latency_ms,
+      attempts = usage.attempts,
+      prompt_tokens = usage.prompt_tokens,
+      completion_tokens = usage.completion_tokens,
+      "llm call"
+    );
+  }
+  if let Err(err) = client.storage.record_llm_call(record) {
+    error!(target: "llm", error = %err, "usage record failed");
+  }
+}
+
+/// Run one LLM request through the shared limiter, retrying transient failures
+/// (429, 5xx, timeouts) with jittered exponential backoff. Retries and the final
+/// outcome are logged with attempt counts. Returns the outcome together with the
+/// attempt count and the final attempt's latency (token counts left empty).
+async fn call_with_retry<T, F, Fut>(client: &LlmClient, mut call: F) -> (Result<T, LlmError>, LlmCallUsage)
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<T, LlmError>>,
+{
+  let max_attempts = client.retry.max_attempts.max(1);
+  let mut attempt = 1;
+  loop {
+    let permit = client.limiter.acquire().await;
+    let started = Instant::now();
+    let result = call().await;
+    let usage = LlmCallUsage {
+      provider: client.provider.kind().as_str(),
+      prompt_tokens: None,
+      completion_tokens: None,
+      latency_ms: started.elapsed().as_millis() as u64,
+      attempts: attempt,
+    };
+    drop(permit);
+    match result {
+      Ok(response) => {
+        if attempt > 1 {
+          info!(target: "llm", attempt, max_attempts, "call succeeded after retrying");
         }
+        return (Ok(response), usage);
+      }
+      Err(error) if client.retry.should_retry(attempt, &error) => {
+        let delay = client.retry.backoff(attempt);
+        warn!(
+          target: "llm",
+          attempt,
+          max_attempts,
+          retry_in_ms = delay.as_millis() as u64,
+          %error,
+          "call failed, retrying"
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+      }
+      Err(error) => {
+        error!(target: "llm", attempt, max_attempts, %error, "call failed");
+        return (Err(error), usage);
       }
+    }
+  }
+}
+
+/// Ask the LLM to classify a prompt. The `is_prompt` verdict comes back separately for the
+/// `classifications` table.
+async fn classify_prompt_with_qwen(
+  client: &LlmClient,
+  prompt_id: &str,
+  body: &str,
+  source: &str,
+  instructions: &str,
+  examples: &[ClassifierExample],
+) -> Result<(NewAnalysis, NewClassification), LlmError> {
+  let mut request = ChatRequest {
+    model: client.model_for(LlmTask::Classification),
+    messages: vec![
+      ChatMessage::system(classify_system_prompt(instructions, examples)),
+      ChatMessage::user(body),
+    ],
+    ..ChatRequest::default()
+  };
+  client.generation().classification.apply(&mut request);
+  let (response, usage, reply) =
+    call_structured(client, "classify", Some(prompt_id), request, ClassificationReply::from_reply).await?;
+  let reply = reply?;
+  let classification = json!({
+    "topic": reply.topic,
+    "targets": reply.targets,
+    "keywords": reply.tags,
+    "role": reply.role,
+    "intent": reply.intent,
+    "is_prompt": reply.is_prompt,
+    "confidence": reply.confidence,
+    "source": source,
+    "provider": usage.provider,
+    "usage": usage
+  });
+  let verdict = NewClassification {
+    prompt_id: prompt_id.to_string(),
+    source: source.to_string(),
+    is_prompt: reply.is_prompt,
+    confidence: reply.confidence,
+    model: response.model.clone(),
+  };
+  let analysis = NewAnalysis {
+    prompt_id: prompt_id.to_string(),
+    summary: reply.summary,
+    tags: reply.tags,
+    classification,
+    qwen_model: Some(response.model),
+    analyzer_version: None,
+  };
+  Ok((analysis, verdict))
+}
+
+const DEFAULT_OPTIMIZE_GOAL: &str = "表达更清晰、结构更完整、约束更明确";
+const OPTIMIZE_SYSTEM_PROMPT: &str = concat!(
+  "你是提示词优化专家。按用户给出的优化目标改写提示词，",
+  "保留原有意图、变量占位符与输出要求，语言与原文一致。",
+  "只输出一个 JSON 对象，字段：optimized（改写后的完整提示词）、",
+  "changes（所做修改的简短说明数组）。不要输出其他内容。"
+);
+
+const TRANSLATE_SYSTEM_PROMPT: &str = concat!(
+  "你是专业的提示词翻译。把用户给出的提示词标题和正文翻译成目标语言，",
+  "保持原意、语气与格式，变量占位符（如 {name}、{{input}}）、代码块和专有名词保持原样。",
+  "只输出一个 JSON 对象，字段：title（译后标题）、body（译后正文）。",
+  "不要输出其他内容。"
+);
 
-      let vocab_guard = vocab.lock().unwrap().clone();
-      let analysis = summarize_prompt_with_vocab(candidate, &vocab_guard);
-      let title = derive_title(candidate);
-
-      let new_prompt = NewPrompt {
-        title: title.to_string(),
-        body: candidate.to_string(), // keep original text in prompt body
-        language: None,
-        model_hint: None,
-        metadata: json!({
-          "source": "clipboard",
-          "raw": candidate,
-          "structured": build_structured_body(&analysis, candidate),
-          "tags": analysis.suggested_tags,
-          "theme": analysis.theme,
-          "topic": analysis.topic,
-          "role": analysis.role,
-          "targets": analysis.target_entities
-        }),
-      };
+const TITLE_SYSTEM_PROMPT: &str = concat!(
+  "你为提示词起标题。输入是 JSON 数组，每项含 id 与 body（提示词正文）。",
+  "为每条提示词写一个概括其用途的简洁标题，不超过 20 个字，",
+  "语言与正文一致，不加引号与句末标点。",
+  "只输出一个 JSON 对象：{\"titles\": [{\"id\": 1, \"title\": \"...\"}]}，不要输出其他内容。"
+);
 
-      match storage.create_prompt(new_prompt) {
-        Ok(prompt) => {
-          let _ = append_log(&log_path, &format!("clipboard saved prompt {}", prompt.id));
-          let classification = json!({
-            "topic": analysis.theme.clone().or(analysis.topic.clone()).unwrap_or_default(),
-            "theme": analysis.theme,
-            "targets": analysis.target_entities,
-            "keywords": analysis.suggested_tags,
-            "length": analysis.length,
-            "role": analysis.role,
-            "source": "clipboard"
-          });
-
-          let record = NewAnalysis {
-            prompt_id: prompt.id,
-            summary: analysis.summary,
-            tags: analysis.suggested_tags,
-            classification,
-            qwen_model: Some("local-nlp".into()),
-          };
-
-          if let Err(err) = storage.create_analysis(record) {
-            let _ = append_log(&log_path, &format!("clipboard analysis save failed: {err}"));
-          }
-        }
-        Err(err) => {
-          let _ = append_log(&log_path, &format!("clipboard save prompt failed: {err}"));
-        }
-      }
+/// Sent with the screenshot in one user message; OCR models may ignore system prompts.
+const OCR_PROMPT: &str = concat!(
+  "识别图片中的全部文字，按原有的段落、换行与列表格式原样输出，",
+  "不要翻译、总结、解释或补全，不要加 Markdown 代码块。",
+  "图片中没有文字时只输出 NO_TEXT。"
+);
+const OCR_NO_TEXT: &str = "NO_TEXT";
+
+const JSON_FIX_PROMPT: &str = concat!(
+  "上一条回复不符合要求的 JSON 格式。",
+  "请按原要求只输出修正后的 JSON 对象，不要输出其他内容。"
+);
+
+const TAG_GROUP_SYSTEM_PROMPT: &str = concat!(
+  "你整理提示词库的标签。输入是标签的 JSON 数组。",
+  "找出含义相同、只是写法或语言不同的标签（如 \"LLM\"、\"大模型\"），每组至少两个，",
+  "只使用输入中原样出现的标签，含义相近但不相同的不要合并。",
+  "只输出一个 JSON 对象：{\"groups\": [[\"LLM\", \"大模型\"]]}，",
+  "没有可合并的标签时输出 {\"groups\": []}，不要输出其他内容。"
+);
+
+const MERGE_SYSTEM_PROMPT: &str = concat!(
+  "你负责提示词库去重。输入是若干条文字相近的提示词（JSON 数组，含 id、title、body）。",
+  "判断它们是否是同一个提示词：任务、约束和输出要求相同，只是措辞、格式或细节不同。",
+  "只输出一个 JSON 对象，字段：same（布尔值）、",
+  "merged（same 为 true 时给出合并后的规范版本：保留各版本中有价值的约束与细节，去掉重复，",
+  "保持原文语言与变量占位符；same 为 false 时为空字符串）、reason（一句话说明理由）。",
+  "不要输出其他内容。"
+);
+
+/// Trim a caller-supplied rubric and reject empty or ambiguous ones.
+fn normalize_rubric(rubric: Vec<RubricCriterion>) -> Result<Vec<RubricCriterion>, String> {
+  let mut normalized: Vec<RubricCriterion> = Vec::with_capacity(rubric.len());
+  for criterion in rubric {
+    let key = criterion.key.trim().to_string();
+    if key.is_empty() {
+      return Err("评分维度缺少 key".into());
     }
-  });
+    if normalized.iter().any(|existing| existing.key == key) {
+      return Err(format!("评分维度重复: {key}"));
+    }
+    let name = criterion.name.trim();
+    normalized.push(RubricCriterion {
+      name: if name.is_empty() { key.clone() } else { name.to_string() },
+      description: criterion.description.trim().to_string(),
+      key,
+    });
+  }
+  if normalized.is_empty() {
+    return Err("评分维度不能为空".into());
+  }
+  Ok(normalized)
+}
+
+fn rubric_system_prompt(rubric: &[RubricCriterion]) -> String {
+  let criteria: Vec<String> = rubric
+    .iter()
+    .map(|criterion| match criterion.description.is_empty() {
+      true => format!("- {}（{}）", criterion.key, criterion.name),
+      false => format!("- {}（{}）：{}", criterion.key, criterion.name, criterion.description),
+    })
+    .collect();
+  format!(
+    "你是资深提示词工程师。按下列维度给用户给出的 Prompt 打分，每个维度 {RUBRIC_MIN_SCORE}-{RUBRIC_MAX_SCORE} 分\
+     （{RUBRIC_MIN_SCORE} 分很差，{RUBRIC_MAX_SCORE} 分无可挑剔）：\n{}\n\
+     只输出一个 JSON 对象，字段：scores（数组，每个维度一项，含 criterion（维度 key）、score（整数）、\
+     rationale（一句中文理由））、summary（一句中文总评）。不要输出其他内容。",
+    criteria.join("\n")
+  )
+}
+
+fn deep_analysis_system_prompt() -> String {
+  let categories: Vec<&str> = PROMPT_CATEGORIES
+    .iter()
+    .map(|(category, _)| *category)
+    .chain([FALLBACK_CATEGORY])
+    .collect();
+  format!(
+    "你是资深提示词工程师。深入分析用户给出的 Prompt，只输出一个 JSON 对象，字段：\
+     summary（一两句中文摘要）、tags（3-8 个关键词数组）、category（{} 之一）、\
+     role（Prompt 设定的角色，没有则为空字符串）、audience（输出内容的受众数组）、\
+     quality_notes（Prompt 本身的不足与改进建议数组，每条一句）。不要输出其他内容。",
+    categories.join("/")
+  )
 }
 
 fn normalize_vocab_term(term: &str) -> String {
-  let cleaned = term.trim();
+  let folded = normalize_text(term);
+  let cleaned = folded.trim();
   if cleaned.chars().all(|c| c.is_ascii()) {
     cleaned.to_lowercase()
   } else {
@@ -529,35 +9378,45 @@ fn normalize_vocab_term(term: &str) -> String {
 fn derive_title(body: &str) -> String {
   let first_line = body.split('\n').next().unwrap_or("").trim();
   if first_line.is_empty() {
-    "剪贴板导入".to_string()
+    FALLBACK_TITLE.to_string()
   } else {
     first_line.chars().take(80).collect()
   }
 }
 
-fn is_potential_prompt(text: &str) -> bool {
-  let trimmed = text.trim();
-  if trimmed.is_empty() {
-    return false;
-  }
-  let len = trimmed.chars().count();
-  if len < 8 || len > 600 {
-    return false;
-  }
-  let lines: Vec<&str> = trimmed.lines().collect();
-  if lines.len() > 12 {
-    return false;
-  }
-  let chat_like = lines.iter().filter(|line| line.contains(':') || line.contains('：')).count();
-  if chat_like >= 6 {
-    return false;
+/// Whether a derived title says nothing about the prompt: the import placeholder, or a
+/// first line that is only markdown (a code fence, rule, table border, image or bare heading).
+fn is_junk_title(title: &str) -> bool {
+  let title = title.trim();
+  if title.is_empty() || title == FALLBACK_TITLE || title.starts_with("```") || title.starts_with("![") {
+    return true;
   }
-  let url_like = ["http://", "https://", ".com", ".net", ".org"];
-  let url_hits = url_like.iter().filter(|pat| trimmed.contains(*pat)).count();
-  if url_hits >= 3 {
-    return false;
+  title.chars().filter(|c| c.is_alphanumeric()).count() < 2
+}
+
+/// A model-written title without quotes, markdown or a closing full stop; `None` when
+/// nothing usable is left.
+fn clean_generated_title(raw: &str) -> Option<String> {
+  let markup = |c: char| matches!(c, '"' | '\'' | '“' | '”' | '「' | '」' | '#' | '*' | '`');
+  let title = raw
+    .trim()
+    .trim_start_matches(markup)
+    .trim_end_matches(|c: char| markup(c) || matches!(c, '。' | '.'))
+    .trim();
+  let title: String = title.chars().take(80).collect();
+  (!is_junk_title(&title)).then_some(title)
+}
+
+/// The transcription in an OCR reply, without a code fence wrapped around it; `None` when the
+/// model found no text.
+fn clean_ocr_text(raw: &str) -> Option<String> {
+  let mut text = raw.trim();
+  if let Some(fenced) = text.strip_prefix("```") {
+    // The opening fence line may carry a language tag.
+    text = fenced.split_once('\n').map_or("", |(_, rest)| rest).trim_end();
+    text = text.strip_suffix("```").unwrap_or(text).trim();
   }
-  true
+  (!text.is_empty() && text != OCR_NO_TEXT).then(|| text.to_string())
 }
 
 fn build_structured_body(analysis: &PromptAnalysis, original: &str) -> String {
@@ -578,29 +9437,543 @@ fn build_structured_body(analysis: &PromptAnalysis, original: &str) -> String {
   parts.join("\n")
 }
 
-fn load_vocabulary(path: &Path) -> Vec<String> {
-  if path.exists() {
-    if let Ok(data) = std::fs::read_to_string(path) {
-      if let Ok(entries) = serde_json::from_str::<Vec<String>>(&data) {
-        let mut cleaned: Vec<String> = entries
-          .into_iter()
-          .map(|item| normalize_vocab_term(&item))
-          .filter(|item| !item.is_empty())
-          .collect();
-        cleaned.sort();
-        cleaned.dedup();
-        return cleaned;
+/// Rows of a glossary file with their 1-based line numbers. The delimiter follows the
+/// extension (.csv / .tsv); other files are split on tabs when present, else one term per line.
+/// A header row ("term", "词条", …) and `#` comments are skipped.
+fn read_glossary_rows(path: &Path) -> Result<Vec<(usize, Vec<String>)>, String> {
+  let data = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+  let data = data.trim_start_matches('\u{feff}');
+  let extension = path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .map(|ext| ext.to_lowercase())
+    .unwrap_or_default();
+  let delimiter = match extension.as_str() {
+    "csv" => Some(b','),
+    "tsv" => Some(b'\t'),
+    _ if data.contains('\t') => Some(b'\t'),
+    _ => None,
+  };
+
+  let mut rows: Vec<(usize, Vec<String>)> = match delimiter {
+    Some(delimiter) => {
+      let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .comment(Some(b'#'))
+        .from_reader(data.as_bytes());
+      let mut rows = Vec::new();
+      for record in reader.records() {
+        let record = record.map_err(|error| error.to_string())?;
+        let line = record.position().map_or(rows.len() + 1, |position| position.line() as usize);
+        rows.push((line, record.iter().map(str::to_string).collect()));
+      }
+      rows
+    }
+    None => data
+      .lines()
+      .enumerate()
+      .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+      .map(|(idx, line)| (idx + 1, vec![line.trim().to_string()]))
+      .collect(),
+  };
+
+  let is_header = rows.first().and_then(|(_, row)| row.first()).is_some_and(|cell| {
+    matches!(cell.trim().to_lowercase().as_str(), "term" | "word" | "keyword" | "词条" | "术语" | "词语")
+  });
+  if is_header {
+    rows.remove(0);
+  }
+  Ok(rows)
+}
+
+/// Load `vocabulary.json`. The legacy format (a plain array of terms) is migrated to
+/// [`VocabularyFile`] in place; the original is kept next to it as `vocabulary.v1.json`.
+fn load_vocabulary(path: &Path) -> Vec<VocabularyEntry> {
+  let Ok(data) = std::fs::read_to_string(path) else {
+    return Vec::new();
+  };
+  if let Ok(file) = serde_json::from_str::<VocabularyFile>(&data) {
+    return clean_vocabulary(file.entries);
+  }
+  let Ok(legacy) = serde_json::from_str::<Vec<String>>(&data) else {
+    return Vec::new();
+  };
+  let entries = clean_vocabulary(legacy.into_iter().map(VocabularyEntry::new).collect());
+  let backup = path.with_file_name("vocabulary.v1.json");
+  if std::fs::copy(path, &backup).is_ok() {
+    if let Err(error) = persist_vocabulary(path, &entries) {
+      warn!(target: "vocabulary", %error, "failed to migrate vocabulary");
+    }
+  }
+  entries
+}
+
+/// Normalize terms and aliases, merge duplicate terms and drop aliases that collide with a term.
+fn clean_vocabulary(entries: Vec<VocabularyEntry>) -> Vec<VocabularyEntry> {
+  let mut cleaned: Vec<VocabularyEntry> = Vec::new();
+  for entry in entries {
+    let term = normalize_vocab_term(&entry.term);
+    if term.is_empty() {
+      continue;
+    }
+    let aliases = entry.aliases.iter().map(|alias| normalize_vocab_term(alias));
+    match cleaned.iter_mut().find(|item| item.term == term) {
+      Some(existing) => existing.aliases.extend(aliases),
+      None => cleaned.push(VocabularyEntry {
+        term,
+        aliases: aliases.collect(),
+        weight: entry.weight.filter(|weight| *weight > 0),
+      }),
+    }
+  }
+  let terms: Vec<String> = cleaned.iter().map(|entry| entry.term.clone()).collect();
+  let mut claimed: Vec<String> = Vec::new();
+  for entry in &mut cleaned {
+    entry.aliases.retain(|alias| {
+      let keep = !alias.is_empty() && !terms.contains(alias) && !claimed.contains(alias);
+      if keep {
+        claimed.push(alias.clone());
       }
+      keep
+    });
+  }
+  cleaned.sort_by(|a, b| a.term.cmp(&b.term));
+  cleaned
+}
+
+fn persist_vocabulary(path: &Path, vocab: &[VocabularyEntry]) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let file = VocabularyFile {
+    version: VOCABULARY_FORMAT_VERSION,
+    entries: vocab.to_vec(),
+  };
+  let payload = serde_json::to_string_pretty(&file).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn validate_vocabulary_weight(weight: Option<usize>) -> Result<(), String> {
+  match weight {
+    Some(weight) if !(1..=MAX_VOCABULARY_BOOST).contains(&weight) => {
+      Err(format!("权重需在 1~{MAX_VOCABULARY_BOOST} 之间"))
     }
+    _ => Ok(()),
+  }
+}
+
+fn describe_weight(weight: Option<usize>) -> String {
+  match weight {
+    Some(weight) => format!("×{weight}"),
+    None => format!("默认 ×{DEFAULT_VOCABULARY_BOOST}"),
+  }
+}
+
+fn vocabulary_terms(vocab: &[VocabularyEntry]) -> Vec<String> {
+  let mut terms: Vec<String> = vocab.iter().map(|entry| entry.term.clone()).collect();
+  terms.sort();
+  terms
+}
+
+fn load_vocabulary_suggestions(path: &Path) -> VocabularySuggestionState {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<VocabularySuggestionState>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_vocabulary_suggestions(path: &Path, cache: &VocabularySuggestionState) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(cache).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_llm_limits(path: &Path) -> RateLimits {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<RateLimits>(&data).ok())
+    .filter(|limits| limits.validate().is_ok())
+    .unwrap_or_default()
+}
+
+fn persist_llm_limits(path: &Path, limits: &RateLimits) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(limits).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+/// Saved network settings; `None` until the user saves some (the environment applies).
+fn load_llm_backend(path: &Path) -> Option<BackendSettings> {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<BackendSettings>(&data).ok())
+    .map(BackendSettings::normalized)
+}
+
+fn persist_llm_backend(path: &Path, settings: &BackendSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_llm_network(path: &Path) -> Option<NetworkSettings> {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<NetworkSettings>(&data).ok())
+    .map(NetworkSettings::normalized)
+}
+
+fn persist_llm_network(path: &Path, network: &NetworkSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(network).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_llm_models(path: &Path) -> TaskModels {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<TaskModels>(&data).ok())
+    .map(TaskModels::normalized)
+    .unwrap_or_default()
+}
+
+fn persist_llm_models(path: &Path, models: &TaskModels) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(models).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_llm_generation(path: &Path) -> GenerationSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<GenerationSettings>(&data).ok())
+    .filter(|settings| settings.validate().is_ok())
+    .unwrap_or_default()
+}
+
+fn persist_llm_generation(path: &Path, settings: &GenerationSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_local_llm(path: &Path) -> LocalLlmSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<LocalLlmSettings>(&data).ok())
+    .map(LocalLlmSettings::normalized)
+    .filter(|settings| settings.validate().is_ok())
+    .unwrap_or_default()
+}
+
+fn persist_local_llm(path: &Path, settings: &LocalLlmSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_llm_fallback(path: &Path) -> FallbackSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<FallbackSettings>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_llm_fallback(path: &Path, settings: &FallbackSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_ignore_rules(path: &Path) -> Vec<IgnoreRule> {
+  let mut rules = std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<Vec<IgnoreRule>>(&data).ok())
+    .unwrap_or_default();
+  rules.retain(|rule| rule.validate().is_ok());
+  rules
+}
+
+fn persist_ignore_rules(path: &Path, rules: &[IgnoreRule]) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(rules).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_capture_templates(path: &Path) -> Vec<CaptureTemplate> {
+  let mut templates = std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<Vec<CaptureTemplate>>(&data).ok())
+    .unwrap_or_default();
+  templates.retain(|template| template.validate().is_ok());
+  templates
+}
+
+fn persist_capture_templates(path: &Path, templates: &[CaptureTemplate]) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(templates).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_clipboard_settings(path: &Path) -> ClipboardSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<ClipboardSettings>(&data).ok())
+    .map(ClipboardSettings::normalized)
+    .unwrap_or_default()
+}
+
+fn persist_clipboard_settings(path: &Path, settings: &ClipboardSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_capture_filter(path: &Path) -> CaptureFilter {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<CaptureFilter>(&data).ok())
+    .filter(|filter| filter.validate().is_ok())
+    .unwrap_or_default()
+}
+
+fn persist_capture_filter(path: &Path, filter: &CaptureFilter) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(filter).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_capture_apps(path: &Path) -> CaptureAppSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<CaptureAppSettings>(&data).ok())
+    .map(CaptureAppSettings::normalized)
+    .unwrap_or_default()
+}
+
+fn persist_capture_apps(path: &Path, settings: &CaptureAppSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_privacy_filter(path: &Path) -> PrivacyFilter {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<PrivacyFilter>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_privacy_filter(path: &Path, filter: &PrivacyFilter) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(filter).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_hotkey_settings(path: &Path) -> HotkeySettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<HotkeySettings>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_hotkey_settings(path: &Path, settings: &HotkeySettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_title_settings(path: &Path) -> TitleSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<TitleSettings>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_title_settings(path: &Path, settings: &TitleSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_budget_settings(path: &Path) -> BudgetSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<BudgetSettings>(&data).ok())
+    .map(BudgetSettings::normalized)
+    .unwrap_or_default()
+}
+
+fn persist_budget_settings(path: &Path, settings: &BudgetSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_export_schedule(path: &Path) -> ExportSchedule {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<ExportSchedule>(&data).ok())
+    .map(ExportSchedule::normalized)
+    .unwrap_or_default()
+}
+
+fn persist_export_schedule(path: &Path, schedule: &ExportSchedule) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(schedule).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_export_schedule_status(path: &Path) -> ExportScheduleStatus {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<ExportScheduleStatus>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_export_schedule_status(path: &Path, status: &ExportScheduleStatus) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(status).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_budget_alerts(path: &Path) -> BudgetAlertState {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<BudgetAlertState>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_budget_alerts(path: &Path, alerts: &BudgetAlertState) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(alerts).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_logging_settings(path: &Path) -> LoggingSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<LoggingSettings>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_logging_settings(path: &Path, settings: &LoggingSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_llm_debug_settings(path: &Path) -> LlmDebugSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<LlmDebugSettings>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_llm_debug_settings(path: &Path, settings: &LlmDebugSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_classifier_settings(path: &Path) -> ClassifierSettings {
+  std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<ClassifierSettings>(&data).ok())
+    .unwrap_or_default()
+}
+
+fn persist_classifier_settings(path: &Path, settings: &ClassifierSettings) -> std::io::Result<()> {
+  if let Some(parent) = path.parent() {
+    std::fs::create_dir_all(parent)?;
+  }
+  let payload = serde_json::to_string_pretty(settings).map_err(|error| std::io::Error::other(error.to_string()))?;
+  std::fs::write(path, payload)
+}
+
+fn load_analysis_config(path: &Path) -> AnalysisConfig {
+  let mut config = std::fs::read_to_string(path)
+    .ok()
+    .and_then(|data| serde_json::from_str::<AnalysisConfig>(&data).ok())
+    .unwrap_or_default();
+  config.suppressed_terms = config
+    .suppressed_terms
+    .iter()
+    .map(|item| normalize_vocab_term(item))
+    .filter(|item| !item.is_empty())
+    .collect();
+  config.suppressed_terms.sort();
+  config.suppressed_terms.dedup();
+  for boost in &mut config.vocabulary_boosts {
+    boost.term = normalize_vocab_term(&boost.term);
+    boost.categories = boost
+      .categories
+      .iter()
+      .filter_map(|category| canonical_category(category))
+      .map(str::to_string)
+      .collect();
+  }
+  config
+    .vocabulary_boosts
+    .retain(|boost| !boost.term.is_empty() && boost.factor > 0);
+  config.extraction_rules.retain(|rule| rule.validate().is_ok());
+  if config.summary_max_length == 0 {
+    config.summary_max_length = AnalysisConfig::default().summary_max_length;
   }
-  Vec::new()
+  config
 }
 
-fn persist_vocabulary(path: &Path, vocab: &[String]) -> std::io::Result<()> {
+fn persist_analysis_config(path: &Path, config: &AnalysisConfig) -> std::io::Result<()> {
   if let Some(parent) = path.parent() {
     std::fs::create_dir_all(parent)?;
   }
-  let payload = serde_json::to_string_pretty(vocab)
-    .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error.to_string()))?;
+  let payload = serde_json::to_string_pretty(config).map_err(|error| std::io::Error::other(error.to_string()))?;
   std::fs::write(path, payload)
 }
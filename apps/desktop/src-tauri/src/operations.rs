@@ -0,0 +1,67 @@
+//! Long-running background operations (exports, bulk re-analysis, embedding backfills) that
+//! the user can stop with `cancel_operation`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Cancellation flags of the running operations, by operation id.
+#[derive(Clone, Default)]
+pub struct Operations {
+  running: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+  next: Arc<AtomicU64>,
+}
+
+impl Operations {
+  /// A new operation id: `kind` and a number unique to this session.
+  pub fn new_id(&self, kind: &str) -> String {
+    format!("{kind}-{}", self.next.fetch_add(1, Ordering::Relaxed) + 1)
+  }
+
+  /// Register an operation of `kind`, which can be cancelled until the token is dropped.
+  pub fn start(&self, kind: &str) -> CancellationToken {
+    let id = self.new_id(kind);
+    let cancelled = Arc::new(AtomicBool::new(false));
+    self.running.lock().unwrap().insert(id.clone(), cancelled.clone());
+    CancellationToken {
+      id,
+      cancelled,
+      running: self.running.clone(),
+    }
+  }
+
+  /// Ask a running operation to stop after its current step; `false` when no operation with
+  /// that id is running (any more).
+  pub fn cancel(&self, id: &str) -> bool {
+    match self.running.lock().unwrap().get(id) {
+      Some(cancelled) => {
+        cancelled.store(true, Ordering::Relaxed);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+/// Held by the code running an operation, which checks it between steps.
+pub struct CancellationToken {
+  id: String,
+  cancelled: Arc<AtomicBool>,
+  running: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+impl CancellationToken {
+  pub fn id(&self) -> &str {
+    &self.id
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::Relaxed)
+  }
+}
+
+impl Drop for CancellationToken {
+  fn drop(&mut self) {
+    self.running.lock().unwrap().remove(&self.id);
+  }
+}
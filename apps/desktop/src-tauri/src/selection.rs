@@ -0,0 +1,169 @@
+//! Copy the current selection of whatever app has focus, for the capture hotkey, and paste into
+//! it, for the quick picker.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the user to let go of the hotkey's modifiers before sending the copy
+/// shortcut, which would otherwise arrive as e.g. Ctrl+Alt+C.
+const MODIFIER_RELEASE_TIMEOUT: Duration = Duration::from_secs(1);
+const MODIFIER_POLL: Duration = Duration::from_millis(20);
+
+/// Press the platform's copy shortcut (Ctrl+C) in the focused app once the hotkey's modifiers
+/// are released. Windows uses `SendInput`; Linux uses the X11 XTEST extension, which reaches
+/// X11 and XWayland apps only. Fails elsewhere (macOS, native Wayland apps are not reached at
+/// all), and the caller captures what is already on the clipboard instead.
+pub fn copy_selection() -> Result<(), String> {
+  platform::press_with_control(b'c')
+}
+
+/// Press the platform's paste shortcut (Ctrl+V) in the focused app, with the same reach as
+/// [`copy_selection`].
+pub fn paste() -> Result<(), String> {
+  platform::press_with_control(b'v')
+}
+
+/// Poll `released` until it holds or [`MODIFIER_RELEASE_TIMEOUT`] passes.
+#[cfg_attr(not(any(windows, target_os = "linux")), allow(dead_code))]
+fn wait_for_release(mut released: impl FnMut() -> Result<bool, String>) -> Result<(), String> {
+  let deadline = Instant::now() + MODIFIER_RELEASE_TIMEOUT;
+  while !released()? && Instant::now() < deadline {
+    thread::sleep(MODIFIER_POLL);
+  }
+  Ok(())
+}
+
+#[cfg(windows)]
+mod platform {
+  use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, VIRTUAL_KEY, VK_CONTROL,
+    VK_LWIN, VK_MENU, VK_RWIN, VK_SHIFT,
+  };
+
+  fn key(vk: VIRTUAL_KEY, flags: u32) -> INPUT {
+    INPUT {
+      r#type: INPUT_KEYBOARD,
+      Anonymous: INPUT_0 {
+        ki: KEYBDINPUT {
+          wVk: vk,
+          wScan: 0,
+          dwFlags: flags,
+          time: 0,
+          dwExtraInfo: 0,
+        },
+      },
+    }
+  }
+
+  /// `letter` is a lowercase ASCII letter; its virtual-key code is the uppercase character.
+  pub fn press_with_control(letter: u8) -> Result<(), String> {
+    let letter = VIRTUAL_KEY::from(letter.to_ascii_uppercase());
+    super::wait_for_release(|| {
+      // The high bit of `GetAsyncKeyState` is set while the key is down.
+      Ok(
+        [VK_SHIFT, VK_MENU, VK_LWIN, VK_RWIN]
+          .iter()
+          .all(|vk| unsafe { GetAsyncKeyState(i32::from(*vk)) } >= 0),
+      )
+    })?;
+    let inputs = [
+      key(VK_CONTROL, 0),
+      key(letter, 0),
+      key(letter, KEYEVENTF_KEYUP),
+      key(VK_CONTROL, KEYEVENTF_KEYUP),
+    ];
+    let sent = unsafe { SendInput(inputs.len() as u32, inputs.as_ptr(), std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+      return Err(format!("SendInput failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+  use x11rb::connection::{Connection, RequestConnection};
+  use x11rb::protocol::xproto::{ConnectionExt as _, Keycode, Keysym, KEY_PRESS_EVENT, KEY_RELEASE_EVENT};
+  use x11rb::protocol::xtest::{self, ConnectionExt as _};
+  use x11rb::wrapper::ConnectionExt as _;
+  use x11rb::CURRENT_TIME;
+
+  const XK_SHIFT_L: Keysym = 0xffe1;
+  const XK_SHIFT_R: Keysym = 0xffe2;
+  const XK_CONTROL_L: Keysym = 0xffe3;
+  const XK_ALT_L: Keysym = 0xffe9;
+  const XK_ALT_R: Keysym = 0xffea;
+  const XK_SUPER_L: Keysym = 0xffeb;
+  const XK_SUPER_R: Keysym = 0xffec;
+
+  fn describe(error: impl std::fmt::Display) -> String {
+    format!("X11 key events unavailable: {error}")
+  }
+
+  /// `letter` is a lowercase ASCII letter, which is also its keysym.
+  pub fn press_with_control(letter: u8) -> Result<(), String> {
+    let (conn, screen_num) = x11rb::connect(None).map_err(describe)?;
+    if conn
+      .extension_information(xtest::X11_EXTENSION_NAME)
+      .map_err(describe)?
+      .is_none()
+    {
+      return Err(describe("no XTEST extension"));
+    }
+    let root = conn.setup().roots[screen_num].root;
+
+    // Keycodes depend on the keyboard layout; look them up from the keysyms.
+    let (min, max) = (conn.setup().min_keycode, conn.setup().max_keycode);
+    let mapping = conn
+      .get_keyboard_mapping(min, max - min + 1)
+      .map_err(describe)?
+      .reply()
+      .map_err(describe)?;
+    let per_keycode = usize::from(mapping.keysyms_per_keycode.max(1));
+    let keycodes = |wanted: &[Keysym]| -> Vec<Keycode> {
+      mapping
+        .keysyms
+        .chunks(per_keycode)
+        .zip(min..=max)
+        .filter(|(keysyms, _)| keysyms.iter().any(|keysym| wanted.contains(keysym)))
+        .map(|(_, keycode)| keycode)
+        .collect()
+    };
+    let modifiers = keycodes(&[XK_SHIFT_L, XK_SHIFT_R, XK_ALT_L, XK_ALT_R, XK_SUPER_L, XK_SUPER_R]);
+    let (Some(&control), Some(&key)) = (
+      keycodes(&[XK_CONTROL_L]).first(),
+      keycodes(&[Keysym::from(letter)]).first(),
+    ) else {
+      return Err(describe(format!("no keycode for Control_L or {}", char::from(letter))));
+    };
+
+    super::wait_for_release(|| {
+      let keymap = conn.query_keymap().map_err(describe)?.reply().map_err(describe)?;
+      Ok(
+        modifiers
+          .iter()
+          .all(|keycode| keymap.keys[usize::from(*keycode / 8)] & (1 << (keycode % 8)) == 0),
+      )
+    })?;
+    for (event, keycode) in [
+      (KEY_PRESS_EVENT, control),
+      (KEY_PRESS_EVENT, key),
+      (KEY_RELEASE_EVENT, key),
+      (KEY_RELEASE_EVENT, control),
+    ] {
+      conn
+        .xtest_fake_input(event, keycode, CURRENT_TIME, root, 0, 0, 0)
+        .map_err(describe)?;
+    }
+    // Wait until the server has processed the events before the caller reads the clipboard.
+    conn.sync().map_err(describe)?;
+    Ok(())
+  }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod platform {
+  pub fn press_with_control(_letter: u8) -> Result<(), String> {
+    Err("sending key shortcuts is not supported on this platform".into())
+  }
+}
@@ -1,5 +1,7 @@
 ﻿import { useEffect, useMemo, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { getCurrentWebview } from "@tauri-apps/api/webview";
 import "./App.css";
 
 type PromptAnalysis = {
@@ -10,6 +12,41 @@ type PromptAnalysis = {
   theme: string | null;
   role: string;
   targetEntities: string[];
+  intent: string;
+  chainSteps: number;
+  outputLanguage: string | null;
+  extractedFields: Record<string, string | number>;
+};
+
+type AnalysisOverridesStatus = {
+  path: string;
+  present: boolean;
+  errors: string[];
+};
+
+type ExportProgress = {
+  path: string;
+  written: number;
+  total: number;
+};
+
+type FileImportResult = {
+  path: string;
+  found: number;
+  imported: number;
+  duplicates: number;
+  skipped: number;
+  promptIds: string[];
+  error: string | null;
+};
+
+type ImportProgress = {
+  operationId: string;
+  filesDone: number;
+  filesTotal: number;
+  imported: number;
+  done: boolean;
+  cancelled: boolean;
 };
 
 type StoredPrompt = {
@@ -23,6 +60,13 @@ type StoredPrompt = {
   updated_at: string;
 };
 
+type TextHit = {
+  prompt: StoredPrompt;
+  tags: string[];
+  fields: ("title" | "body" | "tags")[];
+  score: number;
+};
+
 type AnalysisClassification = {
   topic?: string;
   targets?: string[];
@@ -64,10 +108,13 @@ export default function App() {
   const [newVocab, setNewVocab] = useState("");
   const [isVocabBusy, setIsVocabBusy] = useState(false);
   const [exportPath, setExportPath] = useState("");
+  const [importResults, setImportResults] = useState<FileImportResult[] | null>(null);
   const [lastExportPath, setLastExportPath] = useState("");
   const [historyPage, setHistoryPage] = useState(1);
   const [pageSize, setPageSize] = useState(8);
   const [selectedIds, setSelectedIds] = useState<string[]>([]);
+  const [historyQuery, setHistoryQuery] = useState("");
+  const [bulkValue, setBulkValue] = useState("");
 
   const tokenCount = useMemo(() => prompt.trim().split(/\s+/).filter(Boolean).length, [prompt]);
 
@@ -128,7 +175,10 @@ export default function App() {
 
   const refreshHistory = async () => {
     try {
-      const records = await invoke<StoredPrompt[]>("list_prompts");
+      const query = historyQuery.trim();
+      const records = query
+        ? (await invoke<TextHit[]>("search_prompts", { query, limit: 500 })).map((hit) => hit.prompt)
+        : await invoke<StoredPrompt[]>("list_prompts");
       setHistory(records);
       if (activePromptId) {
         const active = records.find((item) => item.id === activePromptId);
@@ -153,10 +203,92 @@ export default function App() {
   };
 
   useEffect(() => {
-    refreshHistory();
     fetchVocabulary();
   }, []);
 
+  useEffect(() => {
+    setHistoryPage(1);
+    const timer = window.setTimeout(refreshHistory, 250);
+    return () => window.clearTimeout(timer);
+  }, [historyQuery]);
+
+  useEffect(() => {
+    const unlisten = listen<AnalysisOverridesStatus>("analysis:overrides-reloaded", (event) => {
+      const { errors } = event.payload;
+      setStatus(errors.length > 0 ? `analysis.toml 校验失败：${errors.join("；")}` : "analysis.toml 已重新加载");
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<ExportProgress>("export:progress", (event) => {
+      const { written, total } = event.payload;
+      setStatus(`导出 CSV 中... ${written}/${total}`);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<ImportProgress>("import:progress", (event) => {
+      const { filesDone, filesTotal, imported, done } = event.payload;
+      if (!done) setStatus(`导入中... 文件 ${filesDone}/${filesTotal}，已导入 ${imported} 条`);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  useEffect(() => {
+    const open = async (id: string | null) => {
+      if (!id) return;
+      const record = await invoke<StoredPrompt | null>("get_prompt", { id });
+      if (record) handleSelectHistory(record);
+    };
+    // A link that launched the app arrives before this listener is set up.
+    invoke<string | null>("take_deep_link_prompt").then(open);
+    const unlisten = listen<string>("deep-link:prompt", (event) => {
+      void invoke("take_deep_link_prompt");
+      void refreshHistory();
+      void open(event.payload);
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = getCurrentWebview().onDragDropEvent(async (event) => {
+      if (event.payload.type !== "drop" || event.payload.paths.length === 0) return;
+      try {
+        setImportResults(await invoke<FileImportResult[]>("import_files", { paths: event.payload.paths }));
+      } catch (error) {
+        console.error(error);
+        setStatus("导入失败");
+      }
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  useEffect(() => {
+    if (!importResults) return;
+    const imported = importResults.reduce((sum, result) => sum + result.imported, 0);
+    const duplicates = importResults.reduce((sum, result) => sum + result.duplicates, 0);
+    const failed = importResults.filter((result) => result.error);
+    const summary = `已导入 ${imported} 条，跳过重复 ${duplicates} 条`;
+    setStatus(
+      failed.length > 0
+        ? `${summary}；${failed.map((result) => `${result.path}: ${result.error}`).join("；")}`
+        : summary,
+    );
+    void refreshHistory();
+  }, [importResults]);
+
   const loadAnalyses = async (promptId: string) => {
     try {
       const items = await invoke<AnalysisRecord[]>("list_analyses", { promptId });
@@ -176,6 +308,10 @@ export default function App() {
           theme: (cls as any).theme ?? cls.topic ?? null,
           role: (cls as any).role ?? "",
           targetEntities: targets,
+          intent: (cls as any).intent ?? "other",
+          chainSteps: (cls as any).chain_steps ?? 0,
+          outputLanguage: (cls as any).output_language ?? null,
+          extractedFields: {},
         });
       } else {
         setAnalysis(null);
@@ -282,6 +418,16 @@ export default function App() {
     }
   };
 
+  const handleOpenWindow = async () => {
+    if (!activePromptId) return;
+    try {
+      await invoke<string>("open_prompt_window", { id: activePromptId });
+    } catch (error) {
+      console.error(error);
+      setStatus("无法打开窗口");
+    }
+  };
+
   const handleCommitAnalysis = async () => {
     if (!analysis) {
       setStatus("暂无可保存的分析");
@@ -355,7 +501,7 @@ export default function App() {
     setStatus("导出 CSV 中...");
     try {
       const path = await invoke<string>("export_prompts_csv", {
-        target_path: exportPath.trim() || null,
+        targetPath: exportPath.trim() || null,
       });
       setLastExportPath(path);
       setStatus(`已导出到 ${path}`);
@@ -393,22 +539,56 @@ export default function App() {
 
   const handleDeleteSelected = async () => {
     if (selectedIds.length === 0) {
-      setStatus("δѡ��Ҫɾ���ļ�¼");
+      setStatus("未选择要删除的记录");
       return;
     }
-    setStatus("����ɾ����...");
+    setStatus("批量删除中...");
     try {
-      await Promise.all(selectedIds.map((id) => invoke<boolean>("delete_prompt", { id })));
+      const deleted = await invoke<number>("delete_prompts", { ids: selectedIds });
       setHistory((prev) => prev.filter((item) => !selectedIds.includes(item.id)));
       if (activePromptId && selectedIds.includes(activePromptId)) {
         handleNewDraft();
       }
       setSelectedIds([]);
-      setStatus("����ɾ�����");
+      setStatus(`已删除 ${deleted} 条记录`);
+    } catch (error) {
+      console.error(error);
+      setStatus("批量删除失败");
+    }
+  };
+
+  const runBulkAction = async (command: string, args: Record<string, unknown>, label: string) => {
+    if (selectedIds.length === 0) {
+      setStatus("未选择记录");
+      return;
+    }
+    setStatus(`${label}中...`);
+    try {
+      const changed = await invoke<number>(command, { ids: selectedIds, ...args });
+      await refreshHistory();
+      setStatus(`${label}完成：更新 ${changed} 条`);
     } catch (error) {
       console.error(error);
-      setStatus("����ɾ��ʧ��");
+      setStatus(`${label}失败`);
+    }
+  };
+
+  const handleTagSelected = () => {
+    const tags = parseTags(bulkValue);
+    if (tags.length === 0) {
+      setStatus("请先填写要添加的标签");
+      return;
     }
+    void runBulkAction("tag_prompts", { tags }, "批量添加标签");
+  };
+
+  const handleMoveSelected = () => {
+    const collection = bulkValue.trim() || null;
+    void runBulkAction("move_prompts_to_collection", { collection }, collection ? "批量移到合集" : "批量移出合集");
+  };
+
+  const handleSetLanguageSelected = () => {
+    void runBulkAction("set_language", { lang: bulkValue.trim() || null }, "批量设置语言");
   };
 
   return (
@@ -461,6 +641,9 @@ export default function App() {
             <button type="button" className="ghost" onClick={handleNewDraft}>
               新建草稿
             </button>
+            <button type="button" className="ghost" onClick={handleOpenWindow} disabled={!activePromptId}>
+              新窗口打开
+            </button>
             <button type="button" className="ghost danger" onClick={handleDelete} disabled={!activePromptId}>
               删除
             </button>
@@ -553,6 +736,12 @@ export default function App() {
           <div className="panel-head">
             <h2>提示词仓库</h2>
             <div className="history-actions">
+              <input
+                className="history-search"
+                placeholder="搜索标题、标签或正文"
+                value={historyQuery}
+                onChange={(event) => setHistoryQuery(event.target.value)}
+              />
               <label className="page-size">
                 每页显示
                 <input
@@ -569,6 +758,21 @@ export default function App() {
               <button type="button" className="ghost danger" onClick={handleDeleteSelected} disabled={selectedIds.length === 0}>
                 删除选中
               </button>
+              <input
+                className="history-search"
+                placeholder="标签 / 合集 / 语言"
+                value={bulkValue}
+                onChange={(event) => setBulkValue(event.target.value)}
+              />
+              <button type="button" className="ghost" onClick={handleTagSelected} disabled={selectedIds.length === 0}>
+                加标签
+              </button>
+              <button type="button" className="ghost" onClick={handleMoveSelected} disabled={selectedIds.length === 0}>
+                移到合集
+              </button>
+              <button type="button" className="ghost" onClick={handleSetLanguageSelected} disabled={selectedIds.length === 0}>
+                设语言
+              </button>
               <button type="button" className="ghost" onClick={refreshHistory}>
                 刷新
               </button>
@@ -0,0 +1,190 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+import "./App.css";
+
+type WindowMode = "edit" | "compare" | "playground";
+
+type PromptWindowState = {
+  promptId: string;
+  mode: WindowMode;
+  compareWith: string | null;
+};
+
+type StoredPrompt = {
+  id: string;
+  title: string;
+  body: string;
+};
+
+type DiffSegment = {
+  kind: "equal" | "removed" | "added";
+  text: string;
+};
+
+type ComparisonReport = {
+  keywordSimilarity: number;
+  sharedKeywords: string[];
+  lengthDelta: number;
+  diff: DiffSegment[];
+};
+
+type Run = {
+  model: string;
+  output: string;
+  latency_ms: number;
+};
+
+const MODES: [WindowMode, string][] = [
+  ["edit", "编辑"],
+  ["compare", "对比"],
+  ["playground", "运行"],
+];
+
+export default function PromptWindow() {
+  const [windowState, setWindowState] = useState<PromptWindowState | null>(null);
+  const [mode, setMode] = useState<WindowMode>("edit");
+  const [prompt, setPrompt] = useState<StoredPrompt | null>(null);
+  const [title, setTitle] = useState("");
+  const [body, setBody] = useState("");
+  const [comparison, setComparison] = useState<ComparisonReport | null>(null);
+  const [variableNames, setVariableNames] = useState<string[]>([]);
+  const [variables, setVariables] = useState<Record<string, string>>({});
+  const [run, setRun] = useState<Run | null>(null);
+  const [status, setStatus] = useState("");
+
+  useEffect(() => {
+    invoke<PromptWindowState | null>("get_prompt_window_state").then(setWindowState);
+    // Sent to this window only, when the prompt is opened again from elsewhere.
+    const unlisten = getCurrentWindow().listen<PromptWindowState>("prompt-window:state", (event) =>
+      setWindowState(event.payload)
+    );
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  useEffect(() => {
+    if (!windowState) return;
+    setMode(windowState.mode);
+    invoke<StoredPrompt | null>("get_prompt", { id: windowState.promptId })
+      .then((record) => {
+        if (!record) {
+          setStatus("提示词不存在");
+          return;
+        }
+        setPrompt(record);
+        setTitle(record.title);
+        setBody(record.body);
+        void getCurrentWindow().setTitle(`${record.title} - PromptLab`);
+      })
+      .catch((err) => setStatus(String(err)));
+  }, [windowState]);
+
+  useEffect(() => {
+    if (!prompt || mode !== "compare" || !windowState?.compareWith) return;
+    invoke<ComparisonReport>("compare_prompts", { idA: prompt.id, idB: windowState.compareWith })
+      .then(setComparison)
+      .catch((err) => setStatus(String(err)));
+  }, [prompt, mode, windowState]);
+
+  useEffect(() => {
+    if (!prompt || mode !== "playground") return;
+    invoke<string[]>("prompt_template_variables", { id: prompt.id })
+      .then((names) => {
+        setVariableNames(names);
+        setVariables((current) => Object.fromEntries(names.map((name) => [name, current[name] ?? ""])));
+      })
+      .catch((err) => setStatus(String(err)));
+  }, [prompt, mode]);
+
+  const save = async () => {
+    if (!prompt) return;
+    setStatus("保存中...");
+    try {
+      const record = await invoke<StoredPrompt>("update_prompt", { id: prompt.id, payload: { title, body } });
+      setPrompt(record);
+      setStatus("已保存");
+    } catch (err) {
+      setStatus(String(err));
+    }
+  };
+
+  const execute = async () => {
+    if (!prompt) return;
+    setStatus("运行中...");
+    try {
+      const result = await invoke<Run>("run_prompt", { id: prompt.id, variables });
+      setRun(result);
+      setStatus(`${result.model} · ${result.latency_ms}ms`);
+    } catch (err) {
+      setStatus(String(err));
+    }
+  };
+
+  if (!prompt) {
+    return <div className="prompt-window">{status || "载入中..."}</div>;
+  }
+
+  return (
+    <div className="prompt-window">
+      <div className="prompt-window-modes">
+        {MODES.filter(([value]) => value !== "compare" || windowState?.compareWith).map(([value, label]) => (
+          <button
+            key={value}
+            type="button"
+            className={value === mode ? undefined : "ghost"}
+            onClick={() => setMode(value)}
+          >
+            {label}
+          </button>
+        ))}
+        <span className="prompt-window-status">{status}</span>
+      </div>
+
+      {mode === "edit" && (
+        <>
+          <input value={title} onChange={(event) => setTitle(event.target.value)} />
+          <textarea value={body} onChange={(event) => setBody(event.target.value)} spellCheck={false} />
+          <button type="button" onClick={save}>
+            保存
+          </button>
+        </>
+      )}
+
+      {mode === "compare" && comparison && (
+        <>
+          <p>
+            关键词相似度 {(comparison.keywordSimilarity * 100).toFixed(0)}% · 长度差 {comparison.lengthDelta}
+          </p>
+          <pre className="prompt-window-diff">
+            {comparison.diff.map((segment, index) => (
+              <span key={index} className={`diff-${segment.kind}`}>
+                {segment.text}
+              </span>
+            ))}
+          </pre>
+        </>
+      )}
+
+      {mode === "playground" && (
+        <>
+          {variableNames.map((name) => (
+            <label key={name}>
+              <span>{name}</span>
+              <textarea
+                rows={2}
+                value={variables[name] ?? ""}
+                onChange={(event) => setVariables({ ...variables, [name]: event.target.value })}
+              />
+            </label>
+          ))}
+          <button type="button" onClick={execute}>
+            运行
+          </button>
+          {run && <pre className="prompt-window-output">{run.output}</pre>}
+        </>
+      )}
+    </div>
+  );
+}
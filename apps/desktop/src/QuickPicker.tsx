@@ -0,0 +1,182 @@
+import { useEffect, useRef, useState, type KeyboardEvent } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import "./App.css";
+
+type QuickPick = {
+  promptId: string;
+  title: string;
+  tags: string[];
+  preview: string;
+  variables: string[];
+  score: number;
+};
+
+type TemplatePreview = {
+  text: string;
+  missing: string[];
+  unused: string[];
+};
+
+export default function QuickPicker() {
+  const [query, setQuery] = useState("");
+  const [picks, setPicks] = useState<QuickPick[]>([]);
+  const [selected, setSelected] = useState(0);
+  const [filling, setFilling] = useState<QuickPick | null>(null);
+  const [variables, setVariables] = useState<Record<string, string>>({});
+  const [preview, setPreview] = useState<TemplatePreview | null>(null);
+  const [error, setError] = useState("");
+  const searchRef = useRef<HTMLInputElement>(null);
+
+  useEffect(() => {
+    if (filling) return;
+    let cancelled = false;
+    invoke<QuickPick[]>("quick_search", { query })
+      .then((found) => {
+        if (cancelled) return;
+        setPicks(found);
+        setSelected(0);
+        setError("");
+      })
+      .catch((err) => {
+        if (!cancelled) setError(String(err));
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [query, filling]);
+
+  useEffect(() => {
+    if (!filling) {
+      setPreview(null);
+      return;
+    }
+    let cancelled = false;
+    invoke<TemplatePreview>("render_template", { id: filling.promptId, variables })
+      .then((rendered) => {
+        if (!cancelled) setPreview(rendered);
+      })
+      .catch((err) => {
+        if (!cancelled) setError(String(err));
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [filling, variables]);
+
+  useEffect(() => {
+    const unlisten = listen("picker:opened", () => {
+      setQuery("");
+      setFilling(null);
+      setError("");
+      searchRef.current?.focus();
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, []);
+
+  const paste = async (pick: QuickPick, values: Record<string, string> = {}) => {
+    try {
+      await invoke<boolean>("paste_prompt", { id: pick.promptId, variables: values });
+      setFilling(null);
+    } catch (err) {
+      setError(String(err));
+    }
+  };
+
+  const choose = (pick: QuickPick | undefined) => {
+    if (!pick) return;
+    if (pick.variables.length === 0) {
+      void paste(pick);
+      return;
+    }
+    setVariables(Object.fromEntries(pick.variables.map((name) => [name, ""])));
+    setFilling(pick);
+  };
+
+  const onKeyDown = (event: KeyboardEvent) => {
+    if (event.key === "Escape") {
+      event.preventDefault();
+      if (filling) {
+        setFilling(null);
+        searchRef.current?.focus();
+      } else {
+        void invoke("hide_quick_picker");
+      }
+      return;
+    }
+    if (filling) {
+      if (event.key === "Enter" && (event.ctrlKey || event.metaKey)) {
+        event.preventDefault();
+        void paste(filling, variables);
+      }
+      return;
+    }
+    if (event.key === "ArrowDown") {
+      event.preventDefault();
+      setSelected((index) => Math.min(index + 1, picks.length - 1));
+    } else if (event.key === "ArrowUp") {
+      event.preventDefault();
+      setSelected((index) => Math.max(index - 1, 0));
+    } else if (event.key === "Enter") {
+      event.preventDefault();
+      choose(picks[selected]);
+    }
+  };
+
+  return (
+    <div className="quick-picker" onKeyDown={onKeyDown}>
+      {filling ? (
+        <form
+          className="quick-picker-variables"
+          onSubmit={(event) => {
+            event.preventDefault();
+            void paste(filling, variables);
+          }}
+        >
+          <strong>{filling.title}</strong>
+          {filling.variables.map((name, index) => (
+            <label key={name}>
+              <span>{name}</span>
+              <textarea
+                autoFocus={index === 0}
+                rows={2}
+                value={variables[name] ?? ""}
+                onChange={(event) => setVariables({ ...variables, [name]: event.target.value })}
+              />
+            </label>
+          ))}
+          {preview && <pre className="quick-picker-preview">{preview.text}</pre>}
+          <button type="submit">粘贴（Ctrl+Enter）</button>
+        </form>
+      ) : (
+        <>
+          <input
+            ref={searchRef}
+            autoFocus
+            placeholder="搜索标题或标签…"
+            value={query}
+            onChange={(event) => setQuery(event.target.value)}
+          />
+          <ul>
+            {picks.map((pick, index) => (
+              <li
+                key={pick.promptId}
+                className={index === selected ? "selected" : undefined}
+                onMouseEnter={() => setSelected(index)}
+                onClick={() => choose(pick)}
+              >
+                <strong>{pick.title}</strong>
+                {pick.tags.length > 0 && <span className="quick-picker-tags">{pick.tags.join(" · ")}</span>}
+                <p>{pick.preview}</p>
+              </li>
+            ))}
+            {picks.length === 0 && <li className="quick-picker-empty">没有匹配的提示词</li>}
+          </ul>
+        </>
+      )}
+      {error && <p className="quick-picker-error">{error}</p>}
+    </div>
+  );
+}
@@ -1,10 +1,13 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
+import { getCurrentWindow } from "@tauri-apps/api/window";
 import App from "./App";
+import PromptWindow from "./PromptWindow";
+import QuickPicker from "./QuickPicker";
 import "./index.css";
 
-ReactDOM.createRoot(document.getElementById("root")!).render(
-  <React.StrictMode>
-    <App />
-  </React.StrictMode>
-);
+// The quick picker and the prompt windows load the same page as the main window.
+const label = getCurrentWindow().label;
+const root = label === "picker" ? <QuickPicker /> : label.startsWith("prompt-") ? <PromptWindow /> : <App />;
+
+ReactDOM.createRoot(document.getElementById("root")!).render(<React.StrictMode>{root}</React.StrictMode>);
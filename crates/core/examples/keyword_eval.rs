@@ -0,0 +1,35 @@
+//! Compare keyword extraction algorithms against a labeled fixture set.
+//!
+//! ```sh
+//! cargo run -p promptlab-core --example keyword_eval -- [fixtures.json] [top-k]
+//! ```
+//!
+//! Defaults to `fixtures/keyword_eval.json` and the top 8 keywords per prompt.
+
+use std::path::PathBuf;
+
+use promptlab_core::eval::{evaluate_keyword_algorithms, KeywordFixture};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/keyword_eval.json"));
+    let limit = args.next().map(|value| value.parse()).transpose()?.unwrap_or(8);
+
+    let fixtures: Vec<KeywordFixture> = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    println!("{} fixtures from {}, top {limit}", fixtures.len(), path.display());
+    println!("{:<10} {:>9} {:>9} {:>9} {:>6}", "algorithm", "precision", "recall", "f1", "hits");
+    for report in evaluate_keyword_algorithms(&fixtures, limit) {
+        println!(
+            "{:<10} {:>9.3} {:>9.3} {:>9.3} {:>6}",
+            report.algorithm.as_str(),
+            report.precision,
+            report.recall,
+            report.f1,
+            format!("{}/{}", report.hits, report.expected)
+        );
+    }
+    Ok(())
+}
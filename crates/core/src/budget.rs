@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::llm::ProviderKind;
+use crate::storage::{DailyTokenUsage, LlmTokenUsage};
+
+/// Price of a model per million tokens, in the budget's currency.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+/// Monthly limits on LLM usage; either limit may be left unset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BudgetSettings {
+    /// Prompt plus completion tokens per calendar month.
+    pub monthly_tokens: Option<u64>,
+    /// Spend per calendar month, priced with `prices`.
+    pub monthly_spend: Option<f64>,
+    /// Only shown to the user; prices and the spend limit share it.
+    pub currency: String,
+    /// Model name → price. Models without a price count towards the token limit only.
+    pub prices: BTreeMap<String, ModelPrice>,
+    /// Fractions of a limit at which an alert is raised, e.g. 0.8 for 80 %.
+    pub alert_thresholds: Vec<f64>,
+    /// Stop automatic classification once a limit is reached, until the user acknowledges.
+    pub pause_classification: bool,
+}
+
+impl Default for BudgetSettings {
+    fn default() -> Self {
+        Self {
+            monthly_tokens: None,
+            monthly_spend: None,
+            currency: "CNY".into(),
+            prices: BTreeMap::new(),
+            alert_thresholds: vec![0.5, 0.8, 1.0],
+            pause_classification: true,
+        }
+    }
+}
+
+impl BudgetSettings {
+    pub fn normalized(self) -> Self {
+        let mut thresholds = self.alert_thresholds;
+        thresholds.sort_by(f64::total_cmp);
+        thresholds.dedup();
+        let currency = self.currency.trim();
+        Self {
+            currency: if currency.is_empty() { "CNY".into() } else { currency.to_string() },
+            prices: self
+                .prices
+                .into_iter()
+                .map(|(model, price)| (model.trim().to_string(), price))
+                .filter(|(model, _)| !model.is_empty())
+                .collect(),
+            alert_thresholds: thresholds,
+            ..self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.monthly_tokens == Some(0) {
+            return Err("monthlyTokens must be at least 1".into());
+        }
+        if self.monthly_spend.is_some_and(|spend| !spend.is_finite() || spend <= 0.0) {
+            return Err("monthlySpend must be positive".into());
+        }
+        if self.alert_thresholds.iter().any(|threshold| !threshold.is_finite() || *threshold <= 0.0) {
+            return Err("alertThresholds must be positive fractions".into());
+        }
+        for (model, price) in &self.prices {
+            let valid = |value: f64| value.is_finite() && value >= 0.0;
+            if !valid(price.prompt) || !valid(price.completion) {
+                return Err(format!("price of {model} must not be negative"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One month's usage measured against [`BudgetSettings`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetReport {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub calls: u64,
+    pub tokens: u64,
+    pub spend: f64,
+    pub currency: String,
+    pub token_limit: Option<u64>,
+    pub spend_limit: Option<f64>,
+    /// The larger of tokens / token limit and spend / spend limit; `None` without limits.
+    pub used_ratio: Option<f64>,
+    /// Models used this month that have no price, so `spend` leaves them out.
+    pub unpriced_models: Vec<String>,
+}
+
+impl BudgetReport {
+    /// Whether a limit has been reached.
+    pub fn exceeded(&self) -> bool {
+        self.used_ratio.is_some_and(|ratio| ratio >= 1.0)
+    }
+}
+
+/// Total `usage` for `month` and compare it with the limits. Ollama runs on this machine,
+/// so its calls cost nothing and are left out.
+pub fn budget_report(month: &str, usage: &[LlmTokenUsage], settings: &BudgetSettings) -> BudgetReport {
+    let mut calls = 0;
+    let mut tokens = 0;
+    let mut spend = 0.0;
+    let mut unpriced = BTreeSet::new();
+    for entry in usage.iter().filter(|entry| entry.provider != ProviderKind::Ollama.as_str()) {
+        calls += entry.calls;
+        tokens += entry.prompt_tokens + entry.completion_tokens;
+        match cost(entry, settings) {
+            Some(cost) => spend += cost,
+            None if entry.prompt_tokens + entry.completion_tokens > 0 => {
+                unpriced.insert(entry.model.clone());
+            }
+            None => {}
+        }
+    }
+    let token_ratio = settings.monthly_tokens.map(|limit| tokens as f64 / limit as f64);
+    let spend_ratio = settings.monthly_spend.map(|limit| spend / limit);
+    let used_ratio = match (token_ratio, spend_ratio) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (ratio, None) | (None, ratio) => ratio,
+    };
+    BudgetReport {
+        month: month.to_string(),
+        calls,
+        tokens,
+        spend,
+        currency: settings.currency.clone(),
+        token_limit: settings.monthly_tokens,
+        spend_limit: settings.monthly_spend,
+        used_ratio,
+        unpriced_models: unpriced.into_iter().collect(),
+    }
+}
+
+/// Spend of one local calendar day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailySpend {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub calls: u64,
+    pub tokens: u64,
+    /// Priced like [`budget_report`]: models without a price count as free.
+    pub spend: f64,
+}
+
+/// Total `usage` per day, oldest first. As in [`budget_report`], Ollama calls are left out.
+pub fn daily_spend(usage: &[DailyTokenUsage], settings: &BudgetSettings) -> Vec<DailySpend> {
+    let mut days: BTreeMap<&str, DailySpend> = BTreeMap::new();
+    for DailyTokenUsage { date, usage: entry } in usage {
+        if entry.provider == ProviderKind::Ollama.as_str() {
+            continue;
+        }
+        let day = days.entry(date).or_insert_with(|| DailySpend {
+            date: date.clone(),
+            calls: 0,
+            tokens: 0,
+            spend: 0.0,
+        });
+        day.calls += entry.calls;
+        day.tokens += entry.prompt_tokens + entry.completion_tokens;
+        day.spend += cost(entry, settings).unwrap_or_default();
+    }
+    days.into_values().collect()
+}
+
+/// Price of `usage` with the model's price, `None` when the model has none.
+fn cost(usage: &LlmTokenUsage, settings: &BudgetSettings) -> Option<f64> {
+    let price = settings.prices.get(&usage.model)?;
+    Some((usage.prompt_tokens as f64 * price.prompt + usage.completion_tokens as f64 * price.completion) / 1_000_000.0)
+}
+
+/// The thresholds `ratio` has reached, lowest first.
+pub fn reached_thresholds(thresholds: &[f64], ratio: Option<f64>) -> Vec<f64> {
+    let Some(ratio) = ratio else {
+        return Vec::new();
+    };
+    let mut reached: Vec<f64> = thresholds.iter().copied().filter(|threshold| ratio >= *threshold).collect();
+    reached.sort_by(f64::total_cmp);
+    reached
+}
+
+/// Start of the calendar month containing `now`, in `now`'s time zone.
+pub fn month_start<Tz: TimeZone>(now: &DateTime<Tz>) -> DateTime<Utc> {
+    now.timezone()
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| now.with_timezone(&Utc))
+}
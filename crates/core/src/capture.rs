@@ -0,0 +1,425 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Built-in heuristics deciding whether a copied text is a potential prompt, listed in the
+/// order they are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureRule {
+    /// Rejects texts shorter than `min_chars`.
+    MinChars,
+    /// Accepts texts that open like a prompt ("You are", "你是", "Act as", ...) before the
+    /// limits below are checked, so long system prompts get through. Off by default.
+    PromptOpening,
+    /// Rejects texts longer than `max_chars`.
+    MaxChars,
+    /// Rejects texts with more than `max_lines` lines.
+    MaxLines,
+    /// Rejects chat transcripts: more than `max_chat_lines` lines containing a colon.
+    ChatTranscript,
+    /// Rejects link lists: more than `max_url_hits` URL markers.
+    Links,
+}
+
+impl CaptureRule {
+    pub const ALL: [CaptureRule; 6] = [
+        CaptureRule::MinChars,
+        CaptureRule::PromptOpening,
+        CaptureRule::MaxChars,
+        CaptureRule::MaxLines,
+        CaptureRule::ChatTranscript,
+        CaptureRule::Links,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CaptureRule::MinChars => "min_chars",
+            CaptureRule::PromptOpening => "prompt_opening",
+            CaptureRule::MaxChars => "max_chars",
+            CaptureRule::MaxLines => "max_lines",
+            CaptureRule::ChatTranscript => "chat_transcript",
+            CaptureRule::Links => "links",
+        }
+    }
+
+    pub fn enabled_by_default(self) -> bool {
+        self != CaptureRule::PromptOpening
+    }
+
+    /// Shown next to the switch in settings.
+    pub fn description(self) -> &'static str {
+        match self {
+            CaptureRule::MinChars => "过短的文本不捕获",
+            CaptureRule::PromptOpening => "以“你是”“You are”等角色设定开头的文本直接捕获，不受长度与行数限制",
+            CaptureRule::MaxChars => "过长的文本不捕获",
+            CaptureRule::MaxLines => "行数过多的文本不捕获",
+            CaptureRule::ChatTranscript => "多行带冒号、像聊天记录的文本不捕获",
+            CaptureRule::Links => "网址特征过多、像链接列表的文本不捕获",
+        }
+    }
+}
+
+/// What one rule says about a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleVerdict {
+    Accept,
+    Reject,
+    /// Leave the decision to the rules after it.
+    Neutral,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleOutcome {
+    pub rule: CaptureRule,
+    pub verdict: RuleVerdict,
+    pub reason: String,
+}
+
+/// Result of [`evaluate_capture`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureDecision {
+    pub accepted: bool,
+    /// The rule that decided; `None` when every enabled rule was neutral.
+    pub rule: Option<CaptureRule>,
+    pub reason: String,
+    /// Every enabled rule checked, up to and including the deciding one.
+    pub outcomes: Vec<RuleOutcome>,
+}
+
+/// Limits the capture rules apply to copied texts. Unset maxima are not checked.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CaptureFilter {
+    /// Characters after trimming.
+    pub min_chars: usize,
+    pub max_chars: Option<usize>,
+    pub max_lines: Option<usize>,
+    /// Lines containing a colon, beyond which the text looks like a chat transcript.
+    pub max_chat_lines: Option<usize>,
+    /// URL markers (`http://`, `https://`, `.com`, `.net`, `.org`), beyond which the text
+    /// looks like a list of links.
+    pub max_url_hits: Option<usize>,
+    /// Rules switched on or off; the others keep [`CaptureRule::enabled_by_default`].
+    pub rules: BTreeMap<CaptureRule, bool>,
+}
+
+impl Default for CaptureFilter {
+    fn default() -> Self {
+        Self {
+            min_chars: 8,
+            max_chars: Some(600),
+            max_lines: Some(12),
+            max_chat_lines: Some(5),
+            max_url_hits: Some(2),
+            rules: BTreeMap::new(),
+        }
+    }
+}
+
+impl CaptureFilter {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_chars == 0 {
+            return Err("minChars must be at least 1".into());
+        }
+        if self.max_chars.is_some_and(|max| max < self.min_chars) {
+            return Err("maxChars must not be less than minChars".into());
+        }
+        if self.max_lines == Some(0) {
+            return Err("maxLines must be at least 1".into());
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, rule: CaptureRule) -> bool {
+        self.rules.get(&rule).copied().unwrap_or(rule.enabled_by_default())
+    }
+}
+
+const URL_MARKERS: [&str; 5] = ["http://", "https://", ".com", ".net", ".org"];
+
+/// Openings of role and instruction prompts, matched case-insensitively.
+const PROMPT_OPENINGS: [&str; 14] = [
+    "you are",
+    "you're",
+    "act as",
+    "i want you to act",
+    "imagine you are",
+    "pretend you are",
+    "你是",
+    "你现在是",
+    "现在你是",
+    "请你扮演",
+    "请扮演",
+    "假设你是",
+    "作为一名",
+    "作为一个",
+];
+
+/// Run the enabled rules over the trimmed `text` in [`CaptureRule::ALL`] order. The first
+/// rule that accepts or rejects decides; a text no rule objects to is accepted. Empty texts
+/// are always rejected.
+pub fn evaluate_capture(text: &str, filter: &CaptureFilter) -> CaptureDecision {
+    let text = text.trim();
+    if text.is_empty() {
+        return CaptureDecision {
+            accepted: false,
+            rule: None,
+            reason: "空文本".into(),
+            outcomes: Vec::new(),
+        };
+    }
+    let mut outcomes = Vec::new();
+    for rule in CaptureRule::ALL.into_iter().filter(|rule| filter.is_enabled(*rule)) {
+        let (verdict, reason) = check_rule(rule, text, filter);
+        outcomes.push(RuleOutcome {
+            rule,
+            verdict,
+            reason: reason.clone(),
+        });
+        if verdict != RuleVerdict::Neutral {
+            return CaptureDecision {
+                accepted: verdict == RuleVerdict::Accept,
+                rule: Some(rule),
+                reason,
+                outcomes,
+            };
+        }
+    }
+    CaptureDecision {
+        accepted: true,
+        rule: None,
+        reason: "未触发任何规则".into(),
+        outcomes,
+    }
+}
+
+fn check_rule(rule: CaptureRule, text: &str, filter: &CaptureFilter) -> (RuleVerdict, String) {
+    let over = |count: usize, max: Option<usize>| max.filter(|max| count > *max);
+    match rule {
+        CaptureRule::MinChars => {
+            let chars = text.chars().count();
+            if chars < filter.min_chars {
+                (RuleVerdict::Reject, format!("少于 {} 字（{chars} 字）", filter.min_chars))
+            } else {
+                (RuleVerdict::Neutral, format!("{chars} 字"))
+            }
+        }
+        CaptureRule::PromptOpening => {
+            let opening: String = text.chars().take(20).flat_map(char::to_lowercase).collect();
+            match PROMPT_OPENINGS.iter().find(|cue| opening.starts_with(*cue)) {
+                Some(cue) => (RuleVerdict::Accept, format!("以“{cue}”开头，像提示词")),
+                None => (RuleVerdict::Neutral, "开头不像角色设定".into()),
+            }
+        }
+        CaptureRule::MaxChars => {
+            let chars = text.chars().count();
+            match over(chars, filter.max_chars) {
+                Some(max) => (RuleVerdict::Reject, format!("超过 {max} 字（{chars} 字）")),
+                None => (RuleVerdict::Neutral, format!("{chars} 字")),
+            }
+        }
+        CaptureRule::MaxLines => {
+            let lines = text.lines().count();
+            match over(lines, filter.max_lines) {
+                Some(max) => (RuleVerdict::Reject, format!("超过 {max} 行（{lines} 行）")),
+                None => (RuleVerdict::Neutral, format!("{lines} 行")),
+            }
+        }
+        CaptureRule::ChatTranscript => {
+            let chat_lines = text
+                .lines()
+                .filter(|line| line.contains(':') || line.contains('：'))
+                .count();
+            match over(chat_lines, filter.max_chat_lines) {
+                Some(max) => (
+                    RuleVerdict::Reject,
+                    format!("{chat_lines} 行带冒号，像聊天记录（上限 {max} 行）"),
+                ),
+                None => (RuleVerdict::Neutral, format!("{chat_lines} 行带冒号")),
+            }
+        }
+        CaptureRule::Links => {
+            let hits = URL_MARKERS.iter().filter(|marker| text.contains(*marker)).count();
+            match over(hits, filter.max_url_hits) {
+                Some(max) => (
+                    RuleVerdict::Reject,
+                    format!("含 {hits} 种网址特征，像链接列表（上限 {max}）"),
+                ),
+                None => (RuleVerdict::Neutral, format!("{hits} 种网址特征")),
+            }
+        }
+    }
+}
+
+/// `model` of the `is_prompt` verdicts [`classify_locally`] makes.
+pub const LOCAL_CLASSIFIER_MODEL: &str = "local-heuristic";
+
+/// Log-odds of a text with no signals at all being a prompt.
+const LOCAL_CLASSIFIER_BIAS: f64 = -1.0;
+
+/// Requests and instructions at the start of a line, matched case-insensitively.
+const IMPERATIVE_CUES: [&str; 30] = [
+    "请", "帮我", "帮忙", "给我", "写一", "生成", "翻译", "总结", "概括", "列出", "解释", "分析", "改写", "润色",
+    "设计", "write", "generate", "create", "explain", "summarize", "translate", "list", "rewrite", "describe",
+    "draft", "give me", "help me", "please", "make", "tell me",
+];
+
+/// Output format requirements.
+const FORMAT_CUES: [&str; 14] = [
+    "json", "markdown", "表格", "格式", "输出", "分点", "步骤", "字以内", "bullet", "format", "table",
+    "step by step", "output", "respond in",
+];
+
+/// Constraints on the answer.
+const CONSTRAINT_CUES: [&str; 13] = [
+    "不要", "必须", "至少", "不超过", "避免", "确保", "要求", "must", "should", "do not", "don't", "avoid",
+    "make sure",
+];
+
+/// Starts of lines that look like code rather than prose.
+const CODE_LINE_STARTS: [&str; 12] = [
+    "import ", "from ", "def ", "fn ", "function ", "#include", "const ", "let ", "var ", "public ", "class ",
+    "return ",
+];
+
+/// `{name}`, `{{name}}` and `<name>` template placeholders.
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\{\{?\s*[\w\p{Han} ]{1,30}\s*\}\}?|<[\w\p{Han}]{1,20}>").expect("valid placeholder pattern")
+});
+
+/// A feature [`classify_locally`] found, with what it added to the log-odds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PromptSignal {
+    pub feature: &'static str,
+    pub weight: f64,
+}
+
+/// Result of [`classify_locally`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LocalVerdict {
+    /// Estimated probability that the text is a prompt.
+    pub score: f64,
+    pub signals: Vec<PromptSignal>,
+}
+
+/// Estimate whether `text` is a prompt written for an AI from surface features: a role
+/// opening, imperative requests, format markers, constraints, placeholders and addressing the
+/// reader count for it; code, bare links, mostly non-letters and very short texts against.
+/// The weighted features are summed as log-odds. Used when no LLM classifies captures.
+pub fn classify_locally(text: &str) -> LocalVerdict {
+    let text = text.trim();
+    let lower = text.to_lowercase();
+    let lines: Vec<&str> = lower.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let line_starts = |cues: &[&str]| {
+        lines.iter().any(|line| {
+            let line = line.trim_start_matches(|c: char| c.is_ascii_digit() || "-*•.、)） ".contains(c));
+            cues.iter().any(|cue| line.starts_with(cue))
+        })
+    };
+    let contains_any = |cues: &[&str]| cues.iter().any(|cue| lower.contains(cue));
+    let opening: String = lower.chars().take(20).collect();
+    let chars = text.chars().count();
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+    let code_lines = lines
+        .iter()
+        .filter(|line| {
+            CODE_LINE_STARTS.iter().any(|start| line.starts_with(start))
+                || line.ends_with(';')
+                || line.ends_with('{')
+                || line.ends_with('}')
+        })
+        .count();
+    let words = lower.split_whitespace().count();
+
+    let features: [(&'static str, bool, f64); 10] = [
+        ("role", PROMPT_OPENINGS.iter().any(|cue| opening.starts_with(cue)), 2.0),
+        ("imperative", line_starts(&IMPERATIVE_CUES), 1.2),
+        ("format", contains_any(&FORMAT_CUES), 1.0),
+        ("constraint", contains_any(&CONSTRAINT_CUES), 0.6),
+        ("placeholder", PLACEHOLDER.is_match(text), 0.8),
+        (
+            "second_person",
+            lower.contains('你') || lower.split(|c: char| !c.is_alphanumeric()).any(|word| word == "you"),
+            0.5,
+        ),
+        ("code", !lines.is_empty() && code_lines * 10 >= lines.len() * 3, -2.0),
+        ("link", lower.contains("http") && words <= 3, -1.5),
+        ("non_letters", letters * 2 < chars, -1.5),
+        ("short", chars < 15, -1.0),
+    ];
+    let signals: Vec<PromptSignal> = features
+        .into_iter()
+        .filter(|(_, found, _)| *found)
+        .map(|(feature, _, weight)| PromptSignal { feature, weight })
+        .collect();
+    let log_odds = LOCAL_CLASSIFIER_BIAS + signals.iter().map(|signal| signal.weight).sum::<f64>();
+    LocalVerdict {
+        score: 1.0 / (1.0 + (-log_odds).exp()),
+        signals,
+    }
+}
+
+/// What happens to captures beyond the burst limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BurstOverflow {
+    /// Hold them in the inbox for review.
+    #[default]
+    Inbox,
+    /// Drop them; they stay in the clipboard history.
+    Skip,
+}
+
+/// Where a capture falls relative to a burst of copying, from [`BurstTracker::record`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BurstState {
+    /// Within the limit.
+    Calm,
+    /// The first capture beyond the limit.
+    Started,
+    /// A later capture while the burst lasts.
+    Ongoing,
+}
+
+/// Counts recent captures to spot bursts of copying, e.g. many snippets copied in a row while
+/// reading an article.
+#[derive(Debug, Default)]
+pub struct BurstTracker {
+    /// Times of the latest captures, oldest first; at most `limit + 1`.
+    recent: VecDeque<Instant>,
+    bursting: bool,
+}
+
+impl BurstTracker {
+    /// Record a capture at `now`. A burst lasts while more than `limit` captures fall within
+    /// `window`; a `limit` of 0 never bursts.
+    pub fn record(&mut self, now: Instant, limit: usize, window: Duration) -> BurstState {
+        while self
+            .recent
+            .front()
+            .is_some_and(|time| now.saturating_duration_since(*time) >= window)
+        {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(now);
+        while self.recent.len() > limit + 1 {
+            self.recent.pop_front();
+        }
+        if limit == 0 || self.recent.len() <= limit {
+            self.bursting = false;
+            return BurstState::Calm;
+        }
+        if std::mem::replace(&mut self.bursting, true) {
+            BurstState::Ongoing
+        } else {
+            BurstState::Started
+        }
+    }
+}
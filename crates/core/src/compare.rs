@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{
+    split_sentences, summarize_prompt_with_config, AnalysisConfig, PromptAnalysis, PromptIntent, VocabularyEntry,
+};
+
+/// Structured side-by-side comparison of two prompts, ready for a comparison view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonReport {
+    pub shared_keywords: Vec<String>,
+    pub keywords_only_in_a: Vec<String>,
+    pub keywords_only_in_b: Vec<String>,
+    /// Jaccard similarity of the two keyword sets (0.0 – 1.0).
+    pub keyword_similarity: f64,
+    pub role_a: String,
+    pub role_b: String,
+    pub same_role: bool,
+    pub intent_a: PromptIntent,
+    pub intent_b: PromptIntent,
+    pub length_a: usize,
+    pub length_b: usize,
+    /// `length_b - length_a`, in characters.
+    pub length_delta: i64,
+    pub shared_targets: Vec<String>,
+    pub targets_only_in_a: Vec<String>,
+    pub targets_only_in_b: Vec<String>,
+    /// Sentence-level diff turning `a` into `b`.
+    pub diff: Vec<DiffSegment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffKind {
+    Equal,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffSegment {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+pub fn compare_prompts_detailed(a: &str, b: &str) -> ComparisonReport {
+    compare_prompts_detailed_with_config(a, b, &[], &AnalysisConfig::default())
+}
+
+pub fn compare_prompts_detailed_with_config(
+    a: &str,
+    b: &str,
+    vocabulary: &[VocabularyEntry],
+    config: &AnalysisConfig,
+) -> ComparisonReport {
+    let left = summarize_prompt_with_config(a, vocabulary, config);
+    let right = summarize_prompt_with_config(b, vocabulary, config);
+
+    let (shared_keywords, keywords_only_in_a, keywords_only_in_b) =
+        partition(&meaningful_tags(&left), &meaningful_tags(&right));
+    let union = shared_keywords.len() + keywords_only_in_a.len() + keywords_only_in_b.len();
+    let keyword_similarity = if union == 0 {
+        0.0
+    } else {
+        shared_keywords.len() as f64 / union as f64
+    };
+    let (shared_targets, targets_only_in_a, targets_only_in_b) =
+        partition(&left.target_entities, &right.target_entities);
+
+    ComparisonReport {
+        shared_keywords,
+        keywords_only_in_a,
+        keywords_only_in_b,
+        keyword_similarity,
+        same_role: left.role == right.role,
+        role_a: left.role,
+        role_b: right.role,
+        intent_a: left.intent,
+        intent_b: right.intent,
+        length_a: left.length,
+        length_b: right.length,
+        length_delta: right.length as i64 - left.length as i64,
+        shared_targets,
+        targets_only_in_a,
+        targets_only_in_b,
+        diff: diff_sentences(a, b),
+    }
+}
+
+/// Sentence-granularity diff (LCS based) between two texts.
+pub fn diff_sentences(a: &str, b: &str) -> Vec<DiffSegment> {
+    let left = split_sentences(a);
+    let right = split_sentences(b);
+    let (n, m) = (left.len(), right.len());
+
+    // lcs[i][j] = length of the LCS of left[i..] and right[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if left[i] == right[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut segments = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && left[i] == right[j] {
+            push_segment(&mut segments, DiffKind::Equal, left[i]);
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push_segment(&mut segments, DiffKind::Removed, left[i]);
+            i += 1;
+        } else {
+            push_segment(&mut segments, DiffKind::Added, right[j]);
+            j += 1;
+        }
+    }
+    segments
+}
+
+fn push_segment(segments: &mut Vec<DiffSegment>, kind: DiffKind, sentence: &str) {
+    match segments.last_mut() {
+        Some(last) if last.kind == kind => {
+            last.text.push('\n');
+            last.text.push_str(sentence);
+        }
+        _ => segments.push(DiffSegment {
+            kind,
+            text: sentence.to_string(),
+        }),
+    }
+}
+
+fn meaningful_tags(analysis: &PromptAnalysis) -> Vec<String> {
+    analysis
+        .suggested_tags
+        .iter()
+        .filter(|tag| *tag != "general")
+        .cloned()
+        .collect()
+}
+
+fn partition(a: &[String], b: &[String]) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let left: BTreeSet<&String> = a.iter().collect();
+    let right: BTreeSet<&String> = b.iter().collect();
+    (
+        left.intersection(&right).map(|item| item.to_string()).collect(),
+        left.difference(&right).map(|item| item.to_string()).collect(),
+        right.difference(&left).map(|item| item.to_string()).collect(),
+    )
+}
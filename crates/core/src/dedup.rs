@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::tags::{find, union};
+use crate::text::normalize_text;
+
+/// MinHash signature length; split into [`BANDS`] bands for locality-sensitive hashing.
+/// Six rows per band find pairs at 0.8 similarity about 90 % of the time while texts that
+/// only share boilerplate (0.5) rarely become candidates.
+const SIGNATURE_LEN: usize = 48;
+const BANDS: usize = 8;
+const ROWS: usize = SIGNATURE_LEN / BANDS;
+
+/// Prompts whose texts are nearly the same.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// In input order.
+    pub prompt_ids: Vec<String>,
+    /// Lowest similarity among the pairs that linked the group.
+    pub similarity: f64,
+}
+
+/// Thresholds for [`find_near_duplicates`].
+#[derive(Debug, Clone)]
+pub struct DuplicateOptions {
+    /// Texts whose character shingles overlap at least this much (Jaccard) are linked.
+    pub min_similarity: f64,
+    /// Characters per shingle.
+    pub shingle_chars: usize,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.8,
+            shingle_chars: 3,
+        }
+    }
+}
+
+/// Group `(id, text)` pairs whose texts differ only slightly: whitespace, case, width,
+/// punctuation or a few edited words. Candidates come from MinHash banding, so the library
+/// is never compared pair by pair; every candidate pair is then checked exactly. Only groups
+/// of two or more are returned, most similar first.
+pub fn find_near_duplicates<'a>(
+    prompts: impl IntoIterator<Item = (&'a str, &'a str)>,
+    options: &DuplicateOptions,
+) -> Vec<DuplicateGroup> {
+    let (ids, shingles): (Vec<&str>, Vec<Vec<u64>>) = prompts
+        .into_iter()
+        .map(|(id, text)| (id, shingle_set(text, options.shingle_chars.max(1))))
+        .filter(|(_, shingles)| !shingles.is_empty())
+        .unzip();
+
+    let signatures: Vec<[u64; SIGNATURE_LEN]> = shingles.iter().map(|shingles| signature(shingles)).collect();
+    let mut buckets: HashMap<(usize, &[u64]), Vec<usize>> = HashMap::new();
+    for (position, signature) in signatures.iter().enumerate() {
+        for (band, rows) in signature.chunks(ROWS).enumerate() {
+            buckets.entry((band, rows)).or_default().push(position);
+        }
+    }
+
+    let mut parent: Vec<usize> = (0..ids.len()).collect();
+    let mut weakest: HashMap<usize, f64> = HashMap::new();
+    for (a, signature) in signatures.iter().enumerate() {
+        let candidates: HashSet<usize> = signature
+            .chunks(ROWS)
+            .enumerate()
+            .flat_map(|(band, rows)| buckets[&(band, rows)].iter().copied())
+            .filter(|&b| b > a)
+            .collect();
+        for b in candidates {
+            // Pairs already grouped through other members need no exact check.
+            if find(&mut parent, a) == find(&mut parent, b) {
+                continue;
+            }
+            let similarity = jaccard(&shingles[a], &shingles[b]);
+            if similarity >= options.min_similarity {
+                union(&mut parent, a, b);
+                let entry = weakest.entry(a).or_insert(1.0);
+                *entry = entry.min(similarity);
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for position in 0..ids.len() {
+        let root = find(&mut parent, position);
+        groups.entry(root).or_default().push(position);
+    }
+    let mut groups: Vec<DuplicateGroup> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| DuplicateGroup {
+            similarity: members
+                .iter()
+                .filter_map(|position| weakest.get(position))
+                .fold(1.0, |lowest, similarity| similarity.min(lowest)),
+            prompt_ids: members.into_iter().map(|position| ids[position].to_string()).collect(),
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| a.prompt_ids.cmp(&b.prompt_ids))
+    });
+    groups
+}
+
+/// The `(id, text)` pair most similar to `text`, with its similarity, if it reaches
+/// `options.min_similarity`. Every candidate is compared exactly; meant for checking one new
+/// text against the library.
+pub fn closest_match<'a>(
+    text: &str,
+    candidates: impl IntoIterator<Item = (&'a str, &'a str)>,
+    options: &DuplicateOptions,
+) -> Option<(&'a str, f64)> {
+    let size = options.shingle_chars.max(1);
+    let shingles = shingle_set(text, size);
+    if shingles.is_empty() {
+        return None;
+    }
+    candidates
+        .into_iter()
+        .map(|(id, candidate)| (id, jaccard(&shingles, &shingle_set(candidate, size))))
+        .filter(|(_, similarity)| *similarity >= options.min_similarity)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Sorted, distinct hashed character shingles of the normalized, lowercased text with
+/// whitespace removed. Texts shorter than one shingle become a single shingle.
+fn shingle_set(text: &str, size: usize) -> Vec<u64> {
+    let chars: Vec<char> = normalize_text(text)
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if chars.is_empty() {
+        return Vec::new();
+    }
+    if chars.len() <= size {
+        return vec![hash_chars(&chars)];
+    }
+    let mut shingles: Vec<u64> = chars.windows(size).map(hash_chars).collect();
+    shingles.sort_unstable();
+    shingles.dedup();
+    shingles
+}
+
+/// FNV-1a, so shingle hashes do not depend on the process.
+fn hash_chars(chars: &[char]) -> u64 {
+    chars.iter().fold(0xcbf2_9ce4_8422_2325, |hash, c| {
+        (hash ^ u64::from(*c)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn signature(shingles: &[u64]) -> [u64; SIGNATURE_LEN] {
+    let mut signature = [u64::MAX; SIGNATURE_LEN];
+    for shingle in shingles {
+        for (seed, slot) in signature.iter_mut().enumerate() {
+            *slot = (*slot).min(mix(shingle ^ (seed as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+        }
+    }
+    signature
+}
+
+/// SplitMix64 finalizer: one cheap independent-looking permutation per seed.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Jaccard similarity of two sorted, distinct shingle lists.
+fn jaccard(a: &[u64], b: &[u64]) -> f64 {
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
@@ -0,0 +1,427 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::tokens::{candidate_terms, extract_keywords, normalize_token, KeywordOptions};
+use crate::storage::AbPreference;
+use crate::text::normalize_text;
+
+/// One labeled example: a prompt and the tags a human would expect for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordFixture {
+    pub prompt: String,
+    pub expected_tags: Vec<String>,
+}
+
+/// Keyword ranking algorithms that can be compared against the fixtures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeywordAlgorithm {
+    /// Term frequency with vocabulary boost, i.e. what `summarize_prompt*` uses today.
+    Frequency,
+    /// Term frequency weighted by inverse document frequency across the fixture set.
+    TfIdf,
+    /// PageRank over a token co-occurrence graph.
+    TextRank,
+}
+
+impl KeywordAlgorithm {
+    pub const ALL: [KeywordAlgorithm; 3] = [
+        KeywordAlgorithm::Frequency,
+        KeywordAlgorithm::TfIdf,
+        KeywordAlgorithm::TextRank,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeywordAlgorithm::Frequency => "frequency",
+            KeywordAlgorithm::TfIdf => "tfidf",
+            KeywordAlgorithm::TextRank => "textrank",
+        }
+    }
+}
+
+/// Micro-averaged scores of one algorithm over the whole fixture set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlgorithmReport {
+    pub algorithm: KeywordAlgorithm,
+    pub fixtures: usize,
+    /// Tags returned by the algorithm, summed over all fixtures.
+    pub predicted: usize,
+    /// Expected tags, summed over all fixtures.
+    pub expected: usize,
+    /// Predicted tags that were expected.
+    pub hits: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+const TEXTRANK_WINDOW: usize = 5;
+const TEXTRANK_DAMPING: f64 = 0.85;
+const TEXTRANK_ITERATIONS: usize = 50;
+
+/// Score every algorithm in [`KeywordAlgorithm::ALL`] against `fixtures`, keeping the top
+/// `limit` keywords per prompt. Tags are compared after the same normalization the
+/// tokenizer applies, so "API" and "api" match.
+pub fn evaluate_keyword_algorithms(fixtures: &[KeywordFixture], limit: usize) -> Vec<AlgorithmReport> {
+    KeywordAlgorithm::ALL
+        .iter()
+        .map(|algorithm| evaluate_keyword_algorithm(fixtures, *algorithm, limit))
+        .collect()
+}
+
+pub fn evaluate_keyword_algorithm(
+    fixtures: &[KeywordFixture],
+    algorithm: KeywordAlgorithm,
+    limit: usize,
+) -> AlgorithmReport {
+    let options = KeywordOptions {
+        limit,
+        ..KeywordOptions::default()
+    };
+    let documents: Vec<Vec<String>> = fixtures
+        .iter()
+        .map(|fixture| candidate_terms(&fixture.prompt, &options))
+        .collect();
+    let idf = inverse_document_frequency(&documents);
+
+    let (mut predicted, mut expected, mut hits) = (0, 0, 0);
+    for (fixture, terms) in fixtures.iter().zip(&documents) {
+        let keywords = match algorithm {
+            KeywordAlgorithm::Frequency => extract_keywords(&fixture.prompt, &[], &options),
+            KeywordAlgorithm::TfIdf => rank_tfidf(terms, &idf, limit),
+            KeywordAlgorithm::TextRank => rank_textrank(terms, limit),
+        };
+        let wanted: HashSet<String> = fixture
+            .expected_tags
+            .iter()
+            .map(|tag| normalize_token(normalize_text(tag).trim()))
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        predicted += keywords.len();
+        expected += wanted.len();
+        hits += keywords.iter().filter(|keyword| wanted.contains(*keyword)).count();
+    }
+
+    let precision = ratio(hits, predicted);
+    let recall = ratio(hits, expected);
+    let f1 = if precision + recall > 0.0 {
+        2.0 * precision * recall / (precision + recall)
+    } else {
+        0.0
+    };
+    AlgorithmReport {
+        algorithm,
+        fixtures: fixtures.len(),
+        predicted,
+        expected,
+        hits,
+        precision,
+        recall,
+        f1,
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Smoothed IDF: `ln((N + 1) / (df + 1)) + 1`.
+fn inverse_document_frequency(documents: &[Vec<String>]) -> HashMap<String, f64> {
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for terms in documents {
+        let unique: BTreeSet<&str> = terms.iter().map(String::as_str).collect();
+        for term in unique {
+            *document_frequency.entry(term).or_insert(0) += 1;
+        }
+    }
+    let total = documents.len() as f64;
+    document_frequency
+        .into_iter()
+        .map(|(term, df)| (term.to_string(), ((total + 1.0) / (df as f64 + 1.0)).ln() + 1.0))
+        .collect()
+}
+
+fn rank_tfidf(terms: &[String], idf: &HashMap<String, f64>, limit: usize) -> Vec<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for term in terms {
+        *counts.entry(term.as_str()).or_insert(0) += 1;
+    }
+    let scores = counts
+        .into_iter()
+        .map(|(term, count)| {
+            let weight = idf.get(term).copied().unwrap_or(1.0);
+            (term.to_string(), count as f64 / terms.len() as f64 * weight)
+        })
+        .collect();
+    top_terms(scores, limit)
+}
+
+/// Undirected co-occurrence graph over a sliding window, weighted by co-occurrence count.
+fn rank_textrank(terms: &[String], limit: usize) -> Vec<String> {
+    let mut edges: HashMap<&str, HashMap<&str, f64>> = HashMap::new();
+    for (idx, term) in terms.iter().enumerate() {
+        edges.entry(term.as_str()).or_default();
+        for other in terms.iter().skip(idx + 1).take(TEXTRANK_WINDOW - 1) {
+            if other == term {
+                continue;
+            }
+            *edges.entry(term.as_str()).or_default().entry(other.as_str()).or_insert(0.0) += 1.0;
+            *edges.entry(other.as_str()).or_default().entry(term.as_str()).or_insert(0.0) += 1.0;
+        }
+    }
+    let out_weight: HashMap<&str, f64> = edges
+        .iter()
+        .map(|(node, neighbours)| (*node, neighbours.values().sum()))
+        .collect();
+
+    let mut scores: HashMap<&str, f64> = edges.keys().map(|node| (*node, 1.0)).collect();
+    for _ in 0..TEXTRANK_ITERATIONS {
+        let mut next = HashMap::with_capacity(scores.len());
+        let mut delta: f64 = 0.0;
+        for (node, neighbours) in &edges {
+            let incoming: f64 = neighbours
+                .iter()
+                .map(|(neighbour, weight)| weight / out_weight[neighbour] * scores[neighbour])
+                .sum();
+            let score = (1.0 - TEXTRANK_DAMPING) + TEXTRANK_DAMPING * incoming;
+            delta = delta.max((score - scores[node]).abs());
+            next.insert(*node, score);
+        }
+        scores = next;
+        if delta < 1e-6 {
+            break;
+        }
+    }
+
+    top_terms(
+        scores.into_iter().map(|(term, score)| (term.to_string(), score)).collect(),
+        limit,
+    )
+}
+
+fn top_terms(mut scores: Vec<(String, f64)>, limit: usize) -> Vec<String> {
+    scores.sort_by(|(a_term, a_score), (b_term, b_score)| b_score.total_cmp(a_score).then_with(|| a_term.cmp(b_term)));
+    scores.into_iter().take(limit).map(|(term, _)| term).collect()
+}
+
+/// Win rates of an A/B test over the cases the user has judged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbSummary {
+    pub cases: usize,
+    pub decided: usize,
+    pub wins_a: usize,
+    pub wins_b: usize,
+    pub ties: usize,
+    /// Share of decided cases won by A, counting a tie as half a win; 0 before any decision.
+    pub win_rate_a: f64,
+    pub win_rate_b: f64,
+}
+
+pub fn summarize_ab_preferences(preferences: impl IntoIterator<Item = Option<AbPreference>>) -> AbSummary {
+    let (mut cases, mut wins_a, mut wins_b, mut ties) = (0, 0, 0, 0);
+    for preference in preferences {
+        cases += 1;
+        match preference {
+            Some(AbPreference::A) => wins_a += 1,
+            Some(AbPreference::B) => wins_b += 1,
+            Some(AbPreference::Tie) => ties += 1,
+            None => {}
+        }
+    }
+    let decided = wins_a + wins_b + ties;
+    let rate = |wins: usize| {
+        if decided == 0 {
+            0.0
+        } else {
+            (wins as f64 + ties as f64 / 2.0) / decided as f64
+        }
+    };
+    AbSummary {
+        cases,
+        decided,
+        wins_a,
+        wins_b,
+        ties,
+        win_rate_a: rate(wins_a),
+        win_rate_b: rate(wins_b),
+    }
+}
+
+/// A classifier score next to the human label, for [`calibration_report`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LabeledPrediction {
+    /// Predicted probability that the text is a prompt, 0.0 – 1.0.
+    pub score: f64,
+    pub label: bool,
+}
+
+/// Samples whose score falls in `[lower, upper)` (the last bin includes 1.0).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub samples: usize,
+    /// Average predicted score; matches `positive_rate` when the classifier is calibrated.
+    pub mean_score: Option<f64>,
+    /// Share of samples labeled as prompts.
+    pub positive_rate: Option<f64>,
+    /// Share of samples the threshold classifies correctly.
+    pub accuracy: Option<f64>,
+}
+
+/// One point of the ROC curve: predict "prompt" when `score >= threshold`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RocPoint {
+    pub threshold: f64,
+    pub true_positive_rate: f64,
+    pub false_positive_rate: f64,
+    pub accuracy: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfusionMatrix {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub true_negatives: usize,
+    pub false_negatives: usize,
+}
+
+impl ConfusionMatrix {
+    pub fn at(samples: &[LabeledPrediction], threshold: f64) -> Self {
+        let mut matrix = ConfusionMatrix::default();
+        for sample in samples {
+            match (sample.score >= threshold, sample.label) {
+                (true, true) => matrix.true_positives += 1,
+                (true, false) => matrix.false_positives += 1,
+                (false, false) => matrix.true_negatives += 1,
+                (false, true) => matrix.false_negatives += 1,
+            }
+        }
+        matrix
+    }
+
+    pub fn total(&self) -> usize {
+        self.true_positives + self.false_positives + self.true_negatives + self.false_negatives
+    }
+
+    pub fn accuracy(&self) -> Option<f64> {
+        let total = self.total();
+        (total > 0).then(|| (self.true_positives + self.true_negatives) as f64 / total as f64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationReport {
+    pub samples: usize,
+    pub positives: usize,
+    pub threshold: f64,
+    pub bins: Vec<CalibrationBin>,
+    /// From threshold 1.0 down to 0.0 in steps of 0.05.
+    pub roc: Vec<RocPoint>,
+    /// Area under the ROC curve; `None` unless both labels are present.
+    pub auc: Option<f64>,
+    /// At `threshold`.
+    pub confusion: ConfusionMatrix,
+    pub accuracy: Option<f64>,
+}
+
+const ROC_STEPS: usize = 20;
+
+/// Bin labeled predictions by score into `bins` equal-width buckets and describe how well
+/// the scores separate prompts from non-prompts, at `threshold` and across thresholds.
+pub fn calibration_report(samples: &[LabeledPrediction], threshold: f64, bins: usize) -> CalibrationReport {
+    let bins = bins.max(1);
+    let positives = samples.iter().filter(|sample| sample.label).count();
+    let negatives = samples.len() - positives;
+    let mean = |sum: f64, count: usize| (count > 0).then(|| sum / count as f64);
+
+    let bins = (0..bins)
+        .map(|index| {
+            let lower = index as f64 / bins as f64;
+            let upper = (index + 1) as f64 / bins as f64;
+            let last = index + 1 == bins;
+            let members: Vec<&LabeledPrediction> = samples
+                .iter()
+                .filter(|sample| sample.score >= lower && (sample.score < upper || (last && sample.score <= upper)))
+                .collect();
+            let count = members.len();
+            let correct = members
+                .iter()
+                .filter(|sample| (sample.score >= threshold) == sample.label)
+                .count();
+            CalibrationBin {
+                lower,
+                upper,
+                samples: count,
+                mean_score: mean(members.iter().map(|sample| sample.score).sum(), count),
+                positive_rate: mean(members.iter().filter(|sample| sample.label).count() as f64, count),
+                accuracy: mean(correct as f64, count),
+            }
+        })
+        .collect();
+
+    let roc = (0..=ROC_STEPS)
+        .rev()
+        .map(|step| {
+            let threshold = step as f64 / ROC_STEPS as f64;
+            let matrix = ConfusionMatrix::at(samples, threshold);
+            let rate = |hits: usize, total: usize| if total == 0 { 0.0 } else { hits as f64 / total as f64 };
+            RocPoint {
+                threshold,
+                true_positive_rate: rate(matrix.true_positives, positives),
+                false_positive_rate: rate(matrix.false_positives, negatives),
+                accuracy: matrix.accuracy().unwrap_or(0.0),
+            }
+        })
+        .collect();
+
+    let confusion = ConfusionMatrix::at(samples, threshold);
+    CalibrationReport {
+        samples: samples.len(),
+        positives,
+        threshold,
+        bins,
+        roc,
+        auc: roc_auc(samples, positives, negatives),
+        accuracy: confusion.accuracy(),
+        confusion,
+    }
+}
+
+/// Probability that a random prompt scores above a random non-prompt (ties count half),
+/// which equals the exact area under the ROC curve.
+fn roc_auc(samples: &[LabeledPrediction], positives: usize, negatives: usize) -> Option<f64> {
+    if positives == 0 || negatives == 0 {
+        return None;
+    }
+    let mut sorted: Vec<&LabeledPrediction> = samples.iter().collect();
+    sorted.sort_by(|a, b| a.score.total_cmp(&b.score));
+    // Sum of (1-based, tie-averaged) ranks of the positives.
+    let mut rank_sum = 0.0;
+    let mut start = 0;
+    while start < sorted.len() {
+        let mut end = start;
+        while end + 1 < sorted.len() && sorted[end + 1].score == sorted[start].score {
+            end += 1;
+        }
+        let average_rank = (start + end) as f64 / 2.0 + 1.0;
+        rank_sum += average_rank * sorted[start..=end].iter().filter(|sample| sample.label).count() as f64;
+        start = end + 1;
+    }
+    let (positives, negatives) = (positives as f64, negatives as f64);
+    Some((rank_sum - positives * (positives + 1.0) / 2.0) / (positives * negatives))
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Why an LLM rewrite was refused instead of saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    Empty,
+    /// The backend hit its token limit or the text stops inside a code block.
+    Truncated,
+    /// The output repeats our own instructions instead of (or besides) the rewrite.
+    LeakedInstructions,
+    LengthBlowup,
+}
+
+impl RejectionReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RejectionReason::Empty => "empty",
+            RejectionReason::Truncated => "truncated",
+            RejectionReason::LeakedInstructions => "leaked_instructions",
+            RejectionReason::LengthBlowup => "length_blowup",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{detail}")]
+pub struct OutputRejection {
+    pub reason: RejectionReason,
+    pub detail: String,
+}
+
+impl OutputRejection {
+    fn new(reason: RejectionReason, detail: impl Into<String>) -> Self {
+        Self {
+            reason,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Limits for [`check_llm_output`].
+#[derive(Debug, Clone)]
+pub struct GuardrailOptions {
+    /// The output may be at most this many times longer than the original...
+    pub max_length_ratio: f64,
+    /// ...or this many characters longer, whichever allows more, so short prompts can grow.
+    pub length_slack: usize,
+    /// Instruction fragments shorter than this (in non-space characters) are not checked
+    /// for leaks; short phrases overlap with ordinary prompts too easily.
+    pub min_leak_chars: usize,
+}
+
+impl Default for GuardrailOptions {
+    fn default() -> Self {
+        Self {
+            max_length_ratio: 4.0,
+            length_slack: 400,
+            min_leak_chars: 6,
+        }
+    }
+}
+
+const FRAGMENT_SEPARATORS: &[char] = &['，', '。', '；', '！', '？', '、', ',', ';', '!', '?', '\n'];
+
+/// Check a model's rewrite of `original` before it is stored. `truncated` is the backend's
+/// own stop-at-token-limit flag; `instructions` are the system prompt and any scaffolding we
+/// put around the user text (e.g. "原始提示词："), which must not show up in the output
+/// unless the original already contained them.
+pub fn check_llm_output(
+    original: &str,
+    output: &str,
+    truncated: bool,
+    instructions: &[&str],
+    options: &GuardrailOptions,
+) -> Result<(), OutputRejection> {
+    let output = output.trim();
+    if output.is_empty() {
+        return Err(OutputRejection::new(RejectionReason::Empty, "模型返回了空结果"));
+    }
+    if truncated {
+        return Err(OutputRejection::new(RejectionReason::Truncated, "模型输出达到长度上限被截断"));
+    }
+    let open_fence = |text: &str| !text.matches("```").count().is_multiple_of(2);
+    if open_fence(output) && !open_fence(original) {
+        return Err(OutputRejection::new(RejectionReason::Truncated, "模型输出在代码块中途结束"));
+    }
+    if let Some(fragment) = leaked_fragment(original, output, instructions, options.min_leak_chars) {
+        return Err(OutputRejection::new(
+            RejectionReason::LeakedInstructions,
+            format!("模型输出包含系统指令: {fragment}"),
+        ));
+    }
+    let original_chars = original.trim().chars().count();
+    let output_chars = output.chars().count();
+    let allowed = ((original_chars as f64) * options.max_length_ratio)
+        .max((original_chars + options.length_slack) as f64);
+    if output_chars as f64 > allowed {
+        return Err(OutputRejection::new(
+            RejectionReason::LengthBlowup,
+            format!("模型输出过长: {output_chars} 字（原文 {original_chars} 字）"),
+        ));
+    }
+    Ok(())
+}
+
+fn leaked_fragment<'a>(original: &str, output: &str, instructions: &[&'a str], min_chars: usize) -> Option<&'a str> {
+    instructions
+        .iter()
+        .flat_map(|text| text.split(FRAGMENT_SEPARATORS))
+        .map(str::trim)
+        .filter(|fragment| fragment.chars().filter(|c| !c.is_whitespace()).count() >= min_chars)
+        .find(|fragment| output.contains(fragment) && !original.contains(fragment))
+}
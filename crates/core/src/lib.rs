@@ -1,55 +1,19 @@
 pub mod analysis {
-    use jieba_rs::Jieba;
-    use once_cell::sync::Lazy;
     use serde::{Deserialize, Serialize};
-    use std::collections::{HashMap, HashSet};
+    use serde_json::Value;
+    use std::collections::{BTreeMap, HashSet};
     use uuid::Uuid;
 
-    static TOKENIZER: Lazy<Jieba> = Lazy::new(Jieba::new);
-    static STOPWORDS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
-        [
-            "",
-            "\u{7684}",
-            "\u{4e86}",
-            "\u{548c}",
-            "\u{4e0e}",
-            "\u{5728}",
-            "\u{53ca}",
-            "\u{4ee5}\u{53ca}",
-            "\u{9700}\u{8981}",
-            "\u{6211}\u{4eec}",
-            "\u{7528}\u{6237}",
-            "\u{8fdb}\u{884c}",
-            "\u{5e0c}\u{671b}",
-            "\u{8bf7}",
-            "\u{4f7f}\u{7528}",
-            "\u{8fd9}\u{4e2a}",
-            "\u{90a3}\u{4e2a}",
-            "\u{8fd9}\u{4e9b}",
-            "\u{90a3}\u{4e9b}",
-            "\u{4e00}\u{4e0b}",
-            "\u{4e00}\u{4e2a}",
-            "\u{5982}\u{4f55}",
-            "\u{600e}\u{4e48}",
-            "\u{5417}",
-            "\u{5462}",
-            "\u{554a}",
-            "\u{54e6}",
-            "the",
-            "and",
-            "or",
-            "for",
-            "with",
-            "into",
-            "from",
-            "to",
-            "of",
-            "is",
-            "are",
-        ]
-        .into_iter()
-        .collect()
-    });
+    use self::tokens::{
+        extract_keywords, is_meaningful, is_meaningful_str, normalize_token, tokenize_normalized, KeywordOptions,
+    };
+    use crate::rules::{apply_extraction_rules, ExtractionRule};
+    use crate::text::normalize_text;
+
+    /// Version of the local extraction logic. Bump whenever tokenization, keyword ranking,
+    /// summary, role/target or intent rules change so stored analyses can be refreshed.
+    pub const ANALYZER_VERSION: u32 = 5;
+
     const TARGET_MARKERS: [&str; 7] = [
         "\u{9762}\u{5411}",
         "\u{9488}\u{5bf9}",
@@ -71,36 +35,317 @@ pub mod analysis {
         pub theme: Option<String>,
         pub role: String,
         pub target_entities: Vec<String>,
+        pub intent: PromptIntent,
+        /// Number of explicit steps when the prompt is a multi-step chain ("第一步…", "Step 1:"), else 0.
+        pub chain_steps: usize,
+        /// Language the prompt asks the answer to be written in ("用中文回答" → "zh"), as an
+        /// ISO 639-1 code. Independent of the language the prompt itself is written in.
+        pub output_language: Option<String>,
+        /// Values captured by the user's [`ExtractionRule`]s, keyed by rule field.
+        pub extracted_fields: BTreeMap<String, Value>,
+    }
+
+    /// What a prompt asks the model to do, independent of its topic.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum PromptIntent {
+        Generation,
+        Rewriting,
+        Extraction,
+        Coding,
+        Translation,
+        RolePlay,
+        Evaluation,
+        #[default]
+        Other,
+    }
+
+    impl PromptIntent {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                PromptIntent::Generation => "generation",
+                PromptIntent::Rewriting => "rewriting",
+                PromptIntent::Extraction => "extraction",
+                PromptIntent::Coding => "coding",
+                PromptIntent::Translation => "translation",
+                PromptIntent::RolePlay => "rolePlay",
+                PromptIntent::Evaluation => "evaluation",
+                PromptIntent::Other => "other",
+            }
+        }
+    }
+
+    /// How `PromptAnalysis.summary` is produced.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub enum SummaryStrategy {
+        /// "提示词概览：" followed by the first N characters.
+        #[default]
+        FirstChars,
+        /// The first complete sentence.
+        FirstSentence,
+        /// Sentences that mention the top keywords, stitched in original order.
+        KeywordStitched,
+        /// "角色：…；任务：…" synthesized from the detected role and the first task sentence.
+        RoleTask,
+    }
+
+    /// User-tunable knobs applied on top of the built-in analysis rules.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase", default)]
+    pub struct AnalysisConfig {
+        /// Terms that must never surface as suggested tags (internal codenames, profanity, ...).
+        pub suppressed_terms: Vec<String>,
+        pub summary_strategy: SummaryStrategy,
+        /// Maximum summary length in characters (excluding the strategy prefix).
+        pub summary_max_length: usize,
+        /// Derive `PromptAnalysis.id` from the content instead of a random UUID,
+        /// see [`deterministic_analysis_id`].
+        pub deterministic_ids: bool,
+        /// Per-term boost factors for vocabulary terms, optionally limited to prompt categories.
+        pub vocabulary_boosts: Vec<VocabularyBoost>,
+        /// User regex rules evaluated on every analysis, see [`crate::rules`].
+        pub extraction_rules: Vec<ExtractionRule>,
+        /// Extra rules from the user's `analysis.toml`. Loaded from that file, never persisted here.
+        #[serde(skip)]
+        pub overrides: AnalysisOverrides,
+    }
+
+    impl Default for AnalysisConfig {
+        fn default() -> Self {
+            Self {
+                suppressed_terms: Vec::new(),
+                summary_strategy: SummaryStrategy::default(),
+                summary_max_length: DEFAULT_SUMMARY_MAX_LENGTH,
+                deterministic_ids: false,
+                vocabulary_boosts: Vec::new(),
+                extraction_rules: Vec::new(),
+                overrides: AnalysisOverrides::default(),
+            }
+        }
+    }
+
+    pub const DEFAULT_SUMMARY_MAX_LENGTH: usize = 160;
+
+    /// A vocabulary term plus alternative spellings that count as the same term
+    /// ("大语言模型" ↔ "llm" ↔ "大模型"). Matching any spelling boosts `term`, and
+    /// suggested tags always use `term`.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct VocabularyEntry {
+        pub term: String,
+        #[serde(default)]
+        pub aliases: Vec<String>,
+        /// Frequency multiplier when the term is found; `None` uses [`DEFAULT_VOCABULARY_BOOST`].
+        /// A matching category-scoped [`VocabularyBoost`] takes precedence.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub weight: Option<usize>,
+    }
+
+    impl VocabularyEntry {
+        pub fn new(term: impl Into<String>) -> Self {
+            Self {
+                term: term.into(),
+                aliases: Vec::new(),
+                weight: None,
+            }
+        }
+
+        /// The canonical term followed by its aliases.
+        pub fn spellings(&self) -> impl Iterator<Item = &str> {
+            std::iter::once(self.term.as_str()).chain(self.aliases.iter().map(String::as_str))
+        }
+    }
+
+    impl From<&str> for VocabularyEntry {
+        fn from(term: &str) -> Self {
+            Self::new(term)
+        }
+    }
+
+    impl From<String> for VocabularyEntry {
+        fn from(term: String) -> Self {
+            Self::new(term)
+        }
+    }
+
+    /// Frequency multiplier for a vocabulary term found in the prompt when neither its entry
+    /// weight nor a [`VocabularyBoost`] says otherwise.
+    pub const DEFAULT_VOCABULARY_BOOST: usize = 3;
+
+    /// Boost settings for one vocabulary term: "RAG" ×5, but only in 编程/数据 prompts.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct VocabularyBoost {
+        pub term: String,
+        /// Categories from [`PROMPT_CATEGORIES`] the boost applies to. Empty means every prompt;
+        /// otherwise prompts outside these categories get no boost for the term at all.
+        #[serde(default)]
+        pub categories: Vec<String>,
+        pub factor: usize,
+    }
+
+    /// Longest accepted entry in `analysis.toml`, in characters.
+    const MAX_OVERRIDE_ENTRY_CHARS: usize = 32;
+
+    /// Extra stopwords, target markers and role patterns merged on top of the built-in rules.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase", default)]
+    pub struct AnalysisOverrides {
+        pub stopwords: Vec<String>,
+        pub target_markers: Vec<String>,
+        pub role_patterns: Vec<String>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    struct AnalysisOverridesFile {
+        stopwords: Vec<String>,
+        target_markers: Vec<String>,
+        role_patterns: Vec<String>,
+    }
+
+    impl AnalysisOverrides {
+        /// Parse the contents of `analysis.toml`:
+        ///
+        /// ```toml
+        /// stopwords = ["请问", "帮我"]
+        /// target_markers = ["面向于"]
+        /// role_patterns = ["act as"]
+        /// ```
+        ///
+        /// Syntax errors and unknown keys reject the whole file. Invalid entries (empty,
+        /// too long, whitespace inside a stopword or marker) are dropped and reported,
+        /// so the rest of the file still applies.
+        pub fn from_toml(source: &str) -> Result<(Self, Vec<String>), toml::de::Error> {
+            let file: AnalysisOverridesFile = toml::from_str(source)?;
+            let mut errors = Vec::new();
+            let overrides = Self {
+                stopwords: validate_override_entries("stopwords", file.stopwords, false, &mut errors),
+                target_markers: validate_override_entries("target_markers", file.target_markers, false, &mut errors),
+                role_patterns: validate_override_entries("role_patterns", file.role_patterns, true, &mut errors),
+            };
+            Ok((overrides, errors))
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.stopwords.is_empty() && self.target_markers.is_empty() && self.role_patterns.is_empty()
+        }
+    }
+
+    fn validate_override_entries(
+        key: &str,
+        entries: Vec<String>,
+        allow_spaces: bool,
+        errors: &mut Vec<String>,
+    ) -> Vec<String> {
+        let mut accepted: Vec<String> = Vec::new();
+        for (idx, entry) in entries.iter().enumerate() {
+            let cleaned = normalize_text(entry).trim().to_lowercase();
+            if cleaned.is_empty() {
+                errors.push(format!("{key}[{idx}]: entry is empty"));
+            } else if cleaned.chars().count() > MAX_OVERRIDE_ENTRY_CHARS {
+                errors.push(format!(
+                    "{key}[{idx}]: \"{entry}\" is longer than {MAX_OVERRIDE_ENTRY_CHARS} characters"
+                ));
+            } else if !allow_spaces && cleaned.chars().any(char::is_whitespace) {
+                errors.push(format!("{key}[{idx}]: \"{entry}\" must be a single word"));
+            } else if !accepted.contains(&cleaned) {
+                accepted.push(cleaned);
+            }
+        }
+        accepted
+    }
+
+    /// Namespace for content-derived analysis ids (UUID v5).
+    const ANALYSIS_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6c1f_2a7e_93d4_4b0e_9f51_0d2c_8a7b_3e64);
+
+    /// Stable id for an analysis run: identical body (after normalization), vocabulary,
+    /// analysis settings and [`ANALYZER_VERSION`] always yield the same UUID.
+    pub fn deterministic_analysis_id(body: &str, vocabulary: &[VocabularyEntry], config: &AnalysisConfig) -> String {
+        let mut terms: Vec<String> = vocabulary
+            .iter()
+            .map(|entry| {
+                let mut aliases: Vec<String> = entry.aliases.iter().map(|alias| normalize_text(alias.trim())).collect();
+                aliases.sort();
+                format!("{}={}*{:?}", normalize_text(entry.term.trim()), aliases.join("|"), entry.weight)
+            })
+            .collect();
+        terms.sort();
+        terms.dedup();
+        let mut suppressed = config.suppressed_terms.clone();
+        suppressed.sort();
+
+        let overrides = &config.overrides;
+        let mut boosts = config.vocabulary_boosts.clone();
+        boosts.sort_by(|a, b| a.term.cmp(&b.term));
+        let rules: Vec<(&str, &str)> = config
+            .extraction_rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .map(|rule| (rule.field.as_str(), rule.pattern.as_str()))
+            .collect();
+
+        let fingerprint = format!(
+            "v{ANALYZER_VERSION}\u{0}{}\u{0}{}\u{0}{}\u{0}{:?}:{}\u{0}{}\u{0}{}\u{0}{}\u{0}{:?}\u{0}{:?}",
+            normalize_text(body).trim(),
+            terms.join("\u{1f}"),
+            suppressed.join("\u{1f}"),
+            config.summary_strategy,
+            config.summary_max_length,
+            overrides.stopwords.join("\u{1f}"),
+            overrides.target_markers.join("\u{1f}"),
+            overrides.role_patterns.join("\u{1f}"),
+            boosts,
+            rules,
+        );
+        Uuid::new_v5(&ANALYSIS_ID_NAMESPACE, fingerprint.as_bytes()).to_string()
     }
 
     pub fn summarize_prompt(body: &str) -> PromptAnalysis {
         summarize_prompt_with_vocab(body, &[])
     }
 
-    pub fn summarize_prompt_with_vocab(body: &str, vocabulary: &[String]) -> PromptAnalysis {
-        let normalized = body.trim();
-        let summary = if normalized.is_empty() {
-            "\u{8bf7}\u{8f93}\u{5165}\u{6709}\u{6548}\u{7684}\u{63d0}\u{793a}\u{8bcd}\u{4ee5}\u{89e6}\u{53d1}\u{5206}\u{6790}"
-                .to_string()
-        } else {
-            format!(
-                "\u{63d0}\u{793a}\u{8bcd}\u{6982}\u{89c8}\u{ff1a}{}",
-                &normalized.chars().take(160).collect::<String>()
-            )
-        };
+    pub fn summarize_prompt_with_vocab(body: &str, vocabulary: &[VocabularyEntry]) -> PromptAnalysis {
+        summarize_prompt_with_config(body, vocabulary, &AnalysisConfig::default())
+    }
 
-        let tokens = tokenize(normalized);
-        let mut keywords = extract_keywords(&tokens, normalized, vocabulary);
+    pub fn summarize_prompt_with_config(
+        body: &str,
+        vocabulary: &[VocabularyEntry],
+        config: &AnalysisConfig,
+    ) -> PromptAnalysis {
+        let cleaned = normalize_text(body);
+        let normalized = cleaned.trim();
+
+        let keyword_options = KeywordOptions {
+            suppressed_terms: config.suppressed_terms.clone(),
+            extra_stopwords: config.overrides.stopwords.clone(),
+            vocabulary_boosts: config.vocabulary_boosts.clone(),
+            ..KeywordOptions::default()
+        };
+        let mut keywords = extract_keywords(normalized, vocabulary, &keyword_options);
         if keywords.is_empty() {
             keywords.push("general".into());
         }
-        let target_entities = extract_targets(&tokens);
+        let target_entities = extract_targets(normalized, &config.overrides.target_markers);
         let theme = derive_theme(&keywords, &target_entities, normalized);
         let topic = theme.clone().or_else(|| derive_topic(normalized));
-        let role = derive_role(normalized);
+        let role = derive_role(normalized, &config.overrides.role_patterns);
+        let intent = classify_intent(normalized);
+        let chain_steps = detect_prompt_chain(normalized).map_or(0, |chain| chain.steps.len());
+        let output_language = detect_output_language(normalized).map(str::to_string);
+        let extracted_fields = apply_extraction_rules(normalized, &config.extraction_rules);
+        let summary = build_summary(normalized, &keywords, &role, config);
+
+        let id = if config.deterministic_ids {
+            deterministic_analysis_id(body, vocabulary, config)
+        } else {
+            Uuid::new_v4().to_string()
+        };
 
         PromptAnalysis {
-            id: Uuid::new_v4().to_string(),
+            id,
             summary,
             suggested_tags: keywords.clone(),
             length: normalized.chars().count(),
@@ -108,102 +353,601 @@ pub mod analysis {
             theme,
             role,
             target_entities,
+            intent,
+            chain_steps,
+            output_language,
+            extracted_fields,
         }
     }
 
-    fn tokenize(text: &str) -> Vec<String> {
-        if text.is_empty() {
-            return Vec::new();
+    /// A prompt split into its explicit steps.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct PromptChain {
+        /// Shared context before the first step marker (role, background, ...).
+        pub preamble: String,
+        pub steps: Vec<ChainStep>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ChainStep {
+        /// 1-based step number as written in the prompt.
+        pub index: usize,
+        pub text: String,
+    }
+
+    /// Detect prompts made of explicitly numbered steps ("第一步…第二步…", "步骤1", "Step 1:").
+    /// Returns `None` unless at least two consecutive steps starting from 1 are found.
+    pub fn detect_prompt_chain(text: &str) -> Option<PromptChain> {
+        let markers = find_step_markers(text);
+        // Keep the first run of consecutive ordinals 1, 2, 3, ...
+        let mut run: Vec<&(usize, usize, usize)> = Vec::new();
+        for marker in &markers {
+            if marker.2 == run.len() + 1 {
+                run.push(marker);
+            }
         }
-        TOKENIZER
-            .cut(text, true)
-            .into_iter()
-            .flat_map(|token| {
-                let owned = token.to_string();
-                if owned.is_empty() {
-                    return Vec::new();
-                }
-                if owned.chars().all(|c| c.is_ascii()) {
-                    owned
-                        .split_whitespace()
-                        .map(|t| trim_punctuation(t).to_string())
-                        .filter(|t| !t.is_empty())
-                        .map(|t| t.to_lowercase())
-                        .filter(|t| !is_noise_ascii(t))
-                        .collect::<Vec<_>>()
-                } else {
-                    let cleaned = trim_punctuation(&owned);
-                    if cleaned.is_empty() {
-                        Vec::new()
-                    } else {
-                        vec![cleaned.to_string()]
-                    }
+        if run.len() < 2 {
+            return None;
+        }
+
+        let strip = |part: &str| {
+            part.trim_start_matches(|c: char| {
+                c.is_whitespace()
+                    || matches!(c, ':' | '.' | ')' | ',' | '\u{ff1a}' | '\u{3001}' | '\u{ff09}' | '\u{ff0c}')
+            })
+            .trim()
+            .to_string()
+        };
+        let steps = run
+            .iter()
+            .enumerate()
+            .map(|(pos, (_, body_start, index))| {
+                let end = run.get(pos + 1).map_or(text.len(), |next| next.0);
+                ChainStep {
+                    index: *index,
+                    text: strip(&text[*body_start..end]),
                 }
             })
-            .collect()
+            .filter(|step| !step.text.is_empty())
+            .collect::<Vec<_>>();
+        if steps.len() < 2 {
+            return None;
+        }
+
+        Some(PromptChain {
+            preamble: text[..run[0].0].trim().to_string(),
+            steps,
+        })
     }
 
-    fn extract_keywords(tokens: &[String], text: &str, vocabulary: &[String]) -> Vec<String> {
-        let mut freq: HashMap<String, usize> = HashMap::new();
-        for token in tokens {
-            if !is_meaningful(token) || is_numeric_token(token) {
-                continue;
+    /// Locate step markers as `(marker_start, body_start, ordinal)` byte offsets.
+    fn find_step_markers(text: &str) -> Vec<(usize, usize, usize)> {
+        let mut markers = Vec::new();
+        for (idx, _) in text.char_indices() {
+            let rest = &text[idx..];
+            // 第N步
+            if let Some(after) = rest.strip_prefix('\u{7b2c}') {
+                if let Some((ordinal, len)) = parse_ordinal(after) {
+                    if after[len..].starts_with('\u{6b65}') {
+                        markers.push((idx, idx + '\u{7b2c}'.len_utf8() + len + '\u{6b65}'.len_utf8(), ordinal));
+                        continue;
+                    }
+                }
             }
-            let normalized = normalize_token(token);
-            if normalized.is_empty() || STOPWORDS.contains(normalized.as_str()) {
-                continue;
+            // 步骤N
+            if let Some(after) = rest.strip_prefix("\u{6b65}\u{9aa4}") {
+                let after_trimmed = after.trim_start();
+                let skipped = after.len() - after_trimmed.len();
+                if let Some((ordinal, len)) = parse_ordinal(after_trimmed) {
+                    markers.push((idx, idx + "\u{6b65}\u{9aa4}".len() + skipped + len, ordinal));
+                    continue;
+                }
+            }
+            // Step N, only at a word boundary
+            let at_boundary = !matches!(text[..idx].chars().next_back(), Some(c) if c.is_ascii_alphanumeric());
+            if at_boundary && rest.len() >= 4 && rest.as_bytes()[..4].eq_ignore_ascii_case(b"step") {
+                let after = &text[idx + 4..];
+                let after_trimmed = after.trim_start();
+                let skipped = after.len() - after_trimmed.len();
+                if skipped > 0 {
+                    if let Some((ordinal, len)) = parse_ordinal(after_trimmed) {
+                        if !matches!(after_trimmed.as_bytes().get(len), Some(b) if b.is_ascii_alphanumeric()) {
+                            markers.push((idx, idx + 4 + skipped + len, ordinal));
+                        }
+                    }
+                }
             }
-            *freq.entry(normalized).or_insert(0) += 1;
         }
+        markers
+    }
 
-        boost_vocabulary_terms(&mut freq, text, vocabulary);
+    /// Parse a leading ordinal written as ASCII digits or Chinese numerals (一 … 十九).
+    fn parse_ordinal(text: &str) -> Option<(usize, usize)> {
+        let digits: String = text.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if !digits.is_empty() {
+            return digits.parse().ok().map(|value| (value, digits.len()));
+        }
+        const NUMERALS: [char; 10] = [
+            '\u{4e00}', '\u{4e8c}', '\u{4e09}', '\u{56db}', '\u{4e94}', '\u{516d}', '\u{4e03}', '\u{516b}',
+            '\u{4e5d}', '\u{5341}',
+        ]; // 一二三四五六七八九十
+        let numeral = |c: char| NUMERALS.iter().position(|n| *n == c).map(|pos| pos + 1);
+        let mut chars = text.chars();
+        let first = numeral(chars.next()?)?;
+        if first == 10 {
+            // 十 / 十N
+            return match chars.next().and_then(numeral) {
+                Some(unit) if unit < 10 => Some((10 + unit, '\u{5341}'.len_utf8() * 2)),
+                _ => Some((10, '\u{5341}'.len_utf8())),
+            };
+        }
+        Some((first, '\u{4e00}'.len_utf8()))
+    }
 
-        let mut ranked: Vec<(String, usize)> = freq.into_iter().collect();
-        ranked.sort_by(|(a_token, a_count), (b_token, b_count)| {
-            b_count
-                .cmp(a_count)
-                .then_with(|| b_token.len().cmp(&a_token.len()))
-                .then_with(|| a_token.cmp(b_token))
-        });
+    /// Verb markers per intent. Listed in tie-break order: when two intents score the same,
+    /// the more specific one (earlier in the list) wins.
+    const INTENT_MARKERS: [(PromptIntent, &[&str]); 7] = [
+        (
+            PromptIntent::Translation,
+            &["\u{7ffb}\u{8bd1}", "\u{8bd1}\u{6210}", "translate", "translation"], // 翻译 译成
+        ),
+        (
+            PromptIntent::Coding,
+            &[
+                "\u{4ee3}\u{7801}", // 代码
+                "\u{7f16}\u{7a0b}", // 编程
+                "\u{51fd}\u{6570}", // 函数
+                "\u{8c03}\u{8bd5}", // 调试
+                "code",
+                "function",
+                "debug",
+                "refactor",
+                "sql",
+                "python",
+                "rust",
+                "javascript",
+            ],
+        ),
+        (
+            PromptIntent::RolePlay,
+            &[
+                "\u{626e}\u{6f14}",                 // 扮演
+                "\u{89d2}\u{8272}\u{626e}\u{6f14}", // 角色扮演
+                "role-play",
+                "roleplay",
+                "pretend",
+            ],
+        ),
+        (
+            PromptIntent::Evaluation,
+            &[
+                "\u{8bc4}\u{4f30}", // 评估
+                "\u{8bc4}\u{5206}", // 评分
+                "\u{6253}\u{5206}", // 打分
+                "\u{8bc4}\u{5ba1}", // 评审
+                "\u{70b9}\u{8bc4}", // 点评
+                "evaluate",
+                "review",
+                "rate",
+                "score",
+            ],
+        ),
+        (
+            PromptIntent::Extraction,
+            &[
+                "\u{63d0}\u{53d6}", // 提取
+                "\u{62bd}\u{53d6}", // 抽取
+                "\u{8bc6}\u{522b}\u{51fa}", // 识别出
+                "\u{5217}\u{51fa}", // 列出
+                "extract",
+                "parse",
+                "identify",
+            ],
+        ),
+        (
+            PromptIntent::Rewriting,
+            &[
+                "\u{6539}\u{5199}", // 改写
+                "\u{6da6}\u{8272}", // 润色
+                "\u{91cd}\u{5199}", // 重写
+                "\u{4f18}\u{5316}", // 优化
+                "\u{7cbe}\u{7b80}", // 精简
+                "\u{7ea0}\u{6b63}", // 纠正
+                "rewrite",
+                "rephrase",
+                "paraphrase",
+                "polish",
+                "proofread",
+            ],
+        ),
+        (
+            PromptIntent::Generation,
+            &[
+                "\u{751f}\u{6210}", // 生成
+                "\u{64b0}\u{5199}", // 撰写
+                "\u{7f16}\u{5199}", // 编写
+                "\u{521b}\u{4f5c}", // 创作
+                "\u{8bbe}\u{8ba1}", // 设计
+                "\u{5199}\u{4e00}", // 写一
+                "write",
+                "generate",
+                "create",
+                "draft",
+                "compose",
+            ],
+        ),
+    ];
+
+    /// Rule-based intent detection from task verbs plus a few structural hints
+    /// (code fences, quoted source text, "你是…" role openers).
+    pub fn classify_intent(text: &str) -> PromptIntent {
+        let lower = text.to_lowercase();
+        if lower.trim().is_empty() {
+            return PromptIntent::Other;
+        }
+        let ascii_words: HashSet<&str> = lower
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        let mut scores: Vec<(PromptIntent, usize)> = INTENT_MARKERS
+            .iter()
+            .map(|(intent, markers)| {
+                let hits = markers
+                    .iter()
+                    .filter(|marker| {
+                        if marker.is_ascii() {
+                            ascii_words.contains(*marker)
+                        } else {
+                            lower.contains(*marker)
+                        }
+                    })
+                    .count();
+                (*intent, hits * 2)
+            })
+            .collect();
+
+        let mut bump = |target: PromptIntent, amount: usize| {
+            if let Some((_, score)) = scores.iter_mut().find(|(intent, _)| *intent == target) {
+                *score += amount;
+            }
+        };
+        if lower.contains("```") {
+            bump(PromptIntent::Coding, 3);
+        }
+        let opener: String = lower.chars().take(40).collect();
+        if ["\u{4f60}\u{662f}", "\u{626e}\u{6f14}", "you are", "act as"]
+            .iter()
+            .any(|marker| opener.contains(marker))
+            && lower.contains("\u{5bf9}\u{8bdd}")
+        {
+            // "你是…" opener combined with 对话 (dialogue) reads as a persona to play.
+            bump(PromptIntent::RolePlay, 2);
+        }
+        if ["\u{4ee5}\u{4e0b}\u{6587}\u{672c}", "\u{4e0b}\u{9762}\u{8fd9}\u{6bb5}", "following text"]
+            .iter()
+            .any(|marker| lower.contains(marker))
+        {
+            // Prompts operating on supplied text are transformations, not free generation.
+            bump(PromptIntent::Rewriting, 1);
+            bump(PromptIntent::Extraction, 1);
+        }
 
-        ranked
+        scores
             .into_iter()
-            .map(|(token, _)| token)
-            .filter(|token| token.chars().count() >= 2 || token.len() >= 4)
-            .take(8)
+            .enumerate()
+            .filter(|(_, (_, score))| *score > 0)
+            .max_by(|(a_idx, (_, a_score)), (b_idx, (_, b_score))| a_score.cmp(b_score).then(b_idx.cmp(a_idx)))
+            .map(|(_, (intent, _))| intent)
+            .unwrap_or_default()
+    }
+
+    /// Broad prompt categories used to scope [`VocabularyBoost`]s, with their markers.
+    /// ASCII markers match whole words, others match as substrings.
+    pub const PROMPT_CATEGORIES: [(&str, &[&str]); 6] = [
+        (
+            "编程",
+            &[
+                "代码", "编程", "函数", "接口", "算法", "脚本", "程序", "报错",
+                "python", "rust", "javascript", "typescript", "java", "api", "bug", "debug", "code", "sdk",
+            ],
+        ),
+        (
+            "数据",
+            &[
+                "数据", "表格", "统计", "报表", "指标", "可视化", "数据库",
+                "sql", "csv", "excel", "dataset", "etl",
+            ],
+        ),
+        ("写作", &["文章", "写作", "小说", "故事", "润色", "大纲", "标题", "散文", "诗"]),
+        ("营销", &["营销", "推广", "广告", "品牌", "文案", "转化", "小红书", "种草", "seo"]),
+        ("教育", &["老师", "学生", "教学", "课程", "讲解", "考试", "题目", "作业", "知识点"]),
+        ("办公", &["邮件", "会议", "周报", "简历", "汇报", "纪要", "公文", "email"]),
+    ];
+
+    /// Categories from [`PROMPT_CATEGORIES`] the text touches, in table order.
+    pub fn detect_categories(text: &str) -> Vec<&'static str> {
+        let lower = text.to_lowercase();
+        let ascii_words: HashSet<&str> = lower
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        PROMPT_CATEGORIES
+            .iter()
+            .filter(|(_, markers)| {
+                markers.iter().any(|marker| {
+                    if marker.is_ascii() {
+                        ascii_words.contains(*marker)
+                    } else {
+                        lower.contains(*marker)
+                    }
+                })
+            })
+            .map(|(category, _)| *category)
             .collect()
     }
 
-    fn boost_vocabulary_terms(freq: &mut HashMap<String, usize>, text: &str, vocabulary: &[String]) {
-        if vocabulary.is_empty() || text.is_empty() {
-            return;
+    /// Map user input ("编程类", " 数据 ") onto a known category name.
+    pub fn canonical_category(name: &str) -> Option<&'static str> {
+        let trimmed = name.trim();
+        let trimmed = trimmed.strip_suffix('类').unwrap_or(trimmed).trim();
+        PROMPT_CATEGORIES
+            .iter()
+            .map(|(category, _)| *category)
+            .find(|category| *category == trimmed)
+    }
+
+    /// ISO 639-1 code, Chinese names, English names.
+    const OUTPUT_LANGUAGES: [(&str, &[&str], &[&str]); 8] = [
+        ("zh", &["简体中文", "繁体中文", "中文", "汉语", "普通话"], &["chinese", "mandarin"]),
+        ("en", &["英文", "英语"], &["english"]),
+        ("ja", &["日文", "日语"], &["japanese"]),
+        ("ko", &["韩文", "韩语"], &["korean"]),
+        ("fr", &["法文", "法语"], &["french"]),
+        ("de", &["德文", "德语"], &["german"]),
+        ("es", &["西班牙文", "西班牙语"], &["spanish"]),
+        ("ru", &["俄文", "俄语"], &["russian"]),
+    ];
+
+    /// Directly precedes the language name: "翻译成英文", "输出语言：中文", "用日语".
+    const OUTPUT_LANGUAGE_PREFIXES: [&str; 14] = [
+        "翻译成", "翻译为", "译成", "译为", "转换成", "转成", "改写成",
+        "语言：", "语言:", "语言为", "语言是", "使用", "用", "以",
+    ];
+    /// Directly follows the language name: "中文回答", "英文输出".
+    const OUTPUT_LANGUAGE_SUFFIXES: [&str; 6] = ["回答", "回复", "作答", "输出", "撰写", "表述"];
+    /// Verbs that make a following "in <language>" an output requirement.
+    const OUTPUT_LANGUAGE_VERBS: [&str; 12] = [
+        "respond", "response", "responses", "reply", "answer", "answers", "write", "output", "translate", "explain",
+        "summarize", "speak",
+    ];
+
+    const OUTPUT_LANGUAGE_QUALIFIERS: [&str; 5] = ["simplified", "traditional", "plain", "british", "american"];
+
+    /// Map an ISO 639-1 code or a Chinese/English language name ("英文", "English") onto
+    /// a supported code.
+    pub fn canonical_language(name: &str) -> Option<&'static str> {
+        let trimmed = name.trim();
+        let lowered = trimmed.to_lowercase();
+        OUTPUT_LANGUAGES
+            .iter()
+            .find(|(code, zh_names, en_names)| {
+                *code == lowered || zh_names.contains(&trimmed) || en_names.contains(&lowered.as_str())
+            })
+            .map(|(code, _, _)| *code)
+    }
+
+    /// Chinese display name of a supported language code ("en" → "英文").
+    pub fn language_display_name(code: &str) -> Option<&'static str> {
+        OUTPUT_LANGUAGES
+            .iter()
+            .find(|(candidate, _, _)| *candidate == code)
+            .and_then(|(_, zh_names, _)| zh_names.first().copied())
+    }
+
+    /// Detect an explicit instruction about the answer's language ("请用中文回答",
+    /// "翻译成英文", "respond in English", "output language: Japanese").
+    /// Returns the ISO 639-1 code of the first instruction found.
+    pub fn detect_output_language(text: &str) -> Option<&'static str> {
+        let lower = text.to_lowercase();
+        detect_output_language_zh(&lower).or_else(|| detect_output_language_en(&lower))
+    }
+
+    fn detect_output_language_zh(lower: &str) -> Option<&'static str> {
+        let mut best: Option<(usize, &'static str)> = None;
+        for (code, names, _) in OUTPUT_LANGUAGES {
+            for name in names {
+                for (pos, _) in lower.match_indices(name) {
+                    let before = &lower[..pos];
+                    let after = &lower[pos + name.len()..];
+                    let instructed = OUTPUT_LANGUAGE_PREFIXES.iter().any(|prefix| before.ends_with(prefix))
+                        || OUTPUT_LANGUAGE_SUFFIXES.iter().any(|suffix| after.starts_with(suffix));
+                    if instructed && !matches!(best, Some((best_pos, _)) if best_pos <= pos) {
+                        best = Some((pos, code));
+                    }
+                }
+            }
         }
+        best.map(|(_, code)| code)
+    }
 
-        let lower_text = text.to_lowercase();
-        for term in vocabulary {
-            let cleaned = term.trim();
-            if cleaned.is_empty() {
+    fn detect_output_language_en(lower: &str) -> Option<&'static str> {
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        for (idx, word) in words.iter().enumerate() {
+            let Some((code, _, _)) = OUTPUT_LANGUAGES.iter().find(|(_, _, names)| names.contains(word)) else {
                 continue;
+            };
+            // Skip qualifiers: "in simplified chinese", "in plain english".
+            let mut prev = idx;
+            while prev > 0 && OUTPUT_LANGUAGE_QUALIFIERS.contains(&words[prev - 1]) {
+                prev -= 1;
             }
-            let is_ascii = cleaned.chars().all(|c| c.is_ascii());
-            let normalized = normalize_token(cleaned);
-            let haystack = if is_ascii { lower_text.as_str() } else { text };
-            let needle = if is_ascii {
-                normalized.as_str()
-            } else {
-                cleaned
+            let Some(before) = prev.checked_sub(1).map(|i| words[i]) else {
+                continue;
+            };
+            let verb_nearby = words[prev.saturating_sub(9)..prev - 1]
+                .iter()
+                .any(|candidate| OUTPUT_LANGUAGE_VERBS.contains(candidate));
+            // "language: french", "language is french", "language should be french".
+            let language_label = before == "language"
+                || (matches!(before, "is" | "be") && words[prev.saturating_sub(3)..prev - 1].contains(&"language"));
+            if (matches!(before, "in" | "into") && verb_nearby) || language_label {
+                return Some(code);
+            }
+        }
+        None
+    }
+
+    const SENTENCE_TERMINATORS: [char; 7] = [
+        '\u{3002}', // 。
+        '\u{ff01}', // ！
+        '\u{ff1f}', // ？
+        '\u{ff1b}', // ；
+        '!',
+        '?',
+        ';',
+    ];
+
+    /// Split text into trimmed sentences on Chinese (。！？；) and Western (. ! ? ;) terminators
+    /// as well as line breaks. Terminators stay attached to their sentence. A Western `.` only
+    /// ends a sentence when followed by whitespace or the end of the text, so decimals,
+    /// version numbers, and URLs are kept intact.
+    pub fn split_sentences(text: &str) -> Vec<&str> {
+        let mut sentences = Vec::new();
+        let mut start = 0;
+        let mut chars = text.char_indices().peekable();
+        while let Some((idx, c)) = chars.next() {
+            let after = idx + c.len_utf8();
+            let boundary = match c {
+                '\n' | '\r' => Some((idx, after)),
+                '.' => match chars.peek() {
+                    None => Some((after, after)),
+                    Some((_, next)) if next.is_whitespace() => Some((after, after)),
+                    _ => None,
+                },
+                c if SENTENCE_TERMINATORS.contains(&c) => Some((after, after)),
+                _ => None,
             };
-            let count = haystack.match_indices(needle).count();
-            if count > 0 {
-                *freq.entry(normalized.clone()).or_insert(0) += count * 3;
+            if let Some((end, next_start)) = boundary {
+                let sentence = text[start..end].trim();
+                if !sentence.is_empty() {
+                    sentences.push(sentence);
+                }
+                start = next_start;
             }
         }
+        let tail = text[start..].trim();
+        if !tail.is_empty() {
+            sentences.push(tail);
+        }
+        sentences
     }
 
-    fn extract_targets(tokens: &[String]) -> Vec<String> {
+    fn build_summary(text: &str, keywords: &[String], role: &str, config: &AnalysisConfig) -> String {
+        if text.is_empty() {
+            return "\u{8bf7}\u{8f93}\u{5165}\u{6709}\u{6548}\u{7684}\u{63d0}\u{793a}\u{8bcd}\u{4ee5}\u{89e6}\u{53d1}\u{5206}\u{6790}"
+                .to_string();
+        }
+        let max_len = config.summary_max_length.max(1);
+        let first_chars = || {
+            format!(
+                "\u{63d0}\u{793a}\u{8bcd}\u{6982}\u{89c8}\u{ff1a}{}",
+                truncate_chars(text, max_len)
+            )
+        };
+
+        match config.summary_strategy {
+            SummaryStrategy::FirstChars => first_chars(),
+            SummaryStrategy::FirstSentence => split_sentences(text)
+                .into_iter()
+                .next()
+                .map(|sentence| truncate_chars(sentence, max_len))
+                .unwrap_or_else(first_chars),
+            SummaryStrategy::KeywordStitched => {
+                let lower_keywords: Vec<String> = keywords
+                    .iter()
+                    .filter(|keyword| *keyword != "general")
+                    .take(3)
+                    .map(|keyword| keyword.to_lowercase())
+                    .collect();
+                let mut stitched = String::new();
+                for sentence in split_sentences(text) {
+                    let lower = sentence.to_lowercase();
+                    if !lower_keywords.iter().any(|keyword| lower.contains(keyword.as_str())) {
+                        continue;
+                    }
+                    if stitched.chars().count() + sentence.chars().count() > max_len && !stitched.is_empty() {
+                        break;
+                    }
+                    if !stitched.is_empty() {
+                        stitched.push(' ');
+                    }
+                    stitched.push_str(sentence);
+                }
+                if stitched.is_empty() {
+                    first_chars()
+                } else {
+                    truncate_chars(&stitched, max_len)
+                }
+            }
+            SummaryStrategy::RoleTask => {
+                let task = split_sentences(text)
+                    .into_iter()
+                    .find(|sentence| !sentence.contains(role) && is_task_sentence(sentence));
+                match (role != "\u{7a7a}", task) {
+                    (true, Some(task)) => truncate_chars(&format!("角色：{role}；任务：{task}"), max_len),
+                    (false, Some(task)) => truncate_chars(&format!("任务：{task}"), max_len),
+                    (true, None) => truncate_chars(&format!("角色：{role}"), max_len),
+                    (false, None) => first_chars(),
+                }
+            }
+        }
+    }
+
+    fn is_task_sentence(sentence: &str) -> bool {
+        const TASK_MARKERS: [&str; 12] = [
+            "\u{8bf7}",         // 请
+            "\u{5e2e}\u{6211}", // 帮我
+            "\u{9700}\u{8981}", // 需要
+            "\u{751f}\u{6210}", // 生成
+            "\u{7f16}\u{5199}", // 编写
+            "\u{64b0}\u{5199}", // 撰写
+            "\u{603b}\u{7ed3}", // 总结
+            "\u{5206}\u{6790}", // 分析
+            "please",
+            "write",
+            "generate",
+            "create",
+        ];
+        let lower = sentence.to_lowercase();
+        TASK_MARKERS.iter().any(|marker| lower.contains(marker))
+    }
+
+    fn truncate_chars(text: &str, max_chars: usize) -> String {
+        text.chars().take(max_chars).collect()
+    }
+
+    fn extract_targets(text: &str, extra_markers: &[String]) -> Vec<String> {
+        let mut markers: Vec<&str> = TARGET_MARKERS.to_vec();
+        markers.extend(extra_markers.iter().map(String::as_str));
         let mut targets = Vec::new();
+        // Work sentence by sentence so a marker never pairs with the next sentence's first word.
+        for sentence in split_sentences(text) {
+            collect_targets(&tokenize_normalized(sentence), &markers, &mut targets);
+        }
+        targets.sort();
+        targets.dedup();
+        targets.into_iter().take(5).collect()
+    }
+
+    fn collect_targets(tokens: &[String], markers: &[&str], targets: &mut Vec<String>) {
         for (idx, token) in tokens.iter().enumerate() {
-            if let Some(marker) = TARGET_MARKERS.iter().find(|marker| token.contains(*marker)) {
+            if let Some(marker) = markers.iter().find(|marker| token.contains(*marker)) {
                 let tail = token.replacen(marker, "", 1).trim().to_string();
                 if is_meaningful_str(&tail) {
                     targets.push(normalize_token(&tail));
@@ -215,11 +959,30 @@ pub mod analysis {
                         continue;
                     }
                 }
+            } else if let Some(span) = markers.iter().find_map(|marker| marker_span(&tokens[idx..], marker)) {
+                // Marker split across tokens by the segmenter ("服务" + "于").
+                if let Some(next) = tokens.get(idx + span) {
+                    if is_meaningful(next) {
+                        targets.push(normalize_token(next));
+                    }
+                }
             }
         }
-        targets.sort();
-        targets.dedup();
-        targets.into_iter().take(5).collect()
+    }
+
+    /// Number of leading tokens that concatenate to exactly `marker`, if more than one.
+    fn marker_span(tokens: &[String], marker: &str) -> Option<usize> {
+        let mut joined = String::new();
+        for (count, token) in tokens.iter().enumerate() {
+            joined.push_str(token);
+            if joined == marker {
+                return (count > 0).then_some(count + 1);
+            }
+            if !marker.starts_with(joined.as_str()) {
+                return None;
+            }
+        }
+        None
     }
 
     fn derive_topic(text: &str) -> Option<String> {
@@ -239,7 +1002,7 @@ pub mod analysis {
         derive_topic(text)
     }
 
-    fn derive_role(text: &str) -> String {
+    fn derive_role(text: &str, extra_patterns: &[String]) -> String {
         let window: String = text.chars().take(200).collect();
         let patterns = [
             "\u{4f5c}\u{4e3a}", // 作为
@@ -250,77 +1013,350 @@ pub mod analysis {
             "role:",
             "角色",
         ];
-        for part in window.split(|c| matches!(c, '\u{ff0c}' | '\u{3002}' | '\u{ff1b}' | '\u{ff1a}' | '.' | ';')) {
-            let trimmed = part.trim();
+        let clauses = split_sentences(&window)
+            .into_iter()
+            .flat_map(|sentence| sentence.split(['\u{ff0c}', '\u{ff1a}']));
+        for part in clauses {
+            let trimmed = part.trim().trim_end_matches(SENTENCE_TERMINATORS).trim_end_matches('.');
             if trimmed.is_empty() {
                 continue;
             }
-            if patterns.iter().any(|p| trimmed.contains(p)) {
+            // User patterns are stored lowercased, so match them case-insensitively.
+            let lowered = trimmed.to_lowercase();
+            if patterns.iter().any(|p| trimmed.contains(p))
+                || extra_patterns.iter().any(|p| lowered.contains(p.as_str()))
+            {
                 return trimmed.chars().take(48).collect();
             }
         }
         "空".to_string()
     }
 
-    fn is_meaningful(token: &str) -> bool {
-        is_meaningful_str(token)
-    }
 
-    fn is_meaningful_str(token: &str) -> bool {
-        let trimmed = trim_punctuation(token);
-        if trimmed.is_empty() {
-            return false;
+    /// Tokenization and keyword extraction shared by analysis, search indexing and external
+    /// callers, so everyone sees exactly the same tokens.
+    pub mod tokens {
+        use jieba_rs::Jieba;
+        use once_cell::sync::Lazy;
+        use std::collections::{HashMap, HashSet};
+
+        use super::{
+            canonical_category, detect_categories, VocabularyBoost, VocabularyEntry, DEFAULT_VOCABULARY_BOOST,
+        };
+        use crate::text::normalize_text;
+
+        /// Options for [`extract_keywords`].
+        #[derive(Debug, Clone)]
+        pub struct KeywordOptions {
+            /// Drop built-in stopwords (的/了/the/and …) before ranking.
+            pub filter_stopwords: bool,
+            /// Terms that must never be returned, whatever their frequency.
+            pub suppressed_terms: Vec<String>,
+            /// Additional stopwords on top of the built-in list (user `analysis.toml`).
+            pub extra_stopwords: Vec<String>,
+            /// Category-scoped boost factors, taking precedence over vocabulary entry weights.
+            pub vocabulary_boosts: Vec<VocabularyBoost>,
+            /// Maximum number of keywords returned.
+            pub limit: usize,
         }
-        if trimmed.chars().all(|c| c.is_ascii_digit()) {
-            return false;
+
+        impl Default for KeywordOptions {
+            fn default() -> Self {
+                Self {
+                    filter_stopwords: true,
+                    suppressed_terms: Vec::new(),
+                    extra_stopwords: Vec::new(),
+                    vocabulary_boosts: Vec::new(),
+                    limit: 8,
+                }
+            }
         }
-        trimmed.chars().count() > 1 || trimmed.len() > 3
-    }
 
-    fn is_numeric_token(token: &str) -> bool {
-        let trimmed = trim_punctuation(token);
-        if trimmed.is_empty() {
-            return false;
+        static TOKENIZER: Lazy<Jieba> = Lazy::new(Jieba::new);
+        static STOPWORDS: Lazy<HashSet<&'static str>> = Lazy::new(|| {
+            [
+                "",
+                "\u{7684}",
+                "\u{4e86}",
+                "\u{548c}",
+                "\u{4e0e}",
+                "\u{5728}",
+                "\u{53ca}",
+                "\u{4ee5}\u{53ca}",
+                "\u{9700}\u{8981}",
+                "\u{6211}\u{4eec}",
+                "\u{7528}\u{6237}",
+                "\u{8fdb}\u{884c}",
+                "\u{5e0c}\u{671b}",
+                "\u{8bf7}",
+                "\u{4f7f}\u{7528}",
+                "\u{8fd9}\u{4e2a}",
+                "\u{90a3}\u{4e2a}",
+                "\u{8fd9}\u{4e9b}",
+                "\u{90a3}\u{4e9b}",
+                "\u{4e00}\u{4e0b}",
+                "\u{4e00}\u{4e2a}",
+                "\u{5982}\u{4f55}",
+                "\u{600e}\u{4e48}",
+                "\u{5417}",
+                "\u{5462}",
+                "\u{554a}",
+                "\u{54e6}",
+                "the",
+                "and",
+                "or",
+                "for",
+                "with",
+                "into",
+                "from",
+                "to",
+                "of",
+                "is",
+                "are",
+            ]
+            .into_iter()
+            .collect()
+        });
+
+        /// Split text into cleaned tokens: Unicode-normalized, punctuation trimmed, ASCII
+        /// lowercased, single characters and pure numbers dropped. Stopwords are kept.
+        pub fn tokenize(text: &str) -> Vec<String> {
+            tokenize_with(text, false)
         }
-        if trimmed.chars().all(|c| c.is_ascii_digit()) {
-            return true;
+
+        /// Like [`tokenize`], optionally dropping stopwords.
+        pub fn tokenize_with(text: &str, filter_stopwords: bool) -> Vec<String> {
+            let mut tokens = tokenize_normalized(&normalize_text(text));
+            if filter_stopwords {
+                tokens.retain(|token| !is_stopword(token));
+            }
+            tokens
         }
-        trimmed.chars().any(|c| c.is_ascii_digit())
-    }
 
-    fn is_noise_ascii(token: &str) -> bool {
-        token.len() <= 1 || token.chars().all(|c| c.is_ascii_digit())
-    }
+        /// Whether the token is one of the built-in stopwords.
+        pub fn is_stopword(token: &str) -> bool {
+            STOPWORDS.contains(normalize_token(token).as_str())
+        }
 
-    fn trim_punctuation(token: &str) -> &str {
-        token.trim_matches(|c: char| {
-            c.is_ascii_punctuation()
-                || matches!(
-                    c,
-                    '\u{ff0c}'
-                        | '\u{3002}'
-                        | '\u{ff01}'
-                        | '\u{ff1f}'
-                        | '\u{3001}'
-                        | '\u{ff1b}'
-                        | '\u{ff1a}'
-                        | '\u{ff08}'
-                        | '\u{ff09}'
-                        | '\u{3010}'
-                        | '\u{3011}'
-                )
-        })
-    }
+        pub(super) fn tokenize_normalized(text: &str) -> Vec<String> {
+            if text.is_empty() {
+                return Vec::new();
+            }
+            TOKENIZER
+                .cut(text, true)
+                .into_iter()
+                .flat_map(|token| {
+                    let owned = token.to_string();
+                    if owned.is_empty() {
+                        return Vec::new();
+                    }
+                    if owned.chars().all(|c| c.is_ascii()) {
+                        owned
+                            .split_whitespace()
+                            .map(|t| trim_punctuation(t).to_string())
+                            .filter(|t| !t.is_empty())
+                            .map(|t| t.to_lowercase())
+                            .filter(|t| !is_noise_ascii(t))
+                            .collect::<Vec<_>>()
+                    } else {
+                        let cleaned = trim_punctuation(&owned);
+                        if cleaned.is_empty() {
+                            Vec::new()
+                        } else {
+                            vec![cleaned.to_string()]
+                        }
+                    }
+                })
+                .collect()
+        }
+
+        /// Rank the most significant keywords of `text`, boosting vocabulary terms, exactly
+        /// as `summarize_prompt*` does for `suggested_tags` (minus the "general" fallback).
+        pub fn extract_keywords(text: &str, vocabulary: &[VocabularyEntry], options: &KeywordOptions) -> Vec<String> {
+            let normalized_text = normalize_text(text);
+            let text = normalized_text.trim();
+            let suppressed = &options.suppressed_terms;
+
+            let mut freq: HashMap<String, usize> = HashMap::new();
+            for term in candidate_terms(text, options) {
+                *freq.entry(term).or_insert(0) += 1;
+            }
+
+            boost_vocabulary_terms(&mut freq, text, vocabulary, &options.vocabulary_boosts);
 
-    fn normalize_token(token: &str) -> String {
-        let cleaned = trim_punctuation(token);
-        if cleaned.is_empty() {
-            return String::new();
+            if !suppressed.is_empty() {
+                let blocked: HashSet<String> = suppressed
+                    .iter()
+                    .map(|term| normalize_token(normalize_text(term).trim()))
+                    .filter(|term| !term.is_empty())
+                    .collect();
+                freq.retain(|token, _| !blocked.contains(token));
+            }
+
+            let mut ranked: Vec<(String, usize)> = freq.into_iter().collect();
+            ranked.sort_by(|(a_token, a_count), (b_token, b_count)| {
+                b_count
+                    .cmp(a_count)
+                    .then_with(|| b_token.len().cmp(&a_token.len()))
+                    .then_with(|| a_token.cmp(b_token))
+            });
+
+            ranked
+                .into_iter()
+                .map(|(token, _)| token)
+                .filter(|token| token.chars().count() >= 2 || token.len() >= 4)
+                .take(options.limit)
+                .collect()
         }
-        if cleaned.chars().all(|c| c.is_ascii()) {
-            cleaned.to_lowercase()
-        } else {
-            cleaned.to_string()
+
+        /// Normalized keyword candidates in text order: meaningful, non-numeric and, when
+        /// `options.filter_stopwords` is set, not a built-in or extra stopword.
+        pub(crate) fn candidate_terms(text: &str, options: &KeywordOptions) -> Vec<String> {
+            let extra_stopwords: HashSet<String> =
+                options.extra_stopwords.iter().map(|term| normalize_token(term)).collect();
+            tokenize_normalized(normalize_text(text).trim())
+                .iter()
+                .filter(|token| is_meaningful(token) && !is_numeric_token(token))
+                .map(|token| normalize_token(token))
+                .filter(|term| {
+                    !term.is_empty()
+                        && !(options.filter_stopwords
+                            && (STOPWORDS.contains(term.as_str()) || extra_stopwords.contains(term)))
+                })
+                .collect()
+        }
+
+        fn boost_vocabulary_terms(
+            freq: &mut HashMap<String, usize>,
+            text: &str,
+            vocabulary: &[VocabularyEntry],
+            boosts: &[VocabularyBoost],
+        ) {
+            if vocabulary.is_empty() || text.is_empty() {
+                return;
+            }
+
+            let categories = if boosts.iter().any(|boost| !boost.categories.is_empty()) {
+                detect_categories(text)
+            } else {
+                Vec::new()
+            };
+            let lower_text = text.to_lowercase();
+            for entry in vocabulary {
+                let canonical = normalize_token(normalize_text(&entry.term).trim());
+                if canonical.is_empty() {
+                    continue;
+                }
+                let factor = match boosts
+                    .iter()
+                    .find(|boost| normalize_token(normalize_text(&boost.term).trim()) == canonical)
+                {
+                    None => Some(entry.weight.unwrap_or(DEFAULT_VOCABULARY_BOOST)),
+                    Some(boost)
+                        if boost.categories.is_empty()
+                            || boost.categories.iter().any(|category| {
+                                canonical_category(category).is_some_and(|name| categories.contains(&name))
+                            }) =>
+                    {
+                        Some(boost.factor)
+                    }
+                    // Scoped to other categories: leave the term at its plain frequency.
+                    Some(_) => None,
+                };
+
+                let mut count = 0;
+                for spelling in entry.spellings() {
+                    let folded = normalize_text(spelling);
+                    let cleaned = folded.trim();
+                    if cleaned.is_empty() {
+                        continue;
+                    }
+                    let is_ascii = cleaned.chars().all(|c| c.is_ascii());
+                    let normalized = normalize_token(cleaned);
+                    // Tags always use the canonical spelling.
+                    if normalized != canonical {
+                        if let Some(alias_count) = freq.remove(&normalized) {
+                            *freq.entry(canonical.clone()).or_insert(0) += alias_count;
+                        }
+                    }
+                    let haystack = if is_ascii { lower_text.as_str() } else { text };
+                    let needle = if is_ascii {
+                        normalized.as_str()
+                    } else {
+                        cleaned
+                    };
+                    count += haystack.match_indices(needle).count();
+                }
+                if let Some(factor) = factor {
+                    if count > 0 {
+                        *freq.entry(canonical).or_insert(0) += count * factor;
+                    }
+                }
+            }
+        }
+
+        pub(super) fn is_meaningful(token: &str) -> bool {
+            is_meaningful_str(token)
+        }
+
+        pub(super) fn is_meaningful_str(token: &str) -> bool {
+            let trimmed = trim_punctuation(token);
+            if trimmed.is_empty() {
+                return false;
+            }
+            if trimmed.chars().all(|c| c.is_ascii_digit()) {
+                return false;
+            }
+            trimmed.chars().count() > 1 || trimmed.len() > 3
+        }
+
+        fn is_numeric_token(token: &str) -> bool {
+            let trimmed = trim_punctuation(token);
+            if trimmed.is_empty() {
+                return false;
+            }
+            if trimmed.chars().all(|c| c.is_ascii_digit()) {
+                return true;
+            }
+            trimmed.chars().any(|c| c.is_ascii_digit())
+        }
+
+        fn is_noise_ascii(token: &str) -> bool {
+            token.len() <= 1 || token.chars().all(|c| c.is_ascii_digit())
+        }
+
+        /// Trim ASCII and CJK punctuation from both ends of a token.
+        pub fn trim_punctuation(token: &str) -> &str {
+            token.trim_matches(|c: char| {
+                c.is_ascii_punctuation()
+                    || matches!(
+                        c,
+                        '\u{ff0c}'
+                            | '\u{3002}'
+                            | '\u{ff01}'
+                            | '\u{ff1f}'
+                            | '\u{3001}'
+                            | '\u{ff1b}'
+                            | '\u{ff1a}'
+                            | '\u{ff08}'
+                            | '\u{ff09}'
+                            | '\u{3010}'
+                            | '\u{3011}'
+                    )
+            })
+        }
+
+        /// Canonical form used for comparing tokens: punctuation trimmed, ASCII lowercased.
+        pub fn normalize_token(token: &str) -> String {
+            let cleaned = trim_punctuation(token);
+            if cleaned.is_empty() {
+                return String::new();
+            }
+            if cleaned.chars().all(|c| c.is_ascii()) {
+                cleaned.to_lowercase()
+            } else {
+                cleaned.to_string()
+            }
         }
     }
 }
@@ -347,4 +1383,18 @@ pub mod prompts {
     }
 }
 
+pub mod budget;
+pub mod capture;
+pub mod compare;
+pub mod dedup;
+pub mod eval;
+pub mod guardrail;
+pub mod llm;
+pub mod redact;
+pub mod rules;
+pub mod search;
 pub mod storage;
+pub mod tags;
+pub mod template;
+pub mod text;
+pub mod vocabulary;
@@ -0,0 +1,1973 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use reqwest::{Certificate, Client, Proxy, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::analysis::canonical_category;
+
+/// LLM backends a [`Provider`] can talk to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// Alibaba Cloud DashScope (Qwen), through its OpenAI-compatible endpoint.
+    #[serde(rename = "dashscope")]
+    DashScope,
+    /// Any `/chat/completions` endpoint: OpenAI, Azure OpenAI, DeepSeek, Moonshot, vLLM,
+    /// LM Studio, ...
+    OpenAiCompatible,
+    Anthropic,
+    Ollama,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 4] = [
+        ProviderKind::DashScope,
+        ProviderKind::OpenAiCompatible,
+        ProviderKind::Anthropic,
+        ProviderKind::Ollama,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderKind::DashScope => "dashscope",
+            ProviderKind::OpenAiCompatible => "openai_compatible",
+            ProviderKind::Anthropic => "anthropic",
+            ProviderKind::Ollama => "ollama",
+        }
+    }
+
+    /// Accepts the names from [`ProviderKind::as_str`] plus a few common spellings.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "dashscope" | "qwen" => Some(ProviderKind::DashScope),
+            "openai_compatible" | "openai" => Some(ProviderKind::OpenAiCompatible),
+            "anthropic" | "claude" => Some(ProviderKind::Anthropic),
+            "ollama" => Some(ProviderKind::Ollama),
+            _ => None,
+        }
+    }
+
+    pub fn default_base_url(&self) -> &'static str {
+        match self {
+            ProviderKind::DashScope => "https://dashscope.aliyuncs.com/compatible-mode/v1",
+            ProviderKind::OpenAiCompatible => "https://api.openai.com/v1",
+            ProviderKind::Anthropic => "https://api.anthropic.com",
+            ProviderKind::Ollama => "http://localhost:11434",
+        }
+    }
+
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            ProviderKind::DashScope => "qwen-max",
+            ProviderKind::OpenAiCompatible => "gpt-4o-mini",
+            ProviderKind::Anthropic => "claude-3-5-haiku-latest",
+            ProviderKind::Ollama => "qwen2.5",
+        }
+    }
+
+    /// Cheaper model for classification, which runs on every clipboard capture.
+    pub fn default_classification_model(&self) -> &'static str {
+        match self {
+            ProviderKind::DashScope => "qwen-turbo",
+            _ => self.default_model(),
+        }
+    }
+
+    /// Embedding model used when none is configured; `None` if the backend has no
+    /// embeddings endpoint.
+    pub fn default_embedding_model(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::DashScope => Some("text-embedding-v3"),
+            ProviderKind::OpenAiCompatible => Some("text-embedding-3-small"),
+            ProviderKind::Anthropic => None,
+            ProviderKind::Ollama => Some("nomic-embed-text"),
+        }
+    }
+
+    /// Vision model that reads text from screenshots; `None` where the default chat model
+    /// already accepts images.
+    pub fn default_ocr_model(&self) -> Option<&'static str> {
+        match self {
+            ProviderKind::DashScope => Some("qwen-vl-ocr"),
+            ProviderKind::Ollama => Some("qwen2.5vl"),
+            ProviderKind::OpenAiCompatible | ProviderKind::Anthropic => None,
+        }
+    }
+
+    /// Local Ollama needs no key; every hosted backend does.
+    pub fn requires_api_key(&self) -> bool {
+        !matches!(self, ProviderKind::Ollama)
+    }
+}
+
+/// Which backend to use and how to reach it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmSettings {
+    pub provider: ProviderKind,
+    /// Falls back to [`ProviderKind::default_base_url`].
+    pub base_url: Option<String>,
+    #[serde(skip_serializing)]
+    pub api_key: Option<String>,
+    /// Header that carries the API key for OpenAI-compatible backends. `Authorization` (the
+    /// default) sends `Bearer <key>`; any other name, such as Azure's `api-key`, sends the key
+    /// as is.
+    pub api_key_header: Option<String>,
+    /// Falls back to [`ProviderKind::default_model`].
+    pub model: Option<String>,
+    /// Falls back to [`ProviderKind::default_embedding_model`].
+    pub embedding_model: Option<String>,
+    #[serde(default)]
+    pub network: NetworkSettings,
+    pub retry: RetryPolicy,
+}
+
+impl Default for LlmSettings {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::DashScope,
+            base_url: None,
+            api_key: None,
+            api_key_header: None,
+            model: None,
+            embedding_model: None,
+            network: NetworkSettings::default(),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl LlmSettings {
+    /// Read `PROMPTLAB_LLM_PROVIDER`, `PROMPTLAB_LLM_BASE_URL`, `PROMPTLAB_LLM_API_KEY`,
+    /// `PROMPTLAB_LLM_API_KEY_HEADER`, `PROMPTLAB_LLM_MODEL`, `PROMPTLAB_LLM_EMBEDDING_MODEL`,
+    /// `PROMPTLAB_LLM_TIMEOUT_SECS`, `PROMPTLAB_LLM_MAX_ATTEMPTS` and
+    /// `PROMPTLAB_LLM_RETRY_BASE_MS`. For DashScope the key may also come from
+    /// `DASHSCOPE_API_KEY`.
+    pub fn from_env() -> Result<Self, LlmError> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let mut settings = LlmSettings::default();
+        if let Some(name) = var("PROMPTLAB_LLM_PROVIDER") {
+            settings.provider = ProviderKind::parse(&name).ok_or(LlmError::UnknownProvider(name))?;
+        }
+        settings.base_url = var("PROMPTLAB_LLM_BASE_URL");
+        settings.api_key = var("PROMPTLAB_LLM_API_KEY").or_else(|| match settings.provider {
+            ProviderKind::DashScope => var("DASHSCOPE_API_KEY"),
+            _ => None,
+        });
+        settings.api_key_header = var("PROMPTLAB_LLM_API_KEY_HEADER");
+        settings.model = var("PROMPTLAB_LLM_MODEL");
+        settings.embedding_model = var("PROMPTLAB_LLM_EMBEDDING_MODEL");
+        if let Some(timeout) = var("PROMPTLAB_LLM_TIMEOUT_SECS").and_then(|value| value.parse().ok()) {
+            settings.network.timeout_secs = timeout;
+        }
+        if let Some(attempts) = var("PROMPTLAB_LLM_MAX_ATTEMPTS").and_then(|value| value.parse().ok()) {
+            settings.retry.max_attempts = attempts;
+        }
+        if let Some(base) = var("PROMPTLAB_LLM_RETRY_BASE_MS").and_then(|value| value.parse().ok()) {
+            settings.retry.base_delay_ms = base;
+        }
+        Ok(settings)
+    }
+
+    pub fn model(&self) -> &str {
+        self.model.as_deref().unwrap_or(self.provider.default_model())
+    }
+
+    pub fn embedding_model(&self) -> Option<&str> {
+        self.embedding_model
+            .as_deref()
+            .or(self.provider.default_embedding_model())
+    }
+
+    /// Model handling `task`: the per-task override, else the configured model, else the
+    /// provider default (its cheaper model for classification, its vision model for OCR).
+    /// `None` only for embeddings on a backend without an embeddings endpoint.
+    pub fn task_model<'a>(&'a self, models: &'a TaskModels, task: LlmTask) -> Option<&'a str> {
+        if let Some(model) = models.get(task) {
+            return Some(model);
+        }
+        match task {
+            LlmTask::Embedding => self.embedding_model(),
+            LlmTask::Classification => Some(
+                self.model
+                    .as_deref()
+                    .unwrap_or(self.provider.default_classification_model()),
+            ),
+            LlmTask::Summarization | LlmTask::Optimization => Some(self.model()),
+            LlmTask::Ocr => Some(self.provider.default_ocr_model().unwrap_or(self.model())),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.base_url
+            .as_deref()
+            .unwrap_or(self.provider.default_base_url())
+            .trim_end_matches('/')
+    }
+
+    pub fn backend(&self) -> BackendSettings {
+        BackendSettings {
+            provider: self.provider,
+            base_url: self.base_url.clone(),
+            api_key_header: self.api_key_header.clone(),
+            model: self.model.clone(),
+            embedding_model: self.embedding_model.clone(),
+            retry: self.retry,
+        }
+    }
+
+    /// These settings with `backend` in place of the backend parts. The API key is kept only
+    /// while the provider stays the same, since it was set for that provider.
+    pub fn with_backend(self, backend: BackendSettings) -> Self {
+        Self {
+            api_key: self.api_key.filter(|_| backend.provider == self.provider),
+            provider: backend.provider,
+            base_url: backend.base_url,
+            api_key_header: backend.api_key_header,
+            model: backend.model,
+            embedding_model: backend.embedding_model,
+            retry: backend.retry,
+            network: self.network,
+        }
+    }
+}
+
+/// The parts of [`LlmSettings`] an app saves in place of the environment variables: which
+/// backend, where and with which models it is reached, and how failures are retried. The API
+/// key and the network settings are kept separately.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackendSettings {
+    pub provider: ProviderKind,
+    pub base_url: Option<String>,
+    pub api_key_header: Option<String>,
+    pub model: Option<String>,
+    pub embedding_model: Option<String>,
+    pub retry: RetryPolicy,
+}
+
+impl Default for BackendSettings {
+    fn default() -> Self {
+        LlmSettings::default().backend()
+    }
+}
+
+/// Upper bound for [`RetryPolicy::max_attempts`] in saved settings.
+const MAX_RETRY_ATTEMPTS: u32 = 10;
+
+impl BackendSettings {
+    /// Trim values; blank ones become `None`, and a trailing `/` is dropped from the base URL.
+    pub fn normalized(self) -> Self {
+        let clean = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            base_url: clean(self.base_url).map(|url| url.trim_end_matches('/').to_string()),
+            api_key_header: clean(self.api_key_header),
+            model: clean(self.model),
+            embedding_model: clean(self.embedding_model),
+            ..self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(url) = &self.base_url {
+            let lower = url.to_ascii_lowercase();
+            if !lower.starts_with("http://") && !lower.starts_with("https://") {
+                return Err("baseUrl must start with http:// or https://".into());
+            }
+        }
+        if !(1..=MAX_RETRY_ATTEMPTS).contains(&self.retry.max_attempts) {
+            return Err(format!("retry.maxAttempts must be between 1 and {MAX_RETRY_ATTEMPTS}"));
+        }
+        if self.retry.base_delay_ms > self.retry.max_delay_ms {
+            return Err("retry.baseDelayMs must not exceed retry.maxDelayMs".into());
+        }
+        Ok(())
+    }
+}
+
+/// How the HTTP client reaches the backend, e.g. from behind a corporate proxy that
+/// re-signs TLS traffic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkSettings {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` URL, credentials allowed in the
+    /// URL. `None` honours the `HTTPS_PROXY` / `ALL_PROXY` environment variables.
+    pub proxy: Option<String>,
+    pub timeout_secs: u64,
+    /// PEM file with extra root certificates, trusted in addition to the built-in ones.
+    pub ca_bundle_path: Option<String>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            timeout_secs: 12,
+            ca_bundle_path: None,
+        }
+    }
+}
+
+const PROXY_SCHEMES: [&str; 4] = ["http://", "https://", "socks5://", "socks5h://"];
+
+impl NetworkSettings {
+    /// Trim values; blank proxy / CA paths become `None`.
+    pub fn normalized(self) -> Self {
+        let clean = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            proxy: clean(self.proxy),
+            ca_bundle_path: clean(self.ca_bundle_path),
+            ..self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.timeout_secs == 0 {
+            return Err("timeoutSecs must be at least 1".into());
+        }
+        if let Some(proxy) = &self.proxy {
+            let lower = proxy.to_ascii_lowercase();
+            if !PROXY_SCHEMES.iter().any(|scheme| lower.starts_with(scheme)) {
+                return Err(format!("proxy must start with one of {}", PROXY_SCHEMES.join(", ")));
+            }
+        }
+        if let Some(path) = &self.ca_bundle_path {
+            if !std::path::Path::new(path).is_file() {
+                return Err(format!("CA bundle not found: {path}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// HTTP client for a server on this machine: same timeout, no proxy or extra CAs.
+    pub fn local_http_client(&self) -> Result<Client, LlmError> {
+        Ok(Client::builder()
+            .timeout(Duration::from_secs(self.timeout_secs.max(1)))
+            .no_proxy()
+            .build()?)
+    }
+
+    /// Build the HTTP client, loading every certificate in the CA bundle.
+    pub fn http_client(&self) -> Result<Client, LlmError> {
+        let mut builder = Client::builder().timeout(Duration::from_secs(self.timeout_secs.max(1)));
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy).map_err(|error| LlmError::Network(format!("proxy {proxy}: {error}")))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(path) = &self.ca_bundle_path {
+            let pem = std::fs::read(path).map_err(|error| LlmError::Network(format!("CA bundle {path}: {error}")))?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .map_err(|error| LlmError::Network(format!("CA bundle {path}: {error}")))?;
+            if certificates.is_empty() {
+                return Err(LlmError::Network(format!("CA bundle {path}: no certificates")));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// A model server on this machine (Ollama, or LM Studio and similar through
+/// `openai_compatible`) that classification is routed to, so clipboard captures are
+/// classified without any cloud call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LocalLlmSettings {
+    pub enabled: bool,
+    pub provider: ProviderKind,
+    /// Falls back to [`LocalLlmSettings::default_base_url`].
+    pub base_url: Option<String>,
+    /// Required for `openai_compatible`; Ollama falls back to its default model.
+    pub model: Option<String>,
+    /// Local models on a laptop can take a while per request.
+    pub timeout_secs: u64,
+}
+
+impl Default for LocalLlmSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: ProviderKind::Ollama,
+            base_url: None,
+            model: None,
+            timeout_secs: 120,
+        }
+    }
+}
+
+/// LM Studio's default server address.
+pub const LM_STUDIO_BASE_URL: &str = "http://localhost:1234/v1";
+
+impl LocalLlmSettings {
+    pub fn normalized(self) -> Self {
+        let clean = |value: Option<String>| {
+            value
+                .map(|value| value.trim().trim_end_matches('/').to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            base_url: clean(self.base_url),
+            model: clean(self.model),
+            ..self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.provider, ProviderKind::Ollama | ProviderKind::OpenAiCompatible) {
+            return Err("local provider must be ollama or openai_compatible".into());
+        }
+        if self.timeout_secs == 0 {
+            return Err("timeoutSecs must be at least 1".into());
+        }
+        if endpoint_host(self.base_url()).is_none() {
+            return Err(format!("invalid base URL: {}", self.base_url()));
+        }
+        if self.enabled && self.provider == ProviderKind::OpenAiCompatible && self.model.is_none() {
+            return Err("model is required for openai_compatible".into());
+        }
+        Ok(())
+    }
+
+    pub fn default_base_url(&self) -> &'static str {
+        match self.provider {
+            ProviderKind::OpenAiCompatible => LM_STUDIO_BASE_URL,
+            _ => ProviderKind::Ollama.default_base_url(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.base_url.as_deref().unwrap_or(self.default_base_url())
+    }
+
+    /// Backend settings for the local server: no key, no proxy, a single attempt per call
+    /// (a local server that is down will not come back within the backoff).
+    pub fn llm_settings(&self) -> LlmSettings {
+        LlmSettings {
+            provider: self.provider,
+            base_url: Some(self.base_url().to_string()),
+            api_key: None,
+            api_key_header: None,
+            model: self.model.clone(),
+            embedding_model: None,
+            network: NetworkSettings {
+                timeout_secs: self.timeout_secs,
+                ..NetworkSettings::default()
+            },
+            retry: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+        }
+    }
+}
+
+/// Exponential backoff with full jitter for transient failures (see [`LlmError::is_retryable`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// Total attempts including the first one; 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 8_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether another attempt may follow the failed attempt number `attempt` (1-based).
+    pub fn should_retry(&self, attempt: u32, error: &LlmError) -> bool {
+        attempt < self.max_attempts.max(1) && error.is_retryable()
+    }
+
+    /// Delay before the attempt following `attempt`: a random duration in
+    /// `[0, min(max_delay, base_delay * 2^(attempt - 1))]`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let ceiling = self
+            .base_delay_ms
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_ms);
+        if ceiling == 0 {
+            return Duration::ZERO;
+        }
+        let jitter = (Uuid::new_v4().as_u128() % (ceiling as u128 + 1)) as u64;
+        Duration::from_millis(jitter)
+    }
+}
+
+/// Kinds of work that can be routed to different models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmTask {
+    Classification,
+    Summarization,
+    Optimization,
+    Embedding,
+    /// Reading the text in a captured screenshot; needs a model that accepts images.
+    Ocr,
+}
+
+impl LlmTask {
+    pub const ALL: [LlmTask; 5] = [
+        LlmTask::Classification,
+        LlmTask::Summarization,
+        LlmTask::Optimization,
+        LlmTask::Embedding,
+        LlmTask::Ocr,
+    ];
+}
+
+/// Per-task model overrides; `None` keeps the default from [`LlmSettings::task_model`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TaskModels {
+    pub classification: Option<String>,
+    pub summarization: Option<String>,
+    pub optimization: Option<String>,
+    pub embedding: Option<String>,
+    pub ocr: Option<String>,
+}
+
+impl TaskModels {
+    pub fn get(&self, task: LlmTask) -> Option<&str> {
+        match task {
+            LlmTask::Classification => self.classification.as_deref(),
+            LlmTask::Summarization => self.summarization.as_deref(),
+            LlmTask::Optimization => self.optimization.as_deref(),
+            LlmTask::Embedding => self.embedding.as_deref(),
+            LlmTask::Ocr => self.ocr.as_deref(),
+        }
+    }
+
+    /// Trim model names; blank ones become `None`.
+    pub fn normalized(self) -> Self {
+        let clean = |model: Option<String>| {
+            model
+                .map(|model| model.trim().to_string())
+                .filter(|model| !model.is_empty())
+        };
+        Self {
+            classification: clean(self.classification),
+            summarization: clean(self.summarization),
+            optimization: clean(self.optimization),
+            embedding: clean(self.embedding),
+            ocr: clean(self.ocr),
+        }
+    }
+}
+
+/// Shape of the reply asked from the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Text,
+    /// A bare JSON object, where the backend supports it.
+    Json,
+}
+
+/// Sampling settings for one kind of request; `None` leaves the value to the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub response_format: ResponseFormat,
+}
+
+impl GenerationParams {
+    /// Set `request`'s sampling fields from these params.
+    pub fn apply(&self, request: &mut ChatRequest) {
+        request.temperature = self.temperature;
+        request.top_p = self.top_p;
+        request.max_tokens = self.max_tokens;
+        request.json_output = self.response_format == ResponseFormat::Json;
+    }
+
+    fn validate(&self, task: &str) -> Result<(), String> {
+        if self.temperature.is_some_and(|temperature| !(0.0..=2.0).contains(&temperature)) {
+            return Err(format!("{task}.temperature must be between 0 and 2"));
+        }
+        if self.top_p.is_some_and(|top_p| !(top_p > 0.0 && top_p <= 1.0)) {
+            return Err(format!("{task}.topP must be greater than 0 and at most 1"));
+        }
+        if self.max_tokens == Some(0) {
+            return Err(format!("{task}.maxTokens must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Generation params per task. Classification and optimization replies are parsed as JSON;
+/// the playground sends the user's prompt as is.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GenerationSettings {
+    pub classification: GenerationParams,
+    pub optimization: GenerationParams,
+    /// Defaults for `run_prompt`; params given with a run take precedence.
+    pub playground: GenerationParams,
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        Self {
+            classification: GenerationParams {
+                temperature: Some(0.2),
+                top_p: None,
+                max_tokens: Some(800),
+                response_format: ResponseFormat::Json,
+            },
+            optimization: GenerationParams {
+                temperature: Some(0.5),
+                top_p: None,
+                max_tokens: Some(2000),
+                response_format: ResponseFormat::Json,
+            },
+            playground: GenerationParams::default(),
+        }
+    }
+}
+
+impl GenerationSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        self.classification.validate("classification")?;
+        self.optimization.validate("optimization")?;
+        self.playground.validate("playground")
+    }
+}
+
+/// Client-side limits shared by every LLM call site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RateLimits {
+    /// Sustained request rate; 0 disables the rate limit.
+    pub requests_per_minute: u32,
+    /// Requests that may be sent back to back before the rate applies.
+    pub burst: u32,
+    /// Requests allowed to be awaiting a response at the same time.
+    pub max_in_flight: usize,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: 60,
+            burst: 5,
+            max_in_flight: 3,
+        }
+    }
+}
+
+impl RateLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.burst == 0 {
+            return Err("burst must be at least 1".into());
+        }
+        if self.max_in_flight == 0 {
+            return Err("maxInFlight must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+/// Token bucket refilled at `requests_per_minute`, holding at most `burst` tokens.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Starts full, so the first `burst` requests go out immediately.
+    pub fn new(limits: &RateLimits) -> Self {
+        let capacity = limits.burst.max(1) as f64;
+        Self {
+            capacity,
+            refill_per_sec: limits.requests_per_minute as f64 / 60.0,
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    /// Apply new limits, keeping the tokens already earned (up to the new capacity).
+    pub fn reconfigure(&mut self, limits: &RateLimits) {
+        self.refill(Instant::now());
+        self.capacity = limits.burst.max(1) as f64;
+        self.refill_per_sec = limits.requests_per_minute as f64 / 60.0;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    /// Take one token, or return how long until the next one is available.
+    pub fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        if self.refill_per_sec <= 0.0 {
+            return Ok(());
+        }
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec))
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+}
+
+/// Stops calls to a backend after `threshold` consecutive failures, for `cooldown`. Once
+/// the cooldown is over calls are let through again; the next failure re-opens the
+/// circuit right away and the next success closes it.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    failures: u32,
+    open_until: Option<Instant>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    /// The cooldown is over; the next call decides.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CircuitStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Seconds until calls are let through again, while open.
+    pub retry_in_secs: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            failures: 0,
+            open_until: None,
+            last_error: None,
+        }
+    }
+
+    pub fn allows(&self, now: Instant) -> bool {
+        self.open_until.is_none_or(|until| now >= until)
+    }
+
+    /// Returns `true` when this closes a tripped circuit.
+    pub fn record_success(&mut self) -> bool {
+        self.failures = 0;
+        self.last_error = None;
+        self.open_until.take().is_some()
+    }
+
+    /// Returns `true` when this failure trips a closed circuit.
+    pub fn record_failure(&mut self, now: Instant, error: impl Into<String>) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        self.last_error = Some(error.into());
+        if self.failures < self.threshold {
+            return false;
+        }
+        let tripped = self.open_until.is_none();
+        self.open_until = Some(now + self.cooldown);
+        tripped
+    }
+
+    pub fn reset(&mut self) {
+        self.record_success();
+    }
+
+    pub fn status(&self, now: Instant) -> CircuitStatus {
+        let (state, retry_in_secs) = match self.open_until {
+            None => (CircuitState::Closed, None),
+            Some(until) if now >= until => (CircuitState::HalfOpen, None),
+            Some(until) => (CircuitState::Open, Some(until.duration_since(now).as_secs_f64().ceil() as u64)),
+        };
+        CircuitStatus {
+            state,
+            consecutive_failures: self.failures,
+            retry_in_secs,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::System => "system",
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+}
+
+/// An image sent along with a message, e.g. a screenshot to read text from.
+#[derive(Debug, Clone)]
+pub struct ChatImage {
+    /// e.g. `image/png`.
+    pub media_type: String,
+    pub data: Vec<u8>,
+}
+
+impl ChatImage {
+    pub fn png(data: Vec<u8>) -> Self {
+        Self {
+            media_type: "image/png".into(),
+            data,
+        }
+    }
+
+    fn base64(&self) -> String {
+        BASE64.encode(&self.data)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+    /// Each provider sends these in its own format; serializing a message (e.g. for the debug
+    /// log) leaves them out.
+    #[serde(skip)]
+    pub images: Vec<ChatImage>,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::System,
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
+
+    pub fn user_with_images(content: impl Into<String>, images: Vec<ChatImage>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+            images,
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            content: content.into(),
+            images: Vec::new(),
+        }
+    }
+
+    /// `/chat/completions` format: images become `image_url` parts with data URLs.
+    fn openai_json(&self) -> Value {
+        if self.images.is_empty() {
+            return json!(self);
+        }
+        let mut parts: Vec<Value> = self
+            .images
+            .iter()
+            .map(|image| {
+                let url = format!("data:{};base64,{}", image.media_type, image.base64());
+                json!({ "type": "image_url", "image_url": { "url": url } })
+            })
+            .collect();
+        if !self.content.is_empty() {
+            parts.push(json!({ "type": "text", "text": self.content }));
+        }
+        json!({ "role": self.role, "content": parts })
+    }
+
+    /// Anthropic format: base64 `image` blocks before the text.
+    fn anthropic_json(&self) -> Value {
+        if self.images.is_empty() {
+            return json!(self);
+        }
+        let mut blocks: Vec<Value> = self
+            .images
+            .iter()
+            .map(|image| {
+                json!({
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": image.media_type, "data": image.base64() },
+                })
+            })
+            .collect();
+        if !self.content.is_empty() {
+            blocks.push(json!({ "type": "text", "text": self.content }));
+        }
+        json!({ "role": self.role, "content": blocks })
+    }
+
+    /// Ollama format: bare base64 strings in `images`.
+    fn ollama_json(&self) -> Value {
+        let mut message = json!(self);
+        if !self.images.is_empty() {
+            message["images"] = self.images.iter().map(ChatImage::base64).collect();
+        }
+        message
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatRequest {
+    /// Overrides the provider's configured model for this request.
+    pub model: Option<String>,
+    pub messages: Vec<ChatMessage>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// Ask the backend for a bare JSON object when it supports it.
+    pub json_output: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatResponse {
+    pub content: String,
+    /// Model name reported by the backend (falls back to the requested one).
+    pub model: String,
+    pub usage: Option<TokenUsage>,
+    /// The backend stopped because it reached `max_tokens`.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum LlmError {
+    #[error("unknown LLM provider: {0}")]
+    UnknownProvider(String),
+    #[error("{0} requires an API key")]
+    MissingApiKey(&'static str),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("{provider} returned HTTP {status}: {body}")]
+    Status {
+        provider: &'static str,
+        status: u16,
+        body: String,
+    },
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+    #[error("{0} does not support embeddings")]
+    EmbeddingsUnsupported(&'static str),
+    #[error("invalid network settings: {0}")]
+    Network(String),
+}
+
+impl LlmError {
+    /// Rate limiting (429), server errors (5xx), timeouts and connection failures are
+    /// worth retrying; bad keys, bad requests and unparseable replies are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LlmError::Http(error) => error.is_timeout() || error.is_connect(),
+            LlmError::Status { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// The backend could not be reached at all: no network, DNS or proxy failure, timeout.
+    pub fn is_connection_failure(&self) -> bool {
+        matches!(self, LlmError::Http(error) if error.is_timeout() || error.is_connect())
+    }
+
+    /// The backend itself is failing (unreachable, key rejected, quota exhausted, server
+    /// error), as opposed to one request it refused or a reply we could not use.
+    pub fn is_backend_failure(&self) -> bool {
+        match self {
+            LlmError::MissingApiKey(_) | LlmError::Http(_) | LlmError::Network(_) => true,
+            LlmError::Status { status, .. } => !matches!(status, 400 | 413 | 422),
+            _ => false,
+        }
+    }
+}
+
+/// A chat-completion backend. Implementations are async and safe to share across tasks.
+#[async_trait]
+pub trait Provider: Send + Sync {
+    fn kind(&self) -> ProviderKind;
+    fn model(&self) -> &str;
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError>;
+
+    /// Model names the backend offers (for Ollama, the models pulled locally).
+    async fn list_models(&self) -> Result<Vec<String>, LlmError>;
+
+    /// Embed each input with `model`, in input order.
+    async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        let _ = (model, inputs);
+        Err(LlmError::EmbeddingsUnsupported(self.kind().as_str()))
+    }
+}
+
+/// Whether `base_url` points at this machine (a local Ollama or LM Studio server), which
+/// needs neither an API key nor the proxy.
+pub fn is_local_endpoint(base_url: &str) -> bool {
+    endpoint_host(base_url).is_some_and(|(host, _, _)| {
+        let host = host.to_ascii_lowercase();
+        host == "localhost" || host == "0.0.0.0" || host == "::1" || host.starts_with("127.")
+    })
+}
+
+/// Build the provider selected by `settings`. Hosted backends need an API key unless the
+/// base URL is local (e.g. LM Studio through `openai_compatible`).
+pub fn build_provider(settings: &LlmSettings) -> Result<Box<dyn Provider>, LlmError> {
+    let kind = settings.provider;
+    let api_key = settings.api_key.clone().filter(|key| !key.is_empty());
+    let local = is_local_endpoint(settings.base_url());
+    if kind.requires_api_key() && api_key.is_none() && !local {
+        return Err(LlmError::MissingApiKey(kind.as_str()));
+    }
+    let client = if local {
+        settings.network.local_http_client()?
+    } else {
+        settings.network.http_client()?
+    };
+    let endpoint = Endpoint {
+        client,
+        base_url: settings.base_url().to_string(),
+        api_key,
+        model: settings.model().to_string(),
+    };
+    Ok(match kind {
+        ProviderKind::DashScope | ProviderKind::OpenAiCompatible => Box::new(OpenAiCompatibleProvider {
+            kind,
+            endpoint,
+            api_key_header: settings
+                .api_key_header
+                .as_deref()
+                .map(str::trim)
+                .filter(|header| !header.is_empty())
+                .unwrap_or("Authorization")
+                .to_string(),
+        }),
+        ProviderKind::Anthropic => Box::new(AnthropicProvider { endpoint }),
+        ProviderKind::Ollama => Box::new(OllamaProvider { endpoint }),
+    })
+}
+
+struct Endpoint {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl Endpoint {
+    async fn post(
+        &self,
+        provider: &'static str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &Value,
+    ) -> Result<Value, LlmError> {
+        let request = self.client.post(self.url(path)).json(body);
+        self.send(provider, request, headers).await
+    }
+
+    async fn get(&self, provider: &'static str, path: &str, headers: &[(&str, &str)]) -> Result<Value, LlmError> {
+        let request = self.client.get(self.url(path));
+        self.send(provider, request, headers).await
+    }
+
+    /// `path` appended to the base URL's path, keeping its query string (Azure OpenAI takes
+    /// `?api-version=...` there).
+    fn url(&self, path: &str) -> String {
+        match self.base_url.split_once('?') {
+            Some((base, query)) => format!("{}{path}?{query}", base.trim_end_matches('/')),
+            None => format!("{}{path}", self.base_url),
+        }
+    }
+
+    async fn send(
+        &self,
+        provider: &'static str,
+        mut request: RequestBuilder,
+        headers: &[(&str, &str)],
+    ) -> Result<Value, LlmError> {
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(LlmError::Status {
+                provider,
+                status: status.as_u16(),
+                body: text.chars().take(500).collect(),
+            });
+        }
+        serde_json::from_str(&text).map_err(|error| LlmError::InvalidResponse(error.to_string()))
+    }
+}
+
+fn response_model(payload: &Value, requested: &str) -> String {
+    payload["model"].as_str().unwrap_or(requested).to_string()
+}
+
+/// The string `key` of every item in the `list` array of a model listing.
+fn model_names(payload: &Value, list: &str, key: &str) -> Result<Vec<String>, LlmError> {
+    let items = payload[list]
+        .as_array()
+        .ok_or_else(|| LlmError::InvalidResponse(format!("missing {list}")))?;
+    Ok(items
+        .iter()
+        .filter_map(|item| item[key].as_str())
+        .map(str::to_string)
+        .collect())
+}
+
+/// `POST {base}/chat/completions`; also used for DashScope's compatible mode.
+struct OpenAiCompatibleProvider {
+    kind: ProviderKind,
+    endpoint: Endpoint,
+    api_key_header: String,
+}
+
+impl OpenAiCompatibleProvider {
+    /// The API key header, if a key is configured.
+    fn auth_header(&self) -> Option<(&str, String)> {
+        let key = self.endpoint.api_key.as_deref()?;
+        Some(if self.api_key_header.eq_ignore_ascii_case("authorization") {
+            ("Authorization", format!("Bearer {key}"))
+        } else {
+            (self.api_key_header.as_str(), key.to_string())
+        })
+    }
+
+    fn headers<'a>(auth: &'a Option<(&'a str, String)>) -> Vec<(&'a str, &'a str)> {
+        auth.iter().map(|(name, value)| (*name, value.as_str())).collect()
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAiCompatibleProvider {
+    fn kind(&self) -> ProviderKind {
+        self.kind
+    }
+
+    fn model(&self) -> &str {
+        &self.endpoint.model
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        let model = request.model.as_deref().unwrap_or(&self.endpoint.model);
+        let messages: Vec<Value> = request.messages.iter().map(ChatMessage::openai_json).collect();
+        let mut body = json!({
+            "model": model,
+            "messages": messages,
+        });
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if request.json_output {
+            body["response_format"] = json!({ "type": "json_object" });
+        }
+        let auth = self.auth_header();
+        let headers = Self::headers(&auth);
+        let payload = self
+            .endpoint
+            .post(self.kind.as_str(), "/chat/completions", &headers, &body)
+            .await?;
+        let content = payload["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| LlmError::InvalidResponse("missing choices[0].message.content".into()))?;
+        let usage = payload.get("usage").map(|usage| TokenUsage {
+            prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        });
+        Ok(ChatResponse {
+            content: content.to_string(),
+            model: response_model(&payload, model),
+            usage,
+            truncated: payload["choices"][0]["finish_reason"] == "length",
+        })
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        let auth = self.auth_header();
+        let headers = Self::headers(&auth);
+        let payload = self.endpoint.get(self.kind.as_str(), "/models", &headers).await?;
+        model_names(&payload, "data", "id")
+    }
+
+    async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        let body = json!({ "model": model, "input": inputs });
+        let auth = self.auth_header();
+        let headers = Self::headers(&auth);
+        let payload = self
+            .endpoint
+            .post(self.kind.as_str(), "/embeddings", &headers, &body)
+            .await?;
+        let mut items: Vec<(usize, Vec<f32>)> = payload["data"]
+            .as_array()
+            .ok_or_else(|| LlmError::InvalidResponse("missing data".into()))?
+            .iter()
+            .enumerate()
+            .map(|(position, item)| {
+                let index = item["index"].as_u64().map(|index| index as usize).unwrap_or(position);
+                (index, parse_vector(&item["embedding"]))
+            })
+            .collect();
+        items.sort_by_key(|(index, _)| *index);
+        check_embeddings(items.into_iter().map(|(_, vector)| vector).collect(), inputs.len())
+    }
+}
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 1024;
+
+/// `POST {base}/v1/messages`; system messages go into the top-level `system` field.
+struct AnthropicProvider {
+    endpoint: Endpoint,
+}
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Anthropic
+    }
+
+    fn model(&self) -> &str {
+        &self.endpoint.model
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        let system: Vec<&str> = request
+            .messages
+            .iter()
+            .filter(|message| message.role == ChatRole::System)
+            .map(|message| message.content.as_str())
+            .collect();
+        let messages: Vec<Value> = request
+            .messages
+            .iter()
+            .filter(|message| message.role != ChatRole::System)
+            .map(ChatMessage::anthropic_json)
+            .collect();
+        let model = request.model.as_deref().unwrap_or(&self.endpoint.model);
+        let mut body = json!({
+            "model": model,
+            "messages": messages,
+            "max_tokens": request.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
+        });
+        if !system.is_empty() {
+            body["system"] = json!(system.join("\n\n"));
+        }
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        let api_key = self.endpoint.api_key.as_deref().unwrap_or_default();
+        let headers = [("x-api-key", api_key), ("anthropic-version", ANTHROPIC_VERSION)];
+        let payload = self.endpoint.post("anthropic", "/v1/messages", &headers, &body).await?;
+        let content: String = payload["content"]
+            .as_array()
+            .ok_or_else(|| LlmError::InvalidResponse("missing content blocks".into()))?
+            .iter()
+            .filter_map(|block| block["text"].as_str())
+            .collect();
+        let usage = payload.get("usage").map(|usage| TokenUsage {
+            prompt_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+        });
+        Ok(ChatResponse {
+            content,
+            model: response_model(&payload, model),
+            usage,
+            truncated: payload["stop_reason"] == "max_tokens",
+        })
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        let api_key = self.endpoint.api_key.as_deref().unwrap_or_default();
+        let headers = [("x-api-key", api_key), ("anthropic-version", ANTHROPIC_VERSION)];
+        let payload = self.endpoint.get("anthropic", "/v1/models", &headers).await?;
+        model_names(&payload, "data", "id")
+    }
+}
+
+/// `POST {base}/api/chat` with streaming disabled.
+struct OllamaProvider {
+    endpoint: Endpoint,
+}
+
+#[async_trait]
+impl Provider for OllamaProvider {
+    fn kind(&self) -> ProviderKind {
+        ProviderKind::Ollama
+    }
+
+    fn model(&self) -> &str {
+        &self.endpoint.model
+    }
+
+    async fn chat(&self, request: &ChatRequest) -> Result<ChatResponse, LlmError> {
+        let mut options = json!({});
+        if let Some(temperature) = request.temperature {
+            options["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = request.top_p {
+            options["top_p"] = json!(top_p);
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            options["num_predict"] = json!(max_tokens);
+        }
+        let model = request.model.as_deref().unwrap_or(&self.endpoint.model);
+        let messages: Vec<Value> = request.messages.iter().map(ChatMessage::ollama_json).collect();
+        let mut body = json!({
+            "model": model,
+            "messages": messages,
+            "stream": false,
+            "options": options,
+        });
+        if request.json_output {
+            body["format"] = json!("json");
+        }
+        let payload = self.endpoint.post("ollama", "/api/chat", &[], &body).await?;
+        let content = payload["message"]["content"]
+            .as_str()
+            .ok_or_else(|| LlmError::InvalidResponse("missing message.content".into()))?;
+        let usage = payload.get("eval_count").map(|_| TokenUsage {
+            prompt_tokens: payload["prompt_eval_count"].as_u64().unwrap_or(0),
+            completion_tokens: payload["eval_count"].as_u64().unwrap_or(0),
+        });
+        Ok(ChatResponse {
+            content: content.to_string(),
+            model: response_model(&payload, model),
+            usage,
+            truncated: payload["done_reason"] == "length",
+        })
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>, LlmError> {
+        let payload = self.endpoint.get("ollama", "/api/tags", &[]).await?;
+        model_names(&payload, "models", "name")
+    }
+
+    async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        let body = json!({ "model": model, "input": inputs });
+        let payload = self.endpoint.post("ollama", "/api/embed", &[], &body).await?;
+        let vectors = payload["embeddings"]
+            .as_array()
+            .ok_or_else(|| LlmError::InvalidResponse("missing embeddings".into()))?
+            .iter()
+            .map(parse_vector)
+            .collect();
+        check_embeddings(vectors, inputs.len())
+    }
+}
+
+fn parse_vector(value: &Value) -> Vec<f32> {
+    value
+        .as_array()
+        .map(|items| items.iter().filter_map(Value::as_f64).map(|item| item as f32).collect())
+        .unwrap_or_default()
+}
+
+fn check_embeddings(vectors: Vec<Vec<f32>>, expected: usize) -> Result<Vec<Vec<f32>>, LlmError> {
+    if vectors.len() != expected || vectors.iter().any(Vec::is_empty) {
+        return Err(LlmError::InvalidResponse(format!(
+            "expected {expected} embeddings, got {}",
+            vectors.iter().filter(|vector| !vector.is_empty()).count()
+        )));
+    }
+    Ok(vectors)
+}
+
+/// Outcome of one step of [`diagnose_connection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Passed,
+    Failed,
+    /// Not reached because an earlier step failed, or not applicable (TLS over plain HTTP).
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticCheck {
+    pub status: CheckStatus,
+    pub detail: Option<String>,
+}
+
+impl DiagnosticCheck {
+    pub fn passed() -> Self {
+        Self {
+            status: CheckStatus::Passed,
+            detail: None,
+        }
+    }
+
+    pub fn failed(detail: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Failed,
+            detail: Some(detail.into()),
+        }
+    }
+
+    pub fn skipped() -> Self {
+        Self {
+            status: CheckStatus::Skipped,
+            detail: None,
+        }
+    }
+
+    fn is_failed(&self) -> bool {
+        self.status == CheckStatus::Failed
+    }
+}
+
+/// Step-by-step result of a test request, so "bad key" and "blocked network" look different.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionDiagnostics {
+    pub provider: ProviderKind,
+    pub base_url: String,
+    pub model: String,
+    /// Host name resolution of the base URL.
+    pub dns: DiagnosticCheck,
+    /// Reaching the host (through the proxy, if any) within the timeout.
+    pub connect: DiagnosticCheck,
+    pub tls: DiagnosticCheck,
+    /// API key accepted (no 401/403).
+    pub auth: DiagnosticCheck,
+    /// Not rate limited and not out of quota or balance.
+    pub quota: DiagnosticCheck,
+    /// Round trip of the test request when the backend answered.
+    pub latency_ms: Option<u64>,
+    pub ok: bool,
+    /// The first failure, or what went wrong after every check passed.
+    pub message: Option<String>,
+}
+
+impl ConnectionDiagnostics {
+    fn new(settings: &LlmSettings, dns: DiagnosticCheck) -> Self {
+        Self {
+            provider: settings.provider,
+            base_url: settings.base_url().to_string(),
+            model: settings.model().to_string(),
+            dns,
+            connect: DiagnosticCheck::skipped(),
+            tls: DiagnosticCheck::skipped(),
+            auth: DiagnosticCheck::skipped(),
+            quota: DiagnosticCheck::skipped(),
+            latency_ms: None,
+            ok: false,
+            message: None,
+        }
+    }
+
+    /// Diagnostics for settings that could not produce a provider (see [`build_provider`]).
+    pub fn unavailable(settings: &LlmSettings, dns: DiagnosticCheck, error: &LlmError) -> Self {
+        let mut diagnostics = Self::new(settings, dns);
+        match error {
+            LlmError::MissingApiKey(_) => diagnostics.auth = DiagnosticCheck::failed(error.to_string()),
+            _ => diagnostics.connect = DiagnosticCheck::failed(error.to_string()),
+        }
+        diagnostics.finish(None)
+    }
+
+    fn finish(mut self, message: Option<String>) -> Self {
+        let checks = [&self.dns, &self.connect, &self.tls, &self.auth, &self.quota];
+        let failure = checks.iter().find(|check| check.is_failed()).and_then(|check| check.detail.clone());
+        self.ok = failure.is_none() && message.is_none();
+        self.message = failure.or(message);
+        self
+    }
+}
+
+/// Host and port the base URL points at, plus whether it uses TLS.
+fn endpoint_host(base_url: &str) -> Option<(String, u16, bool)> {
+    let (scheme, rest) = base_url.split_once("://")?;
+    let https = scheme.eq_ignore_ascii_case("https");
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit('@').next()?;
+    let (host, port) = match authority.strip_prefix('[') {
+        // IPv6 literal: [::1]:11434
+        Some(rest) => {
+            let (host, after) = rest.split_once(']')?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse().ok()?,
+        None if https => 443,
+        None => 80,
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port, https))
+}
+
+/// Resolve the base URL's host. Blocks on the system resolver; run it off the async runtime.
+pub fn check_dns(base_url: &str) -> DiagnosticCheck {
+    use std::net::ToSocketAddrs;
+
+    let Some((host, port, _)) = endpoint_host(base_url) else {
+        return DiagnosticCheck::failed(format!("invalid base URL: {base_url}"));
+    };
+    match (host.as_str(), port).to_socket_addrs() {
+        Ok(mut addresses) => match addresses.next() {
+            Some(address) => DiagnosticCheck {
+                status: CheckStatus::Passed,
+                detail: Some(address.ip().to_string()),
+            },
+            None => DiagnosticCheck::failed(format!("{host} has no addresses")),
+        },
+        Err(error) => DiagnosticCheck::failed(format!("cannot resolve {host}: {error}")),
+    }
+}
+
+const QUOTA_MARKERS: [&str; 5] = ["quota", "insufficient", "arrearage", "billing", "credit"];
+const TLS_MARKERS: [&str; 4] = ["certificate", "tls", "ssl", "handshake"];
+
+/// Send the smallest possible chat request and sort the outcome into the diagnostic
+/// steps. `dns` comes from [`check_dns`]; when it failed nothing is sent.
+pub async fn diagnose_connection(
+    provider: &dyn Provider,
+    settings: &LlmSettings,
+    dns: DiagnosticCheck,
+) -> ConnectionDiagnostics {
+    let mut diagnostics = ConnectionDiagnostics::new(settings, dns);
+    diagnostics.model = provider.model().to_string();
+    if diagnostics.dns.is_failed() {
+        return diagnostics.finish(None);
+    }
+    let https = endpoint_host(settings.base_url()).is_some_and(|(_, _, https)| https);
+    let request = ChatRequest {
+        messages: vec![ChatMessage::user("ping")],
+        max_tokens: Some(1),
+        ..ChatRequest::default()
+    };
+    let started = Instant::now();
+    let result = provider.chat(&request).await;
+    let elapsed = started.elapsed().as_millis() as u64;
+    let reached = |diagnostics: &mut ConnectionDiagnostics| {
+        diagnostics.connect = DiagnosticCheck::passed();
+        diagnostics.tls = if https {
+            DiagnosticCheck::passed()
+        } else {
+            DiagnosticCheck::skipped()
+        };
+        diagnostics.latency_ms = Some(elapsed);
+    };
+    let message = match result {
+        Ok(_) => {
+            reached(&mut diagnostics);
+            diagnostics.auth = DiagnosticCheck::passed();
+            diagnostics.quota = DiagnosticCheck::passed();
+            None
+        }
+        Err(LlmError::Http(error)) => {
+            let chain = error_chain(&error);
+            let lower = chain.to_lowercase();
+            if error.is_timeout() {
+                diagnostics.connect = DiagnosticCheck::failed(format!(
+                    "timed out after {}s: {chain}",
+                    settings.network.timeout_secs
+                ));
+            } else if https && TLS_MARKERS.iter().any(|marker| lower.contains(marker)) {
+                diagnostics.connect = DiagnosticCheck::passed();
+                diagnostics.tls = DiagnosticCheck::failed(chain);
+            } else {
+                diagnostics.connect = DiagnosticCheck::failed(chain);
+            }
+            None
+        }
+        Err(LlmError::Status { status, body, .. }) => {
+            reached(&mut diagnostics);
+            let lower = body.to_lowercase();
+            if status == 401 || status == 403 {
+                diagnostics.auth = DiagnosticCheck::failed(format!("HTTP {status}: {body}"));
+                None
+            } else if status == 429 || QUOTA_MARKERS.iter().any(|marker| lower.contains(marker)) {
+                diagnostics.auth = DiagnosticCheck::passed();
+                diagnostics.quota = DiagnosticCheck::failed(format!("HTTP {status}: {body}"));
+                None
+            } else {
+                diagnostics.auth = DiagnosticCheck::passed();
+                diagnostics.quota = DiagnosticCheck::passed();
+                Some(format!("HTTP {status}: {body}"))
+            }
+        }
+        Err(error) => {
+            reached(&mut diagnostics);
+            diagnostics.auth = DiagnosticCheck::passed();
+            diagnostics.quota = DiagnosticCheck::passed();
+            Some(error.to_string())
+        }
+    };
+    diagnostics.finish(message)
+}
+
+/// An error and all of its sources, which is where reqwest keeps the TLS/IO details.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Pull the first JSON object out of a model reply, tolerating ```json fences and chatter
+/// around it.
+pub fn parse_json_reply(content: &str) -> Result<Value, LlmError> {
+    let start = content.find('{');
+    let end = content.rfind('}');
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str(&content[start..=end])
+            .map_err(|error| LlmError::InvalidResponse(format!("invalid JSON in reply: {error}"))),
+        _ => Err(LlmError::InvalidResponse("reply contains no JSON object".into())),
+    }
+}
+
+/// Parse a model reply into `T`. Mismatches name the offending field, e.g.
+/// `reply does not match schema at confidence: invalid type: string "高", expected f64`.
+pub fn parse_typed_reply<T: DeserializeOwned>(content: &str) -> Result<T, LlmError> {
+    let value = parse_json_reply(content)?;
+    serde_path_to_error::deserialize(value).map_err(|error| {
+        LlmError::InvalidResponse(format!("reply does not match schema at {}: {}", error.path(), error.inner()))
+    })
+}
+
+/// Reply to the clipboard classification request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationReply {
+    pub summary: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub topic: String,
+    #[serde(default)]
+    pub role: String,
+    #[serde(default)]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub intent: String,
+    pub is_prompt: bool,
+    /// Clamped to 0.0 – 1.0.
+    pub confidence: f64,
+}
+
+impl ClassificationReply {
+    pub fn from_reply(content: &str) -> Result<Self, LlmError> {
+        let mut reply: ClassificationReply = parse_typed_reply(content)?;
+        reply.summary = reply.summary.trim().to_string();
+        reply.topic = reply.topic.trim().to_string();
+        reply.role = reply.role.trim().to_string();
+        reply.intent = reply.intent.trim().to_string();
+        clean_list(&mut reply.tags);
+        clean_list(&mut reply.targets);
+        if !reply.confidence.is_finite() {
+            return Err(LlmError::InvalidResponse(
+                "reply does not match schema at confidence: not a number".into(),
+            ));
+        }
+        reply.confidence = reply.confidence.clamp(0.0, 1.0);
+        Ok(reply)
+    }
+}
+
+/// Reply to the optimize request: the rewritten prompt and what was changed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptimizationReply {
+    pub optimized: String,
+    #[serde(default)]
+    pub changes: Vec<String>,
+}
+
+impl OptimizationReply {
+    pub fn from_reply(content: &str) -> Result<Self, LlmError> {
+        let mut reply: OptimizationReply = parse_typed_reply(content)?;
+        reply.optimized = reply.optimized.trim().to_string();
+        clean_list(&mut reply.changes);
+        Ok(reply)
+    }
+}
+
+/// Reply to the translate request; a missing title falls back to the original's.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranslationReply {
+    #[serde(default)]
+    pub title: Option<String>,
+    pub body: String,
+}
+
+impl TranslationReply {
+    pub fn from_reply(content: &str) -> Result<Self, LlmError> {
+        let mut reply: TranslationReply = parse_typed_reply(content)?;
+        reply.title = reply
+            .title
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty());
+        reply.body = reply.body.trim().to_string();
+        Ok(reply)
+    }
+}
+
+/// Reply to the duplicate check: whether the prompts mean the same and, if so, one merged
+/// canonical version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergeReply {
+    pub same: bool,
+    #[serde(default)]
+    pub merged: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl MergeReply {
+    pub fn from_reply(content: &str) -> Result<Self, LlmError> {
+        let mut reply: MergeReply = parse_typed_reply(content)?;
+        reply.merged = reply.merged.trim().to_string();
+        reply.reason = reply.reason.trim().to_string();
+        if reply.same && reply.merged.is_empty() {
+            return Err(LlmError::InvalidResponse("same is true but merged is empty".into()));
+        }
+        Ok(reply)
+    }
+}
+
+/// Trim items and drop blanks and duplicates, keeping order.
+fn clean_list(items: &mut Vec<String>) {
+    let mut seen = Vec::new();
+    for item in items.drain(..) {
+        let item = item.trim().to_string();
+        if !item.is_empty() && !seen.contains(&item) {
+            seen.push(item);
+        }
+    }
+    *items = seen;
+}
+
+/// Category used when the model finds none of the known categories fitting.
+pub const FALLBACK_CATEGORY: &str = "其他";
+const DEEP_ANALYSIS_MAX_TAGS: usize = 10;
+
+/// Structured analysis requested from the model by `analyze_with_qwen`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeepAnalysis {
+    pub summary: String,
+    pub tags: Vec<String>,
+    /// One of `PROMPT_CATEGORIES` or [`FALLBACK_CATEGORY`].
+    pub category: String,
+    #[serde(default)]
+    pub role: String,
+    /// Who the prompt's output is for.
+    #[serde(default)]
+    pub audience: Vec<String>,
+    /// Weaknesses and concrete suggestions for improving the prompt.
+    #[serde(default)]
+    pub quality_notes: Vec<String>,
+}
+
+impl DeepAnalysis {
+    /// Parse a model reply and check it against the schema: non-empty summary, 1-10 distinct
+    /// tags and a known category (normalized, e.g. "编程类" → "编程").
+    pub fn from_reply(content: &str) -> Result<Self, LlmError> {
+        let mut analysis: DeepAnalysis = parse_typed_reply(content)?;
+        analysis.normalize();
+        analysis.validate()?;
+        Ok(analysis)
+    }
+
+    fn normalize(&mut self) {
+        self.summary = self.summary.trim().to_string();
+        self.role = self.role.trim().to_string();
+        clean_list(&mut self.tags);
+        clean_list(&mut self.audience);
+        clean_list(&mut self.quality_notes);
+        if let Some(category) = canonical_category(&self.category) {
+            self.category = category.to_string();
+        } else {
+            self.category = self.category.trim().to_string();
+        }
+    }
+
+    fn validate(&self) -> Result<(), LlmError> {
+        let invalid = |message: String| {
+            Err(LlmError::InvalidResponse(format!("analysis does not match schema: {message}")))
+        };
+        if self.summary.is_empty() {
+            return invalid("summary is empty".into());
+        }
+        if self.tags.is_empty() || self.tags.len() > DEEP_ANALYSIS_MAX_TAGS {
+            return invalid(format!("expected 1-{DEEP_ANALYSIS_MAX_TAGS} tags, got {}", self.tags.len()));
+        }
+        if canonical_category(&self.category).is_none() && self.category != FALLBACK_CATEGORY {
+            return invalid(format!("unknown category {:?}", self.category));
+        }
+        Ok(())
+    }
+}
+
+/// One dimension of a scoring rubric.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RubricCriterion {
+    /// Stable identifier the model must echo back, e.g. `clarity`.
+    pub key: String,
+    pub name: String,
+    /// What a high score means, shown to the model.
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Clarity, specificity, constraint completeness and format definition.
+pub fn default_rubric() -> Vec<RubricCriterion> {
+    [
+        ("clarity", "清晰度", "任务目标是否明确、表述无歧义，读一遍就知道要做什么"),
+        ("specificity", "具体性", "是否给出背景、对象、范围与示例，而不是泛泛而谈"),
+        ("constraints", "约束完整性", "长度、语气、禁止事项、边界情况等限制是否交代完整"),
+        ("format", "格式定义", "是否规定了输出的结构、格式或字段"),
+    ]
+    .into_iter()
+    .map(|(key, name, description)| RubricCriterion {
+        key: key.into(),
+        name: name.into(),
+        description: description.into(),
+    })
+    .collect()
+}
+
+pub const RUBRIC_MIN_SCORE: u8 = 1;
+pub const RUBRIC_MAX_SCORE: u8 = 5;
+
+/// Score of a prompt on one rubric criterion.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RubricScore {
+    pub criterion: String,
+    pub score: u8,
+    pub rationale: String,
+}
+
+/// Reply to `score_prompt`: one score per rubric criterion and a one-line verdict.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RubricReply {
+    pub scores: Vec<RubricScore>,
+    #[serde(default)]
+    pub summary: String,
+}
+
+impl RubricReply {
+    /// Parse a model reply and check it against `rubric`: every criterion scored exactly
+    /// once within 1-5 and with a rationale. Scores come back in rubric order.
+    pub fn from_reply(content: &str, rubric: &[RubricCriterion]) -> Result<Self, LlmError> {
+        let mut reply: RubricReply = parse_typed_reply(content)?;
+        let invalid = |message: String| {
+            Err(LlmError::InvalidResponse(format!("scores do not match rubric: {message}")))
+        };
+        let mut ordered = Vec::with_capacity(rubric.len());
+        for criterion in rubric {
+            let matching: Vec<usize> = (0..reply.scores.len())
+                .filter(|&index| reply.scores[index].criterion.trim() == criterion.key)
+                .collect();
+            let [index] = matching[..] else {
+                return invalid(format!("expected one score for {}, got {}", criterion.key, matching.len()));
+            };
+            let mut score = reply.scores[index].clone();
+            score.criterion = criterion.key.clone();
+            score.rationale = score.rationale.trim().to_string();
+            if !(RUBRIC_MIN_SCORE..=RUBRIC_MAX_SCORE).contains(&score.score) {
+                return invalid(format!("score of {} is {}, expected 1-5", criterion.key, score.score));
+            }
+            if score.rationale.is_empty() {
+                return invalid(format!("rationale of {} is empty", criterion.key));
+            }
+            ordered.push(score);
+        }
+        if reply.scores.len() != ordered.len() {
+            return invalid(format!("expected {} scores, got {}", ordered.len(), reply.scores.len()));
+        }
+        reply.scores = ordered;
+        reply.summary = reply.summary.trim().to_string();
+        Ok(reply)
+    }
+
+    /// Mean of the criterion scores.
+    pub fn overall(&self) -> f64 {
+        if self.scores.is_empty() {
+            return 0.0;
+        }
+        self.scores.iter().map(|score| f64::from(score.score)).sum::<f64>() / self.scores.len() as f64
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Secrets shorter than this are not masked literally; they would hit ordinary words.
+const MIN_SECRET_CHARS: usize = 8;
+
+/// Kinds of sensitive content the redaction patterns find.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensitiveKind {
+    /// `-----BEGIN ... PRIVATE KEY-----` blocks.
+    PrivateKey,
+    /// Vendor API keys (`sk-`, `AKIA`, `ghp_`, ...), bearer tokens, JWTs and `api_key=`
+    /// style assignments.
+    ApiKey,
+    /// `password=`, `密码：` style assignments.
+    Password,
+    /// Luhn-valid runs of 13–19 digits.
+    CardNumber,
+    /// Mainland China resident ID numbers.
+    IdNumber,
+    Email,
+    /// Mainland China mobile numbers.
+    Phone,
+}
+
+impl SensitiveKind {
+    pub const ALL: [SensitiveKind; 7] = [
+        SensitiveKind::PrivateKey,
+        SensitiveKind::ApiKey,
+        SensitiveKind::Password,
+        SensitiveKind::CardNumber,
+        SensitiveKind::IdNumber,
+        SensitiveKind::Email,
+        SensitiveKind::Phone,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SensitiveKind::PrivateKey => "private_key",
+            SensitiveKind::ApiKey => "api_key",
+            SensitiveKind::Password => "password",
+            SensitiveKind::CardNumber => "card_number",
+            SensitiveKind::IdNumber => "id_number",
+            SensitiveKind::Email => "email",
+            SensitiveKind::Phone => "phone",
+        }
+    }
+
+    /// Whether the privacy filter checks captures for this kind unless switched. Emails and
+    /// phone numbers are common in ordinary prompts, so they are off.
+    pub fn screened_by_default(self) -> bool {
+        !matches!(self, SensitiveKind::Email | SensitiveKind::Phone)
+    }
+
+    /// Shown next to the switch in settings.
+    pub fn description(self) -> &'static str {
+        match self {
+            SensitiveKind::PrivateKey => "私钥（PEM 格式）",
+            SensitiveKind::ApiKey => "API 密钥、Bearer 令牌、JWT 与 api_key= 形式的赋值",
+            SensitiveKind::Password => "password= 或“密码：”形式的赋值",
+            SensitiveKind::CardNumber => "银行卡号（通过 Luhn 校验）",
+            SensitiveKind::IdNumber => "居民身份证号",
+            SensitiveKind::Email => "电子邮箱地址",
+            SensitiveKind::Phone => "手机号码",
+        }
+    }
+}
+
+/// `(kind, pattern, replacement)` triples applied in order. Digit runs use ASCII word
+/// boundaries so numbers right next to Chinese text are still found.
+static PATTERNS: Lazy<Vec<(SensitiveKind, Regex, &'static str)>> = Lazy::new(|| {
+    [
+        (
+            SensitiveKind::PrivateKey,
+            r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+            "[REDACTED_PRIVATE_KEY]",
+        ),
+        (SensitiveKind::ApiKey, r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/=-]{8,}", "$1 [REDACTED]"),
+        (
+            SensitiveKind::ApiKey,
+            concat!(
+                r"\b(?:sk-(?:ant-)?[A-Za-z0-9_-]{16,}|AKIA[0-9A-Z]{16}|gh[pousr]_[A-Za-z0-9]{30,}",
+                r"|xox[abprs]-[A-Za-z0-9-]{10,}|AIza[0-9A-Za-z_-]{35})",
+            ),
+            "[REDACTED_KEY]",
+        ),
+        (
+            SensitiveKind::ApiKey,
+            r"\beyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}",
+            "[REDACTED_TOKEN]",
+        ),
+        (
+            SensitiveKind::ApiKey,
+            r#"(?i)\b(api[_-]?key|access[_-]?key|secret|token)(["']?\s*[:=]\s*["']?)[^\s"',;]{6,}"#,
+            "$1$2[REDACTED]",
+        ),
+        (
+            SensitiveKind::Password,
+            r#"(?i)\b(password|passwd|pwd|密码)(["']?\s*[:=：]\s*["']?)[^\s"',;]{6,}"#,
+            "$1$2[REDACTED]",
+        ),
+        (
+            SensitiveKind::Email,
+            r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+            "[EMAIL]",
+        ),
+        (
+            SensitiveKind::IdNumber,
+            r"(?-u:\b)[1-9]\d{5}(?:19|20)\d{2}(?:0[1-9]|1[0-2])(?:0[1-9]|[12]\d|3[01])\d{3}[\dXx](?-u:\b)",
+            "[ID_NUMBER]",
+        ),
+        (SensitiveKind::Phone, r"(?:\+86[- ]?)?(?-u:\b)1[3-9]\d{9}(?-u:\b)", "[PHONE]"),
+    ]
+    .into_iter()
+    .map(|(kind, pattern, replacement)| {
+        (kind, Regex::new(pattern).expect("valid redaction pattern"), replacement)
+    })
+    .collect()
+});
+
+/// 13–19 digits, optionally grouped by spaces or dashes; only Luhn-valid runs are masked.
+static CARD_NUMBER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?-u:\b)\d(?:[ -]?\d){12,18}(?-u:\b)").expect("valid card pattern"));
+
+/// Mask API keys, tokens, private keys and personal data (emails, mainland phone and ID
+/// numbers, card numbers) in `text`. `secrets` are exact values, such as the configured API
+/// key, masked wherever they appear.
+pub fn redact_sensitive(text: &str, secrets: &[&str]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets.iter().map(|secret| secret.trim()) {
+        if secret.chars().count() >= MIN_SECRET_CHARS {
+            text = text.replace(secret, "[REDACTED_KEY]");
+        }
+    }
+    redact_kinds(&text, &SensitiveKind::ALL)
+}
+
+/// Mask only the given kinds of sensitive content in `text`.
+pub fn redact_kinds(text: &str, kinds: &[SensitiveKind]) -> String {
+    let mut text = text.to_string();
+    for (_, pattern, replacement) in PATTERNS.iter().filter(|(kind, ..)| kinds.contains(kind)) {
+        if pattern.is_match(&text) {
+            text = pattern.replace_all(&text, *replacement).into_owned();
+        }
+    }
+    if !kinds.contains(&SensitiveKind::CardNumber) {
+        return text;
+    }
+    CARD_NUMBER
+        .replace_all(&text, |captures: &Captures| {
+            if is_card_number(&captures[0]) {
+                "[CARD_NUMBER]".to_string()
+            } else {
+                captures[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// The kinds of sensitive content found in `text`, in [`SensitiveKind::ALL`] order.
+pub fn detect_sensitive(text: &str) -> Vec<SensitiveKind> {
+    // ID numbers are 18 digits too; keep them from passing as card numbers.
+    let without_ids = redact_kinds(text, &[SensitiveKind::IdNumber]);
+    SensitiveKind::ALL
+        .into_iter()
+        .filter(|kind| match kind {
+            SensitiveKind::CardNumber => CARD_NUMBER
+                .find_iter(&without_ids)
+                .any(|found| is_card_number(found.as_str())),
+            _ => PATTERNS
+                .iter()
+                .any(|(pattern_kind, pattern, _)| pattern_kind == kind && pattern.is_match(text)),
+        })
+        .collect()
+}
+
+/// What the privacy filter does with a capture containing sensitive content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyAction {
+    /// Drop the capture.
+    #[default]
+    Block,
+    /// Keep the capture with the sensitive parts masked.
+    Redact,
+}
+
+/// Which kinds of sensitive content keep a copied text from being captured as is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PrivacyFilter {
+    pub action: PrivacyAction,
+    /// Detectors switched on or off; the others keep [`SensitiveKind::screened_by_default`].
+    pub detectors: BTreeMap<SensitiveKind, bool>,
+}
+
+impl PrivacyFilter {
+    pub fn is_enabled(&self, kind: SensitiveKind) -> bool {
+        self.detectors.get(&kind).copied().unwrap_or(kind.screened_by_default())
+    }
+}
+
+/// Result of [`screen_capture`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivacyVerdict {
+    Clean,
+    Blocked(Vec<SensitiveKind>),
+    /// The text with the found kinds masked.
+    Redacted(Vec<SensitiveKind>, String),
+}
+
+/// Check `text` with the enabled detectors of `filter`, before any capture rule.
+pub fn screen_capture(text: &str, filter: &PrivacyFilter) -> PrivacyVerdict {
+    let found: Vec<SensitiveKind> = detect_sensitive(text)
+        .into_iter()
+        .filter(|kind| filter.is_enabled(*kind))
+        .collect();
+    if found.is_empty() {
+        return PrivacyVerdict::Clean;
+    }
+    match filter.action {
+        PrivacyAction::Block => PrivacyVerdict::Blocked(found),
+        PrivacyAction::Redact => {
+            let redacted = redact_kinds(text, &found);
+            PrivacyVerdict::Redacted(found, redacted)
+        }
+    }
+}
+
+/// [`redact_sensitive`] applied to every string (object keys excluded) inside `value`.
+pub fn redact_json(value: &mut Value, secrets: &[&str]) {
+    match value {
+        Value::String(text) => *text = redact_sensitive(text, secrets),
+        Value::Array(items) => items.iter_mut().for_each(|item| redact_json(item, secrets)),
+        Value::Object(fields) => fields.values_mut().for_each(|field| redact_json(field, secrets)),
+        _ => {}
+    }
+}
+
+fn is_card_number(text: &str) -> bool {
+    let digits: Vec<u32> = text.chars().filter_map(|c| c.to_digit(10)).collect();
+    luhn_valid(&digits)
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(position, &digit)| match position % 2 {
+            0 => digit,
+            _ if digit * 2 > 9 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+
+use once_cell::sync::Lazy;
+use regex::{NoExpand, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// A user-defined extraction rule: the first match of `pattern` is stored under `field`,
+/// e.g. `字数不超过(\d+)` → `word_limit: 300`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractionRule {
+    pub id: String,
+    pub field: String,
+    pub pattern: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Error)]
+pub enum RuleError {
+    #[error("field name must be snake_case ASCII (letters, digits, underscores): {0}")]
+    InvalidField(String),
+    #[error("field name is reserved by the built-in analysis: {0}")]
+    ReservedField(String),
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+    #[error("pattern must not be empty")]
+    EmptyPattern,
+    #[error("a length rule needs minChars or maxChars, and minChars must not exceed maxChars")]
+    InvalidLength,
+    #[error("template name must not be empty")]
+    EmptyName,
+    #[error("a wrap template needs a {{{{text}}}} placeholder for the captured text")]
+    MissingTextPlaceholder,
+}
+
+/// Keys the built-in analysis already writes into the classification JSON.
+pub const RESERVED_FIELDS: [&str; 13] = [
+    "topic",
+    "theme",
+    "targets",
+    "keywords",
+    "length",
+    "role",
+    "intent",
+    "chain_steps",
+    "output_language",
+    "analysis_id",
+    "source",
+    "trigger",
+    "fields",
+];
+
+impl ExtractionRule {
+    /// Validate and build a new enabled rule with a fresh id.
+    pub fn new(field: &str, pattern: &str) -> Result<Self, RuleError> {
+        let rule = Self {
+            id: Uuid::new_v4().to_string(),
+            field: field.trim().to_string(),
+            pattern: pattern.to_string(),
+            enabled: true,
+        };
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    pub fn validate(&self) -> Result<(), RuleError> {
+        let field = self.field.as_str();
+        let well_formed = field.chars().next().is_some_and(|c| c.is_ascii_lowercase())
+            && field.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !well_formed {
+            return Err(RuleError::InvalidField(self.field.clone()));
+        }
+        if RESERVED_FIELDS.contains(&field) {
+            return Err(RuleError::ReservedField(self.field.clone()));
+        }
+        Regex::new(&self.pattern)?;
+        Ok(())
+    }
+}
+
+/// Run every enabled rule over `text`. The value is the first capture group when the
+/// pattern has one, otherwise the whole match; integers are stored as JSON numbers.
+/// When two rules target the same field, the earlier rule wins. Invalid patterns are skipped.
+pub fn apply_extraction_rules(text: &str, rules: &[ExtractionRule]) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        if fields.contains_key(&rule.field) {
+            continue;
+        }
+        let Ok(regex) = Regex::new(&rule.pattern) else {
+            continue;
+        };
+        let Some(captures) = regex.captures(text) else {
+            continue;
+        };
+        let Some(matched) = captures.get(1).or_else(|| captures.get(0)) else {
+            continue;
+        };
+        let raw = matched.as_str().trim();
+        let value = raw
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::from(raw));
+        fields.insert(rule.field.clone(), value);
+    }
+    fields
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IgnoreKind {
+    /// `pattern` is a regex found anywhere in the text.
+    Regex,
+    /// The text starts with `pattern`, ignoring case.
+    Prefix,
+    /// The text is shorter than `min_chars` or longer than `max_chars`.
+    Length,
+}
+
+/// A user-defined rule for clipboard texts that are never captured, e.g. anything starting
+/// with `password:`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IgnoreRule {
+    pub id: String,
+    pub kind: IgnoreKind,
+    /// Regex or prefix; empty for length rules.
+    #[serde(default)]
+    pub pattern: String,
+    #[serde(default)]
+    pub min_chars: Option<usize>,
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Texts this rule has ignored.
+    #[serde(default)]
+    pub hits: u64,
+}
+
+impl IgnoreRule {
+    /// Validate and build a new enabled rule with a fresh id.
+    pub fn new(
+        kind: IgnoreKind,
+        pattern: &str,
+        min_chars: Option<usize>,
+        max_chars: Option<usize>,
+    ) -> Result<Self, RuleError> {
+        let rule = Self {
+            id: Uuid::new_v4().to_string(),
+            kind,
+            pattern: pattern.to_string(),
+            min_chars,
+            max_chars,
+            enabled: true,
+            hits: 0,
+        };
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    pub fn validate(&self) -> Result<(), RuleError> {
+        match self.kind {
+            IgnoreKind::Regex | IgnoreKind::Prefix if self.pattern.trim().is_empty() => Err(RuleError::EmptyPattern),
+            IgnoreKind::Regex => Regex::new(&self.pattern).map(|_| ()).map_err(RuleError::from),
+            IgnoreKind::Prefix => Ok(()),
+            IgnoreKind::Length => match (self.min_chars, self.max_chars) {
+                (None, None) => Err(RuleError::InvalidLength),
+                (Some(min), Some(max)) if min > max => Err(RuleError::InvalidLength),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Whether `text` (already trimmed) should be ignored. Invalid regexes match nothing.
+    pub fn matches(&self, text: &str) -> bool {
+        match self.kind {
+            IgnoreKind::Regex => Regex::new(&self.pattern).is_ok_and(|regex| regex.is_match(text)),
+            IgnoreKind::Prefix => {
+                let prefix = self.pattern.trim().to_lowercase();
+                let head: String = text.chars().take(prefix.chars().count()).collect();
+                head.to_lowercase() == prefix
+            }
+            IgnoreKind::Length => {
+                let chars = text.chars().count();
+                self.min_chars.is_some_and(|min| chars < min) || self.max_chars.is_some_and(|max| chars > max)
+            }
+        }
+    }
+}
+
+/// Index of the first enabled rule that ignores `text`.
+pub fn matching_ignore_rule(text: &str, rules: &[IgnoreRule]) -> Option<usize> {
+    rules.iter().position(|rule| rule.enabled && rule.matches(text))
+}
+
+/// `{{text}}` in a wrap template, where the captured text goes.
+static TEXT_PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*text\s*\}\}").expect("valid text placeholder pattern"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateAction {
+    /// Put the text into `body` at its `{{text}}` placeholder, e.g. under a standard role and
+    /// constraints scaffold.
+    Wrap,
+    /// `body` is a regex; keep its first capture group, or what follows its first match,
+    /// e.g. `(?i)prompt[:：]` keeps what comes after `Prompt:`.
+    Extract,
+}
+
+/// A user-defined template that restructures captured texts matching its conditions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureTemplate {
+    pub id: String,
+    pub name: String,
+    pub action: TemplateAction,
+    /// Scaffold for `wrap`, regex for `extract`.
+    pub body: String,
+    /// Regex the text must contain; empty for every text.
+    #[serde(default)]
+    pub text_pattern: String,
+    /// Process name fragments (case-insensitive) of the app the text was copied from; empty
+    /// for any app.
+    #[serde(default)]
+    pub apps: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Texts this template restructured.
+    #[serde(default)]
+    pub hits: u64,
+}
+
+impl CaptureTemplate {
+    /// Validate and build a new enabled template with a fresh id.
+    pub fn new(
+        name: &str,
+        action: TemplateAction,
+        body: &str,
+        text_pattern: &str,
+        apps: Vec<String>,
+    ) -> Result<Self, RuleError> {
+        let template = Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.trim().to_string(),
+            action,
+            body: body.to_string(),
+            text_pattern: text_pattern.to_string(),
+            apps,
+            enabled: true,
+            hits: 0,
+        };
+        template.validate()?;
+        Ok(template)
+    }
+
+    pub fn validate(&self) -> Result<(), RuleError> {
+        if self.name.trim().is_empty() {
+            return Err(RuleError::EmptyName);
+        }
+        if self.body.trim().is_empty() {
+            return Err(RuleError::EmptyPattern);
+        }
+        match self.action {
+            TemplateAction::Wrap if !TEXT_PLACEHOLDER.is_match(&self.body) => {
+                return Err(RuleError::MissingTextPlaceholder)
+            }
+            TemplateAction::Wrap => {}
+            TemplateAction::Extract => {
+                Regex::new(&self.body)?;
+            }
+        }
+        if !self.text_pattern.trim().is_empty() {
+            Regex::new(&self.text_pattern)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the template applies to `text` copied from `process`. A template limited to
+    /// some apps does not apply when the app is unknown.
+    pub fn matches(&self, text: &str, process: Option<&str>) -> bool {
+        let text_matches = self.text_pattern.trim().is_empty()
+            || Regex::new(&self.text_pattern).is_ok_and(|regex| regex.is_match(text));
+        let app_matches = self.apps.iter().all(|app| app.trim().is_empty())
+            || process.is_some_and(|process| {
+                let process = process.to_lowercase();
+                self.apps
+                    .iter()
+                    .map(|app| app.trim().to_lowercase())
+                    .any(|app| !app.is_empty() && process.contains(&app))
+            });
+        text_matches && app_matches
+    }
+
+    /// The restructured text, trimmed; `None` when an extract pattern finds nothing to keep.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        let result = match self.action {
+            TemplateAction::Wrap => TEXT_PLACEHOLDER.replace_all(&self.body, NoExpand(text)).into_owned(),
+            TemplateAction::Extract => {
+                let captures = Regex::new(&self.body).ok()?.captures(text)?;
+                match captures.get(1) {
+                    Some(group) => group.as_str().to_string(),
+                    None => text[captures.get(0)?.end()..].to_string(),
+                }
+            }
+        };
+        let result = result.trim();
+        (!result.is_empty()).then(|| result.to_string())
+    }
+}
+
+/// Index of the first enabled template that matches `text` and restructures it, with the
+/// result.
+pub fn apply_capture_templates(
+    text: &str,
+    process: Option<&str>,
+    templates: &[CaptureTemplate],
+) -> Option<(usize, String)> {
+    templates
+        .iter()
+        .enumerate()
+        .filter(|(_, template)| template.enabled && template.matches(text, process))
+        .find_map(|(index, template)| template.apply(text).map(|result| (index, result)))
+}
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{Prompt, PromptEmbedding};
+use crate::text::normalize_text;
+
+/// A prompt ranked by embedding similarity to a query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticHit {
+    pub prompt_id: String,
+    /// Cosine similarity to the query (-1.0 – 1.0).
+    pub score: f32,
+}
+
+/// Where a text search looks for the query terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchField {
+    Title,
+    Body,
+    /// Tags of the latest analysis.
+    Tags,
+}
+
+impl SearchField {
+    pub const ALL: [SearchField; 3] = [SearchField::Title, SearchField::Tags, SearchField::Body];
+
+    /// How much a term found in this field counts when ranking.
+    pub fn weight(self) -> f64 {
+        match self {
+            SearchField::Title => 3.0,
+            SearchField::Tags => 2.0,
+            SearchField::Body => 1.0,
+        }
+    }
+}
+
+/// A prompt found by a text search.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextHit {
+    pub prompt: Prompt,
+    pub tags: Vec<String>,
+    /// Fields at least one term was found in, by weight.
+    pub fields: Vec<SearchField>,
+    /// 0.0 – 1.0; 1.0 when every term is in the title.
+    pub score: f64,
+}
+
+/// Cosine similarity of two vectors; 0.0 when the lengths differ or either is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// The `limit` candidates most similar to `query` scoring at least `min_score`, best first.
+pub fn nearest_prompts(query: &[f32], candidates: &[PromptEmbedding], limit: usize, min_score: f32) -> Vec<SemanticHit> {
+    let mut hits: Vec<SemanticHit> = candidates
+        .iter()
+        .map(|candidate| SemanticHit {
+            prompt_id: candidate.prompt_id.clone(),
+            score: cosine_similarity(query, &candidate.vector),
+        })
+        .filter(|hit| hit.score >= min_score)
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.prompt_id.cmp(&b.prompt_id)));
+    hits.truncate(limit);
+    hits
+}
+
+/// How well `query` fuzzily matches `text`, 0.0 – 1.0: every whitespace-separated term must
+/// appear in `text` as a subsequence (case-insensitive). Consecutive characters and matches at
+/// the start of a word score higher; `None` when a term does not match.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<f64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let terms: Vec<Vec<char>> = query
+        .split_whitespace()
+        .map(|term| term.to_lowercase().chars().collect())
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    let mut total = 0.0;
+    for term in &terms {
+        total += term_score(term, &text)?;
+    }
+    Some(total / terms.len() as f64)
+}
+
+/// Greedy subsequence match of one term: each character earns 1, plus 2 when it follows the
+/// previous match directly and 1 at a word start (any non-ASCII character counts as one),
+/// relative to a prefix match.
+fn term_score(term: &[char], text: &[char]) -> Option<f64> {
+    let mut score = 0.0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for &wanted in term {
+        let found = position + text[position..].iter().position(|&c| c == wanted)?;
+        score += 1.0;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 2.0;
+        }
+        let word_start = found == 0 || !text[found - 1].is_alphanumeric() || !wanted.is_ascii();
+        if word_start {
+            score += 1.0;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    // A prefix match: a word start, then only consecutive characters. CJK characters, which
+    // count as word starts too, can go beyond it.
+    let best = 3.0 * term.len() as f64 - 1.0;
+    Some((score / best).min(1.0))
+}
+
+/// Split a search query into terms: [`normalize_text`], lowercased, split at whitespace and
+/// wherever CJK text meets other text, since Chinese is written without spaces ("gpt翻译"
+/// looks for "gpt" and "翻译"). Duplicates are dropped.
+pub fn search_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    let mut push = |term: &mut String| {
+        if !term.is_empty() && !terms.contains(term) {
+            terms.push(term.clone());
+        }
+        term.clear();
+    };
+    for word in normalize_text(query).to_lowercase().split_whitespace() {
+        let mut term = String::new();
+        let mut in_cjk = None;
+        for c in word.chars() {
+            if in_cjk.is_some_and(|in_cjk| in_cjk != is_cjk(c)) {
+                push(&mut term);
+            }
+            in_cjk = Some(is_cjk(c));
+            term.push(c);
+        }
+        push(&mut term);
+    }
+    terms
+}
+
+/// Rank the prompts a text search found, best first, keeping at most `limit`. Each term counts
+/// with the weight of the best field among `fields` it occurs in; prompts missing a term are
+/// dropped. Ties keep the order of `candidates`.
+pub fn rank_text_hits(
+    terms: &[String],
+    fields: &[SearchField],
+    candidates: Vec<(Prompt, Vec<String>)>,
+    limit: usize,
+) -> Vec<TextHit> {
+    let mut hits: Vec<TextHit> = candidates
+        .into_iter()
+        .filter_map(|(prompt, tags)| {
+            let texts: Vec<(SearchField, String)> = fields
+                .iter()
+                .map(|&field| {
+                    let text = match field {
+                        SearchField::Title => normalize_text(&prompt.title),
+                        SearchField::Body => normalize_text(&prompt.body),
+                        SearchField::Tags => normalize_text(&tags.join("\n")),
+                    };
+                    (field, text.to_lowercase())
+                })
+                .collect();
+            let mut matched: Vec<SearchField> = Vec::new();
+            let mut score = 0.0;
+            for term in terms {
+                let best = texts
+                    .iter()
+                    .filter(|(_, text)| text.contains(term.as_str()))
+                    .map(|(field, _)| *field)
+                    .max_by(|a, b| a.weight().total_cmp(&b.weight()))?;
+                score += best.weight();
+                if !matched.contains(&best) {
+                    matched.push(best);
+                }
+            }
+            matched.sort_by(|a, b| b.weight().total_cmp(&a.weight()));
+            let perfect = terms.len() as f64 * SearchField::Title.weight();
+            Some(TextHit {
+                prompt,
+                tags,
+                fields: matched,
+                score: if terms.is_empty() { 0.0 } else { score / perfect },
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    hits
+}
+
+/// CJK ideographs, kana and Hangul, which are written without spaces between words.
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{ac00}'..='\u{d7af}'
+            | '\u{f900}'..='\u{faff}'
+            | '\u{20000}'..='\u{2fa1f}'
+    )
+}
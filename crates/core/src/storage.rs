@@ -1,4 +1,4 @@
-use std::{path::Path, time::Duration};
+use std::{collections::BTreeMap, path::Path, time::Duration};
 
 use chrono::{DateTime, Utc};
 use r2d2::{Pool, PooledConnection};
@@ -9,6 +9,15 @@ use serde_json::Value;
 use thiserror::Error;
 use uuid::Uuid;
 
+use crate::capture::LOCAL_CLASSIFIER_MODEL;
+use crate::search::SearchField;
+use crate::text::content_hash;
+
+/// Condition on `prompts` for the prompts that listings, searches and statistics show:
+/// neither in the trash nor quarantined. [`VISIBLE_PROMPT_P`] is the same for the alias `p`.
+const VISIBLE_PROMPT: &str = "deleted_at IS NULL AND quarantined_at IS NULL";
+const VISIBLE_PROMPT_P: &str = "p.deleted_at IS NULL AND p.quarantined_at IS NULL";
+
 /// Alias for pooled SQLite connections.
 pub type DbPool = Pool<SqliteConnectionManager>;
 
@@ -81,6 +90,76 @@ impl Storage {
             CREATE INDEX IF NOT EXISTS idx_analyses_prompt_id_created_at
                 ON analyses (prompt_id, datetime(created_at) DESC);
 
+            CREATE TABLE IF NOT EXISTS classifications (
+                id TEXT PRIMARY KEY,
+                prompt_id TEXT NOT NULL,
+                source TEXT NOT NULL,
+                is_prompt INTEGER NOT NULL,
+                confidence REAL NOT NULL,
+                model TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_classifications_prompt_id_created_at
+                ON classifications (prompt_id, datetime(created_at) DESC);
+
+            CREATE TABLE IF NOT EXISTS prompt_labels (
+                prompt_id TEXT PRIMARY KEY,
+                is_prompt INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS classifier_examples (
+                id TEXT PRIMARY KEY,
+                text TEXT NOT NULL,
+                is_prompt INTEGER NOT NULL,
+                prompt_id TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE SET NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS merge_suggestions (
+                id TEXT PRIMARY KEY,
+                group_key TEXT NOT NULL UNIQUE,
+                prompt_ids TEXT NOT NULL,
+                similarity REAL NOT NULL,
+                same_prompt INTEGER,
+                merged_body TEXT,
+                reason TEXT,
+                model TEXT,
+                status TEXT NOT NULL,
+                merged_prompt_id TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (merged_prompt_id) REFERENCES prompts(id) ON DELETE SET NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS captures (
+                id TEXT PRIMARY KEY,
+                text TEXT NOT NULL,
+                source TEXT NOT NULL,
+                status TEXT NOT NULL,
+                prompt_id TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE SET NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_captures_status ON captures (status);
+
+            CREATE TABLE IF NOT EXISTS prompt_versions (
+                id TEXT PRIMARY KEY,
+                prompt_id TEXT NOT NULL,
+                title TEXT NOT NULL,
+                body TEXT NOT NULL,
+                source TEXT NOT NULL,
+                replaced_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_prompt_versions_prompt_id
+                ON prompt_versions (prompt_id, datetime(replaced_at) DESC);
+
             CREATE TABLE IF NOT EXISTS attachments (
                 id TEXT PRIMARY KEY,
                 prompt_id TEXT NOT NULL,
@@ -89,36 +168,176 @@ impl Storage {
                 FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
             );
             CREATE INDEX IF NOT EXISTS idx_attachments_prompt_id ON attachments (prompt_id);
+
+            CREATE TABLE IF NOT EXISTS prompt_relations (
+                id TEXT PRIMARY KEY,
+                source_id TEXT NOT NULL,
+                target_id TEXT NOT NULL,
+                relation TEXT NOT NULL,
+                metadata TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (source_id) REFERENCES prompts(id) ON DELETE CASCADE,
+                FOREIGN KEY (target_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_prompt_relations_source ON prompt_relations (source_id);
+            CREATE INDEX IF NOT EXISTS idx_prompt_relations_target ON prompt_relations (target_id);
+
+            CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                prompt_id TEXT,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                max_attempts INTEGER NOT NULL,
+                last_error TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_jobs_status_created_at ON jobs (status, datetime(created_at));
+            CREATE INDEX IF NOT EXISTS idx_jobs_prompt_id ON jobs (prompt_id);
+
+            CREATE TABLE IF NOT EXISTS embeddings (
+                prompt_id TEXT PRIMARY KEY,
+                model TEXT NOT NULL,
+                dimensions INTEGER NOT NULL,
+                vector BLOB NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS llm_calls (
+                id TEXT PRIMARY KEY,
+                task TEXT NOT NULL,
+                prompt_id TEXT,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                prompt_tokens INTEGER,
+                completion_tokens INTEGER,
+                latency_ms INTEGER NOT NULL,
+                attempts INTEGER NOT NULL,
+                success INTEGER NOT NULL,
+                error TEXT,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_llm_calls_created_at ON llm_calls (datetime(created_at));
+
+            CREATE TABLE IF NOT EXISTS runs (
+                id TEXT PRIMARY KEY,
+                prompt_id TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                params TEXT NOT NULL,
+                variables TEXT NOT NULL,
+                input TEXT NOT NULL,
+                output TEXT NOT NULL,
+                prompt_tokens INTEGER,
+                completion_tokens INTEGER,
+                latency_ms INTEGER NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_runs_prompt_id_created_at ON runs (prompt_id, datetime(created_at));
+
+            CREATE TABLE IF NOT EXISTS llm_rejections (
+                id TEXT PRIMARY KEY,
+                prompt_id TEXT NOT NULL,
+                task TEXT NOT NULL,
+                model TEXT NOT NULL,
+                reason TEXT NOT NULL,
+                detail TEXT NOT NULL,
+                output TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_llm_rejections_created_at ON llm_rejections (datetime(created_at));
+
+            CREATE TABLE IF NOT EXISTS ab_tests (
+                id TEXT PRIMARY KEY,
+                prompt_a_id TEXT NOT NULL,
+                prompt_b_id TEXT NOT NULL,
+                model TEXT NOT NULL,
+                relation_id TEXT,
+                created_at TEXT NOT NULL,
+                FOREIGN KEY (prompt_a_id) REFERENCES prompts(id) ON DELETE CASCADE,
+                FOREIGN KEY (prompt_b_id) REFERENCES prompts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_ab_tests_prompt_a_id ON ab_tests (prompt_a_id);
+            CREATE INDEX IF NOT EXISTS idx_ab_tests_prompt_b_id ON ab_tests (prompt_b_id);
+
+            CREATE TABLE IF NOT EXISTS ab_test_cases (
+                id TEXT PRIMARY KEY,
+                test_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                variables TEXT NOT NULL,
+                run_a_id TEXT NOT NULL,
+                run_b_id TEXT NOT NULL,
+                preference TEXT,
+                decided_at TEXT,
+                FOREIGN KEY (test_id) REFERENCES ab_tests(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_ab_test_cases_test_id ON ab_test_cases (test_id, position);
             "#,
         )?;
+        ensure_column(&conn, "analyses", "analyzer_version", "INTEGER")?;
+        ensure_column(&conn, "prompts", "content_hash", "TEXT")?;
+        ensure_column(&conn, "prompts", "deleted_at", "TEXT")?;
+        ensure_column(&conn, "prompts", "quarantined_at", "TEXT")?;
+        ensure_column(&conn, "prompts", "use_count", "INTEGER NOT NULL DEFAULT 0")?;
+        ensure_column(&conn, "prompts", "last_used_at", "TEXT")?;
+        ensure_column(&conn, "captures", "similar_prompt_id", "TEXT")?;
+        ensure_column(&conn, "captures", "similarity", "REAL")?;
+        conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_prompts_content_hash ON prompts (content_hash);")?;
+        backfill_content_hashes(&conn)?;
         Ok(())
     }
 
     /// Insert a new prompt entry and return the hydrated record.
     pub fn create_prompt(&self, data: NewPrompt) -> Result<Prompt, StorageError> {
         let conn = self.conn()?;
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
-        conn.execute(
-            r#"
-            INSERT INTO prompts (id, title, body, language, model_hint, metadata, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
-            "#,
-            params![
-                id,
-                data.title,
-                data.body,
-                data.language,
-                data.model_hint,
-                data.metadata.to_string(),
-                now.to_rfc3339(),
-                now.to_rfc3339()
-            ],
-        )?;
+        let id = insert_prompt(&conn, &data)?;
         self.get_prompt(&id)?
             .ok_or(StorageError::NotFound("prompt".into()))
     }
 
+    /// Insert the steps of a prompt chain as child prompts of `parent_id`, in one transaction,
+    /// each linked from the parent (`chain_step`) and from the step before it (`next_step`).
+    /// Each step comes with its step number, recorded as `step` in the links' metadata.
+    /// Returns the children in order.
+    pub fn create_chain_steps(
+        &self,
+        parent_id: &str,
+        steps: Vec<(usize, NewPrompt)>,
+    ) -> Result<Vec<Prompt>, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let mut ids: Vec<String> = Vec::with_capacity(steps.len());
+        for (step, data) in &steps {
+            let id = insert_prompt(&tx, data)?;
+            let link = |source_id: &str, relation: &str| {
+                insert_relation(
+                    &tx,
+                    NewPromptRelation {
+                        source_id: source_id.to_string(),
+                        target_id: id.clone(),
+                        relation: relation.to_string(),
+                        metadata: serde_json::json!({ "step": step }),
+                    },
+                )
+            };
+            link(parent_id, "chain_step")?;
+            if let Some(previous) = ids.last() {
+                link(previous, "next_step")?;
+            }
+            ids.push(id);
+        }
+        tx.commit()?;
+        ids.iter()
+            .map(|id| self.get_prompt(id)?.ok_or(StorageError::NotFound("prompt".into())))
+            .collect()
+    }
+
     /// Update an existing prompt in-place. Returns `None` if not found.
     pub fn update_prompt(&self, id: &str, changes: UpdatePrompt) -> Result<Option<Prompt>, StorageError> {
         let conn = self.conn()?;
@@ -154,7 +373,8 @@ impl Storage {
                 language = ?4,
                 model_hint = ?5,
                 metadata = ?6,
-                updated_at = ?7
+                updated_at = ?7,
+                content_hash = ?8
             WHERE id = ?1
             "#,
             params![
@@ -164,7 +384,8 @@ impl Storage {
                 updated.language,
                 updated.model_hint,
                 updated.metadata.to_string(),
-                updated.updated_at.to_rfc3339()
+                updated.updated_at.to_rfc3339(),
+                content_hash(&updated.body)
             ],
         )?;
 
@@ -189,7 +410,9 @@ impl Storage {
         let conn = self.conn()?;
         let prompt = conn
             .query_row(
-                "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at FROM prompts WHERE body = ?1 LIMIT 1",
+                "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
+                 FROM prompts WHERE body = ?1 AND deleted_at IS NULL
+                 LIMIT 1",
                 params![body],
                 |row| row_to_prompt(row),
             )
@@ -197,14 +420,49 @@ impl Storage {
         Ok(prompt)
     }
 
-    /// List prompts ordered by most recently updated.
+    /// Find a prompt whose body has the same [`content_hash`] as `text`, i.e. the same text
+    /// up to whitespace, smart quotes, width and placeholder names (used for clipboard
+    /// deduplication).
+    pub fn find_prompt_by_content(&self, text: &str) -> Result<Option<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let prompt = conn
+            .query_row(
+                "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
+                 FROM prompts WHERE content_hash = ?1 AND deleted_at IS NULL
+                 ORDER BY datetime(created_at)
+                 LIMIT 1",
+                params![content_hash(text)],
+                row_to_prompt,
+            )
+            .optional()?;
+        Ok(prompt)
+    }
+
+    /// The earliest prompt saved from a clipboard image with this `image_hash` in its metadata.
+    pub fn find_prompt_by_image_hash(&self, hash: &str) -> Result<Option<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let prompt = conn
+            .query_row(
+                "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
+                 FROM prompts WHERE json_extract(metadata, '$.image_hash') = ?1 AND deleted_at IS NULL
+                 ORDER BY datetime(created_at)
+                 LIMIT 1",
+                params![hash],
+                row_to_prompt,
+            )
+            .optional()?;
+        Ok(prompt)
+    }
+
+    /// List prompts ordered by most recently updated, leaving out quarantined ones.
     pub fn list_prompts(&self) -> Result<Vec<Prompt>, StorageError> {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
+        let mut stmt = conn.prepare(&format!(
             "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
              FROM prompts
-             ORDER BY datetime(updated_at) DESC",
-        )?;
+             WHERE {VISIBLE_PROMPT}
+             ORDER BY datetime(updated_at) DESC"
+        ))?;
 
         let rows = stmt
             .query_map([], |row| row_to_prompt(row))?
@@ -212,153 +470,2429 @@ impl Storage {
         Ok(rows)
     }
 
-    /// Delete a prompt (analyses/attachments cascade).
-    pub fn delete_prompt(&self, id: &str) -> Result<bool, StorageError> {
+    /// List prompts whose body mentions `term` (ASCII case-insensitive).
+    pub fn list_prompts_mentioning(&self, term: &str) -> Result<Vec<Prompt>, StorageError> {
         let conn = self.conn()?;
-        let affected = conn.execute("DELETE FROM prompts WHERE id = ?1", params![id])?;
-        Ok(affected > 0)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
+             FROM prompts
+             WHERE instr(lower(body), lower(?1)) > 0 AND {VISIBLE_PROMPT}
+             ORDER BY datetime(updated_at) DESC"
+        ))?;
+
+        let rows = stmt
+            .query_map(params![term], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
     }
 
-    /// Store a new AI analysis result.
-    pub fn create_analysis(&self, input: NewAnalysis) -> Result<Analysis, StorageError> {
+    /// Prompts where every term occurs in at least one of `fields`, most recently updated first,
+    /// each with the tags of its latest analysis; all prompts when `terms` is empty. Uses
+    /// `LIKE`, which ignores case for ASCII only, so terms should be lowercased; each is also
+    /// tried with fullwidth letters and digits, as some Chinese input methods type them.
+    pub fn search_prompts(
+        &self,
+        terms: &[String],
+        fields: &[SearchField],
+    ) -> Result<Vec<(Prompt, Vec<String>)>, StorageError> {
+        let columns: Vec<&str> = fields
+            .iter()
+            .map(|field| match field {
+                SearchField::Title => "title",
+                SearchField::Body => "body",
+                SearchField::Tags => "tags",
+            })
+            .collect();
+        let mut patterns: Vec<String> = Vec::new();
+        let mut conditions: Vec<String> = Vec::new();
+        for term in terms {
+            let mut alternatives = Vec::new();
+            for spelling in like_spellings(term) {
+                patterns.push(format!("%{spelling}%"));
+                let idx = patterns.len();
+                alternatives.extend(columns.iter().map(|column| format!("{column} LIKE ?{idx} ESCAPE '\\'")));
+            }
+            conditions.push(format!("({})", alternatives.join(" OR ")));
+        }
+        let filter = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
         let conn = self.conn()?;
-        let id = Uuid::new_v4().to_string();
-        let created_at = Utc::now();
+        let mut stmt = conn.prepare(&format!(
+            "WITH candidates AS (
+                 SELECT p.id, p.title, p.body, p.language, p.model_hint, p.metadata, p.created_at, p.updated_at,
+                        COALESCE((
+                            SELECT a.tags FROM analyses a
+                            WHERE a.prompt_id = p.id
+                            ORDER BY datetime(a.created_at) DESC
+                            LIMIT 1
+                        ), '[]') AS tags
+                 FROM prompts p
+                 WHERE {VISIBLE_PROMPT_P}
+             )
+             SELECT * FROM candidates
+             {filter}
+             ORDER BY datetime(updated_at) DESC"
+        ))?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(patterns.iter()), |row| {
+                let tags: String = row.get(8)?;
+                Ok((row_to_prompt(row)?, serde_json::from_str(&tags).unwrap_or_default()))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
 
-        conn.execute(
-            r#"
-            INSERT INTO analyses (id, prompt_id, summary, tags, classification, qwen_model, created_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            "#,
-            params![
-                id,
-                input.prompt_id,
-                input.summary,
-                serde_json::to_string(&input.tags)?,
-                input.classification.to_string(),
-                input.qwen_model,
-                created_at.to_rfc3339()
-            ],
+    /// Count prompts whose body mentions any of `spellings` (ASCII case-insensitive) and
+    /// prompts whose latest analysis carries `term` as a tag.
+    pub fn term_usage(&self, term: &str, spellings: &[String]) -> Result<TermUsage, StorageError> {
+        let conn = self.conn()?;
+        let mentions = if spellings.is_empty() {
+            0
+        } else {
+            let conditions: Vec<String> = (1..=spellings.len())
+                .map(|idx| format!("instr(lower(body), lower(?{idx})) > 0"))
+                .collect();
+            conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM prompts WHERE {VISIBLE_PROMPT} AND ({})",
+                    conditions.join(" OR ")
+                ),
+                rusqlite::params_from_iter(spellings.iter()),
+                |row| row.get::<_, i64>(0),
+            )?
+        };
+        let tagged = conn.query_row(
+            &format!(
+                "SELECT COUNT(*)
+                 FROM prompts p
+                 WHERE {VISIBLE_PROMPT_P} AND EXISTS (
+                     SELECT 1 FROM json_each(
+                         (SELECT a.tags FROM analyses a
+                          WHERE a.prompt_id = p.id
+                          ORDER BY datetime(a.created_at) DESC
+                          LIMIT 1)
+                     ) WHERE value = ?1
+                 )"
+            ),
+            params![term],
+            |row| row.get::<_, i64>(0),
         )?;
+        Ok(TermUsage {
+            term: term.to_string(),
+            mentions: mentions as usize,
+            tagged: tagged as usize,
+        })
+    }
 
-        self.get_analysis(&id)?
-            .ok_or(StorageError::NotFound("analysis".into()))
+    /// Every tag of the visible prompts' latest analyses with the number of prompts using it,
+    /// most used first.
+    pub fn list_tag_counts(&self) -> Result<Vec<TagCount>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT tag.value, COUNT(DISTINCT a.prompt_id) AS uses
+             FROM analyses a
+             JOIN prompts p ON p.id = a.prompt_id, json_each(a.tags) tag
+             WHERE {VISIBLE_PROMPT_P}
+             AND a.id = (
+                 SELECT latest.id FROM analyses latest
+                 WHERE latest.prompt_id = a.prompt_id
+                 ORDER BY datetime(latest.created_at) DESC
+                 LIMIT 1
+             )
+             AND json_valid(a.tags)
+             GROUP BY tag.value
+             ORDER BY uses DESC, tag.value"
+        ))?;
+        let counts = stmt
+            .query_map([], |row| {
+                Ok(TagCount {
+                    tag: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(counts)
     }
 
-    /// Get a specific analysis by ID.
-    pub fn get_analysis(&self, id: &str) -> Result<Option<Analysis>, StorageError> {
+    /// The tags of each visible prompt's latest analysis, by prompt id.
+    pub fn list_latest_tags(&self) -> Result<BTreeMap<String, Vec<String>>, StorageError> {
         let conn = self.conn()?;
-        let analysis = conn
-            .query_row(
-                "SELECT id, prompt_id, summary, tags, classification, qwen_model, created_at FROM analyses WHERE id = ?1",
-                params![id],
-                |row| row_to_analysis(row),
-            )
-            .optional()?;
-        Ok(analysis)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT a.prompt_id, a.tags
+             FROM analyses a
+             JOIN prompts p ON p.id = a.prompt_id
+             WHERE {VISIBLE_PROMPT_P}
+             AND a.id = (
+                 SELECT latest.id FROM analyses latest
+                 WHERE latest.prompt_id = a.prompt_id
+                 ORDER BY datetime(latest.created_at) DESC
+                 LIMIT 1
+             )"
+        ))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows
+            .into_iter()
+            .map(|(prompt_id, tags)| (prompt_id, serde_json::from_str(&tags).unwrap_or_default()))
+            .collect())
     }
 
-    /// List analyses for a prompt.
-    pub fn list_analyses_for_prompt(&self, prompt_id: &str) -> Result<Vec<Analysis>, StorageError> {
+    /// Rewrite the tags of every analysis (and the `keywords` copy in its classification)
+    /// with `rewrite`, in one transaction. Returns the number of analyses changed.
+    pub fn rewrite_analysis_tags(&self, rewrite: impl Fn(&[String]) -> Vec<String>) -> Result<usize, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let rows: Vec<(String, String, String)> = {
+            let mut stmt = tx.prepare("SELECT id, tags, classification FROM analyses")?;
+            let rows = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            rows
+        };
+        let mut changed = 0;
+        for (id, tags, classification) in rows {
+            let Ok(tags) = serde_json::from_str::<Vec<String>>(&tags) else {
+                continue;
+            };
+            let new_tags = rewrite(&tags);
+            let mut classification = serde_json::from_str::<Value>(&classification).unwrap_or(Value::Null);
+            let keywords = classification.get("keywords").and_then(|keywords| {
+                serde_json::from_value::<Vec<String>>(keywords.clone()).ok()
+            });
+            let new_keywords = keywords.as_deref().map(&rewrite);
+            if new_tags == tags && new_keywords == keywords {
+                continue;
+            }
+            if let Some(new_keywords) = new_keywords {
+                classification["keywords"] = serde_json::to_value(new_keywords)?;
+            }
+            tx.execute(
+                "UPDATE analyses SET tags = ?2, classification = ?3 WHERE id = ?1",
+                params![id, serde_json::to_string(&new_tags)?, classification.to_string()],
+            )?;
+            changed += 1;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// List prompts whose latest analysis classified them with the given intent.
+    pub fn list_prompts_by_intent(&self, intent: &str) -> Result<Vec<Prompt>, StorageError> {
         let conn = self.conn()?;
-        let mut stmt = conn.prepare(
-            "SELECT id, prompt_id, summary, tags, classification, qwen_model, created_at
-             FROM analyses
-             WHERE prompt_id = ?1
-             ORDER BY datetime(created_at) DESC",
-        )?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT p.id, p.title, p.body, p.language, p.model_hint, p.metadata, p.created_at, p.updated_at
+             FROM prompts p
+             WHERE {VISIBLE_PROMPT_P} AND json_extract(
+                 (SELECT a.classification FROM analyses a
+                  WHERE a.prompt_id = p.id
+                  ORDER BY datetime(a.created_at) DESC
+                  LIMIT 1),
+                 '$.intent'
+             ) = ?1
+             ORDER BY datetime(p.updated_at) DESC"
+        ))?;
 
-        let items = stmt
-            .query_map(params![prompt_id], |row| row_to_analysis(row))?
+        let rows = stmt
+            .query_map(params![intent], row_to_prompt)?
             .collect::<Result<Vec<_>, _>>()?;
-        Ok(items)
+        Ok(rows)
     }
 
-    /// Fetch the latest analysis for a prompt (if any).
-    pub fn latest_analysis_for_prompt(&self, prompt_id: &str) -> Result<Option<Analysis>, StorageError> {
+    /// Prompts copied from an app whose process name and window title (`metadata.source_app`)
+    /// contain the given texts, ASCII case-insensitive; `None` matches anything, including
+    /// prompts with no source app recorded.
+    pub fn list_prompts_by_source_app(
+        &self,
+        process: Option<&str>,
+        title: Option<&str>,
+    ) -> Result<Vec<Prompt>, StorageError> {
         let conn = self.conn()?;
-        let analysis = conn
-            .query_row(
-                "SELECT id, prompt_id, summary, tags, classification, qwen_model, created_at
-                 FROM analyses
-                 WHERE prompt_id = ?1
-                 ORDER BY datetime(created_at) DESC
-                 LIMIT 1",
-                params![prompt_id],
-                |row| row_to_analysis(row),
-            )
-            .optional()?;
-        Ok(analysis)
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
+             FROM prompts
+             WHERE {VISIBLE_PROMPT}
+               AND (?1 IS NULL OR instr(lower(json_extract(metadata, '$.source_app.process')), lower(?1)) > 0)
+               AND (?2 IS NULL OR instr(lower(json_extract(metadata, '$.source_app.title')), lower(?2)) > 0)
+             ORDER BY datetime(updated_at) DESC"
+        ))?;
+
+        let rows = stmt
+            .query_map(params![process, title], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
     }
 
-    /// Store a binary attachment for a prompt.
-    pub fn add_attachment(&self, payload: NewAttachment) -> Result<Attachment, StorageError> {
+    /// Every process prompts were copied from, with the number of prompts, most used first.
+    pub fn list_source_apps(&self) -> Result<Vec<SourceAppCount>, StorageError> {
         let conn = self.conn()?;
-        let id = Uuid::new_v4().to_string();
-        conn.execute(
-            r#"
-            INSERT INTO attachments (id, prompt_id, filename, bytes)
-            VALUES (?1, ?2, ?3, ?4)
-            "#,
-            params![id, payload.prompt_id, payload.filename, payload.bytes],
+        let mut stmt = conn.prepare(&format!(
+            "SELECT json_extract(metadata, '$.source_app.process') AS process, COUNT(*) AS uses
+             FROM prompts
+             WHERE {VISIBLE_PROMPT} AND process IS NOT NULL
+             GROUP BY process
+             ORDER BY uses DESC, process"
+        ))?;
+        let counts = stmt
+            .query_map([], |row| {
+                Ok(SourceAppCount {
+                    process: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(counts)
+    }
+
+    /// Every collection prompts were put in (`metadata.collection`), with the number of
+    /// prompts, by name.
+    pub fn list_collections(&self) -> Result<Vec<CollectionCount>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT json_extract(metadata, '$.collection') AS collection, COUNT(*)
+             FROM prompts
+             WHERE {VISIBLE_PROMPT} AND collection IS NOT NULL
+             GROUP BY collection
+             ORDER BY collection"
+        ))?;
+        let counts = stmt
+            .query_map([], |row| {
+                Ok(CollectionCount {
+                    name: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(counts)
+    }
+
+    /// Prompts whose latest analysis carries a rubric `quality_score`, best first. A newer
+    /// analysis without a score (e.g. a reanalysis) hides the old score.
+    pub fn list_prompts_by_quality(&self) -> Result<Vec<RankedPrompt>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at, score
+             FROM (
+                 SELECT p.*, json_extract(
+                     (SELECT a.classification FROM analyses a
+                      WHERE a.prompt_id = p.id
+                      ORDER BY datetime(a.created_at) DESC
+                      LIMIT 1),
+                     '$.quality_score'
+                 ) AS score
+                 FROM prompts p
+                 WHERE {VISIBLE_PROMPT_P}
+             )
+             WHERE score IS NOT NULL
+             ORDER BY score DESC, datetime(updated_at) DESC"
+        ))?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(RankedPrompt {
+                    prompt: row_to_prompt(row)?,
+                    quality_score: row.get(8)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// List prompts whose latest analysis requires answers in the given language (ISO 639-1).
+    pub fn list_prompts_by_output_language(&self, language: &str) -> Result<Vec<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT p.id, p.title, p.body, p.language, p.model_hint, p.metadata, p.created_at, p.updated_at
+             FROM prompts p
+             WHERE {VISIBLE_PROMPT_P} AND json_extract(
+                 (SELECT a.classification FROM analyses a
+                  WHERE a.prompt_id = p.id
+                  ORDER BY datetime(a.created_at) DESC
+                  LIMIT 1),
+                 '$.output_language'
+             ) = ?1
+             ORDER BY datetime(p.updated_at) DESC"
+        ))?;
+
+        let rows = stmt
+            .query_map(params![language], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Move a prompt to the trash: listings, searches and deduplication skip it until it is
+    /// restored, while [`Self::get_prompt`] still finds it. Returns `false` if the prompt does
+    /// not exist or is already trashed.
+    pub fn soft_delete_prompt(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE prompts SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+            params![id, Utc::now().to_rfc3339()],
         )?;
-        self.get_attachment(&id)?
-            .ok_or(StorageError::NotFound("attachment".into()))
+        Ok(affected > 0)
     }
 
-    /// Fetch attachment metadata + bytes.
-    pub fn get_attachment(&self, id: &str) -> Result<Option<Attachment>, StorageError> {
+    /// Count one use of a prompt, e.g. copying it to the clipboard. Returns the updated usage,
+    /// or `None` if the prompt does not exist or is trashed.
+    pub fn record_prompt_use(&self, id: &str) -> Result<Option<PromptUsage>, StorageError> {
         let conn = self.conn()?;
-        let attachment = conn
+        let usage = conn
             .query_row(
-                "SELECT id, prompt_id, filename, bytes FROM attachments WHERE id = ?1",
-                params![id],
+                "UPDATE prompts SET use_count = use_count + 1, last_used_at = ?2
+                 WHERE id = ?1 AND deleted_at IS NULL
+                 RETURNING id, use_count, last_used_at",
+                params![id, Utc::now().to_rfc3339()],
                 |row| {
-                    Ok(Attachment {
-                        id: row.get(0)?,
-                        prompt_id: row.get(1)?,
-                        filename: row.get(2)?,
-                        bytes: row.get(3)?,
+                    Ok(PromptUsage {
+                        prompt_id: row.get(0)?,
+                        use_count: row.get::<_, i64>(1)? as u64,
+                        last_used_at: parse_datetime(&row.get::<_, String>(2)?)?,
                     })
                 },
             )
             .optional()?;
-        Ok(attachment)
+        Ok(usage)
     }
 
-    /// Remove attachment by id.
-    pub fn delete_attachment(&self, id: &str) -> Result<bool, StorageError> {
+    /// Take a prompt out of the trash. Returns `false` if it was not trashed.
+    pub fn restore_prompt(&self, id: &str) -> Result<bool, StorageError> {
         let conn = self.conn()?;
-        let affected = conn.execute("DELETE FROM attachments WHERE id = ?1", params![id])?;
+        let affected = conn.execute(
+            "UPDATE prompts SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+            params![id],
+        )?;
         Ok(affected > 0)
     }
-}
-
-fn row_to_prompt(row: &rusqlite::Row<'_>) -> rusqlite::Result<Prompt> {
-    Ok(Prompt {
-        id: row.get(0)?,
-        title: row.get(1)?,
-        body: row.get(2)?,
-        language: row.get(3)?,
-        model_hint: row.get(4)?,
-        metadata: serde_json::from_str::<Value>(&row.get::<_, String>(5)?).unwrap_or(Value::Null),
-        created_at: parse_datetime(&row.get::<_, String>(6)?)?,
-        updated_at: parse_datetime(&row.get::<_, String>(7)?)?,
-    })
-}
 
-fn row_to_analysis(row: &rusqlite::Row<'_>) -> rusqlite::Result<Analysis> {
-    Ok(Analysis {
-        id: row.get(0)?,
-        prompt_id: row.get(1)?,
+    /// Trashed prompts, most recently deleted first.
+    pub fn list_deleted_prompts(&self) -> Result<Vec<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
+             FROM prompts
+             WHERE deleted_at IS NOT NULL
+             ORDER BY datetime(deleted_at) DESC",
+        )?;
+        let rows = stmt
+            .query_map([], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Hold a prompt back from the library until it is approved: listings, searches and
+    /// statistics skip it, while deduplication and [`Self::get_prompt`] still find it. Returns `false` if the
+    /// prompt does not exist, is trashed or is already quarantined.
+    pub fn quarantine_prompt(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE prompts SET quarantined_at = ?2
+             WHERE id = ?1 AND deleted_at IS NULL AND quarantined_at IS NULL",
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Let a quarantined prompt into the library. Returns `false` if it was not quarantined.
+    pub fn release_prompt(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE prompts SET quarantined_at = NULL WHERE id = ?1 AND quarantined_at IS NOT NULL",
+            params![id],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Quarantined prompts that are not trashed, most recently quarantined first.
+    pub fn list_quarantined_prompts(&self) -> Result<Vec<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, title, body, language, model_hint, metadata, created_at, updated_at
+             FROM prompts
+             WHERE quarantined_at IS NOT NULL AND deleted_at IS NULL
+             ORDER BY datetime(quarantined_at) DESC",
+        )?;
+        let rows = stmt
+            .query_map([], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Delete a prompt (analyses/attachments cascade).
+    pub fn delete_prompt(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute("DELETE FROM prompts WHERE id = ?1", params![id])?;
+        Ok(affected > 0)
+    }
+
+    /// Delete several prompts in one transaction, like [`Self::delete_prompt`]. Returns the
+    /// number deleted; unknown ids are skipped.
+    pub fn delete_prompts(&self, ids: &[String]) -> Result<usize, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let mut deleted = 0;
+        for id in ids {
+            deleted += tx.execute("DELETE FROM prompts WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Fold `duplicate_ids` into `primary_id` in one transaction: their analyses and
+    /// attachments move to the primary, their latest tags join the primary's, their
+    /// `metadata.notes` are appended to the primary's and their use counts added to it. Each
+    /// then gets a `merged` relation to the primary and goes to the trash. Ids that are
+    /// unknown, trashed or the primary itself are skipped. Returns `None` if the primary does
+    /// not exist or is trashed.
+    pub fn merge_prompts(
+        &self,
+        primary_id: &str,
+        duplicate_ids: &[String],
+    ) -> Result<Option<MergeReport>, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let select_prompt =
+            "SELECT metadata, use_count, last_used_at FROM prompts WHERE id = ?1 AND deleted_at IS NULL";
+        let read_prompt = |row: &rusqlite::Row| -> rusqlite::Result<(String, i64, Option<String>)> {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        };
+        let latest_analysis = |id: &str| -> Result<Option<(String, Vec<String>)>, StorageError> {
+            let latest: Option<(String, String)> = tx
+                .query_row(
+                    "SELECT id, tags FROM analyses WHERE prompt_id = ?1 ORDER BY datetime(created_at) DESC LIMIT 1",
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            Ok(latest.map(|(id, tags)| (id, serde_json::from_str(&tags).unwrap_or_default())))
+        };
+        let notes_of = |metadata: &str| {
+            serde_json::from_str::<Value>(metadata)
+                .ok()
+                .and_then(|metadata| metadata.get("notes")?.as_str().map(|notes| notes.trim().to_string()))
+                .filter(|notes| !notes.is_empty())
+        };
+
+        let Some((metadata, mut use_count, mut last_used_at)) = tx
+            .query_row(select_prompt, params![primary_id], read_prompt)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let own_analysis = latest_analysis(primary_id)?;
+        let primary_tags = own_analysis.as_ref().map(|(_, tags)| tags.clone()).unwrap_or_default();
+        let mut tags = primary_tags.clone();
+        let mut notes: Vec<String> = notes_of(&metadata).into_iter().collect();
+        let now = Utc::now().to_rfc3339();
+        let mut report = MergeReport {
+            primary_id: primary_id.to_string(),
+            ..MergeReport::default()
+        };
+
+        for id in duplicate_ids {
+            if id == primary_id || report.merged_ids.contains(id) || report.skipped_ids.contains(id) {
+                continue;
+            }
+            let Some((duplicate_metadata, duplicate_uses, duplicate_last_used)) =
+                tx.query_row(select_prompt, params![id], read_prompt).optional()?
+            else {
+                report.skipped_ids.push(id.clone());
+                continue;
+            };
+            for tag in latest_analysis(id)?.map(|(_, tags)| tags).unwrap_or_default() {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            if let Some(duplicate_notes) = notes_of(&duplicate_metadata) {
+                if !notes.contains(&duplicate_notes) {
+                    notes.push(duplicate_notes);
+                    report.notes_merged += 1;
+                }
+            }
+            use_count += duplicate_uses;
+            let later = match (&last_used_at, &duplicate_last_used) {
+                (Some(current), Some(other)) => parse_datetime(other)? > parse_datetime(current)?,
+                (None, Some(_)) => true,
+                _ => false,
+            };
+            if later {
+                last_used_at = duplicate_last_used;
+            }
+            report.analyses += tx.execute(
+                "UPDATE analyses SET prompt_id = ?2 WHERE prompt_id = ?1",
+                params![id, primary_id],
+            )?;
+            report.attachments += tx.execute(
+                "UPDATE attachments SET prompt_id = ?2 WHERE prompt_id = ?1",
+                params![id, primary_id],
+            )?;
+            tx.execute(
+                "INSERT INTO prompt_relations (id, source_id, target_id, relation, metadata, created_at)
+                 VALUES (?1, ?2, ?3, 'merged', '{}', ?4)",
+                params![Uuid::new_v4().to_string(), id, primary_id, now],
+            )?;
+            tx.execute(
+                "UPDATE prompts SET use_count = 0, deleted_at = ?2 WHERE id = ?1",
+                params![id, now],
+            )?;
+            report.merged_ids.push(id.clone());
+        }
+        if report.merged_ids.is_empty() {
+            return Ok(Some(report));
+        }
+
+        report.tags_added = tags.iter().filter(|tag| !primary_tags.contains(tag)).cloned().collect();
+        // A moved analysis may be newer than the primary's own, so the merged tags go on a
+        // copy of the primary's latest analysis (or, if it had none, of the newest moved one).
+        let base = match own_analysis {
+            Some((id, _)) if report.analyses > 0 || !report.tags_added.is_empty() => Some(id),
+            Some(_) => None,
+            None => latest_analysis(primary_id)?.map(|(id, _)| id),
+        };
+        if let Some(base) = base {
+            let (summary, classification, qwen_model, analyzer_version): (String, String, Option<String>, Option<i64>) =
+                tx.query_row(
+                    "SELECT summary, classification, qwen_model, analyzer_version FROM analyses WHERE id = ?1",
+                    params![base],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )?;
+            let mut classification = serde_json::from_str::<Value>(&classification).unwrap_or(Value::Null);
+            let keywords = classification
+                .get("keywords")
+                .and_then(|keywords| serde_json::from_value::<Vec<String>>(keywords.clone()).ok());
+            if let Some(mut keywords) = keywords {
+                for tag in &report.tags_added {
+                    if !keywords.contains(tag) {
+                        keywords.push(tag.clone());
+                    }
+                }
+                classification["keywords"] = serde_json::to_value(keywords)?;
+            }
+            tx.execute(
+                "INSERT INTO analyses (id, prompt_id, summary, tags, classification, qwen_model, analyzer_version, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    Uuid::new_v4().to_string(),
+                    primary_id,
+                    summary,
+                    serde_json::to_string(&tags)?,
+                    classification.to_string(),
+                    qwen_model,
+                    analyzer_version,
+                    now
+                ],
+            )?;
+        }
+
+        let mut fields = match serde_json::from_str::<Value>(&metadata) {
+            Ok(Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        if !notes.is_empty() {
+            fields.insert("notes".into(), Value::from(notes.join("\n\n")));
+        }
+        tx.execute(
+            "UPDATE prompts SET metadata = ?2, use_count = ?3, last_used_at = ?4, updated_at = ?5 WHERE id = ?1",
+            params![
+                primary_id,
+                Value::Object(fields).to_string(),
+                use_count,
+                last_used_at,
+                now
+            ],
+        )?;
+        tx.commit()?;
+        report.use_count = use_count as u64;
+        Ok(Some(report))
+    }
+
+    /// Add `tags` to the latest analysis of each prompt (and to the `keywords` copy in its
+    /// classification), in one transaction; a prompt without an analysis gets one holding just
+    /// the tags. Trashed and unknown ids are skipped. Returns the number of prompts whose tags
+    /// changed.
+    pub fn add_prompt_tags(&self, ids: &[String], tags: &[String]) -> Result<usize, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let mut changed = 0;
+        for id in ids {
+            let exists = tx
+                .query_row(
+                    "SELECT 1 FROM prompts WHERE id = ?1 AND deleted_at IS NULL",
+                    params![id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if !exists {
+                continue;
+            }
+            let latest: Option<(String, String, String)> = tx
+                .query_row(
+                    "SELECT id, tags, classification FROM analyses
+                     WHERE prompt_id = ?1
+                     ORDER BY datetime(created_at) DESC
+                     LIMIT 1",
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .optional()?;
+            let Some((analysis_id, current, classification)) = latest else {
+                tx.execute(
+                    "INSERT INTO analyses (id, prompt_id, summary, tags, classification, created_at)
+                     VALUES (?1, ?2, '', ?3, '{}', ?4)",
+                    params![Uuid::new_v4().to_string(), id, serde_json::to_string(tags)?, Utc::now().to_rfc3339()],
+                )?;
+                changed += 1;
+                continue;
+            };
+            let add = |existing: &mut Vec<String>| {
+                let before = existing.len();
+                for tag in tags {
+                    if !existing.contains(tag) {
+                        existing.push(tag.clone());
+                    }
+                }
+                existing.len() > before
+            };
+            let mut current = serde_json::from_str::<Vec<String>>(&current).unwrap_or_default();
+            if !add(&mut current) {
+                continue;
+            }
+            let mut classification = serde_json::from_str::<Value>(&classification).unwrap_or(Value::Null);
+            let keywords = classification.get("keywords").and_then(|keywords| {
+                serde_json::from_value::<Vec<String>>(keywords.clone()).ok()
+            });
+            if let Some(mut keywords) = keywords {
+                add(&mut keywords);
+                classification["keywords"] = serde_json::to_value(keywords)?;
+            }
+            tx.execute(
+                "UPDATE analyses SET tags = ?2, classification = ?3 WHERE id = ?1",
+                params![analysis_id, serde_json::to_string(&current)?, classification.to_string()],
+            )?;
+            changed += 1;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Put several prompts in `collection` (`metadata.collection`), or take them out of any
+    /// with `None`, in one transaction. Trashed and unknown ids are skipped. Returns the number
+    /// of prompts changed.
+    pub fn set_prompts_collection(&self, ids: &[String], collection: Option<&str>) -> Result<usize, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let now = Utc::now().to_rfc3339();
+        let mut changed = 0;
+        for id in ids {
+            let metadata: Option<String> = tx
+                .query_row(
+                    "SELECT metadata FROM prompts WHERE id = ?1 AND deleted_at IS NULL",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(metadata) = metadata else {
+                continue;
+            };
+            let mut fields = match serde_json::from_str::<Value>(&metadata) {
+                Ok(Value::Object(fields)) => fields,
+                _ => serde_json::Map::new(),
+            };
+            let previous = match collection {
+                Some(collection) => fields.insert("collection".into(), Value::from(collection)),
+                None => fields.remove("collection"),
+            };
+            if previous == collection.map(Value::from) {
+                continue;
+            }
+            tx.execute(
+                "UPDATE prompts SET metadata = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, Value::Object(fields).to_string(), now],
+            )?;
+            changed += 1;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Set the language of several prompts (`None` clears it) in one transaction. Trashed and
+    /// unknown ids are skipped. Returns the number of prompts changed.
+    pub fn set_prompts_language(&self, ids: &[String], language: Option<&str>) -> Result<usize, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let now = Utc::now().to_rfc3339();
+        let mut changed = 0;
+        for id in ids {
+            changed += tx.execute(
+                "UPDATE prompts SET language = ?2, updated_at = ?3
+                 WHERE id = ?1 AND deleted_at IS NULL AND language IS NOT ?2",
+                params![id, language, now],
+            )?;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Store a new AI analysis result.
+    pub fn create_analysis(&self, input: NewAnalysis) -> Result<Analysis, StorageError> {
+        let conn = self.conn()?;
+        let id = Uuid::new_v4().to_string();
+        let created_at = Utc::now();
+
+        conn.execute(
+            r#"
+            INSERT INTO analyses (id, prompt_id, summary, tags, classification, qwen_model, analyzer_version, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                id,
+                input.prompt_id,
+                input.summary,
+                serde_json::to_string(&input.tags)?,
+                input.classification.to_string(),
+                input.qwen_model,
+                input.analyzer_version,
+                created_at.to_rfc3339()
+            ],
+        )?;
+
+        self.get_analysis(&id)?
+            .ok_or(StorageError::NotFound("analysis".into()))
+    }
+
+    /// Get a specific analysis by ID.
+    pub fn get_analysis(&self, id: &str) -> Result<Option<Analysis>, StorageError> {
+        let conn = self.conn()?;
+        let analysis = conn
+            .query_row(
+                "SELECT id, prompt_id, summary, tags, classification, qwen_model, created_at, analyzer_version FROM analyses WHERE id = ?1",
+                params![id],
+                |row| row_to_analysis(row),
+            )
+            .optional()?;
+        Ok(analysis)
+    }
+
+    /// List analyses for a prompt.
+    pub fn list_analyses_for_prompt(&self, prompt_id: &str) -> Result<Vec<Analysis>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, prompt_id, summary, tags, classification, qwen_model, created_at, analyzer_version
+             FROM analyses
+             WHERE prompt_id = ?1
+             ORDER BY datetime(created_at) DESC",
+        )?;
+
+        let items = stmt
+            .query_map(params![prompt_id], |row| row_to_analysis(row))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items)
+    }
+
+    /// Fetch the latest analysis for a prompt (if any).
+    pub fn latest_analysis_for_prompt(&self, prompt_id: &str) -> Result<Option<Analysis>, StorageError> {
+        let conn = self.conn()?;
+        let analysis = conn
+            .query_row(
+                "SELECT id, prompt_id, summary, tags, classification, qwen_model, created_at, analyzer_version
+                 FROM analyses
+                 WHERE prompt_id = ?1
+                 ORDER BY datetime(created_at) DESC
+                 LIMIT 1",
+                params![prompt_id],
+                |row| row_to_analysis(row),
+            )
+            .optional()?;
+        Ok(analysis)
+    }
+
+    /// Store one LLM verdict on whether a prompt really is a prompt.
+    pub fn record_classification(&self, input: NewClassification) -> Result<Classification, StorageError> {
+        let conn = self.conn()?;
+        let id = Uuid::new_v4().to_string();
+        let created_at = Utc::now();
+        conn.execute(
+            "INSERT INTO classifications (id, prompt_id, source, is_prompt, confidence, model, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                id,
+                input.prompt_id,
+                input.source,
+                input.is_prompt,
+                input.confidence,
+                input.model,
+                created_at.to_rfc3339()
+            ],
+        )?;
+        Ok(Classification {
+            id,
+            prompt_id: input.prompt_id,
+            source: input.source,
+            is_prompt: input.is_prompt,
+            confidence: input.confidence,
+            model: input.model,
+            created_at,
+        })
+    }
+
+    pub fn latest_classification_for_prompt(&self, prompt_id: &str) -> Result<Option<Classification>, StorageError> {
+        let conn = self.conn()?;
+        let classification = conn
+            .query_row(
+                "SELECT id, prompt_id, source, is_prompt, confidence, model, created_at
+                 FROM classifications
+                 WHERE prompt_id = ?1
+                 ORDER BY datetime(created_at) DESC
+                 LIMIT 1",
+                params![prompt_id],
+                row_to_classification,
+            )
+            .optional()?;
+        Ok(classification)
+    }
+
+    /// The latest classification of every classified prompt, newest first, optionally
+    /// only those produced by `source` (`clipboard`, `job`, ...).
+    pub fn list_latest_classifications(&self, source: Option<&str>) -> Result<Vec<Classification>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.prompt_id, c.source, c.is_prompt, c.confidence, c.model, c.created_at
+             FROM classifications c
+             WHERE c.id = (
+                 SELECT latest.id FROM classifications latest
+                 WHERE latest.prompt_id = c.prompt_id
+                 ORDER BY datetime(latest.created_at) DESC
+                 LIMIT 1
+             )
+             AND (?1 IS NULL OR c.source = ?1)
+             ORDER BY datetime(c.created_at) DESC",
+        )?;
+        let classifications = stmt
+            .query_map(params![source], row_to_classification)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(classifications)
+    }
+
+    /// Few-shot examples for the `is_prompt` classifier, newest first.
+    pub fn list_classifier_examples(&self) -> Result<Vec<ClassifierExample>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, text, is_prompt, prompt_id, created_at, updated_at
+             FROM classifier_examples
+             ORDER BY datetime(created_at) DESC",
+        )?;
+        let examples = stmt
+            .query_map([], row_to_classifier_example)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(examples)
+    }
+
+    pub fn get_classifier_example(&self, id: &str) -> Result<Option<ClassifierExample>, StorageError> {
+        let conn = self.conn()?;
+        let example = conn
+            .query_row(
+                "SELECT id, text, is_prompt, prompt_id, created_at, updated_at FROM classifier_examples WHERE id = ?1",
+                params![id],
+                row_to_classifier_example,
+            )
+            .optional()?;
+        Ok(example)
+    }
+
+    /// The example taken from `prompt_id`, if any.
+    pub fn find_classifier_example_by_prompt(&self, prompt_id: &str) -> Result<Option<ClassifierExample>, StorageError> {
+        let conn = self.conn()?;
+        let example = conn
+            .query_row(
+                "SELECT id, text, is_prompt, prompt_id, created_at, updated_at
+                 FROM classifier_examples WHERE prompt_id = ?1 LIMIT 1",
+                params![prompt_id],
+                row_to_classifier_example,
+            )
+            .optional()?;
+        Ok(example)
+    }
+
+    pub fn create_classifier_example(&self, input: NewClassifierExample) -> Result<ClassifierExample, StorageError> {
+        let conn = self.conn()?;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO classifier_examples (id, text, is_prompt, prompt_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![id, input.text, input.is_prompt, input.prompt_id, now],
+        )?;
+        drop(conn);
+        self.get_classifier_example(&id)?
+            .ok_or(StorageError::NotFound("classifier example".into()))
+    }
+
+    /// Change an example's text and/or label. Returns `None` if not found.
+    pub fn update_classifier_example(
+        &self,
+        id: &str,
+        text: Option<String>,
+        is_prompt: Option<bool>,
+    ) -> Result<Option<ClassifierExample>, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE classifier_examples
+             SET text = COALESCE(?2, text), is_prompt = COALESCE(?3, is_prompt), updated_at = ?4
+             WHERE id = ?1",
+            params![id, text, is_prompt, Utc::now().to_rfc3339()],
+        )?;
+        drop(conn);
+        if affected == 0 {
+            return Ok(None);
+        }
+        self.get_classifier_example(id)
+    }
+
+    pub fn delete_classifier_example(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute("DELETE FROM classifier_examples WHERE id = ?1", params![id])?;
+        Ok(affected > 0)
+    }
+
+    /// Record a near-duplicate group as a pending merge suggestion. Returns `false` when the
+    /// same set of prompts already has a suggestion, whatever its status.
+    pub fn save_merge_suggestion(&self, input: NewMergeSuggestion) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let now = Utc::now().to_rfc3339();
+        let affected = conn.execute(
+            "INSERT INTO merge_suggestions (id, group_key, prompt_ids, similarity, status, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+             ON CONFLICT(group_key) DO NOTHING",
+            params![
+                Uuid::new_v4().to_string(),
+                merge_group_key(&input.prompt_ids),
+                serde_json::to_string(&input.prompt_ids)?,
+                input.similarity,
+                MergeStatus::Pending.as_str(),
+                now
+            ],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Merge suggestions, optionally only those with `status`, most similar first.
+    pub fn list_merge_suggestions(&self, status: Option<MergeStatus>) -> Result<Vec<MergeSuggestion>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, prompt_ids, similarity, same_prompt, merged_body, reason, model, status, merged_prompt_id,
+                    created_at, updated_at
+             FROM merge_suggestions
+             WHERE ?1 IS NULL OR status = ?1
+             ORDER BY similarity DESC, datetime(created_at)",
+        )?;
+        let rows = stmt
+            .query_map(params![status.map(|status| status.as_str())], row_to_merge_suggestion)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn get_merge_suggestion(&self, id: &str) -> Result<Option<MergeSuggestion>, StorageError> {
+        let conn = self.conn()?;
+        let suggestion = conn
+            .query_row(
+                "SELECT id, prompt_ids, similarity, same_prompt, merged_body, reason, model, status, merged_prompt_id,
+                        created_at, updated_at
+                 FROM merge_suggestions WHERE id = ?1",
+                params![id],
+                row_to_merge_suggestion,
+            )
+            .optional()?;
+        Ok(suggestion)
+    }
+
+    /// Store the LLM's judgment of a suggestion. Returns `false` if not found.
+    pub fn set_merge_verdict(&self, id: &str, verdict: MergeVerdict) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE merge_suggestions
+             SET same_prompt = ?2, merged_body = ?3, reason = ?4, model = ?5, updated_at = ?6
+             WHERE id = ?1",
+            params![
+                id,
+                verdict.same_prompt,
+                verdict.merged_body,
+                verdict.reason,
+                verdict.model,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Accept or reject a pending suggestion. Returns `false` if it is not pending (or missing).
+    pub fn resolve_merge_suggestion(
+        &self,
+        id: &str,
+        status: MergeStatus,
+        merged_prompt_id: Option<&str>,
+    ) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE merge_suggestions SET status = ?2, merged_prompt_id = ?3, updated_at = ?4
+             WHERE id = ?1 AND status = ?5",
+            params![
+                id,
+                status.as_str(),
+                merged_prompt_id,
+                Utc::now().to_rfc3339(),
+                MergeStatus::Pending.as_str()
+            ],
+        )?;
+        Ok(affected > 0)
+    }
+
+    pub fn delete_merge_suggestion(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute("DELETE FROM merge_suggestions WHERE id = ?1", params![id])?;
+        Ok(affected > 0)
+    }
+
+    /// Hold a captured text in the inbox until it is accepted or rejected. `similar_to` is an
+    /// existing prompt the text nearly repeats, with the similarity, offered as the prompt the
+    /// capture may become a new version of.
+    pub fn create_capture(
+        &self,
+        text: &str,
+        source: &str,
+        similar_to: Option<(&str, f64)>,
+    ) -> Result<Capture, StorageError> {
+        let conn = self.conn()?;
+        let now = Utc::now();
+        let capture = Capture {
+            id: Uuid::new_v4().to_string(),
+            text: text.to_string(),
+            source: source.to_string(),
+            status: CaptureStatus::Pending,
+            prompt_id: None,
+            similar_prompt_id: similar_to.map(|(prompt_id, _)| prompt_id.to_string()),
+            similarity: similar_to.map(|(_, similarity)| similarity),
+            created_at: now,
+            updated_at: now,
+        };
+        conn.execute(
+            "INSERT INTO captures
+                 (id, text, source, status, prompt_id, created_at, updated_at, similar_prompt_id, similarity)
+             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?5, ?6, ?7)",
+            params![
+                capture.id,
+                capture.text,
+                capture.source,
+                capture.status.as_str(),
+                now.to_rfc3339(),
+                capture.similar_prompt_id,
+                capture.similarity
+            ],
+        )?;
+        Ok(capture)
+    }
+
+    /// Captures, optionally only those with `status`, newest first.
+    pub fn list_captures(&self, status: Option<CaptureStatus>) -> Result<Vec<Capture>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, text, source, status, prompt_id, created_at, updated_at, similar_prompt_id, similarity
+             FROM captures
+             WHERE ?1 IS NULL OR status = ?1
+             ORDER BY datetime(created_at) DESC",
+        )?;
+        let rows = stmt
+            .query_map(params![status.map(|status| status.as_str())], row_to_capture)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub fn get_capture(&self, id: &str) -> Result<Option<Capture>, StorageError> {
+        let conn = self.conn()?;
+        let capture = conn
+            .query_row(
+                "SELECT id, text, source, status, prompt_id, created_at, updated_at, similar_prompt_id, similarity
+                 FROM captures WHERE id = ?1",
+                params![id],
+                row_to_capture,
+            )
+            .optional()?;
+        Ok(capture)
+    }
+
+    /// The newest capture of exactly `text`, whatever its status.
+    pub fn find_capture_by_text(&self, text: &str) -> Result<Option<Capture>, StorageError> {
+        let conn = self.conn()?;
+        let capture = conn
+            .query_row(
+                "SELECT id, text, source, status, prompt_id, created_at, updated_at, similar_prompt_id, similarity
+                 FROM captures WHERE text = ?1
+                 ORDER BY datetime(created_at) DESC
+                 LIMIT 1",
+                params![text],
+                row_to_capture,
+            )
+            .optional()?;
+        Ok(capture)
+    }
+
+    /// Accept or reject a pending capture. Returns `false` if it is not pending (or missing).
+    pub fn resolve_capture(
+        &self,
+        id: &str,
+        status: CaptureStatus,
+        prompt_id: Option<&str>,
+    ) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE captures SET status = ?2, prompt_id = ?3, updated_at = ?4
+             WHERE id = ?1 AND status = ?5",
+            params![
+                id,
+                status.as_str(),
+                prompt_id,
+                Utc::now().to_rfc3339(),
+                CaptureStatus::Pending.as_str()
+            ],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Up to `limit` visible prompts, newest first, that have never been classified by a model
+    /// (verdicts of the local classifier do not count) and have no `job_kind` job pending or
+    /// running.
+    pub fn list_unclassified_prompts(&self, job_kind: &str, limit: usize) -> Result<Vec<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT p.id, p.title, p.body, p.language, p.model_hint, p.metadata, p.created_at, p.updated_at
+             FROM prompts p
+             WHERE {VISIBLE_PROMPT_P}
+               AND NOT EXISTS (SELECT 1 FROM classifications c WHERE c.prompt_id = p.id AND c.model != ?3)
+               AND NOT EXISTS (
+                   SELECT 1 FROM jobs j
+                   WHERE j.kind = ?1 AND j.prompt_id = p.id AND j.status IN ('pending', 'running', 'waiting')
+               )
+             ORDER BY datetime(p.created_at) DESC
+             LIMIT ?2"
+        ))?;
+        let rows = stmt
+            .query_map(params![job_kind, limit as i64, LOCAL_CLASSIFIER_MODEL], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Record the user's own verdict on whether a prompt is a prompt; `None` clears it.
+    pub fn set_prompt_label(&self, prompt_id: &str, is_prompt: Option<bool>) -> Result<(), StorageError> {
+        let conn = self.conn()?;
+        match is_prompt {
+            Some(is_prompt) => {
+                conn.execute(
+                    "INSERT INTO prompt_labels (prompt_id, is_prompt, created_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(prompt_id) DO UPDATE
+                     SET is_prompt = excluded.is_prompt, created_at = excluded.created_at",
+                    params![prompt_id, is_prompt, Utc::now().to_rfc3339()],
+                )?;
+            }
+            None => {
+                conn.execute("DELETE FROM prompt_labels WHERE prompt_id = ?1", params![prompt_id])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The user's `is_prompt` label of a prompt, if any.
+    pub fn get_prompt_label(&self, prompt_id: &str) -> Result<Option<bool>, StorageError> {
+        let conn = self.conn()?;
+        let label = conn
+            .query_row(
+                "SELECT is_prompt FROM prompt_labels WHERE prompt_id = ?1",
+                params![prompt_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(label)
+    }
+
+    /// The latest classification of every labeled prompt, paired with the user's label.
+    pub fn list_labeled_classifications(&self) -> Result<Vec<(Classification, bool)>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT c.id, c.prompt_id, c.source, c.is_prompt, c.confidence, c.model, c.created_at, l.is_prompt
+             FROM classifications c
+             JOIN prompt_labels l ON l.prompt_id = c.prompt_id
+             WHERE c.id = (
+                 SELECT latest.id FROM classifications latest
+                 WHERE latest.prompt_id = c.prompt_id
+                 ORDER BY datetime(latest.created_at) DESC
+                 LIMIT 1
+             )
+             ORDER BY datetime(c.created_at) DESC",
+        )?;
+        let labeled = stmt
+            .query_map([], |row| Ok((row_to_classification(row)?, row.get(7)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(labeled)
+    }
+
+    /// List prompts whose latest analysis predates `current_version` of the local analyzer
+    /// (including legacy rows that were never stamped).
+    pub fn list_prompts_with_outdated_analysis(&self, current_version: u32) -> Result<Vec<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT p.id, p.title, p.body, p.language, p.model_hint, p.metadata, p.created_at, p.updated_at
+             FROM prompts p
+             JOIN analyses a ON a.id = (
+                 SELECT latest.id FROM analyses latest
+                 WHERE latest.prompt_id = p.id
+                 ORDER BY datetime(latest.created_at) DESC
+                 LIMIT 1
+             )
+             WHERE p.deleted_at IS NULL AND (a.analyzer_version IS NULL OR a.analyzer_version < ?1)
+             ORDER BY datetime(p.updated_at) DESC",
+        )?;
+
+        let rows = stmt
+            .query_map(params![current_version], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Link two prompts (e.g. a chain parent to its steps, an original to its rewrite).
+    pub fn add_relation(&self, input: NewPromptRelation) -> Result<PromptRelation, StorageError> {
+        let conn = self.conn()?;
+        insert_relation(&conn, input)
+    }
+
+    /// Replace the body of a prompt with a new version, keeping the current title and body as
+    /// an earlier version. `source` says where the new version came from. Returns `None` if
+    /// the prompt does not exist.
+    pub fn add_prompt_version(
+        &self,
+        prompt_id: &str,
+        body: &str,
+        source: &str,
+    ) -> Result<Option<Prompt>, StorageError> {
+        let Some(current) = self.get_prompt(prompt_id)? else {
+            return Ok(None);
+        };
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO prompt_versions (id, prompt_id, title, body, source, replaced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                Uuid::new_v4().to_string(),
+                prompt_id,
+                current.title,
+                current.body,
+                source,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        self.update_prompt(
+            prompt_id,
+            UpdatePrompt {
+                body: Some(body.to_string()),
+                ..UpdatePrompt::default()
+            },
+        )
+    }
+
+    /// Earlier versions of a prompt, most recently replaced first.
+    pub fn list_prompt_versions(&self, prompt_id: &str) -> Result<Vec<PromptVersion>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, prompt_id, title, body, source, replaced_at
+             FROM prompt_versions
+             WHERE prompt_id = ?1
+             ORDER BY datetime(replaced_at) DESC",
+        )?;
+        let versions = stmt
+            .query_map(params![prompt_id], row_to_version)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(versions)
+    }
+
+    /// List relations where the prompt is either the source or the target.
+    pub fn list_relations_for_prompt(&self, prompt_id: &str) -> Result<Vec<PromptRelation>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, source_id, target_id, relation, metadata, created_at
+             FROM prompt_relations
+             WHERE source_id = ?1 OR target_id = ?1
+             ORDER BY datetime(created_at) ASC",
+        )?;
+        let items = stmt
+            .query_map(params![prompt_id], row_to_relation)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items)
+    }
+
+    /// Queue a background job. Returns `None` when a pending or running job of the same
+    /// kind already exists for the prompt.
+    pub fn enqueue_job(&self, input: NewJob) -> Result<Option<Job>, StorageError> {
+        let conn = self.conn()?;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+        let inserted = conn.execute(
+            r#"
+            INSERT INTO jobs (id, kind, prompt_id, payload, status, attempts, max_attempts, created_at, updated_at)
+            SELECT ?1, ?2, ?3, ?4, 'pending', 0, ?5, ?6, ?6
+            WHERE ?3 IS NULL OR NOT EXISTS (
+                SELECT 1 FROM jobs
+                WHERE kind = ?2 AND prompt_id = ?3 AND status IN ('pending', 'running', 'waiting')
+            )
+            "#,
+            params![
+                id,
+                input.kind,
+                input.prompt_id,
+                input.payload.to_string(),
+                input.max_attempts.max(1),
+                now
+            ],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        self.get_job(&id)
+    }
+
+    pub fn get_job(&self, id: &str) -> Result<Option<Job>, StorageError> {
+        let conn = self.conn()?;
+        let job = conn
+            .query_row(
+                "SELECT id, kind, prompt_id, payload, status, attempts, max_attempts, last_error, created_at, updated_at
+                 FROM jobs WHERE id = ?1",
+                params![id],
+                row_to_job,
+            )
+            .optional()?;
+        Ok(job)
+    }
+
+    /// List jobs, newest first, optionally filtered by status.
+    pub fn list_jobs(&self, status: Option<JobStatus>, limit: usize) -> Result<Vec<Job>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, prompt_id, payload, status, attempts, max_attempts, last_error, created_at, updated_at
+             FROM jobs
+             WHERE ?1 IS NULL OR status = ?1
+             ORDER BY datetime(created_at) DESC
+             LIMIT ?2",
+        )?;
+        let items = stmt
+            .query_map(params![status.map(|status| status.as_str()), limit as i64], row_to_job)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(items)
+    }
+
+    /// Atomically move the oldest pending job of `kind` to `running` and count the attempt.
+    pub fn claim_next_job(&self, kind: &str) -> Result<Option<Job>, StorageError> {
+        let conn = self.conn()?;
+        let id = conn
+            .query_row(
+                "UPDATE jobs
+                 SET status = 'running', attempts = attempts + 1, updated_at = ?2
+                 WHERE id = (
+                     SELECT id FROM jobs
+                     WHERE kind = ?1 AND status = 'pending'
+                     ORDER BY datetime(created_at), rowid
+                     LIMIT 1
+                 )
+                 RETURNING id",
+                params![kind, Utc::now().to_rfc3339()],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        match id {
+            Some(id) => self.get_job(&id),
+            None => Ok(None),
+        }
+    }
+
+    /// Mark a running job as done. Returns `false` if it was cancelled meanwhile.
+    pub fn complete_job(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE jobs SET status = 'done', last_error = NULL, updated_at = ?2
+             WHERE id = ?1 AND status = 'running'",
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Record a failed attempt: the job goes back to `pending` while it has attempts left
+    /// and `retryable` is set, otherwise it ends as `failed`.
+    pub fn fail_job(&self, id: &str, error: &str, retryable: bool) -> Result<Option<Job>, StorageError> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE jobs
+             SET status = CASE WHEN ?3 AND attempts < max_attempts THEN 'pending' ELSE 'failed' END,
+                 last_error = ?2,
+                 updated_at = ?4
+             WHERE id = ?1 AND status = 'running'",
+            params![id, error, retryable, Utc::now().to_rfc3339()],
+        )?;
+        self.get_job(id)
+    }
+
+    /// Cancel a pending, waiting or running job. A running job finishes its current request
+    /// but its result is discarded.
+    pub fn cancel_job(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE jobs SET status = 'cancelled', updated_at = ?2
+             WHERE id = ?1 AND status IN ('pending', 'running', 'waiting')",
+            params![id, Utc::now().to_rfc3339()],
+        )?;
+        Ok(affected > 0)
+    }
+
+    /// Cancel every pending, waiting or running job queued by one operation, i.e. whose
+    /// payload has `"operation": operation`. Returns the number of jobs cancelled.
+    pub fn cancel_operation_jobs(&self, operation: &str) -> Result<usize, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE jobs SET status = 'cancelled', updated_at = ?2
+             WHERE json_extract(payload, '$.operation') = ?1 AND status IN ('pending', 'running', 'waiting')",
+            params![operation, Utc::now().to_rfc3339()],
+        )?;
+        Ok(affected)
+    }
+
+    /// Set a running job aside because the backend could not be reached. The attempt is not
+    /// counted, so an outage never uses up a job's attempts; see [`Storage::resume_waiting_jobs`].
+    pub fn park_job(&self, id: &str, error: &str) -> Result<Option<Job>, StorageError> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE jobs
+             SET status = 'waiting', attempts = MAX(attempts - 1, 0), last_error = ?2, updated_at = ?3
+             WHERE id = ?1 AND status = 'running'",
+            params![id, error, Utc::now().to_rfc3339()],
+        )?;
+        self.get_job(id)
+    }
+
+    /// Put waiting jobs of `kind` back in the queue, e.g. once the backend is reachable again.
+    pub fn resume_waiting_jobs(&self, kind: &str) -> Result<usize, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE jobs SET status = 'pending', updated_at = ?2 WHERE kind = ?1 AND status = 'waiting'",
+            params![kind, Utc::now().to_rfc3339()],
+        )?;
+        Ok(affected)
+    }
+
+    /// Count jobs of `kind` in `status`.
+    pub fn count_jobs(&self, kind: &str, status: JobStatus) -> Result<usize, StorageError> {
+        let conn = self.conn()?;
+        let count = conn.query_row(
+            "SELECT COUNT(*) FROM jobs WHERE kind = ?1 AND status = ?2",
+            params![kind, status.as_str()],
+            |row| row.get::<_, i64>(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Put jobs left `running` by a previous session back in the queue.
+    pub fn requeue_interrupted_jobs(&self) -> Result<usize, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute(
+            "UPDATE jobs SET status = 'pending', updated_at = ?1 WHERE status = 'running'",
+            params![Utc::now().to_rfc3339()],
+        )?;
+        Ok(affected)
+    }
+
+    /// Store (or replace) the embedding of a prompt's body.
+    pub fn upsert_embedding(&self, prompt_id: &str, model: &str, vector: &[f32]) -> Result<(), StorageError> {
+        let conn = self.conn()?;
+        let bytes: Vec<u8> = vector.iter().flat_map(|value| value.to_le_bytes()).collect();
+        conn.execute(
+            r#"
+            INSERT INTO embeddings (prompt_id, model, dimensions, vector, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(prompt_id) DO UPDATE SET
+                model = excluded.model,
+                dimensions = excluded.dimensions,
+                vector = excluded.vector,
+                created_at = excluded.created_at
+            "#,
+            params![prompt_id, model, vector.len() as i64, bytes, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Visible prompts with no embedding from `model`, or whose body changed after it was
+    /// embedded.
+    pub fn list_prompts_missing_embedding(&self, model: &str) -> Result<Vec<Prompt>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT p.id, p.title, p.body, p.language, p.model_hint, p.metadata, p.created_at, p.updated_at
+             FROM prompts p
+             LEFT JOIN embeddings e ON e.prompt_id = p.id
+             WHERE {VISIBLE_PROMPT_P}
+               AND (e.prompt_id IS NULL
+                    OR e.model != ?1
+                    OR datetime(e.created_at) < datetime(p.updated_at))
+             ORDER BY datetime(p.updated_at) DESC"
+        ))?;
+        let rows = stmt
+            .query_map(params![model], row_to_prompt)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// All stored embeddings produced by `model`.
+    pub fn list_embeddings(&self, model: &str) -> Result<Vec<PromptEmbedding>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT e.prompt_id, e.vector
+             FROM embeddings e
+             JOIN prompts p ON p.id = e.prompt_id
+             WHERE e.model = ?1 AND {VISIBLE_PROMPT_P}"
+        ))?;
+        let rows = stmt
+            .query_map(params![model], |row| {
+                let bytes: Vec<u8> = row.get(1)?;
+                Ok(PromptEmbedding {
+                    prompt_id: row.get(0)?,
+                    vector: bytes
+                        .chunks_exact(4)
+                        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                        .collect(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Log one LLM request (after retries) for usage and latency metrics.
+    pub fn record_llm_call(&self, input: NewLlmCall) -> Result<(), StorageError> {
+        let conn = self.conn()?;
+        conn.execute(
+            r#"
+            INSERT INTO llm_calls (id, task, prompt_id, provider, model, prompt_tokens, completion_tokens,
+                                   latency_ms, attempts, success, error, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+            "#,
+            params![
+                Uuid::new_v4().to_string(),
+                input.task,
+                input.prompt_id,
+                input.provider,
+                input.model,
+                input.prompt_tokens.map(|tokens| tokens as i64),
+                input.completion_tokens.map(|tokens| tokens as i64),
+                input.latency_ms as i64,
+                input.attempts,
+                input.error.is_none(),
+                input.error,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Aggregate LLM usage per task and model, optionally only for calls made since `since`.
+    pub fn llm_usage_metrics(&self, since: Option<DateTime<Utc>>) -> Result<Vec<LlmUsageMetrics>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT task, model, COUNT(*), SUM(1 - success), SUM(attempts),
+                    COALESCE(SUM(prompt_tokens), 0), COALESCE(SUM(completion_tokens), 0),
+                    AVG(latency_ms), MAX(latency_ms)
+             FROM llm_calls
+             WHERE ?1 IS NULL OR datetime(created_at) >= datetime(?1)
+             GROUP BY task, model
+             ORDER BY task, model",
+        )?;
+        let rows = stmt
+            .query_map(params![since.map(|since| since.to_rfc3339())], |row| {
+                Ok(LlmUsageMetrics {
+                    task: row.get(0)?,
+                    model: row.get(1)?,
+                    calls: row.get::<_, i64>(2)? as u64,
+                    failures: row.get::<_, i64>(3)? as u64,
+                    attempts: row.get::<_, i64>(4)? as u64,
+                    prompt_tokens: row.get::<_, i64>(5)? as u64,
+                    completion_tokens: row.get::<_, i64>(6)? as u64,
+                    avg_latency_ms: row.get(7)?,
+                    max_latency_ms: row.get::<_, i64>(8)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Token totals per provider/model pair for calls made at or after `since`.
+    pub fn llm_token_usage(&self, since: DateTime<Utc>) -> Result<Vec<LlmTokenUsage>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT provider, model, COUNT(*),
+                    COALESCE(SUM(prompt_tokens), 0), COALESCE(SUM(completion_tokens), 0)
+             FROM llm_calls
+             WHERE datetime(created_at) >= datetime(?1)
+             GROUP BY provider, model
+             ORDER BY provider, model",
+        )?;
+        let rows = stmt
+            .query_map(params![since.to_rfc3339()], |row| {
+                Ok(LlmTokenUsage {
+                    provider: row.get(0)?,
+                    model: row.get(1)?,
+                    calls: row.get::<_, i64>(2)? as u64,
+                    prompt_tokens: row.get::<_, i64>(3)? as u64,
+                    completion_tokens: row.get::<_, i64>(4)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Activity since `since` for the statistics page, aggregated in SQL and bucketed by
+    /// local calendar day (`YYYY-MM-DD`): prompts saved and texts held in the inbox per day,
+    /// where the prompts came from (`metadata.source`), the `top_tags` tags most used by
+    /// their latest analyses, classifier and inbox verdicts, and token usage per day and
+    /// model. Trashed prompts are left out; days without activity are not listed.
+    pub fn dashboard_stats(&self, since: DateTime<Utc>, top_tags: usize) -> Result<DashboardStats, StorageError> {
+        let conn = self.conn()?;
+        let since = since.to_rfc3339();
+        let count_by_day = |sql: &str| -> Result<Vec<(String, i64)>, StorageError> {
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt
+                .query_map(params![since], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(rows)
+        };
+        let mut daily: BTreeMap<String, DailyActivity> = BTreeMap::new();
+        let prompts = count_by_day(&format!(
+            "SELECT date(created_at, 'localtime') AS day, COUNT(*)
+             FROM prompts
+             WHERE {VISIBLE_PROMPT} AND datetime(created_at) >= datetime(?1)
+             GROUP BY day"
+        ))?;
+        for (date, count) in prompts {
+            let day = daily.entry(date.clone()).or_insert_with(|| DailyActivity::new(date));
+            day.prompts = count as u64;
+        }
+        let captures = count_by_day(
+            "SELECT date(created_at, 'localtime') AS day, COUNT(*)
+             FROM captures
+             WHERE datetime(created_at) >= datetime(?1)
+             GROUP BY day",
+        )?;
+        for (date, count) in captures {
+            let day = daily.entry(date.clone()).or_insert_with(|| DailyActivity::new(date));
+            day.captures = count as u64;
+        }
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT COALESCE(json_extract(metadata, '$.source'), 'unknown') AS source, COUNT(*) AS uses
+             FROM prompts
+             WHERE {VISIBLE_PROMPT} AND datetime(created_at) >= datetime(?1)
+             GROUP BY source
+             ORDER BY uses DESC, source"
+        ))?;
+        let sources = stmt
+            .query_map(params![since], |row| {
+                Ok(SourceCount {
+                    source: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        stmt = conn.prepare(&format!(
+            "SELECT tag.value, COUNT(DISTINCT a.prompt_id) AS uses
+             FROM analyses a
+             JOIN prompts p ON p.id = a.prompt_id, json_each(a.tags) tag
+             WHERE {VISIBLE_PROMPT_P} AND datetime(p.created_at) >= datetime(?1)
+             AND a.id = (
+                 SELECT latest.id FROM analyses latest
+                 WHERE latest.prompt_id = a.prompt_id
+                 ORDER BY datetime(latest.created_at) DESC
+                 LIMIT 1
+             )
+             AND json_valid(a.tags)
+             GROUP BY tag.value
+             ORDER BY uses DESC, tag.value
+             LIMIT ?2"
+        ))?;
+        let top_tags = stmt
+            .query_map(params![since, top_tags as i64], |row| {
+                Ok(TagCount {
+                    tag: row.get(0)?,
+                    count: row.get::<_, i64>(1)? as usize,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (classified, accepted) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(is_prompt), 0) FROM classifications
+             WHERE datetime(created_at) >= datetime(?1)",
+            params![since],
+            |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64)),
+        )?;
+        let (inbox_accepted, inbox_rejected, inbox_pending) = conn.query_row(
+            "SELECT COALESCE(SUM(status = 'accepted'), 0), COALESCE(SUM(status = 'rejected'), 0),
+                    COALESCE(SUM(status = 'pending'), 0)
+             FROM captures
+             WHERE datetime(created_at) >= datetime(?1)",
+            params![since],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)? as u64,
+                    row.get::<_, i64>(1)? as u64,
+                    row.get::<_, i64>(2)? as u64,
+                ))
+            },
+        )?;
+
+        stmt = conn.prepare(
+            "SELECT date(created_at, 'localtime') AS day, provider, model, COUNT(*),
+                    COALESCE(SUM(prompt_tokens), 0), COALESCE(SUM(completion_tokens), 0)
+             FROM llm_calls
+             WHERE datetime(created_at) >= datetime(?1)
+             GROUP BY day, provider, model
+             ORDER BY day, provider, model",
+        )?;
+        let llm_usage = stmt
+            .query_map(params![since], |row| {
+                Ok(DailyTokenUsage {
+                    date: row.get(0)?,
+                    usage: LlmTokenUsage {
+                        provider: row.get(1)?,
+                        model: row.get(2)?,
+                        calls: row.get::<_, i64>(3)? as u64,
+                        prompt_tokens: row.get::<_, i64>(4)? as u64,
+                        completion_tokens: row.get::<_, i64>(5)? as u64,
+                    },
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(DashboardStats {
+            daily: daily.into_values().collect(),
+            sources,
+            top_tags,
+            classifier: ClassifierStats {
+                classified,
+                accepted,
+                rejected: classified - accepted,
+                inbox_accepted,
+                inbox_rejected,
+                inbox_pending,
+            },
+            llm_usage,
+        })
+    }
+
+    /// Keep an LLM output that failed the guardrail checks, with the reason it was refused.
+    pub fn record_llm_rejection(&self, input: NewLlmRejection) -> Result<LlmRejection, StorageError> {
+        let conn = self.conn()?;
+        let id = Uuid::new_v4().to_string();
+        let created_at = Utc::now();
+        conn.execute(
+            r#"
+            INSERT INTO llm_rejections (id, prompt_id, task, model, reason, detail, output, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            "#,
+            params![
+                id,
+                input.prompt_id,
+                input.task,
+                input.model,
+                input.reason,
+                input.detail,
+                input.output,
+                created_at.to_rfc3339()
+            ],
+        )?;
+        Ok(LlmRejection {
+            id,
+            prompt_id: input.prompt_id,
+            task: input.task,
+            model: input.model,
+            reason: input.reason,
+            detail: input.detail,
+            output: input.output,
+            created_at,
+        })
+    }
+
+    /// Rejected outputs, newest first, optionally for one prompt.
+    pub fn list_llm_rejections(&self, prompt_id: Option<&str>, limit: usize) -> Result<Vec<LlmRejection>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, prompt_id, task, model, reason, detail, output, created_at
+             FROM llm_rejections
+             WHERE ?1 IS NULL OR prompt_id = ?1
+             ORDER BY datetime(created_at) DESC
+             LIMIT ?2",
+        )?;
+        let rejections = stmt
+            .query_map(params![prompt_id, limit as i64], |row| {
+                Ok(LlmRejection {
+                    id: row.get(0)?,
+                    prompt_id: row.get(1)?,
+                    task: row.get(2)?,
+                    model: row.get(3)?,
+                    reason: row.get(4)?,
+                    detail: row.get(5)?,
+                    output: row.get(6)?,
+                    created_at: parse_datetime(&row.get::<_, String>(7)?)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rejections)
+    }
+
+    /// Store the output of running a prompt against a model.
+    pub fn create_run(&self, input: NewRun) -> Result<Run, StorageError> {
+        let conn = self.conn()?;
+        let id = Uuid::new_v4().to_string();
+        let created_at = Utc::now();
+        conn.execute(
+            r#"
+            INSERT INTO runs (id, prompt_id, provider, model, params, variables, input, output,
+                              prompt_tokens, completion_tokens, latency_ms, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+            "#,
+            params![
+                id,
+                input.prompt_id,
+                input.provider,
+                input.model,
+                input.params.to_string(),
+                serde_json::to_string(&input.variables)?,
+                input.input,
+                input.output,
+                input.prompt_tokens.map(|tokens| tokens as i64),
+                input.completion_tokens.map(|tokens| tokens as i64),
+                input.latency_ms as i64,
+                created_at.to_rfc3339()
+            ],
+        )?;
+        Ok(Run {
+            id,
+            prompt_id: input.prompt_id,
+            provider: input.provider,
+            model: input.model,
+            params: input.params,
+            variables: input.variables,
+            input: input.input,
+            output: input.output,
+            prompt_tokens: input.prompt_tokens,
+            completion_tokens: input.completion_tokens,
+            latency_ms: input.latency_ms,
+            created_at,
+        })
+    }
+
+    /// Most recent runs of a prompt first.
+    pub fn list_runs_for_prompt(&self, prompt_id: &str, limit: usize) -> Result<Vec<Run>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, prompt_id, provider, model, params, variables, input, output,
+                    prompt_tokens, completion_tokens, latency_ms, created_at
+             FROM runs
+             WHERE prompt_id = ?1
+             ORDER BY datetime(created_at) DESC
+             LIMIT ?2",
+        )?;
+        let runs = stmt
+            .query_map(params![prompt_id, limit as i64], row_to_run)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(runs)
+    }
+
+    pub fn get_run(&self, id: &str) -> Result<Option<Run>, StorageError> {
+        let conn = self.conn()?;
+        let run = conn
+            .query_row(
+                "SELECT id, prompt_id, provider, model, params, variables, input, output,
+                        prompt_tokens, completion_tokens, latency_ms, created_at
+                 FROM runs WHERE id = ?1",
+                params![id],
+                row_to_run,
+            )
+            .optional()?;
+        Ok(run)
+    }
+
+    /// Store an A/B test and its paired runs.
+    pub fn create_ab_test(&self, input: NewAbTest) -> Result<AbTest, StorageError> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let id = Uuid::new_v4().to_string();
+        tx.execute(
+            "INSERT INTO ab_tests (id, prompt_a_id, prompt_b_id, model, relation_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                input.prompt_a_id,
+                input.prompt_b_id,
+                input.model,
+                input.relation_id,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        for (position, case) in input.cases.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO ab_test_cases (id, test_id, position, variables, run_a_id, run_b_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    Uuid::new_v4().to_string(),
+                    id,
+                    position as i64,
+                    serde_json::to_string(&case.variables)?,
+                    case.run_a_id,
+                    case.run_b_id
+                ],
+            )?;
+        }
+        tx.commit()?;
+        drop(conn);
+        self.get_ab_test(&id)?
+            .ok_or(StorageError::NotFound("ab test".into()))
+    }
+
+    pub fn get_ab_test(&self, id: &str) -> Result<Option<AbTest>, StorageError> {
+        let conn = self.conn()?;
+        let test = conn
+            .query_row(
+                "SELECT id, prompt_a_id, prompt_b_id, model, relation_id, created_at FROM ab_tests WHERE id = ?1",
+                params![id],
+                row_to_ab_test,
+            )
+            .optional()?;
+        let Some(mut test) = test else {
+            return Ok(None);
+        };
+        let mut stmt = conn.prepare(
+            "SELECT id, position, variables, run_a_id, run_b_id, preference, decided_at
+             FROM ab_test_cases
+             WHERE test_id = ?1
+             ORDER BY position",
+        )?;
+        test.cases = stmt
+            .query_map(params![id], row_to_ab_case)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(test))
+    }
+
+    /// A/B tests the prompt took part in, as either side, newest first.
+    pub fn list_ab_tests_for_prompt(&self, prompt_id: &str) -> Result<Vec<AbTest>, StorageError> {
+        let ids = {
+            let conn = self.conn()?;
+            let mut stmt = conn.prepare(
+                "SELECT id FROM ab_tests
+                 WHERE prompt_a_id = ?1 OR prompt_b_id = ?1
+                 ORDER BY datetime(created_at) DESC",
+            )?;
+            let ids = stmt
+                .query_map(params![prompt_id], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            ids
+        };
+        let mut tests = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(test) = self.get_ab_test(&id)? {
+                tests.push(test);
+            }
+        }
+        Ok(tests)
+    }
+
+    /// Record (or clear, with `None`) which output of a case the user preferred.
+    /// Returns the test id, or `None` when the case does not exist.
+    pub fn set_ab_preference(
+        &self,
+        case_id: &str,
+        preference: Option<AbPreference>,
+    ) -> Result<Option<String>, StorageError> {
+        let conn = self.conn()?;
+        let test_id = conn
+            .query_row(
+                "UPDATE ab_test_cases SET preference = ?2, decided_at = ?3 WHERE id = ?1 RETURNING test_id",
+                params![
+                    case_id,
+                    preference.map(|preference| preference.as_str()),
+                    preference.map(|_| Utc::now().to_rfc3339())
+                ],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(test_id)
+    }
+
+    pub fn set_ab_test_relation(&self, id: &str, relation_id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE ab_tests SET relation_id = ?2 WHERE id = ?1",
+            params![id, relation_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Replace the metadata of a relation, e.g. to refresh a summary kept there.
+    pub fn update_relation_metadata(&self, id: &str, metadata: &Value) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let updated = conn.execute(
+            "UPDATE prompt_relations SET metadata = ?2 WHERE id = ?1",
+            params![id, metadata.to_string()],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Store a binary attachment for a prompt.
+    pub fn add_attachment(&self, payload: NewAttachment) -> Result<Attachment, StorageError> {
+        let conn = self.conn()?;
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            r#"
+            INSERT INTO attachments (id, prompt_id, filename, bytes)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            params![id, payload.prompt_id, payload.filename, payload.bytes],
+        )?;
+        self.get_attachment(&id)?
+            .ok_or(StorageError::NotFound("attachment".into()))
+    }
+
+    /// Fetch attachment metadata + bytes.
+    pub fn get_attachment(&self, id: &str) -> Result<Option<Attachment>, StorageError> {
+        let conn = self.conn()?;
+        let attachment = conn
+            .query_row(
+                "SELECT id, prompt_id, filename, bytes FROM attachments WHERE id = ?1",
+                params![id],
+                |row| {
+                    let bytes: Vec<u8> = row.get(3)?;
+                    Ok(Attachment {
+                        id: row.get(0)?,
+                        prompt_id: row.get(1)?,
+                        filename: row.get(2)?,
+                        size: bytes.len() as i64,
+                        bytes,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(attachment)
+    }
+
+    /// Attachments of a prompt, metadata only: `bytes` is left empty.
+    pub fn list_attachments(&self, prompt_id: &str) -> Result<Vec<Attachment>, StorageError> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, prompt_id, filename, length(bytes) FROM attachments
+             WHERE prompt_id = ?1
+             ORDER BY rowid",
+        )?;
+        let rows = stmt
+            .query_map(params![prompt_id], |row| {
+                Ok(Attachment {
+                    id: row.get(0)?,
+                    prompt_id: row.get(1)?,
+                    filename: row.get(2)?,
+                    size: row.get(3)?,
+                    bytes: Vec::new(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// Remove attachment by id.
+    pub fn delete_attachment(&self, id: &str) -> Result<bool, StorageError> {
+        let conn = self.conn()?;
+        let affected = conn.execute("DELETE FROM attachments WHERE id = ?1", params![id])?;
+        Ok(affected > 0)
+    }
+}
+
+/// `term` escaped for `LIKE … ESCAPE '\'`, plus its fullwidth spellings (lowercase and
+/// uppercase, which `LIKE` does not fold) when it has ASCII letters or digits.
+fn like_spellings(term: &str) -> Vec<String> {
+    let escape = |text: &str| text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    let fullwidth = |text: String| -> String {
+        text.chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' => char::from_u32(c as u32 + 0xfee0).unwrap_or(c),
+                _ => c,
+            })
+            .collect()
+    };
+    let mut spellings = vec![escape(term)];
+    for spelling in [fullwidth(term.to_lowercase()), fullwidth(term.to_uppercase())] {
+        let spelling = escape(&spelling);
+        if !spellings.contains(&spelling) {
+            spellings.push(spelling);
+        }
+    }
+    spellings
+}
+
+fn row_to_prompt(row: &rusqlite::Row<'_>) -> rusqlite::Result<Prompt> {
+    Ok(Prompt {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        body: row.get(2)?,
+        language: row.get(3)?,
+        model_hint: row.get(4)?,
+        metadata: serde_json::from_str::<Value>(&row.get::<_, String>(5)?).unwrap_or(Value::Null),
+        created_at: parse_datetime(&row.get::<_, String>(6)?)?,
+        updated_at: parse_datetime(&row.get::<_, String>(7)?)?,
+    })
+}
+
+fn row_to_analysis(row: &rusqlite::Row<'_>) -> rusqlite::Result<Analysis> {
+    Ok(Analysis {
+        id: row.get(0)?,
+        prompt_id: row.get(1)?,
         summary: row.get(2)?,
         tags: serde_json::from_str::<Vec<String>>(&row.get::<_, String>(3)?).unwrap_or_default(),
         classification: serde_json::from_str::<Value>(&row.get::<_, String>(4)?).unwrap_or(Value::Null),
         qwen_model: row.get(5)?,
         created_at: parse_datetime(&row.get::<_, String>(6)?)?,
+        analyzer_version: row.get(7)?,
+    })
+}
+
+fn row_to_relation(row: &rusqlite::Row<'_>) -> rusqlite::Result<PromptRelation> {
+    Ok(PromptRelation {
+        id: row.get(0)?,
+        source_id: row.get(1)?,
+        target_id: row.get(2)?,
+        relation: row.get(3)?,
+        metadata: serde_json::from_str::<Value>(&row.get::<_, String>(4)?).unwrap_or(Value::Null),
+        created_at: parse_datetime(&row.get::<_, String>(5)?)?,
+    })
+}
+
+fn row_to_job(row: &rusqlite::Row<'_>) -> rusqlite::Result<Job> {
+    let status = row.get::<_, String>(4)?;
+    Ok(Job {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        prompt_id: row.get(2)?,
+        payload: serde_json::from_str::<Value>(&row.get::<_, String>(3)?).unwrap_or(Value::Null),
+        status: JobStatus::parse(&status).unwrap_or(JobStatus::Failed),
+        attempts: row.get(5)?,
+        max_attempts: row.get(6)?,
+        last_error: row.get(7)?,
+        created_at: parse_datetime(&row.get::<_, String>(8)?)?,
+        updated_at: parse_datetime(&row.get::<_, String>(9)?)?,
+    })
+}
+
+fn row_to_classification(row: &rusqlite::Row<'_>) -> rusqlite::Result<Classification> {
+    Ok(Classification {
+        id: row.get(0)?,
+        prompt_id: row.get(1)?,
+        source: row.get(2)?,
+        is_prompt: row.get(3)?,
+        confidence: row.get(4)?,
+        model: row.get(5)?,
+        created_at: parse_datetime(&row.get::<_, String>(6)?)?,
+    })
+}
+
+fn row_to_classifier_example(row: &rusqlite::Row<'_>) -> rusqlite::Result<ClassifierExample> {
+    Ok(ClassifierExample {
+        id: row.get(0)?,
+        text: row.get(1)?,
+        is_prompt: row.get(2)?,
+        prompt_id: row.get(3)?,
+        created_at: parse_datetime(&row.get::<_, String>(4)?)?,
+        updated_at: parse_datetime(&row.get::<_, String>(5)?)?,
+    })
+}
+
+fn row_to_merge_suggestion(row: &rusqlite::Row<'_>) -> rusqlite::Result<MergeSuggestion> {
+    let status: String = row.get(7)?;
+    Ok(MergeSuggestion {
+        id: row.get(0)?,
+        prompt_ids: serde_json::from_str(&row.get::<_, String>(1)?).unwrap_or_default(),
+        similarity: row.get(2)?,
+        same_prompt: row.get(3)?,
+        merged_body: row.get(4)?,
+        reason: row.get(5)?,
+        model: row.get(6)?,
+        status: MergeStatus::parse(&status).unwrap_or(MergeStatus::Pending),
+        merged_prompt_id: row.get(8)?,
+        created_at: parse_datetime(&row.get::<_, String>(9)?)?,
+        updated_at: parse_datetime(&row.get::<_, String>(10)?)?,
+    })
+}
+
+fn row_to_capture(row: &rusqlite::Row<'_>) -> rusqlite::Result<Capture> {
+    let status: String = row.get(3)?;
+    Ok(Capture {
+        id: row.get(0)?,
+        text: row.get(1)?,
+        source: row.get(2)?,
+        status: CaptureStatus::parse(&status).unwrap_or(CaptureStatus::Pending),
+        prompt_id: row.get(4)?,
+        created_at: parse_datetime(&row.get::<_, String>(5)?)?,
+        updated_at: parse_datetime(&row.get::<_, String>(6)?)?,
+        similar_prompt_id: row.get(7)?,
+        similarity: row.get(8)?,
+    })
+}
+
+fn row_to_version(row: &rusqlite::Row<'_>) -> rusqlite::Result<PromptVersion> {
+    Ok(PromptVersion {
+        id: row.get(0)?,
+        prompt_id: row.get(1)?,
+        title: row.get(2)?,
+        body: row.get(3)?,
+        source: row.get(4)?,
+        replaced_at: parse_datetime(&row.get::<_, String>(5)?)?,
+    })
+}
+
+/// Identity of a group regardless of member order.
+fn merge_group_key(prompt_ids: &[String]) -> String {
+    let mut ids: Vec<&str> = prompt_ids.iter().map(String::as_str).collect();
+    ids.sort_unstable();
+    ids.join(",")
+}
+
+fn row_to_run(row: &rusqlite::Row<'_>) -> rusqlite::Result<Run> {
+    Ok(Run {
+        id: row.get(0)?,
+        prompt_id: row.get(1)?,
+        provider: row.get(2)?,
+        model: row.get(3)?,
+        params: serde_json::from_str::<Value>(&row.get::<_, String>(4)?).unwrap_or(Value::Null),
+        variables: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+        input: row.get(6)?,
+        output: row.get(7)?,
+        prompt_tokens: row.get::<_, Option<i64>>(8)?.map(|tokens| tokens as u64),
+        completion_tokens: row.get::<_, Option<i64>>(9)?.map(|tokens| tokens as u64),
+        latency_ms: row.get::<_, i64>(10)? as u64,
+        created_at: parse_datetime(&row.get::<_, String>(11)?)?,
+    })
+}
+
+fn row_to_ab_test(row: &rusqlite::Row<'_>) -> rusqlite::Result<AbTest> {
+    Ok(AbTest {
+        id: row.get(0)?,
+        prompt_a_id: row.get(1)?,
+        prompt_b_id: row.get(2)?,
+        model: row.get(3)?,
+        relation_id: row.get(4)?,
+        created_at: parse_datetime(&row.get::<_, String>(5)?)?,
+        cases: Vec::new(),
+    })
+}
+
+fn row_to_ab_case(row: &rusqlite::Row<'_>) -> rusqlite::Result<AbCase> {
+    let preference = row.get::<_, Option<String>>(5)?;
+    let decided_at = row.get::<_, Option<String>>(6)?;
+    Ok(AbCase {
+        id: row.get(0)?,
+        position: row.get::<_, i64>(1)? as usize,
+        variables: serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+        run_a_id: row.get(3)?,
+        run_b_id: row.get(4)?,
+        preference: preference.as_deref().and_then(AbPreference::parse),
+        decided_at: decided_at.as_deref().map(parse_datetime).transpose()?,
+    })
+}
+
+/// Fill in `prompts.content_hash` for rows written before the column existed.
+/// Insert a prompt row; returns its id.
+fn insert_prompt(conn: &rusqlite::Connection, data: &NewPrompt) -> Result<String, StorageError> {
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now();
+    conn.execute(
+        r#"
+        INSERT INTO prompts (id, title, body, language, model_hint, metadata, created_at, updated_at, content_hash)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![
+            id,
+            data.title,
+            data.body,
+            data.language,
+            data.model_hint,
+            data.metadata.to_string(),
+            now.to_rfc3339(),
+            now.to_rfc3339(),
+            content_hash(&data.body)
+        ],
+    )?;
+    Ok(id)
+}
+
+fn insert_relation(conn: &rusqlite::Connection, input: NewPromptRelation) -> Result<PromptRelation, StorageError> {
+    let id = Uuid::new_v4().to_string();
+    let created_at = Utc::now();
+    conn.execute(
+        r#"
+        INSERT INTO prompt_relations (id, source_id, target_id, relation, metadata, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        "#,
+        params![
+            id,
+            input.source_id,
+            input.target_id,
+            input.relation,
+            input.metadata.to_string(),
+            created_at.to_rfc3339()
+        ],
+    )?;
+    Ok(PromptRelation {
+        id,
+        source_id: input.source_id,
+        target_id: input.target_id,
+        relation: input.relation,
+        metadata: input.metadata,
+        created_at,
     })
 }
 
+fn backfill_content_hashes(conn: &rusqlite::Connection) -> Result<(), StorageError> {
+    let mut stmt = conn.prepare("SELECT id, body FROM prompts WHERE content_hash IS NULL")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let tx = conn.unchecked_transaction()?;
+    for (id, body) in rows {
+        tx.execute(
+            "UPDATE prompts SET content_hash = ?2 WHERE id = ?1",
+            params![id, content_hash(&body)],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Add a column to an existing table when upgrading databases created by older builds.
+fn ensure_column(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), StorageError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"))?;
+    }
+    Ok(())
+}
+
 fn parse_datetime(value: &str) -> rusqlite::Result<DateTime<Utc>> {
     DateTime::parse_from_rfc3339(value)
         .map(|dt| dt.with_timezone(&Utc))
@@ -428,6 +2962,8 @@ pub struct Analysis {
     pub classification: Value,
     pub qwen_model: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Version of the local analyzer that produced this row (`None` for legacy/external rows).
+    pub analyzer_version: Option<u32>,
 }
 
 #[derive(Debug, Clone)]
@@ -437,6 +2973,7 @@ pub struct NewAnalysis {
     pub tags: Vec<String>,
     pub classification: Value,
     pub qwen_model: Option<String>,
+    pub analyzer_version: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -444,6 +2981,8 @@ pub struct Attachment {
     pub id: String,
     pub prompt_id: String,
     pub filename: String,
+    /// Length of `bytes`, which listings leave empty.
+    pub size: i64,
     #[serde(skip_serializing)]
     pub bytes: Vec<u8>,
 }
@@ -455,6 +2994,565 @@ pub struct NewAttachment {
     pub bytes: Vec<u8>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptRelation {
+    pub id: String,
+    pub source_id: String,
+    pub target_id: String,
+    /// Relation kind, e.g. `chain_step`, `next_step`.
+    pub relation: String,
+    pub metadata: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewPromptRelation {
+    pub source_id: String,
+    pub target_id: String,
+    pub relation: String,
+    pub metadata: Value,
+}
+
+/// A prompt with the rubric score from its latest analysis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedPrompt {
+    pub prompt: Prompt,
+    /// Mean of the rubric scores, 1.0 – 5.0.
+    pub quality_score: f64,
+}
+
+/// How much a vocabulary term is actually used across the library.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermUsage {
+    pub term: String,
+    /// Prompts whose body mentions the term or one of its aliases.
+    pub mentions: usize,
+    /// Prompts whose latest analysis lists the term among its tags.
+    pub tagged: usize,
+}
+
+/// How often a prompt was used, counted by [`Storage::record_prompt_use`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptUsage {
+    pub prompt_id: String,
+    pub use_count: u64,
+    pub last_used_at: DateTime<Utc>,
+}
+
+/// What [`Storage::merge_prompts`] folded into the primary prompt.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeReport {
+    pub primary_id: String,
+    /// Duplicates merged and moved to the trash.
+    pub merged_ids: Vec<String>,
+    /// Duplicates that were unknown or already trashed.
+    pub skipped_ids: Vec<String>,
+    /// Analyses and attachments moved to the primary.
+    pub analyses: usize,
+    pub attachments: usize,
+    /// Tags the primary's latest analysis gained.
+    pub tags_added: Vec<String>,
+    /// Duplicates whose notes were appended to the primary's.
+    pub notes_merged: usize,
+    /// The primary's use count after the merge.
+    pub use_count: u64,
+}
+
+/// A tag and how many prompts' latest analyses carry it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// A process prompts were copied from (`metadata.source_app.process`) and how many.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceAppCount {
+    pub process: String,
+    pub count: usize,
+}
+
+/// A collection prompts were put in (`metadata.collection`) and how many.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionCount {
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewLlmCall {
+    /// What the call was for, e.g. `classify`, `optimize`, `embed`.
+    pub task: String,
+    pub prompt_id: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    /// Duration of the final attempt.
+    pub latency_ms: u64,
+    pub attempts: u32,
+    /// `None` when the call succeeded.
+    pub error: Option<String>,
+}
+
+/// Token totals of one provider/model pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmTokenUsage {
+    pub provider: String,
+    pub model: String,
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// What [`Storage::dashboard_stats`] found for the statistics page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardStats {
+    /// Days with activity, oldest first.
+    pub daily: Vec<DailyActivity>,
+    /// Most common first.
+    pub sources: Vec<SourceCount>,
+    pub top_tags: Vec<TagCount>,
+    pub classifier: ClassifierStats,
+    /// Oldest day first.
+    pub llm_usage: Vec<DailyTokenUsage>,
+}
+
+/// Prompts saved and texts held for review on one local calendar day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyActivity {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub prompts: u64,
+    /// Texts held in the inbox.
+    pub captures: u64,
+}
+
+impl DailyActivity {
+    pub fn new(date: impl Into<String>) -> Self {
+        Self {
+            date: date.into(),
+            prompts: 0,
+            captures: 0,
+        }
+    }
+}
+
+/// Where prompts came from (`metadata.source`, e.g. `clipboard`, `hotkey`, `import`) and
+/// how many; `unknown` when it was not recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceCount {
+    pub source: String,
+    pub count: usize,
+}
+
+/// Verdicts of the `is_prompt` classifier and of the user reviewing the inbox.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClassifierStats {
+    pub classified: u64,
+    /// Classifications that found a prompt.
+    pub accepted: u64,
+    pub rejected: u64,
+    pub inbox_accepted: u64,
+    pub inbox_rejected: u64,
+    pub inbox_pending: u64,
+}
+
+/// Token totals of one provider/model pair on one local calendar day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyTokenUsage {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    #[serde(flatten)]
+    pub usage: LlmTokenUsage,
+}
+
+/// Usage totals of one task/model pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmUsageMetrics {
+    pub task: String,
+    pub model: String,
+    pub calls: u64,
+    pub failures: u64,
+    /// Attempts including retries.
+    pub attempts: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub avg_latency_ms: f64,
+    pub max_latency_ms: u64,
+}
+
+/// The LLM's verdict on whether a captured text is a reusable prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Classification {
+    pub id: String,
+    pub prompt_id: String,
+    /// What queued the classification: `clipboard`, `job`, ...
+    pub source: String,
+    pub is_prompt: bool,
+    /// The model's confidence in `is_prompt`, 0.0 – 1.0.
+    pub confidence: f64,
+    pub model: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Classification {
+    /// Predicted probability that the text is a prompt, whichever way the verdict went.
+    pub fn prompt_score(&self) -> f64 {
+        if self.is_prompt {
+            self.confidence
+        } else {
+            1.0 - self.confidence
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewClassification {
+    pub prompt_id: String,
+    pub source: String,
+    pub is_prompt: bool,
+    pub confidence: f64,
+    pub model: String,
+}
+
+/// A user-confirmed text and label shown to the `is_prompt` classifier as a few-shot example.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassifierExample {
+    pub id: String,
+    pub text: String,
+    pub is_prompt: bool,
+    /// The capture the example was taken from; cleared when that prompt is deleted.
+    pub prompt_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewClassifierExample {
+    pub text: String,
+    pub is_prompt: bool,
+    pub prompt_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+impl MergeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeStatus::Pending => "pending",
+            MergeStatus::Accepted => "accepted",
+            MergeStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(MergeStatus::Pending),
+            "accepted" => Some(MergeStatus::Accepted),
+            "rejected" => Some(MergeStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// A group of near-duplicate prompts proposed for merging into one canonical prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeSuggestion {
+    pub id: String,
+    pub prompt_ids: Vec<String>,
+    /// Text similarity found locally, 0.0 – 1.0.
+    pub similarity: f64,
+    /// The LLM's verdict on whether the prompts mean the same; `None` until it was asked.
+    pub same_prompt: Option<bool>,
+    /// The canonical version proposed by the LLM.
+    pub merged_body: Option<String>,
+    pub reason: Option<String>,
+    pub model: Option<String>,
+    pub status: MergeStatus,
+    /// The prompt created when the suggestion was accepted.
+    pub merged_prompt_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewMergeSuggestion {
+    pub prompt_ids: Vec<String>,
+    pub similarity: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeVerdict {
+    pub same_prompt: bool,
+    pub merged_body: Option<String>,
+    pub reason: Option<String>,
+    pub model: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+impl CaptureStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaptureStatus::Pending => "pending",
+            CaptureStatus::Accepted => "accepted",
+            CaptureStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(CaptureStatus::Pending),
+            "accepted" => Some(CaptureStatus::Accepted),
+            "rejected" => Some(CaptureStatus::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// A clipboard text held for review instead of being saved as a prompt right away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Capture {
+    pub id: String,
+    pub text: String,
+    /// Where the text came from, e.g. `clipboard`.
+    pub source: String,
+    pub status: CaptureStatus,
+    /// The prompt created when the capture was accepted, or the one it became a new version of.
+    pub prompt_id: Option<String>,
+    /// An existing prompt the text nearly repeats, which accepting may update instead.
+    pub similar_prompt_id: Option<String>,
+    pub similarity: Option<f64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Title and body a prompt had before a new version replaced them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptVersion {
+    pub id: String,
+    pub prompt_id: String,
+    pub title: String,
+    pub body: String,
+    /// What brought the replacing version, e.g. `capture`.
+    pub source: String,
+    pub replaced_at: DateTime<Utc>,
+}
+
+/// An optimize/translate output that was refused instead of saved as a prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmRejection {
+    pub id: String,
+    /// The prompt that was being rewritten.
+    pub prompt_id: String,
+    pub task: String,
+    pub model: String,
+    /// `empty`, `truncated`, `leaked_instructions` or `length_blowup`.
+    pub reason: String,
+    pub detail: String,
+    /// The raw text the model returned.
+    pub output: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewLlmRejection {
+    pub prompt_id: String,
+    pub task: String,
+    pub model: String,
+    pub reason: String,
+    pub detail: String,
+    pub output: String,
+}
+
+/// One execution of a prompt in the playground.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub id: String,
+    pub prompt_id: String,
+    pub provider: String,
+    pub model: String,
+    /// Sampling parameters the run used (temperature, max tokens, system prompt).
+    pub params: Value,
+    pub variables: BTreeMap<String, String>,
+    /// The prompt body after template variables were filled in.
+    pub input: String,
+    pub output: String,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub latency_ms: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewRun {
+    pub prompt_id: String,
+    pub provider: String,
+    pub model: String,
+    pub params: Value,
+    pub variables: BTreeMap<String, String>,
+    pub input: String,
+    pub output: String,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+    pub latency_ms: u64,
+}
+
+/// Two prompts run over the same inputs with the same model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbTest {
+    pub id: String,
+    pub prompt_a_id: String,
+    pub prompt_b_id: String,
+    pub model: String,
+    /// `ab_test` relation between the prompts that carries the win-rate summary.
+    pub relation_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub cases: Vec<AbCase>,
+}
+
+/// One input of an A/B test with the run of each prompt on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbCase {
+    pub id: String,
+    pub position: usize,
+    pub variables: BTreeMap<String, String>,
+    pub run_a_id: String,
+    pub run_b_id: String,
+    pub preference: Option<AbPreference>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAbTest {
+    pub prompt_a_id: String,
+    pub prompt_b_id: String,
+    pub model: String,
+    pub relation_id: Option<String>,
+    pub cases: Vec<NewAbCase>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAbCase {
+    pub variables: BTreeMap<String, String>,
+    pub run_a_id: String,
+    pub run_b_id: String,
+}
+
+/// Which output of an A/B case the user preferred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AbPreference {
+    A,
+    B,
+    Tie,
+}
+
+impl AbPreference {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AbPreference::A => "a",
+            AbPreference::B => "b",
+            AbPreference::Tie => "tie",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "a" => Some(AbPreference::A),
+            "b" => Some(AbPreference::B),
+            "tie" => Some(AbPreference::Tie),
+            _ => None,
+        }
+    }
+}
+
+/// Embedding vector of a prompt body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptEmbedding {
+    pub prompt_id: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    /// Set aside while the backend is unreachable; queued again once it answers.
+    Waiting,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Pending => "pending",
+            JobStatus::Running => "running",
+            JobStatus::Waiting => "waiting",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(JobStatus::Pending),
+            "running" => Some(JobStatus::Running),
+            "waiting" => Some(JobStatus::Waiting),
+            "done" => Some(JobStatus::Done),
+            "failed" => Some(JobStatus::Failed),
+            "cancelled" => Some(JobStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+/// A persisted background task (e.g. LLM classification of one prompt).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    /// Job type, e.g. `classify`.
+    pub kind: String,
+    pub prompt_id: Option<String>,
+    pub payload: Value,
+    pub status: JobStatus,
+    /// Attempts started so far, including the running one.
+    pub attempts: u32,
+    pub max_attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewJob {
+    pub kind: String,
+    pub prompt_id: Option<String>,
+    pub payload: Value,
+    pub max_attempts: u32,
+}
+
 #[derive(Debug, Error)]
 pub enum StorageError {
     #[error("record not found: {0}")]
@@ -468,3 +3566,56 @@ pub enum StorageError {
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_storage() -> Storage {
+        let path = std::env::temp_dir().join(format!("promptlab-test-{}.db", Uuid::new_v4()));
+        Storage::new(path).unwrap()
+    }
+
+    fn tagged_prompt(storage: &Storage, body: &str, tag: &str) -> Prompt {
+        let prompt = storage.create_prompt(NewPrompt::new(body, body)).unwrap();
+        storage
+            .create_analysis(NewAnalysis {
+                prompt_id: prompt.id.clone(),
+                summary: String::new(),
+                tags: vec![tag.to_string()],
+                classification: Value::Object(Default::default()),
+                qwen_model: None,
+                analyzer_version: None,
+            })
+            .unwrap();
+        prompt
+    }
+
+    #[test]
+    fn quarantined_prompts_are_left_out_of_tag_stats_classification_and_embedding() {
+        let storage = temp_storage();
+        let visible = tagged_prompt(&storage, "visible prompt", "kept");
+        let quarantined = tagged_prompt(&storage, "quarantined prompt", "hidden");
+        assert!(storage.quarantine_prompt(&quarantined.id).unwrap());
+
+        let tags: Vec<String> = storage
+            .list_tag_counts()
+            .unwrap()
+            .into_iter()
+            .map(|count| count.tag)
+            .collect();
+        assert_eq!(tags, ["kept"]);
+        let latest = storage.list_latest_tags().unwrap();
+        assert!(latest.contains_key(&visible.id));
+        assert!(!latest.contains_key(&quarantined.id));
+        let ids = |prompts: Vec<Prompt>| prompts.into_iter().map(|prompt| prompt.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(storage.list_unclassified_prompts("classify", 10).unwrap()),
+            [visible.id.as_str()]
+        );
+        assert_eq!(
+            ids(storage.list_prompts_missing_embedding("model").unwrap()),
+            [visible.id.as_str()]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::tokens::normalize_token;
+use crate::storage::TagCount;
+use crate::text::normalize_text;
+
+/// Tags that probably mean the same thing, proposed for merging into `canonical`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCluster {
+    /// The most used spelling.
+    pub canonical: String,
+    /// Every tag in the cluster, `canonical` included, most used first.
+    pub members: Vec<TagCount>,
+    /// Whether an LLM judgment (rather than spelling alone) linked some of the members.
+    pub llm_suggested: bool,
+}
+
+/// An approved merge: every alias is renamed to `canonical`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagMerge {
+    pub canonical: String,
+    pub aliases: Vec<String>,
+}
+
+/// Thresholds for [`cluster_tags`].
+#[derive(Debug, Clone)]
+pub struct TagClusterOptions {
+    /// Tags whose keys are at least this similar (1 − edit distance / longer length) are linked.
+    pub min_similarity: f64,
+    /// Shorter keys only match exactly; "ai" and "ui" are one edit apart.
+    pub min_fuzzy_chars: usize,
+}
+
+impl Default for TagClusterOptions {
+    fn default() -> Self {
+        Self {
+            min_similarity: 0.8,
+            min_fuzzy_chars: 5,
+        }
+    }
+}
+
+/// Comparison key of a tag: normalized, ASCII lowercased, without spaces, `-`, `_` or `.`,
+/// so "Chat GPT", "chat-gpt" and "ＣｈａｔＧＰＴ" share one key.
+pub fn tag_key(tag: &str) -> String {
+    normalize_token(normalize_text(tag).trim())
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '_' | '.'))
+        .collect()
+}
+
+/// Group tags that differ only in case, width or separators, or that are near-identical
+/// spellings. `llm_groups` are extra synonym sets judged by a model (e.g. "LLM" and
+/// "大模型"); names not in `tags` are ignored. Only clusters of two or more tags are
+/// returned, largest total usage first.
+pub fn cluster_tags(tags: &[TagCount], llm_groups: &[Vec<String>], options: &TagClusterOptions) -> Vec<TagCluster> {
+    let index: HashMap<&str, usize> = tags
+        .iter()
+        .enumerate()
+        .map(|(position, tag)| (tag.tag.as_str(), position))
+        .collect();
+    let keys: Vec<Vec<char>> = tags.iter().map(|tag| tag_key(&tag.tag).chars().collect()).collect();
+    let mut parent: Vec<usize> = (0..tags.len()).collect();
+
+    for a in 0..tags.len() {
+        for b in (a + 1)..tags.len() {
+            if keys[a].is_empty() || keys[b].is_empty() {
+                continue;
+            }
+            let similar = keys[a] == keys[b]
+                || (keys[a].len().min(keys[b].len()) >= options.min_fuzzy_chars
+                    && similarity(&keys[a], &keys[b]) >= options.min_similarity);
+            if similar {
+                union(&mut parent, a, b);
+            }
+        }
+    }
+
+    let mut llm_linked = HashSet::new();
+    for group in llm_groups {
+        let members: Vec<usize> = group
+            .iter()
+            .filter_map(|name| index.get(name.trim()).copied())
+            .collect();
+        for pair in members.windows(2) {
+            if find(&mut parent, pair[0]) != find(&mut parent, pair[1]) {
+                union(&mut parent, pair[0], pair[1]);
+                llm_linked.insert(pair[0]);
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for position in 0..tags.len() {
+        let root = find(&mut parent, position);
+        groups.entry(root).or_default().push(position);
+    }
+    let mut clusters: Vec<TagCluster> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let llm_suggested = members.iter().any(|position| llm_linked.contains(position));
+            let mut members: Vec<TagCount> = members.into_iter().map(|position| tags[position].clone()).collect();
+            members.sort_by(|a, b| {
+                b.count
+                    .cmp(&a.count)
+                    .then_with(|| a.tag.chars().count().cmp(&b.tag.chars().count()))
+                    .then_with(|| a.tag.cmp(&b.tag))
+            });
+            TagCluster {
+                canonical: members[0].tag.clone(),
+                members,
+                llm_suggested,
+            }
+        })
+        .collect();
+    let usage = |cluster: &TagCluster| cluster.members.iter().map(|member| member.count).sum::<usize>();
+    clusters.sort_by(|a, b| usage(b).cmp(&usage(a)).then_with(|| a.canonical.cmp(&b.canonical)));
+    clusters
+}
+
+/// Rename aliases to their canonical tag, dropping duplicates while keeping order.
+pub fn apply_tag_merges(tags: &[String], renames: &HashMap<String, String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.iter()
+        .map(|tag| renames.get(tag).unwrap_or(tag))
+        .filter(|tag| seen.insert(tag.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Alias → canonical lookup for [`apply_tag_merges`]; blank names and self-renames are skipped.
+pub fn merge_renames(merges: &[TagMerge]) -> HashMap<String, String> {
+    merges
+        .iter()
+        .filter(|merge| !merge.canonical.trim().is_empty())
+        .flat_map(|merge| {
+            let canonical = merge.canonical.trim();
+            merge
+                .aliases
+                .iter()
+                .map(|alias| alias.trim())
+                .filter(move |alias| !alias.is_empty() && *alias != canonical)
+                .map(move |alias| (alias.to_string(), canonical.to_string()))
+        })
+        .collect()
+}
+
+fn similarity(a: &[char], b: &[char]) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - edit_distance(a, b) as f64 / longest as f64
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Union-find root of `node`, compressing the path; shared with [`crate::dedup`].
+pub(crate) fn find(parent: &mut [usize], node: usize) -> usize {
+    let mut root = node;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut node = node;
+    while parent[node] != root {
+        let next = parent[node];
+        parent[node] = root;
+        node = next;
+    }
+    root
+}
+
+pub(crate) fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[a.max(b)] = a.min(b);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::{BTreeMap, HashSet};
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::Serialize;
+use thiserror::Error;
+
+/// `{{name}}` or `{name}`; names are letters (CJK included), digits, `_`, `-` and `.`,
+/// so JSON snippets such as `{"a": 1}` are left alone.
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\{\{\s*([\p{L}\p{N}_.\-]+)\s*\}\}|\{([\p{L}\p{N}_.\-]+)\}").expect("valid placeholder pattern")
+});
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("missing template variables: {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
+}
+
+fn placeholder_name<'a>(captures: &Captures<'a>) -> &'a str {
+    captures
+        .get(1)
+        .or_else(|| captures.get(2))
+        .map(|name| name.as_str())
+        .unwrap_or_default()
+}
+
+/// `body` with every placeholder replaced by `{}`, so templates that differ only in
+/// variable names compare equal.
+pub fn mask_placeholders(body: &str) -> String {
+    PLACEHOLDER.replace_all(body, "{}").into_owned()
+}
+
+/// Placeholder names in order of first appearance, without duplicates.
+pub fn template_variables(body: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    PLACEHOLDER
+        .captures_iter(body)
+        .map(|captures| placeholder_name(&captures).to_string())
+        .filter(|name| seen.insert(name.clone()))
+        .collect()
+}
+
+/// Substitute every placeholder. All variables must be provided; extra ones are ignored.
+pub fn render_template(body: &str, variables: &BTreeMap<String, String>) -> Result<String, TemplateError> {
+    let missing: Vec<String> = template_variables(body)
+        .into_iter()
+        .filter(|name| !variables.contains_key(name))
+        .collect();
+    if !missing.is_empty() {
+        return Err(TemplateError::MissingVariables(missing));
+    }
+    let rendered = PLACEHOLDER.replace_all(body, |captures: &Captures<'_>| {
+        variables[placeholder_name(captures)].clone()
+    });
+    Ok(rendered.into_owned())
+}
+
+/// Render `body` for one input set. A prompt without placeholders gets the `input`
+/// variable, when given, appended as its own paragraph, so plain prompts can be run over
+/// test inputs too.
+pub fn fill_prompt(body: &str, variables: &BTreeMap<String, String>) -> Result<String, TemplateError> {
+    if template_variables(body).is_empty() {
+        return Ok(match variables.get("input").map(|input| input.trim()) {
+            Some(input) if !input.is_empty() => format!("{}\n\n{input}", body.trim_end()),
+            _ => body.to_string(),
+        });
+    }
+    render_template(body, variables)
+}
+
+/// A prompt rendered as far as the given variables allow, for previews before filling it in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplatePreview {
+    /// The body with every provided variable substituted; placeholders without a value are left
+    /// as written.
+    pub text: String,
+    /// Placeholders without a value, in order of first appearance.
+    pub missing: Vec<String>,
+    /// Provided variables the body does not use, sorted.
+    pub unused: Vec<String>,
+}
+
+/// Render `body` like [`fill_prompt`], without failing on missing variables. `input` counts as
+/// used when it is appended to a prompt without placeholders.
+pub fn preview_template(body: &str, variables: &BTreeMap<String, String>) -> TemplatePreview {
+    let names = template_variables(body);
+    if names.is_empty() {
+        let text = fill_prompt(body, variables).unwrap_or_else(|_| body.to_string());
+        let appended = text != body;
+        return TemplatePreview {
+            text,
+            missing: Vec::new(),
+            unused: variables
+                .keys()
+                .filter(|name| !(appended && name.as_str() == "input"))
+                .cloned()
+                .collect(),
+        };
+    }
+    let text = PLACEHOLDER.replace_all(body, |captures: &Captures<'_>| {
+        variables
+            .get(placeholder_name(captures))
+            .cloned()
+            .unwrap_or_else(|| captures[0].to_string())
+    });
+    TemplatePreview {
+        text: text.into_owned(),
+        missing: names.iter().filter(|name| !variables.contains_key(*name)).cloned().collect(),
+        unused: variables.keys().filter(|name| !names.contains(name)).cloned().collect(),
+    }
+}
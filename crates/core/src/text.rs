@@ -0,0 +1,79 @@
+use unicode_normalization::UnicodeNormalization;
+
+use crate::template::mask_placeholders;
+
+/// Canonicalize text pasted from chat apps and web pages before it is analyzed, hashed,
+/// or matched against the vocabulary:
+///
+/// - NFC composition, so visually identical strings compare equal;
+/// - fullwidth ASCII letters/digits/symbols folded to halfwidth (CJK punctuation such as
+///   `，：；！？（）` is kept, since the analyzer relies on it);
+/// - smart quotes and non-breaking/ideographic spaces replaced with plain ASCII;
+/// - zero-width characters, variation selectors and emoji removed;
+/// - `\r\n` / `\r` line endings unified to `\n`.
+pub fn normalize_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.nfc().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\r' {
+            if chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            out.push('\n');
+            continue;
+        }
+        if is_invisible(c) || is_emoji(c) {
+            continue;
+        }
+        out.push(fold_char(c));
+    }
+    out
+}
+
+/// Identity of a text regardless of formatting: [`normalize_text`], template placeholders
+/// masked and whitespace runs collapsed, so re-copies with trailing newlines, smart quotes or
+/// renamed variables hash the same. 16 hex digits of FNV-1a, stable across processes.
+pub fn content_hash(text: &str) -> String {
+    let masked = mask_placeholders(&normalize_text(text));
+    let collapsed = masked.split_whitespace().collect::<Vec<_>>().join(" ");
+    let hash = collapsed.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// Fold a single character: fullwidth ASCII to halfwidth and typographic quotes/spaces to
+/// their plain ASCII counterparts. Other characters are returned unchanged.
+pub fn fold_char(c: char) -> char {
+    match c {
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{2033}' => '"',
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2032}' => '\'',
+        '\u{00a0}' | '\u{2002}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}' => ' ',
+        // Keep CJK sentence punctuation in its fullwidth form.
+        '\u{ff01}' | '\u{ff08}' | '\u{ff09}' | '\u{ff0c}' | '\u{ff1a}' | '\u{ff1b}' | '\u{ff1f}' => c,
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(c as u32 - 0xfee0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200b}'..='\u{200d}' // zero-width space / non-joiner / joiner
+            | '\u{2060}'..='\u{2064}' // word joiner, invisible operators
+            | '\u{feff}' // BOM / zero-width no-break space
+            | '\u{00ad}' // soft hyphen
+            | '\u{fe00}'..='\u{fe0f}' // variation selectors
+            | '\u{e0020}'..='\u{e007f}' // emoji tag sequences
+    )
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1f000}'..='\u{1faff}' // emoticons, pictographs, transport, flags, skin tones, ...
+            | '\u{2600}'..='\u{27bf}' // misc symbols and dingbats
+            | '\u{2b00}'..='\u{2bff}' // arrows/stars commonly rendered as emoji
+            | '\u{20e3}' // combining enclosing keycap
+    )
+}
@@ -0,0 +1,103 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::tokens::{candidate_terms, normalize_token, KeywordOptions};
+use crate::analysis::VocabularyEntry;
+use crate::text::normalize_text;
+
+/// A frequent term from the library that is not in the vocabulary yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VocabularySuggestion {
+    pub term: String,
+    /// Number of prompts containing the term.
+    pub documents: usize,
+    /// Total occurrences across all prompts.
+    pub occurrences: usize,
+}
+
+/// Thresholds for [`suggest_vocabulary_terms`].
+#[derive(Debug, Clone)]
+pub struct SuggestionOptions {
+    /// A term must appear in at least this many prompts.
+    pub min_documents: usize,
+    /// Ignore terms found in more than this share of prompts (generic words like "内容"),
+    /// once the library has at least 20 prompts.
+    pub max_document_ratio: f64,
+    pub limit: usize,
+    /// Terms never to suggest again (rejected suggestions, suppressed terms).
+    pub excluded: Vec<String>,
+}
+
+impl Default for SuggestionOptions {
+    fn default() -> Self {
+        Self {
+            min_documents: 3,
+            max_document_ratio: 0.6,
+            limit: 30,
+            excluded: Vec::new(),
+        }
+    }
+}
+
+const RATIO_MIN_PROMPTS: usize = 20;
+
+/// Mine non-stopword terms recurring across many prompts that no vocabulary entry covers
+/// (as term or alias), ranked by document count, then occurrences.
+pub fn suggest_vocabulary_terms<'a>(
+    bodies: impl IntoIterator<Item = &'a str>,
+    vocabulary: &[VocabularyEntry],
+    options: &SuggestionOptions,
+) -> Vec<VocabularySuggestion> {
+    let known: HashSet<String> = vocabulary
+        .iter()
+        .flat_map(|entry| entry.spellings())
+        .chain(options.excluded.iter().map(String::as_str))
+        .map(|term| normalize_token(normalize_text(term).trim()))
+        .collect();
+
+    let keyword_options = KeywordOptions::default();
+    let mut documents: HashMap<String, usize> = HashMap::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut total = 0usize;
+    for body in bodies {
+        total += 1;
+        let terms = candidate_terms(body, &keyword_options);
+        let unique: HashSet<&String> = terms.iter().collect();
+        for term in unique {
+            *documents.entry(term.clone()).or_insert(0) += 1;
+        }
+        for term in terms {
+            *occurrences.entry(term).or_insert(0) += 1;
+        }
+    }
+
+    // In a small library every useful term looks "generic"; only cap once the ratio means something.
+    let max_documents = if total >= RATIO_MIN_PROMPTS {
+        ((total as f64) * options.max_document_ratio).ceil() as usize
+    } else {
+        usize::MAX
+    };
+    let mut suggestions: Vec<VocabularySuggestion> = documents
+        .into_iter()
+        .filter(|(term, count)| {
+            *count >= options.min_documents
+                && *count <= max_documents
+                && !known.contains(term)
+        })
+        .map(|(term, count)| VocabularySuggestion {
+            occurrences: occurrences.get(&term).copied().unwrap_or(count),
+            documents: count,
+            term,
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.documents
+            .cmp(&a.documents)
+            .then_with(|| b.occurrences.cmp(&a.occurrences))
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    suggestions.truncate(options.limit);
+    suggestions
+}